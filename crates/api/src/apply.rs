@@ -0,0 +1,702 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use axum::{
+    debug_handler,
+    extract::{Query, State},
+    http::{header, HeaderMap},
+    Json,
+};
+use chrono::NaiveDate;
+use entity::worker;
+use serde::{Deserialize, Serialize};
+use service::{
+    schedules::Query as ScheduleQuery,
+    sea_orm::{prelude::Uuid, JsonValue},
+    workers::Query as WorkerQuery,
+    workspace::{Mutation, ScheduleState, WorkerChange, WorkerState},
+};
+
+use crate::{
+    bundle::is_identifier,
+    config::AppState,
+    deployments::{go_live, new_deployment},
+    errors::ServerError,
+    proxy::rebuild_routes,
+    scheduler::{is_valid_path, parse_cron},
+    scopes::{Scoped, WorkersWrite},
+    workerd::{
+        stop_worker, validate_entry, validate_host_name, validate_template, Binding, Worker,
+    },
+};
+
+const DEFAULT_EXTERNAL_PATH: &str = "/";
+const DEFAULT_HOST_NAME: &str = "localhost";
+const DEFAULT_NODE_NAME: &str = "default";
+const DEFAULT_ENTRY: &str = "entry.js";
+
+/// Desired set of workers of a user. Workers of the user that are not
+/// listed are deleted.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct WorkspaceManifest {
+    #[serde(default)]
+    pub workers: Vec<WorkerSpec>,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct WorkerSpec {
+    /// Identifies the worker among the workers of the user.
+    pub name: String,
+    pub port: i32,
+    pub external_path: Option<String>,
+    pub host_name: Option<String>,
+    pub node_name: Option<String>,
+    pub entry: Option<String>,
+    pub code: Option<String>,
+    /// File holding the code, relative to the manifest. Only the command
+    /// line client can read it, and replaces it with `code`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub code_path: Option<String>,
+    pub template: Option<String>,
+    #[serde(default)]
+    pub bindings: BTreeMap<String, Binding>,
+    pub compatibility_date: Option<String>,
+    #[serde(default)]
+    pub schedules: Vec<ScheduleSpec>,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct ScheduleSpec {
+    pub cron: String,
+    pub path: Option<String>,
+    #[serde(default = "enabled_by_default")]
+    pub enabled: bool,
+}
+
+fn enabled_by_default() -> bool {
+    true
+}
+
+#[derive(Deserialize)]
+pub struct ApplyQuery {
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PlanAction {
+    Create,
+    Update,
+    Delete,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct PlanStep {
+    pub action: PlanAction,
+    pub name: String,
+    /// Unset for workers that a dry run would create.
+    pub id: Option<String>,
+    /// Fields that an update changes.
+    pub fields: Vec<String>,
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct ApplyResponse {
+    pub dry_run: bool,
+    pub plan: Vec<PlanStep>,
+    /// Workers that were changed but could not be reloaded afterwards.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub failures: Vec<ApplyFailure>,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct ApplyFailure {
+    pub name: String,
+    pub error: String,
+}
+
+/// A worker as it is stored, to compare against the manifest.
+struct CurrentWorker {
+    worker: worker::Model,
+    schedules: Vec<ScheduleState>,
+}
+
+/// A step of the plan along with the state it leads to.
+struct Change {
+    step: PlanStep,
+    /// Desired worker, unset for deletions.
+    worker: Option<worker::Model>,
+    schedules: Option<Vec<ScheduleState>>,
+    redeploy: bool,
+}
+
+#[debug_handler]
+pub async fn apply(
+    State(state): State<AppState>,
+    Scoped(claims, _): Scoped<WorkersWrite>,
+    Query(query): Query<ApplyQuery>,
+    headers: HeaderMap,
+    body: String,
+) -> Result<Json<ApplyResponse>, ServerError> {
+    let content_type = headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok());
+    let manifest = parse_manifest(content_type, &body)?;
+
+    let user_id = Uuid::parse_str(&claims.sub).map_err(|_| ServerError::InvalidToken)?;
+    let current = load_workers(&state, &claims.sub).await?;
+    let mut changes = plan(user_id, current, manifest, Uuid::new_v4)?;
+
+    if query.dry_run {
+        for change in &mut changes {
+            if change.step.action == PlanAction::Create {
+                change.step.id = None;
+            }
+        }
+        return Ok(Json(ApplyResponse {
+            dry_run: true,
+            plan: changes.into_iter().map(|change| change.step).collect(),
+            failures: vec![],
+        }));
+    }
+
+    let mut worker_changes = Vec::with_capacity(changes.len());
+    for change in &changes {
+        let Some(worker) = &change.worker else {
+            worker_changes.push(WorkerChange::Delete(worker_id(&change.step)?));
+            continue;
+        };
+        let deployment = if change.redeploy {
+            let worker = Worker::from(worker.to_owned());
+            Some(new_deployment(&state, claims.sub.to_owned(), &worker).await?)
+        } else {
+            None
+        };
+        let worker_state = WorkerState {
+            worker: worker.to_owned(),
+            schedules: change.schedules.to_owned(),
+            deployment,
+        };
+        worker_changes.push(match change.step.action {
+            PlanAction::Create => WorkerChange::Create(worker_state),
+            _ => WorkerChange::Update(worker_state),
+        });
+    }
+
+    Mutation::apply(&state.db, worker_changes)
+        .await
+        .map_err(|err| {
+            tracing::error!("Failed to apply workspace manifest: {:?}", err);
+            ServerError::InternalServerError
+        })?;
+
+    // The manifest is applied at this point, so failing to reload a worker
+    // is reported along with the plan rather than failing the request.
+    let mut failures = vec![];
+    for change in &changes {
+        let id = change
+            .step
+            .id
+            .to_owned()
+            .unwrap_or_default()
+            .replace('-', "");
+        let result = match &change.worker {
+            Some(worker) => go_live(&state, &Worker::from(worker.to_owned())).await,
+            None => {
+                state.proxy_metrics.remove(&id);
+                match stop_worker(&state, &id).await {
+                    Err(ServerError::WorkerNotRunning) => Ok(()),
+                    result => result,
+                }
+            }
+        };
+        if let Err(err) = result {
+            tracing::error!("Failed to reload worker {}: {:?}", change.step.name, err);
+            failures.push(ApplyFailure {
+                name: change.step.name.to_owned(),
+                error: err.status_and_message().1.to_owned(),
+            });
+        }
+    }
+    rebuild_routes(&state).await;
+    state.schedule_notify.notify_one();
+
+    Ok(Json(ApplyResponse {
+        dry_run: false,
+        plan: changes.into_iter().map(|change| change.step).collect(),
+        failures,
+    }))
+}
+
+/// Parses and validates a manifest as TOML or JSON when the content type
+/// says so, and as YAML otherwise.
+pub fn parse_manifest(
+    content_type: Option<&str>,
+    body: &str,
+) -> Result<WorkspaceManifest, ServerError> {
+    let manifest = read_manifest(content_type, body)?;
+
+    let mut names = HashSet::new();
+    for spec in &manifest.workers {
+        let valid = !spec.name.is_empty()
+            && names.insert(spec.name.as_str())
+            && (1..=65535).contains(&spec.port)
+            && spec.code.is_some()
+            && spec.bindings.keys().all(|name| is_identifier(name))
+            && spec
+                .compatibility_date
+                .as_ref()
+                .is_none_or(|date| NaiveDate::parse_from_str(date, "%Y-%m-%d").is_ok())
+            && spec.schedules.iter().all(|schedule| {
+                parse_cron(&schedule.cron).is_ok()
+                    && schedule.path.as_deref().is_none_or(is_valid_path)
+            });
+        if !valid {
+            tracing::warn!("Invalid worker {:?} in workspace manifest", spec.name);
+            return Err(ServerError::InvalidWorkspaceManifest);
+        }
+    }
+
+    Ok(manifest)
+}
+
+/// Parses a manifest without validating it, for clients that still have to
+/// resolve `code_path`.
+pub fn read_manifest(
+    content_type: Option<&str>,
+    body: &str,
+) -> Result<WorkspaceManifest, ServerError> {
+    let content_type = content_type.unwrap_or_default();
+    let manifest: WorkspaceManifest = if content_type.contains("toml") {
+        toml::from_str(body).map_err(|err| {
+            tracing::warn!("Failed to parse workspace manifest: {:?}", err);
+            ServerError::InvalidWorkspaceManifest
+        })?
+    } else if content_type.contains("json") {
+        serde_json::from_str(body).map_err(|err| {
+            tracing::warn!("Failed to parse workspace manifest: {:?}", err);
+            ServerError::InvalidWorkspaceManifest
+        })?
+    } else {
+        serde_yaml::from_str(body).map_err(|err| {
+            tracing::warn!("Failed to parse workspace manifest: {:?}", err);
+            ServerError::InvalidWorkspaceManifest
+        })?
+    };
+
+    Ok(manifest)
+}
+
+async fn load_workers(state: &AppState, user_id: &str) -> Result<Vec<CurrentWorker>, ServerError> {
+    let workers = WorkerQuery::find_user_workers_with_user_id(&state.db, user_id.to_owned())
+        .await
+        .map_err(|err| {
+            tracing::error!("Failed to get workers: {:?}", err);
+            ServerError::InternalServerError
+        })?;
+
+    let mut current = Vec::with_capacity(workers.len());
+    for worker in workers {
+        let schedules =
+            ScheduleQuery::find_schedules_by_worker_id(&state.db, worker.id.to_string())
+                .await
+                .map_err(|err| {
+                    tracing::error!("Failed to get schedules: {:?}", err);
+                    ServerError::InternalServerError
+                })?
+                .into_iter()
+                .map(|schedule| ScheduleState {
+                    cron: schedule.cron,
+                    path: schedule.path,
+                    enabled: schedule.enabled,
+                })
+                .collect();
+        current.push(CurrentWorker { worker, schedules });
+    }
+    Ok(current)
+}
+
+/// Compares the manifest with the current workers. Workers are matched by
+/// name; current workers left unmatched, including duplicates of a name, are
+/// deleted. Unchanged workers are left out of the plan.
+fn plan(
+    user_id: Uuid,
+    current: Vec<CurrentWorker>,
+    manifest: WorkspaceManifest,
+    mut new_id: impl FnMut() -> Uuid,
+) -> Result<Vec<Change>, ServerError> {
+    let wanted = manifest
+        .workers
+        .iter()
+        .map(|spec| spec.name.as_str())
+        .collect::<HashSet<_>>();
+    let mut matched = HashMap::new();
+    let mut deletions = Vec::new();
+    for current in current {
+        if wanted.contains(current.worker.name.as_str())
+            && !matched.contains_key(&current.worker.name)
+        {
+            matched.insert(current.worker.name.to_owned(), current);
+        } else {
+            deletions.push(Change {
+                step: PlanStep {
+                    action: PlanAction::Delete,
+                    name: current.worker.name,
+                    id: Some(current.worker.id.to_string()),
+                    fields: Vec::new(),
+                },
+                worker: None,
+                schedules: None,
+                redeploy: false,
+            });
+        }
+    }
+
+    let mut changes = Vec::new();
+    for spec in manifest.workers {
+        let mut schedules = spec
+            .schedules
+            .iter()
+            .map(|schedule| ScheduleState {
+                cron: schedule.cron.to_owned(),
+                path: schedule.path.to_owned(),
+                enabled: schedule.enabled,
+            })
+            .collect::<Vec<_>>();
+        schedules.sort();
+
+        let Some(mut current) = matched.remove(&spec.name) else {
+            let worker = desired_worker(
+                &spec,
+                worker::Model {
+                    id: new_id(),
+                    user_id,
+                    tunnel_id: None,
+                    deployment_id: None,
+                    ..empty_worker()
+                },
+            )?;
+            changes.push(Change {
+                step: PlanStep {
+                    action: PlanAction::Create,
+                    name: spec.name,
+                    id: Some(worker.id.to_string()),
+                    fields: Vec::new(),
+                },
+                worker: Some(worker),
+                schedules: Some(schedules),
+                redeploy: true,
+            });
+            continue;
+        };
+
+        let worker = desired_worker(&spec, current.worker.to_owned())?;
+        let mut fields = changed_fields(&current.worker, &worker);
+        let redeploy = fields.iter().any(|field| DEPLOYED_FIELDS.contains(field));
+        current.schedules.sort();
+        let schedules = (current.schedules != schedules).then(|| {
+            fields.push("schedules");
+            schedules
+        });
+        if fields.is_empty() {
+            continue;
+        }
+
+        changes.push(Change {
+            step: PlanStep {
+                action: PlanAction::Update,
+                name: spec.name,
+                id: Some(worker.id.to_string()),
+                fields: fields.into_iter().map(str::to_owned).collect(),
+            },
+            worker: Some(worker),
+            schedules,
+            redeploy,
+        });
+    }
+
+    changes.extend(deletions);
+    Ok(changes)
+}
+
+/// Fields whose change is recorded as a new deployment.
+const DEPLOYED_FIELDS: [&str; 6] = [
+    "entry",
+    "code",
+    "template",
+    "modules",
+    "bindings",
+    "compatibility_date",
+];
+
+fn desired_worker(spec: &WorkerSpec, base: worker::Model) -> Result<worker::Model, ServerError> {
+    let bindings = serde_json::to_value(&spec.bindings).map_err(|err| {
+        tracing::error!("Failed to serialize bindings: {:?}", err);
+        ServerError::InternalServerError
+    })?;
+
+    let desired = worker::Model {
+        external_path: spec
+            .external_path
+            .to_owned()
+            .unwrap_or_else(|| DEFAULT_EXTERNAL_PATH.to_owned()),
+        host_name: spec
+            .host_name
+            .to_owned()
+            .unwrap_or_else(|| DEFAULT_HOST_NAME.to_owned()),
+        node_name: spec
+            .node_name
+            .to_owned()
+            .unwrap_or_else(|| DEFAULT_NODE_NAME.to_owned()),
+        port: spec.port,
+        entry: spec
+            .entry
+            .to_owned()
+            .unwrap_or_else(|| DEFAULT_ENTRY.to_owned()),
+        code: spec.code.to_owned().unwrap_or_default(),
+        name: spec.name.to_owned(),
+        template: spec.template.to_owned(),
+        // Manifests describe single scripts, replacing uploaded bundles.
+        modules: None,
+        bindings,
+        compatibility_date: spec.compatibility_date.to_owned(),
+        // Teams are not part of manifests, so workers stay in theirs.
+        ..base
+    };
+    validate_host_name(&desired.host_name)?;
+    validate_entry(&desired.entry)?;
+    if let Some(template) = &desired.template {
+        validate_template(template)?;
+    }
+    Ok(desired)
+}
+
+fn changed_fields(current: &worker::Model, desired: &worker::Model) -> Vec<&'static str> {
+    [
+        (
+            "external_path",
+            current.external_path != desired.external_path,
+        ),
+        ("host_name", current.host_name != desired.host_name),
+        ("node_name", current.node_name != desired.node_name),
+        ("port", current.port != desired.port),
+        ("entry", current.entry != desired.entry),
+        ("code", current.code != desired.code),
+        ("template", current.template != desired.template),
+        ("modules", current.modules != desired.modules),
+        ("bindings", current.bindings != desired.bindings),
+        (
+            "compatibility_date",
+            current.compatibility_date != desired.compatibility_date,
+        ),
+    ]
+    .into_iter()
+    .filter_map(|(field, changed)| changed.then_some(field))
+    .collect()
+}
+
+fn empty_worker() -> worker::Model {
+    worker::Model {
+        id: Uuid::nil(),
+        external_path: String::new(),
+        host_name: String::new(),
+        node_name: String::new(),
+        port: 0,
+        entry: String::new(),
+        code: String::new(),
+        name: String::new(),
+        tunnel_id: None,
+        template: None,
+        user_id: Uuid::nil(),
+        deployment_id: None,
+        modules: None,
+        bindings: JsonValue::Object(Default::default()),
+        compatibility_date: None,
+        team_id: None,
+    }
+}
+
+fn worker_id(step: &PlanStep) -> Result<Uuid, ServerError> {
+    step.id
+        .as_deref()
+        .and_then(|id| Uuid::parse_str(id).ok())
+        .ok_or(ServerError::InternalServerError)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MANIFEST: &str = r#"
+workers:
+  - name: api
+    port: 8080
+    code: "export default {}"
+    bindings:
+      MODE: { type: text, value: prod }
+    schedules:
+      - cron: "0 */5 * * * *"
+        path: /tick
+  - name: web
+    port: 8081
+    entry: index.js
+    code: "addEventListener('fetch', () => {})"
+"#;
+
+    fn current(name: &str, id: u128, port: i32) -> CurrentWorker {
+        CurrentWorker {
+            worker: worker::Model {
+                id: Uuid::from_u128(id),
+                name: name.to_string(),
+                port,
+                external_path: DEFAULT_EXTERNAL_PATH.to_string(),
+                host_name: DEFAULT_HOST_NAME.to_string(),
+                node_name: DEFAULT_NODE_NAME.to_string(),
+                entry: "index.js".to_string(),
+                code: "addEventListener('fetch', () => {})".to_string(),
+                ..empty_worker()
+            },
+            schedules: Vec::new(),
+        }
+    }
+
+    fn steps(changes: &[Change]) -> Vec<(PlanAction, &str, Vec<&str>)> {
+        changes
+            .iter()
+            .map(|change| {
+                (
+                    change.step.action,
+                    change.step.name.as_str(),
+                    change.step.fields.iter().map(String::as_str).collect(),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_parse_manifest() {
+        let yaml = parse_manifest(None, MANIFEST).unwrap();
+        assert_eq!(yaml.workers.len(), 2);
+        assert_eq!(yaml.workers[0].schedules[0].path.as_deref(), Some("/tick"));
+        assert!(yaml.workers[0].schedules[0].enabled);
+
+        let toml = parse_manifest(
+            Some("application/toml"),
+            r#"
+            [[workers]]
+            name = "api"
+            port = 8080
+            code = "export default {}"
+            bindings.MODE = { type = "text", value = "prod" }
+
+            [[workers.schedules]]
+            cron = "0 */5 * * * *"
+            path = "/tick"
+
+            [[workers]]
+            name = "web"
+            port = 8081
+            entry = "index.js"
+            code = "addEventListener('fetch', () => {})"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(toml, yaml);
+    }
+
+    #[test]
+    fn test_invalid_manifests() {
+        for manifest in [
+            "workers: [{name: api, port: 8080}]",
+            "workers: [{name: api, port: 8080, code_path: api.js}]",
+            "workers: [{name: api, port: 0, code: ''}]",
+            "workers: [{name: a, port: 1, code: ''}, {name: a, port: 2, code: ''}]",
+            "workers: [{name: api, port: 8080, code: '', schedules: [{cron: nope}]}]",
+            "workers: [{name: api, port: 8080, code: '', schedules: [{cron: '* * * * *', path: '@169.254.169.254/'}]}]",
+            "workers: [{name: api, port: 8080, code: '', unknown: 1}]",
+        ] {
+            assert!(
+                matches!(
+                    parse_manifest(None, manifest),
+                    Err(ServerError::InvalidWorkspaceManifest)
+                ),
+                "{manifest}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_plan() {
+        let manifest = parse_manifest(None, MANIFEST).unwrap();
+        let changes = plan(
+            Uuid::nil(),
+            vec![
+                current("web", 1, 8081),
+                current("old", 2, 8082),
+                current("web", 3, 8083),
+            ],
+            manifest,
+            || Uuid::from_u128(4),
+        )
+        .unwrap();
+
+        assert_eq!(
+            steps(&changes),
+            [
+                (PlanAction::Create, "api", vec![]),
+                (PlanAction::Delete, "old", vec![]),
+                (PlanAction::Delete, "web", vec![]),
+            ]
+        );
+        assert_eq!(changes[0].step.id, Some(Uuid::from_u128(4).to_string()));
+        assert_eq!(changes[2].step.id, Some(Uuid::from_u128(3).to_string()));
+        assert_eq!(changes[0].schedules.as_ref().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_plan_updates() {
+        let manifest = parse_manifest(
+            None,
+            r#"
+workers:
+  - name: web
+    port: 9000
+    entry: index.js
+    code: "addEventListener('fetch', () => {})"
+    schedules: [{cron: "0 0 * * * *"}]
+  - name: api
+    port: 8080
+    entry: index.js
+    code: "export default {}"
+"#,
+        )
+        .unwrap();
+        let mut web = current("web", 1, 8081);
+        web.worker.team_id = Some(Uuid::from_u128(7));
+        let changes = plan(
+            Uuid::nil(),
+            vec![web, current("api", 2, 8080)],
+            manifest,
+            Uuid::new_v4,
+        )
+        .unwrap();
+
+        assert_eq!(
+            steps(&changes),
+            [
+                (PlanAction::Update, "web", vec!["port", "schedules"]),
+                (PlanAction::Update, "api", vec!["code"]),
+            ]
+        );
+        assert!(!changes[0].redeploy);
+        assert_eq!(
+            changes[0].worker.as_ref().unwrap().team_id,
+            Some(Uuid::from_u128(7))
+        );
+        assert!(changes[1].redeploy);
+        assert!(changes[1].schedules.is_none());
+    }
+}
@@ -1,32 +1,53 @@
-use crate::{config::AppState, errors::ServerError};
+use crate::{
+    config::AppState,
+    errors::ServerError,
+    keys::{Keys, KEY_PUBLISH_DELAY_SECS},
+    policy::{user_permissions, Permission},
+    scopes::{Scope, Scoped, UsersWrite},
+    security_events::{record_event, SecurityEventKind},
+    sessions::{end_all_sessions, end_session, rotate_session, start_session, ClientInfo},
+    tokens::{self, TOKEN_PREFIX},
+    two_factor::{second_factor_challenge, SecondFactorChallenge},
+    users::MessageResponse,
+};
 use argon2::{
-    password_hash::{rand_core::OsRng, PasswordHash, PasswordHasher, PasswordVerifier, SaltString},
+    password_hash::{
+        rand_core::{OsRng, RngCore},
+        PasswordHash, PasswordHasher, PasswordVerifier, SaltString,
+    },
     Argon2,
 };
 use axum::{
     async_trait, debug_handler,
     extract::{FromRef, FromRequestParts, Request, State},
-    http::{request::Parts, HeaderMap},
+    http::{header::CACHE_CONTROL, request::Parts, HeaderMap},
+    response::IntoResponse,
     Json, RequestPartsExt,
 };
 use axum_extra::{
     headers::{authorization::Bearer, Authorization},
     TypedHeader,
 };
-use entity::sea_orm_active_enums::RoleEnum;
-use jsonwebtoken::{
-    decode, encode, get_current_timestamp, DecodingKey, Header, TokenData, Validation,
-};
+use entity::sea_orm_active_enums::{RoleEnum, UserStatusEnum};
+use jsonwebtoken::{get_current_timestamp, TokenData};
 use redis::Commands;
 use serde::{Deserialize, Serialize};
-use service::users::Query;
+use service::{sea_orm::prelude::Uuid, users::Query};
+use sha2::{Digest, Sha256};
 use std::fmt::Display;
 
+pub const ACCESS_TOKEN_SECS: u64 = 60 * 60;
+/// Lifetime of refresh tokens and of sessions left unused.
+pub const REFRESH_TOKEN_SECS: u64 = 60 * 60 * 24 * 7;
+
+/// Logs in with a password. Users with a second factor get a challenge to
+/// complete at `/auth/login/two-factor` instead of tokens.
 #[debug_handler]
 pub async fn login(
     State(state): State<AppState>,
+    client: ClientInfo,
     Json(payload): Json<AuthPayload>,
-) -> Result<Json<AuthBody>, ServerError> {
+) -> Result<Json<LoginResponse>, ServerError> {
     if payload.email.is_empty() || payload.password.is_empty() {
         return Err(ServerError::MissingCredentials);
     }
@@ -42,19 +63,29 @@ pub async fn login(
         tracing::error!("Failed to verify password: {:?}", payload);
         return Err(ServerError::WrongCredentials);
     }
+    if user.status != UserStatusEnum::Active {
+        return Err(ServerError::UserNotActive);
+    }
 
+    if let Some(challenge) = second_factor_challenge(&state, &user).await? {
+        return Ok(Json(LoginResponse::SecondFactorRequired(challenge)));
+    }
     let (access_token, refresh_token) =
-        generate_token_pair(&state, &user.id.to_string(), None, None)
+        generate_token_pair(&state, &user.id.to_string(), &client, None)
             .await
-            .map_err(|_| ServerError::FailedToGenerateTokenPair)?;
+            .map_err(token_pair_error)?;
 
-    Ok(Json(AuthBody::new(access_token, refresh_token)))
+    Ok(Json(LoginResponse::Tokens(AuthBody::new(
+        access_token,
+        refresh_token,
+    ))))
 }
 
 #[debug_handler]
 pub async fn refresh_token(
     State(state): State<AppState>,
     claims: RefreshTokenClaims,
+    client: ClientInfo,
     req: Request,
 ) -> Result<Json<AuthBody>, ServerError> {
     let jwt = extract_jwt_from_headers(req.headers().to_owned())
@@ -64,19 +95,92 @@ pub async fn refresh_token(
     }
 
     let (access_token, refresh_token) =
-        generate_token_pair(&state, &claims.sub, Some(jwt.as_str()), Some(claims.exp))
+        generate_token_pair(&state, &claims.sub, &client, Some((jwt.as_str(), &claims)))
             .await
-            .map_err(|_| ServerError::FailedToGenerateTokenPair)?;
+            .map_err(token_pair_error)?;
 
     Ok(Json(AuthBody::new(access_token, refresh_token)))
 }
 
+/// Revokes the presented refresh token and ends its session. Access tokens
+/// issued with it stay valid until they expire, an hour at most.
+#[debug_handler]
+pub async fn logout(
+    State(state): State<AppState>,
+    claims: RefreshTokenClaims,
+    req: Request,
+) -> Result<Json<MessageResponse>, ServerError> {
+    let jwt = extract_jwt_from_headers(req.headers().to_owned())
+        .map_err(|_| ServerError::InvalidToken)?;
+
+    blacklist_token(&state, &jwt, &claims.sub)?;
+    if let Some(jti) = &claims.jti {
+        end_session(&state, &claims.sub, jti).await?;
+    }
+
+    Ok(Json(MessageResponse {
+        message: "Logged out successfully".to_owned(),
+    }))
+}
+
+/// Ends every session of the caller by moving to the next token
+/// generation, which rejects all access and refresh tokens issued before.
+/// Personal access tokens are left alone.
+#[debug_handler]
+pub async fn logout_all(
+    State(state): State<AppState>,
+    Scoped(claims, _): Scoped<UsersWrite>,
+) -> Result<Json<MessageResponse>, ServerError> {
+    end_all_logins(&state, &claims.sub).await?;
+
+    Ok(Json(MessageResponse {
+        message: "Logged out of all sessions successfully".to_owned(),
+    }))
+}
+
+/// Rejects every access and refresh token issued to a user so far, and ends
+/// their sessions.
+pub async fn end_all_logins(state: &AppState, user_id: &str) -> Result<(), ServerError> {
+    bump_token_generation(state, user_id)?;
+    end_all_sessions(state, user_id).await
+}
+
+/// Publishes the keys verifying access tokens, for other services to
+/// validate them. Refresh tokens are only ever verified here.
+#[debug_handler]
+pub async fn get_jwks(State(state): State<AppState>) -> impl IntoResponse {
+    (
+        [(
+            CACHE_CONTROL,
+            format!("public, max-age={}", KEY_PUBLISH_DELAY_SECS / 2),
+        )],
+        Json(state.jwt_auth_keys.jwks()),
+    )
+}
+
+/// Keeps the reason a user may not log in, and hides the others.
+pub fn token_pair_error(err: ServerError) -> ServerError {
+    match err {
+        ServerError::UserNotActive | ServerError::InvalidToken => err,
+        _ => ServerError::FailedToGenerateTokenPair,
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct AccessTokenClaims {
     pub sub: String,
     pub username: String,
     pub roles: Vec<RoleEnum>,
-    pub status: i32,
+    pub status: UserStatusEnum,
+    pub scopes: Vec<Scope>,
+    pub permissions: Vec<Permission>,
+    /// Token generation of the user when issued, see `logout_all`.
+    #[serde(default)]
+    pub generation: u64,
+    /// Session of the refresh token issued along, unset for personal
+    /// access tokens.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sid: Option<String>,
     pub exp: u64,
 }
 
@@ -90,13 +194,18 @@ where
 
     async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
         let state = AppState::from_ref(state);
-        let claims = decoding_token_from_request_parts::<AccessTokenClaims>(
-            parts,
-            state.jwt_refresh_keys.decoding,
-        )
-        .await?;
+        let token = bearer_token(parts).await?;
+        if token.starts_with(TOKEN_PREFIX) {
+            return tokens::authenticate(&state, &token).await;
+        }
 
-        Ok(claims)
+        let token_data = state
+            .jwt_auth_keys
+            .decode::<AccessTokenClaims>(&token)
+            .map_err(|_| ServerError::InvalidToken)?;
+        check_token_generation(&state, &token_data.claims.sub, token_data.claims.generation)?;
+
+        Ok(token_data.claims)
     }
 }
 
@@ -132,11 +241,10 @@ where
 
     async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
         let state = AppState::from_ref(state);
-        let claims = decoding_token_from_request_parts::<RefreshTokenClaims>(
-            parts,
-            state.jwt_refresh_keys.decoding,
-        )
-        .await?;
+        let claims =
+            decoding_token_from_request_parts::<RefreshTokenClaims>(parts, &state.jwt_refresh_keys)
+                .await?;
+        check_token_generation(&state, &claims.sub, claims.generation)?;
 
         Ok(claims)
     }
@@ -145,22 +253,39 @@ where
 #[derive(Debug, Serialize, Deserialize)]
 pub struct RefreshTokenClaims {
     pub sub: String,
+    #[serde(default)]
+    pub generation: u64,
+    /// Session id, kept across rotations. Unset in tokens issued before
+    /// sessions were recorded.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub jti: Option<String>,
+    /// Id of this token within its session, which is the family of all the
+    /// tokens rotated from the one issued on login.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub token_id: Option<String>,
     pub exp: u64,
 }
 
 async fn decoding_token_from_request_parts<T: serde::de::DeserializeOwned>(
     parts: &mut Parts,
-    decoding: DecodingKey,
+    keys: &Keys,
 ) -> Result<T, ServerError> {
+    let token = bearer_token(parts).await?;
+
+    let token_data = keys
+        .decode::<T>(&token)
+        .map_err(|_| ServerError::InvalidToken)?;
+
+    Ok(token_data.claims)
+}
+
+async fn bearer_token(parts: &mut Parts) -> Result<String, ServerError> {
     let TypedHeader(Authorization(bearer)) = parts
         .extract::<TypedHeader<Authorization<Bearer>>>()
         .await
         .map_err(|_| ServerError::InvalidToken)?;
 
-    let token_data = decode::<T>(bearer.token(), &decoding, &Validation::default())
-        .map_err(|_| ServerError::InvalidToken)?;
-
-    Ok(token_data.claims)
+    Ok(bearer.token().to_owned())
 }
 
 #[derive(Debug, Serialize)]
@@ -168,6 +293,10 @@ pub struct AuthBody {
     pub access_token: String,
     pub refresh_token: String,
     pub token_type: String,
+    /// Set when the login confirmed an enrollment in two-factor
+    /// authentication.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub recovery_codes: Option<Vec<String>>,
 }
 
 impl AuthBody {
@@ -176,91 +305,167 @@ impl AuthBody {
             access_token,
             refresh_token,
             token_type: "Bearer".to_string(),
+            recovery_codes: None,
         }
     }
 }
 
+#[derive(Debug, Serialize)]
+#[serde(untagged)]
+pub enum LoginResponse {
+    Tokens(AuthBody),
+    SecondFactorRequired(SecondFactorChallenge),
+}
+
 #[derive(Debug, Deserialize)]
 pub struct AuthPayload {
     pub email: String,
     pub password: String,
 }
 
+/// Issues tokens for a new session, or for the session of the current
+/// refresh token when rotating it.
 pub async fn generate_token_pair(
     state: &AppState,
     user_id: &str,
-    current_refresh_token: Option<&str>,
-    current_refresh_token_expires_at: Option<u64>,
+    client: &ClientInfo,
+    current: Option<(&str, &RefreshTokenClaims)>,
 ) -> Result<(String, String), ServerError> {
     let user = Query::find_user_by_id(&state.db, user_id.to_string())
         .await
         .map_err(|_| ServerError::InternalServerError)?
         .ok_or(ServerError::WrongCredentials)?;
+    if user.status != UserStatusEnum::Active {
+        return Err(ServerError::UserNotActive);
+    }
+
+    let permissions = user_permissions(state, &user).await?;
+    let generation = token_generation(state, user_id)?;
+    let token_id = Uuid::new_v4();
+    let session_id = match current {
+        Some((token, claims)) => {
+            rotate_refresh_token(state, token, claims, token_id, client).await?
+        }
+        None => start_session(state, user_id, token_id, client)
+            .await?
+            .to_string(),
+    };
+    let refresh_token = generate_refresh_token(
+        state,
+        user_id,
+        generation,
+        &session_id,
+        &token_id.to_string(),
+    )?;
 
     let access_token = AccessTokenClaims {
         sub: user.id.to_string().to_owned(),
         username: user.username.to_owned(),
         roles: user.roles,
         status: user.status,
-        exp: get_current_timestamp() + 60 * 60,
+        scopes: Scope::ALL.to_vec(),
+        permissions,
+        generation,
+        sid: Some(session_id),
+        exp: get_current_timestamp() + ACCESS_TOKEN_SECS,
     };
 
     Ok((
-        encode(
-            &Header::default(),
-            &access_token,
-            &state.jwt_auth_keys.encoding,
-        )
-        .map_err(|_| ServerError::FailedToEncodeAccessToken)?,
-        generate_refresh_token(
-            state,
-            user_id,
-            current_refresh_token,
-            current_refresh_token_expires_at,
-        )
-        .map_err(|_| ServerError::FailedToEncodeRefreshToken)?,
+        state
+            .jwt_auth_keys
+            .encode(&access_token)
+            .map_err(|_| ServerError::FailedToEncodeAccessToken)?,
+        refresh_token,
     ))
 }
 
-pub fn generate_refresh_token(
+/// Retires the current refresh token of a session in favor of
+/// `next_token_id`, and returns the session id.
+///
+/// A token presented again after it was rotated has been copied, and there
+/// is no telling whether this is its owner or whoever copied it. So the
+/// whole family is revoked by ending the session, which logs out both and
+/// leaves a security event for the user to find.
+async fn rotate_refresh_token(
     state: &AppState,
-    user_id: &str,
-    current_refresh_token: Option<&str>,
-    current_refresh_token_expires_at: Option<u64>,
+    token: &str,
+    claims: &RefreshTokenClaims,
+    next_token_id: Uuid,
+    client: &ClientInfo,
 ) -> Result<String, ServerError> {
-    if current_refresh_token.is_some() && current_refresh_token_expires_at.is_some() {
-        if is_refresh_token_black_listed(state, current_refresh_token.clone().unwrap(), user_id)
-            .unwrap()
-        {
+    let user_id = &claims.sub;
+    let reused = is_refresh_token_black_listed(state, token, user_id)?;
+    if !reused {
+        blacklist_token(state, token, user_id)?;
+    }
+
+    let Some(session_id) = &claims.jti else {
+        // Issued before sessions were recorded, so there is no family to
+        // revoke. Such tokens move to a session of their own.
+        if reused {
             return Err(ServerError::InvalidToken);
         }
-        blacklist_token(state, current_refresh_token.clone().unwrap(), user_id)
-            .expect("Failed to blacklist refresh token");
+        return Ok(start_session(state, user_id, next_token_id, client)
+            .await?
+            .to_string());
+    };
+
+    let current_token_id = match &claims.token_id {
+        Some(token_id) => Some(Uuid::parse_str(token_id).map_err(|_| ServerError::InvalidToken)?),
+        None => None,
+    };
+    let rotated = !reused
+        && rotate_session(
+            state,
+            user_id,
+            session_id,
+            current_token_id,
+            next_token_id,
+            client,
+        )
+        .await?;
+    if !rotated {
+        // Tokens of sessions that were already ended are merely stale.
+        if end_session(state, user_id, session_id).await? > 0 {
+            record_event(
+                state,
+                user_id,
+                SecurityEventKind::RefreshTokenReused,
+                client,
+            )
+            .await;
+        }
+        return Err(ServerError::InvalidToken);
     }
+    Ok(session_id.to_owned())
+}
 
+pub fn generate_refresh_token(
+    state: &AppState,
+    user_id: &str,
+    generation: u64,
+    session_id: &str,
+    token_id: &str,
+) -> Result<String, ServerError> {
     let refresh_token = RefreshTokenClaims {
         sub: user_id.to_owned(),
-        exp: get_current_timestamp() + 60 * 60 * 24 * 7,
+        generation,
+        jti: Some(session_id.to_owned()),
+        token_id: Some(token_id.to_owned()),
+        exp: get_current_timestamp() + REFRESH_TOKEN_SECS,
     };
 
-    Ok(encode(
-        &Header::default(),
-        &refresh_token,
-        &state.jwt_refresh_keys.encoding,
-    )
-    .map_err(|_| ServerError::FailedToEncodeRefreshToken)?)
+    state
+        .jwt_refresh_keys
+        .encode(&refresh_token)
+        .map_err(|_| ServerError::FailedToEncodeRefreshToken)
 }
 
-fn blacklist_token(state: &AppState, token: &str, user_id: &str) -> redis::RedisResult<()> {
-    let redis_client = state.redis_client.clone();
-    let mut con = redis_client.get_connection()?;
-
-    let token_data: TokenData<RefreshTokenClaims> = decode::<RefreshTokenClaims>(
-        &token,
-        &state.jwt_refresh_keys.decoding,
-        &Validation::default(),
-    )
-    .expect("Failed to decode refresh token");
+fn blacklist_token(state: &AppState, token: &str, user_id: &str) -> Result<(), ServerError> {
+    let token_data: TokenData<RefreshTokenClaims> = state
+        .jwt_refresh_keys
+        .decode::<RefreshTokenClaims>(token)
+        .map_err(|_| ServerError::InvalidToken)?;
 
     let exp = token_data.claims.exp;
     let current_time = get_current_timestamp();
@@ -270,22 +475,86 @@ fn blacklist_token(state: &AppState, token: &str, user_id: &str) -> redis::Redis
         60
     };
 
-    con.set_ex(token, user_id, ttl.try_into().unwrap())
+    let mut con = state.redis_client.get_connection().map_err(|err| {
+        tracing::error!("Failed to connect to Redis: {:?}", err);
+        ServerError::InternalServerError
+    })?;
+    state
+        .manager_metrics
+        .time_redis(|| con.set_ex(token, user_id, ttl))
+        .map_err(|err| {
+            tracing::error!("Failed to blacklist refresh token: {:?}", err);
+            ServerError::InternalServerError
+        })
 }
 
 pub fn is_refresh_token_black_listed(
     state: &AppState,
     refresh_token: &str,
     user_id: &str,
-) -> Result<bool, redis::RedisError> {
-    let redis_client = state.redis_client.clone();
-    let mut con = redis_client
-        .get_connection()
-        .expect("Failed to connect to Redis");
-    let result: Option<String> = con
-        .get(&refresh_token)
-        .expect("Failed to get refresh token from Redis");
-    Ok(result.map(|s| s == user_id).unwrap_or(false))
+) -> Result<bool, ServerError> {
+    let mut con = state.redis_client.get_connection().map_err(|err| {
+        tracing::error!("Failed to connect to Redis: {:?}", err);
+        ServerError::InternalServerError
+    })?;
+    let result: Option<String> = state
+        .manager_metrics
+        .time_redis(|| con.get(refresh_token))
+        .map_err(|err| {
+            tracing::error!("Failed to check refresh token: {:?}", err);
+            ServerError::InternalServerError
+        })?;
+    Ok(result.is_some_and(|s| s == user_id))
+}
+
+fn token_generation_key(user_id: &str) -> String {
+    format!("token_generation:{}", user_id)
+}
+
+/// Returns the current token generation of a user, 0 until the first
+/// `logout_all`.
+fn token_generation(state: &AppState, user_id: &str) -> Result<u64, ServerError> {
+    let mut con = state.redis_client.get_connection().map_err(|err| {
+        tracing::error!("Failed to connect to Redis: {:?}", err);
+        ServerError::InternalServerError
+    })?;
+    let result: redis::RedisResult<Option<u64>> = state
+        .manager_metrics
+        .time_redis(|| con.get(token_generation_key(user_id)));
+
+    result.map(Option::unwrap_or_default).map_err(|err| {
+        tracing::error!("Failed to get token generation: {:?}", err);
+        ServerError::InternalServerError
+    })
+}
+
+/// Rejects every access and refresh token issued to a user so far, for
+/// their next ones to carry their current roles.
+pub fn bump_token_generation(state: &AppState, user_id: &str) -> Result<u64, ServerError> {
+    let mut con = state.redis_client.get_connection().map_err(|err| {
+        tracing::error!("Failed to connect to Redis: {:?}", err);
+        ServerError::InternalServerError
+    })?;
+    let result: redis::RedisResult<u64> = state
+        .manager_metrics
+        .time_redis(|| con.incr(token_generation_key(user_id), 1));
+
+    result.map_err(|err| {
+        tracing::error!("Failed to bump token generation: {:?}", err);
+        ServerError::InternalServerError
+    })
+}
+
+/// Rejects tokens issued before the last `logout_all` of their user.
+fn check_token_generation(
+    state: &AppState,
+    user_id: &str,
+    generation: u64,
+) -> Result<(), ServerError> {
+    if generation != token_generation(state, user_id)? {
+        return Err(ServerError::InvalidToken);
+    }
+    Ok(())
 }
 
 pub fn hash_password(password: &str) -> Result<String, argon2::password_hash::Error> {
@@ -293,15 +562,66 @@ pub fn hash_password(password: &str) -> Result<String, argon2::password_hash::Er
     let argon2 = Argon2::default();
     argon2
         .hash_password(password.as_bytes(), &salt)
-        .map_err(|e| e)
         .map(|hash| hash.to_string())
 }
 
 pub fn verify_password(hash: &str, password: &str) -> Result<bool, argon2::password_hash::Error> {
     let argon2 = Argon2::default();
-    let parsed_hash = PasswordHash::new(hash).map_err(|e| e)?;
+    let parsed_hash = PasswordHash::new(hash)?;
     argon2
         .verify_password(password.as_bytes(), &parsed_hash)
-        .map_err(|e| e)
         .map(|_| true)
 }
+
+/// Returns 32 random bytes, hex encoded, for one-time codes and tokens.
+pub fn generate_secret() -> String {
+    let mut bytes = [0u8; 32];
+    OsRng.fill_bytes(&mut bytes);
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// Hashes a secret for storage. Secrets are random, so unlike passwords
+/// they need neither a salt nor a slow hash.
+pub fn hash_secret(secret: &str) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(secret);
+    format!("{:x}", hasher.finalize())
+}
+
+/// Compares secrets in a time that does not depend on where they differ.
+pub fn secrets_match(expected: &str, actual: &str) -> bool {
+    expected.len() == actual.len()
+        && expected
+            .bytes()
+            .zip(actual.bytes())
+            .fold(0, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_secrets() {
+        let secret = generate_secret();
+        assert_eq!(secret.len(), 64);
+        assert!(secret.bytes().all(|byte| byte.is_ascii_hexdigit()));
+        assert_ne!(secret, generate_secret());
+
+        assert!(secrets_match(&secret, &secret.clone()));
+        assert!(!secrets_match(&secret, &secret[1..]));
+        assert!(!secrets_match(&secret, &generate_secret()));
+        assert!(!secrets_match(&secret, ""));
+
+        assert_eq!(hash_secret(&secret), hash_secret(&secret.clone()));
+        assert_ne!(hash_secret(&secret), hash_secret(&generate_secret()));
+    }
+
+    #[test]
+    fn test_claims_without_generation() {
+        // Tokens issued before generations existed belong to the first one.
+        let claims: RefreshTokenClaims = serde_json::from_str(r#"{"sub":"user","exp":1}"#).unwrap();
+        assert_eq!(claims.generation, 0);
+    }
+}
@@ -0,0 +1,434 @@
+use std::{
+    collections::BTreeMap,
+    io::{Cursor, Read},
+};
+
+use axum::{
+    body::{Body, Bytes},
+    debug_handler,
+    extract::{Path, State},
+    Json,
+};
+use chrono::NaiveDate;
+use flate2::read::GzDecoder;
+use http_body_util::LengthLimitError;
+use serde::{Deserialize, Serialize};
+use tar::EntryType;
+use zip::ZipArchive;
+
+use crate::{
+    config::AppState,
+    deployments::{get_owned_worker, go_live, record_deployment, DeploymentInfoResponse},
+    errors::ServerError,
+    scopes::{Scoped, WorkersWrite},
+    teams::WorkerAccess,
+    workerd::{Binding, Module, ModuleKind, Worker},
+};
+
+/// Maximum size of an uploaded archive.
+pub const MAX_BUNDLE_BYTES: usize = 10 * 1024 * 1024;
+/// Maximum total size of the files in an archive once unpacked.
+pub const MAX_UNPACKED_BYTES: u64 = 50 * 1024 * 1024;
+pub const MAX_FILES: usize = 1000;
+pub const MANIFEST_NAME: &str = "manifest.json";
+
+const GZIP_MAGIC: &[u8] = &[0x1f, 0x8b];
+const ZIP_MAGIC: &[u8] = b"PK\x03\x04";
+
+/// `manifest.json` at the root of a bundle.
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Manifest {
+    pub entry: String,
+    /// Files to deploy as modules. Defaults to every file of the bundle.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub modules: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub compatibility_date: Option<String>,
+    #[serde(default)]
+    pub bindings: BTreeMap<String, Binding>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Bundle {
+    pub entry: String,
+    pub modules: BTreeMap<String, Module>,
+    pub bindings: BTreeMap<String, Binding>,
+    pub compatibility_date: Option<String>,
+}
+
+impl Bundle {
+    /// Replaces the code and configuration of `worker` with the bundle.
+    pub fn apply(self, mut worker: Worker) -> Worker {
+        worker.code = self.modules[&self.entry].content.clone();
+        worker.entry = self.entry;
+        worker.modules = Some(self.modules);
+        worker.bindings = self.bindings;
+        worker.compatibility_date = self.compatibility_date;
+        worker
+    }
+}
+
+#[debug_handler]
+pub async fn upload_bundle(
+    State(state): State<AppState>,
+    Scoped(claims, _): Scoped<WorkersWrite>,
+    Path(id): Path<String>,
+    body: Body,
+) -> Result<Json<DeploymentInfoResponse>, ServerError> {
+    let worker: Worker = get_owned_worker(&state, &claims, id, WorkerAccess::Write)
+        .await?
+        .into();
+
+    let bytes = read_body(body).await?;
+    let bundle = tokio::task::spawn_blocking(move || read_bundle(&bytes))
+        .await
+        .map_err(|err| {
+            tracing::error!("Failed to unpack bundle: {:?}", err);
+            ServerError::InternalServerError
+        })??;
+
+    let worker = bundle.apply(worker);
+    let deployment = record_deployment(&state, claims.sub, &worker, true).await?;
+    go_live(&state, &worker).await?;
+
+    let active_id = Some(deployment.id);
+    Ok(Json(DeploymentInfoResponse::new(deployment, active_id)))
+}
+
+/// Reads an uploaded archive, enforcing [`MAX_BUNDLE_BYTES`].
+pub(crate) async fn read_body(body: Body) -> Result<Bytes, ServerError> {
+    axum::body::to_bytes(body, MAX_BUNDLE_BYTES)
+        .await
+        .map_err(
+            |err| match err.into_inner().downcast::<LengthLimitError>() {
+                Ok(_) => ServerError::BundleTooLarge,
+                Err(err) => {
+                    tracing::error!("Failed to read bundle: {:?}", err);
+                    ServerError::InvalidBundle
+                }
+            },
+        )
+}
+
+/// Unpacks a `tar.gz` or `zip` archive and validates its manifest.
+pub fn read_bundle(archive: &[u8]) -> Result<Bundle, ServerError> {
+    build_bundle(unpack(archive)?)
+}
+
+/// Files of an archive being unpacked, keyed by their normalized path.
+#[derive(Default)]
+struct Files {
+    files: BTreeMap<String, Vec<u8>>,
+    unpacked: u64,
+}
+
+impl Files {
+    fn add(&mut self, path: &str, reader: impl Read) -> Result<(), ServerError> {
+        let path = safe_path(path).ok_or_else(|| {
+            tracing::warn!("Rejected bundle path {:?}", path);
+            ServerError::UnsafeBundlePath
+        })?;
+        if self.files.len() >= MAX_FILES {
+            return Err(ServerError::BundleTooLarge);
+        }
+
+        // Read one byte past the limit to detect archives that exceed it
+        // without trusting the sizes they declare.
+        let mut content = Vec::new();
+        reader
+            .take(MAX_UNPACKED_BYTES - self.unpacked + 1)
+            .read_to_end(&mut content)
+            .map_err(invalid_bundle)?;
+        self.unpacked += content.len() as u64;
+        if self.unpacked > MAX_UNPACKED_BYTES {
+            return Err(ServerError::BundleTooLarge);
+        }
+
+        match self.files.insert(path, content) {
+            Some(_) => Err(ServerError::InvalidBundle),
+            None => Ok(()),
+        }
+    }
+
+    /// Archives created from a directory hold everything under it, so a
+    /// single top level directory containing the manifest is stripped.
+    fn into_root(self) -> BTreeMap<String, Vec<u8>> {
+        if self.files.contains_key(MANIFEST_NAME) {
+            return self.files;
+        }
+        let Some(root) = self
+            .files
+            .keys()
+            .find_map(|path| path.strip_suffix(MANIFEST_NAME)?.strip_suffix('/'))
+            .filter(|root| !root.contains('/'))
+            .map(|root| format!("{root}/"))
+        else {
+            return self.files;
+        };
+        if !self.files.keys().all(|path| path.starts_with(&root)) {
+            return self.files;
+        }
+
+        self.files
+            .into_iter()
+            .map(|(path, content)| (path[root.len()..].to_owned(), content))
+            .collect()
+    }
+}
+
+pub(crate) fn unpack(archive: &[u8]) -> Result<BTreeMap<String, Vec<u8>>, ServerError> {
+    let mut files = Files::default();
+
+    if archive.starts_with(GZIP_MAGIC) {
+        let mut archive = tar::Archive::new(GzDecoder::new(archive));
+        for entry in archive.entries().map_err(invalid_bundle)? {
+            let entry = entry.map_err(invalid_bundle)?;
+            match entry.header().entry_type() {
+                EntryType::Regular | EntryType::Continuous => {}
+                EntryType::Directory | EntryType::XGlobalHeader => continue,
+                // Links could point outside of the worker directory.
+                _ => return Err(ServerError::UnsafeBundlePath),
+            }
+            let path = String::from_utf8(entry.path_bytes().into_owned())
+                .map_err(|_| ServerError::UnsafeBundlePath)?;
+            files.add(&path, entry)?;
+        }
+    } else if archive.starts_with(ZIP_MAGIC) {
+        let mut archive = ZipArchive::new(Cursor::new(archive)).map_err(invalid_bundle)?;
+        for index in 0..archive.len() {
+            let file = archive.by_index(index).map_err(invalid_bundle)?;
+            if file.is_dir() {
+                continue;
+            }
+            if file.is_symlink() {
+                return Err(ServerError::UnsafeBundlePath);
+            }
+            let path = file.name().to_owned();
+            files.add(&path, file)?;
+        }
+    } else {
+        return Err(ServerError::InvalidBundle);
+    }
+
+    Ok(files.into_root())
+}
+
+pub(crate) fn build_bundle(mut files: BTreeMap<String, Vec<u8>>) -> Result<Bundle, ServerError> {
+    let manifest = files
+        .remove(MANIFEST_NAME)
+        .ok_or(ServerError::InvalidManifest)?;
+    let manifest: Manifest = serde_json::from_slice(&manifest).map_err(|err| {
+        tracing::warn!("Failed to parse bundle manifest: {:?}", err);
+        ServerError::InvalidManifest
+    })?;
+
+    if let Some(date) = &manifest.compatibility_date {
+        NaiveDate::parse_from_str(date, "%Y-%m-%d").map_err(|_| ServerError::InvalidManifest)?;
+    }
+    if !manifest.bindings.keys().all(|name| is_identifier(name)) {
+        return Err(ServerError::InvalidManifest);
+    }
+
+    let entry = safe_path(&manifest.entry).ok_or(ServerError::InvalidManifest)?;
+    let names = match manifest.modules {
+        Some(names) => names
+            .iter()
+            .map(|name| safe_path(name).ok_or(ServerError::InvalidManifest))
+            .collect::<Result<Vec<_>, _>>()?,
+        None => files.keys().cloned().collect(),
+    };
+
+    let mut modules = BTreeMap::new();
+    for name in names {
+        let content = files.remove(&name).ok_or_else(|| {
+            tracing::warn!("Bundle is missing module {}", name);
+            ServerError::InvalidManifest
+        })?;
+        let module =
+            Module::new(ModuleKind::from_name(&name), content).ok_or(ServerError::InvalidBundle)?;
+        modules.insert(name, module);
+    }
+
+    if !modules
+        .get(&entry)
+        .is_some_and(|module| module.kind.is_script())
+    {
+        return Err(ServerError::InvalidManifest);
+    }
+
+    Ok(Bundle {
+        entry,
+        modules,
+        bindings: manifest.bindings,
+        compatibility_date: manifest.compatibility_date,
+    })
+}
+
+/// Normalizes a relative path of an archive, or returns `None` if it could
+/// escape the directory it is unpacked into.
+pub(crate) fn safe_path(path: &str) -> Option<String> {
+    if path.starts_with('/') || path.contains('\\') {
+        return None;
+    }
+
+    let mut parts = Vec::new();
+    for part in path.split('/') {
+        match part {
+            "" | "." => continue,
+            ".." => return None,
+            // Quotes would break out of the Capfile and colons are drive
+            // letters or alternate data streams on Windows.
+            part if part.chars().any(|c| c.is_control() || c == '"' || c == ':') => return None,
+            part => parts.push(part),
+        }
+    }
+
+    (!parts.is_empty()).then(|| parts.join("/"))
+}
+
+pub(crate) fn is_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+fn invalid_bundle(err: impl std::fmt::Debug) -> ServerError {
+    tracing::warn!("Failed to unpack bundle: {:?}", err);
+    ServerError::InvalidBundle
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use flate2::{write::GzEncoder, Compression};
+    use zip::{write::SimpleFileOptions, ZipWriter};
+
+    use super::*;
+
+    const MANIFEST: &str = r#"{
+        "entry": "index.js",
+        "compatibility_date": "2024-09-01",
+        "bindings": {"API_KEY": {"type": "secret", "value": "hunter2"}}
+    }"#;
+
+    fn tar_gz(files: &[(&str, &[u8])]) -> Vec<u8> {
+        let mut builder = tar::Builder::new(GzEncoder::new(Vec::new(), Compression::default()));
+        for (path, content) in files {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(content.len() as u64);
+            header.set_mode(0o644);
+            // `set_path` refuses `..`, which is exactly what is tested.
+            header.as_gnu_mut().unwrap().name[..path.len()].copy_from_slice(path.as_bytes());
+            header.set_cksum();
+            builder.append(&header, *content).unwrap();
+        }
+        builder.into_inner().unwrap().finish().unwrap()
+    }
+
+    fn zip(files: &[(&str, &[u8])]) -> Vec<u8> {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        for (path, content) in files {
+            writer
+                .start_file(path.to_string(), SimpleFileOptions::default())
+                .unwrap();
+            writer.write_all(content).unwrap();
+        }
+        writer.finish().unwrap().into_inner()
+    }
+
+    #[test]
+    fn test_read_tar_gz_bundle() {
+        let bundle = read_bundle(&tar_gz(&[
+            ("app/manifest.json", MANIFEST.as_bytes()),
+            ("app/index.js", b"import add from './lib/add.wasm';"),
+            ("app/lib/add.wasm", &[0, 97, 115, 109]),
+        ]))
+        .unwrap();
+
+        assert_eq!(bundle.entry, "index.js");
+        assert_eq!(
+            bundle.modules.keys().collect::<Vec<_>>(),
+            ["index.js", "lib/add.wasm"]
+        );
+        assert_eq!(bundle.modules["lib/add.wasm"].kind, ModuleKind::Wasm);
+        assert_eq!(bundle.modules["lib/add.wasm"].content, "AGFzbQ==");
+        assert_eq!(bundle.compatibility_date.as_deref(), Some("2024-09-01"));
+        assert_eq!(
+            bundle.bindings["API_KEY"],
+            Binding::Secret {
+                value: "hunter2".to_string()
+            }
+        );
+    }
+
+    #[test]
+    fn test_read_zip_bundle() {
+        let bundle = read_bundle(&zip(&[
+            (
+                "manifest.json",
+                br#"{"entry": "./worker.mjs", "modules": ["worker.mjs"]}"#,
+            ),
+            ("worker.mjs", b"export default {}"),
+            ("README.md", b"not deployed"),
+        ]))
+        .unwrap();
+
+        assert_eq!(bundle.entry, "worker.mjs");
+        assert_eq!(bundle.modules.keys().collect::<Vec<_>>(), ["worker.mjs"]);
+        assert_eq!(bundle.modules["worker.mjs"].content, "export default {}");
+    }
+
+    #[test]
+    fn test_unsafe_paths() {
+        for path in ["../index.js", "/etc/passwd", "a/../../b", "C:\\x", "a\"b"] {
+            assert!(safe_path(path).is_none(), "{path}");
+        }
+        assert_eq!(safe_path("./a//b/./c.js").as_deref(), Some("a/b/c.js"));
+
+        assert!(matches!(
+            read_bundle(&tar_gz(&[
+                ("manifest.json", MANIFEST.as_bytes()),
+                ("../index.js", b""),
+            ])),
+            Err(ServerError::UnsafeBundlePath)
+        ));
+        assert!(matches!(
+            read_bundle(&zip(&[
+                ("manifest.json", MANIFEST.as_bytes()),
+                ("../../index.js", b""),
+            ])),
+            Err(ServerError::UnsafeBundlePath)
+        ));
+    }
+
+    #[test]
+    fn test_invalid_bundles() {
+        assert!(matches!(
+            read_bundle(b"not an archive"),
+            Err(ServerError::InvalidBundle)
+        ));
+        for manifest in [
+            r#"{"entry": "missing.js"}"#,
+            r#"{"entry": "style.css"}"#,
+            r#"{"entry": "index.js", "compatibility_date": "2024-13-01"}"#,
+            r#"{"entry": "index.js", "bindings": {"NOT-VALID": {"type": "text", "value": ""}}}"#,
+            r#"{"entry": "index.js", "unknown": true}"#,
+        ] {
+            assert!(
+                matches!(
+                    read_bundle(&zip(&[
+                        ("manifest.json", manifest.as_bytes()),
+                        ("index.js", b""),
+                        ("style.css", b""),
+                    ])),
+                    Err(ServerError::InvalidManifest)
+                ),
+                "{manifest}"
+            );
+        }
+    }
+}
@@ -1,9 +1,18 @@
-use crate::errors::ConfigError;
+use crate::{
+    auth::{ACCESS_TOKEN_SECS, REFRESH_TOKEN_SECS},
+    errors::ConfigError,
+    invitations::RegistrationMode,
+    keys::{KeyRotation, Keys},
+    logs::WorkerLogs,
+    metrics::{ManagerMetrics, ProxyMetrics},
+    oidc::{OidcClient, OidcConfig},
+    proxy::{http_client, HttpClient, RouteTable},
+};
 use handlebars::Handlebars;
-use jsonwebtoken::{DecodingKey, EncodingKey};
+use jsonwebtoken::Algorithm;
 use service::sea_orm::{Database, DatabaseConnection};
-use std::{borrow::Cow, collections::HashMap, sync::Arc};
-use tokio::sync::{oneshot, Mutex};
+use std::{borrow::Cow, collections::HashMap, path::Path, sync::Arc, time::Duration};
+use tokio::sync::{oneshot, Mutex, Notify, RwLock};
 
 #[derive(Clone)]
 pub struct AppState {
@@ -15,20 +24,37 @@ pub struct AppState {
     pub template_cache: Arc<Mutex<HashMap<String, Handlebars<'static>>>>,
     pub sign_map: Arc<Mutex<HashMap<String, bool>>>,
     pub chan_map: Arc<Mutex<HashMap<String, oneshot::Sender<()>>>>,
-    pub child_map: Arc<Mutex<HashMap<String, tokio::process::Child>>>,
+    /// The task that owns the workerd process of each running worker and
+    /// waits for it to exit.
+    pub child_map: Arc<Mutex<HashMap<String, tokio::task::JoinHandle<()>>>>,
+    pub routes: Arc<RwLock<RouteTable>>,
+    pub http_client: HttpClient,
+    pub proxy_metrics: Arc<ProxyMetrics>,
+    pub manager_metrics: Arc<ManagerMetrics>,
+    pub schedule_notify: Arc<Notify>,
+    pub worker_logs: Arc<WorkerLogs>,
+    /// Set when single sign-on is configured.
+    pub oidc: Option<OidcClient>,
+    /// Set while no users exist and no admin was configured.
+    pub setup_token: Arc<Mutex<Option<String>>>,
 }
 
 impl AppState {
     pub async fn from_env() -> Result<Self, ConfigError> {
         let env = EnvironmentVariables::from_env()?;
+        let manager_metrics = Arc::new(ManagerMetrics::default());
+
+        let mut db = Database::connect(&*env.database_url).await.map_err(|err| {
+            tracing::error!("failed to connect to the database: {:?}", err);
+            ConfigError::FailedDatabaseConnection
+        })?;
+        db.set_metric_callback({
+            let manager_metrics = manager_metrics.clone();
+            move |info| manager_metrics.observe_db(info.elapsed, info.failed)
+        });
+
         Ok(Self {
-            db: Database::connect(&*env.database_url)
-                .await
-                .map_err(|err| {
-                    tracing::error!("failed to connect to the database: {:?}", err);
-                    ConfigError::FailedDatabaseConnection
-                })?
-                .into(),
+            db: db.into(),
             redis_client: redis::Client::open(env.redis_url.as_ref())
                 .map_err(|err| {
                     tracing::error!("failed to connect to Redis: {:?}", err);
@@ -36,12 +62,30 @@ impl AppState {
                 })?
                 .into(),
             env: env.clone(),
-            jwt_auth_keys: Keys::new(env.clone().jwt_secret.as_bytes()),
-            jwt_refresh_keys: Keys::new(env.clone().jwt_refresh_secret.as_bytes()),
+            jwt_auth_keys: load_keys(
+                &env,
+                env.jwt_secret.as_deref(),
+                env.jwt_key_dir.as_deref(),
+                ACCESS_TOKEN_SECS,
+            )?,
+            jwt_refresh_keys: load_keys(
+                &env,
+                env.jwt_refresh_secret.as_deref(),
+                env.jwt_refresh_key_dir.as_deref(),
+                REFRESH_TOKEN_SECS,
+            )?,
             template_cache: Arc::new(Mutex::new(HashMap::new())),
             sign_map: Arc::new(Mutex::new(HashMap::new())),
             chan_map: Arc::new(Mutex::new(HashMap::new())),
             child_map: Arc::new(Mutex::new(HashMap::new())),
+            routes: Arc::new(RwLock::new(RouteTable::default())),
+            http_client: http_client(),
+            proxy_metrics: Arc::new(ProxyMetrics::default()),
+            manager_metrics,
+            schedule_notify: Arc::new(Notify::new()),
+            worker_logs: Arc::new(WorkerLogs::default()),
+            oidc: env.oidc.clone().map(OidcClient::new),
+            setup_token: Arc::new(Mutex::new(None)),
         })
     }
 }
@@ -50,14 +94,29 @@ impl AppState {
 pub struct EnvironmentVariables {
     pub api_listen_addr: Cow<'static, str>,
     pub api_port: u16,
+    pub proxy_listen_addr: Cow<'static, str>,
+    pub proxy_port: Option<u16>,
     pub database_type: Cow<'static, str>,
     pub database_url: Cow<'static, str>,
     pub redis_url: Cow<'static, str>,
-    pub jwt_secret: Cow<'static, str>,
-    pub jwt_refresh_secret: Cow<'static, str>,
+    /// `HS256` with the secrets, or `EdDSA` or `RS256` with the key
+    /// directories.
+    pub jwt_algorithm: Algorithm,
+    pub jwt_secret: Option<Cow<'static, str>>,
+    pub jwt_refresh_secret: Option<Cow<'static, str>>,
+    pub jwt_key_dir: Option<Cow<'static, str>>,
+    pub jwt_refresh_key_dir: Option<Cow<'static, str>>,
+    /// Generates EdDSA keys this often when set.
+    pub jwt_key_rotation_days: Option<u64>,
     pub workerd_dir: Cow<'static, str>,
     pub worker_info_dir: Cow<'static, str>,
     pub workerd_bin_path: Cow<'static, str>,
+    pub metrics_token: Option<Cow<'static, str>>,
+    pub admin_email: Option<Cow<'static, str>>,
+    pub admin_password: Option<Cow<'static, str>>,
+    pub admin_username: Option<Cow<'static, str>>,
+    pub registration_mode: RegistrationMode,
+    pub oidc: Option<OidcConfig>,
 }
 
 impl EnvironmentVariables {
@@ -74,8 +133,10 @@ impl EnvironmentVariables {
             }
         }
 
+        let api_listen_addr = get_env_var("API_LISTEN_ADDR")?;
+
         Ok(Self {
-            api_listen_addr: get_env_var("API_LISTEN_ADDR")?.into(),
+            api_listen_addr: api_listen_addr.clone().into(),
             api_port: match dotenv::var("API_PORT") {
                 Ok(s) => match s.parse::<u16>() {
                     Ok(port) => port,
@@ -83,29 +144,93 @@ impl EnvironmentVariables {
                 },
                 _ => 8000,
             },
+            proxy_listen_addr: dotenv::var("PROXY_LISTEN_ADDR")
+                .unwrap_or(api_listen_addr)
+                .into(),
+            proxy_port: match dotenv::var("PROXY_PORT") {
+                Ok(s) => match s.parse::<u16>() {
+                    Ok(port) => Some(port),
+                    Err(_) => return Err(ConfigError::FailedParseEnvironment),
+                },
+                _ => None,
+            },
             database_type: get_env_var("DATABASE_TYPE")?.into(),
             database_url: get_env_var("DATABASE_URL")?.into(),
             redis_url: get_env_var("REDIS_URL")?.into(),
-            jwt_secret: get_env_var("JWT_SECRET")?.into(),
-            jwt_refresh_secret: get_env_var("JWT_REFRESH_SECRET")?.into(),
+            jwt_algorithm: match dotenv::var("JWT_ALGORITHM") {
+                Ok(s) => match s.parse() {
+                    Ok(algorithm @ (Algorithm::HS256 | Algorithm::EdDSA | Algorithm::RS256)) => {
+                        algorithm
+                    }
+                    _ => return Err(ConfigError::FailedParseEnvironment),
+                },
+                _ => Algorithm::HS256,
+            },
+            jwt_secret: dotenv::var("JWT_SECRET").ok().map(Into::into),
+            jwt_refresh_secret: dotenv::var("JWT_REFRESH_SECRET").ok().map(Into::into),
+            jwt_key_dir: dotenv::var("JWT_KEY_DIR").ok().map(Into::into),
+            jwt_refresh_key_dir: dotenv::var("JWT_REFRESH_KEY_DIR").ok().map(Into::into),
+            jwt_key_rotation_days: match dotenv::var("JWT_KEY_ROTATION_DAYS") {
+                Ok(s) => match s.parse::<u64>() {
+                    Ok(days) if days > 0 => Some(days),
+                    _ => return Err(ConfigError::FailedParseEnvironment),
+                },
+                _ => None,
+            },
             workerd_dir: get_env_var("WORKERD_DIR")?.into(),
             worker_info_dir: get_env_var("WORKER_INFO_DIR")?.into(),
             workerd_bin_path: get_env_var("WORKERD_BIN_PATH")?.into(),
+            metrics_token: dotenv::var("METRICS_TOKEN").ok().map(Into::into),
+            admin_email: dotenv::var("ADMIN_EMAIL").ok().map(Into::into),
+            admin_password: dotenv::var("ADMIN_PASSWORD").ok().map(Into::into),
+            admin_username: dotenv::var("ADMIN_USERNAME").ok().map(Into::into),
+            registration_mode: match dotenv::var("REGISTRATION_MODE") {
+                Ok(s) => match s.parse() {
+                    Ok(mode) => mode,
+                    Err(_) => return Err(ConfigError::FailedParseEnvironment),
+                },
+                _ => RegistrationMode::InviteOnly,
+            },
+            oidc: OidcConfig::from_env()?,
         })
     }
 }
 
-#[derive(Clone)]
-pub struct Keys {
-    pub encoding: EncodingKey,
-    pub decoding: DecodingKey,
-}
+/// Loads the keys of a kind of token: a secret for HMAC, or a key directory
+/// for the asymmetric algorithms.
+fn load_keys(
+    env: &EnvironmentVariables,
+    secret: Option<&str>,
+    dir: Option<&str>,
+    token_lifetime_secs: u64,
+) -> Result<Keys, ConfigError> {
+    if env.jwt_algorithm == Algorithm::HS256 {
+        let Some(secret) = secret else {
+            tracing::error!("missing JWT_SECRET or JWT_REFRESH_SECRET");
+            return Err(ConfigError::FailedReadEnvironment);
+        };
+        return Ok(Keys::from_secret(secret.as_bytes()));
+    }
 
-impl Keys {
-    fn new(secret: &[u8]) -> Self {
-        Self {
-            encoding: EncodingKey::from_secret(secret),
-            decoding: DecodingKey::from_secret(secret),
+    let Some(dir) = dir else {
+        tracing::error!("missing JWT_KEY_DIR or JWT_REFRESH_KEY_DIR");
+        return Err(ConfigError::FailedReadEnvironment);
+    };
+    let rotation = match env.jwt_key_rotation_days {
+        // Only EdDSA keys can be generated.
+        Some(_) if env.jwt_algorithm != Algorithm::EdDSA => {
+            tracing::error!("JWT_KEY_ROTATION_DAYS requires JWT_ALGORITHM=EdDSA");
+            return Err(ConfigError::FailedParseEnvironment);
         }
-    }
+        Some(days) => Some(KeyRotation {
+            interval: Duration::from_secs(days * 24 * 60 * 60),
+            token_lifetime: Duration::from_secs(token_lifetime_secs),
+        }),
+        None => None,
+    };
+
+    Keys::from_dir(env.jwt_algorithm, Path::new(dir), rotation).map_err(|err| {
+        tracing::error!("failed to load keys from {}: {:?}", dir, err);
+        ConfigError::FailedLoadKeys
+    })
 }
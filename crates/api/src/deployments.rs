@@ -0,0 +1,280 @@
+use axum::{
+    debug_handler,
+    extract::{Path, State},
+    http::header,
+    response::IntoResponse,
+    Json,
+};
+use entity::{deployment, worker};
+use service::{
+    deployments::{diff_deployments, Mutation, NewDeployment, Query},
+    sea_orm::prelude::Uuid,
+    workers::Query as WorkerQuery,
+};
+use sha2::{Digest, Sha256};
+
+use crate::{
+    auth::AccessTokenClaims,
+    config::AppState,
+    errors::ServerError,
+    scopes::{Scoped, WorkersRead, WorkersWrite},
+    teams::{authorize_worker, WorkerAccess},
+    workerd::{render_capfile, restart_worker, write_worker_files, Worker},
+    workers::MessageResponse,
+};
+
+#[derive(serde::Deserialize, serde::Serialize)]
+pub struct DeploymentCreateRequest {
+    pub entry: Option<String>,
+    pub code: Option<String>,
+    pub template: Option<String>,
+}
+
+#[derive(serde::Deserialize, serde::Serialize)]
+pub struct DeploymentInfoResponse {
+    pub id: String,
+    pub worker_id: String,
+    /// Unset once the author is deleted.
+    pub author_id: Option<String>,
+    pub entry: String,
+    pub template: Option<String>,
+    pub content_hash: String,
+    pub created_at: String,
+    pub active: bool,
+}
+
+impl DeploymentInfoResponse {
+    pub(crate) fn new(deployment: deployment::Model, active_id: Option<Uuid>) -> Self {
+        Self {
+            id: deployment.id.to_string(),
+            worker_id: deployment.worker_id.to_string(),
+            author_id: deployment.author_id.map(|id| id.to_string()),
+            entry: deployment.entry,
+            template: deployment.template,
+            content_hash: deployment.content_hash,
+            created_at: deployment.created_at.to_rfc3339(),
+            active: active_id == Some(deployment.id),
+        }
+    }
+}
+
+#[debug_handler]
+pub async fn get_deployments(
+    State(state): State<AppState>,
+    Scoped(claims, _): Scoped<WorkersRead>,
+    Path(id): Path<String>,
+) -> Result<Json<Vec<DeploymentInfoResponse>>, ServerError> {
+    let worker = get_owned_worker(&state, &claims, id.to_owned(), WorkerAccess::Read).await?;
+
+    let deployments = Query::find_deployments_by_worker_id(&state.db, id)
+        .await
+        .map_err(|err| {
+            tracing::error!("Failed to get deployments: {:?}", err);
+            ServerError::InternalServerError
+        })?;
+
+    Ok(Json(
+        deployments
+            .into_iter()
+            .map(|deployment| DeploymentInfoResponse::new(deployment, worker.deployment_id))
+            .collect(),
+    ))
+}
+
+#[debug_handler]
+pub async fn create_deployment(
+    State(state): State<AppState>,
+    Scoped(claims, _): Scoped<WorkersWrite>,
+    Path(id): Path<String>,
+    Json(deployment_request): Json<DeploymentCreateRequest>,
+) -> Result<Json<DeploymentInfoResponse>, ServerError> {
+    let mut worker: Worker = get_owned_worker(&state, &claims, id, WorkerAccess::Write)
+        .await?
+        .into();
+    if deployment_request.entry.is_some() || deployment_request.code.is_some() {
+        // Plain code replaces the modules of a previously uploaded bundle.
+        worker.modules = None;
+    }
+    worker.entry = deployment_request.entry.unwrap_or(worker.entry);
+    worker.code = deployment_request.code.unwrap_or(worker.code);
+    worker.template = deployment_request.template.or(worker.template);
+
+    let deployment = record_deployment(&state, claims.sub, &worker, true).await?;
+    go_live(&state, &worker).await?;
+
+    let active_id = Some(deployment.id);
+    Ok(Json(DeploymentInfoResponse::new(deployment, active_id)))
+}
+
+#[debug_handler]
+pub async fn rollback_deployment(
+    State(state): State<AppState>,
+    Scoped(claims, _): Scoped<WorkersWrite>,
+    Path((id, did)): Path<(String, String)>,
+) -> Result<Json<MessageResponse>, ServerError> {
+    let worker = get_owned_worker(&state, &claims, id, WorkerAccess::Write).await?;
+
+    let deployment = Query::find_deployment_by_id(&state.db, did.to_owned())
+        .await
+        .map_err(|err| {
+            tracing::error!("Failed to get deployment: {:?}", err);
+            ServerError::InternalServerError
+        })?
+        .filter(|deployment| deployment.worker_id == worker.id)
+        .ok_or(ServerError::NotFound)?;
+
+    let worker = Mutation::activate_deployment(&state.db, &deployment)
+        .await
+        .map_err(|err| {
+            tracing::error!("Failed to activate deployment: {:?}", err);
+            ServerError::InternalServerError
+        })?;
+    go_live(&state, &worker.into()).await?;
+
+    Ok(Json(MessageResponse {
+        message: format!("Rolled back to deployment {}", did),
+    }))
+}
+
+#[debug_handler]
+pub async fn get_deployment_diff(
+    State(state): State<AppState>,
+    Scoped(claims, _): Scoped<WorkersRead>,
+    Path((id, a, b)): Path<(String, String, String)>,
+) -> Result<impl IntoResponse, ServerError> {
+    let worker = get_owned_worker(&state, &claims, id, WorkerAccess::Read).await?;
+
+    let mut deployments = Vec::with_capacity(2);
+    for did in [a, b] {
+        let deployment = Query::find_deployment_by_id(&state.db, did)
+            .await
+            .map_err(|err| {
+                tracing::error!("Failed to get deployment: {:?}", err);
+                ServerError::InternalServerError
+            })?
+            .filter(|deployment| deployment.worker_id == worker.id)
+            .ok_or(ServerError::NotFound)?;
+        deployments.push(deployment);
+    }
+
+    Ok((
+        [(header::CONTENT_TYPE, "text/x-diff; charset=utf-8")],
+        diff_deployments(&deployments[0], &deployments[1]),
+    ))
+}
+
+/// Snapshots the worker's code, modules, bindings and rendered Capfile as a
+/// new deployment, marking it as the live one if `live` is set.
+pub async fn record_deployment(
+    state: &AppState,
+    author_id: String,
+    worker: &Worker,
+    live: bool,
+) -> Result<deployment::Model, ServerError> {
+    let deployment = new_deployment(state, author_id, worker).await?;
+
+    Mutation::create_deployment(&state.db, deployment, live)
+        .await
+        .map_err(|err| {
+            tracing::error!("Failed to create deployment: {:?}", err);
+            ServerError::InternalServerError
+        })
+}
+
+/// Builds the snapshot stored by [`record_deployment`]. Secrets are masked in
+/// the stored Capfile.
+pub(crate) async fn new_deployment(
+    state: &AppState,
+    author_id: String,
+    worker: &Worker,
+) -> Result<NewDeployment, ServerError> {
+    let capfile = render_capfile(state, worker, true).await?;
+    let (modules, bindings) = (
+        worker.modules.as_ref().map(serde_json::to_value),
+        serde_json::to_value(&worker.bindings),
+    );
+    let (modules, bindings) = match (modules.transpose(), bindings) {
+        (Ok(modules), Ok(bindings)) => (modules, bindings),
+        (Err(err), _) | (_, Err(err)) => {
+            tracing::error!("Failed to serialize bundle: {:?}", err);
+            return Err(ServerError::InternalServerError);
+        }
+    };
+    let content_hash = content_hash(&[
+        &worker.entry,
+        &worker.code,
+        &modules.as_ref().map(|m| m.to_string()).unwrap_or_default(),
+        // The Capfile has secrets masked, so bindings are hashed as stored
+        // for rotated secrets to count as a change.
+        &bindings.to_string(),
+        &capfile,
+    ]);
+
+    Ok(NewDeployment {
+        worker_id: worker.id.clone(),
+        author_id,
+        entry: worker.entry.clone(),
+        code: worker.code.clone(),
+        template: worker.template.clone(),
+        capfile,
+        content_hash,
+        modules,
+        bindings,
+        compatibility_date: worker.compatibility_date.clone(),
+    })
+}
+
+/// Regenerates the files of the worker and restarts it if it is running.
+pub(crate) async fn go_live(state: &AppState, worker: &Worker) -> Result<(), ServerError> {
+    write_worker_files(state, worker).await?;
+    if restart_worker(state, worker).await? {
+        tracing::info!("Restarted {} with a new deployment", worker.id);
+    }
+    Ok(())
+}
+
+pub(crate) async fn get_owned_worker(
+    state: &AppState,
+    claims: &AccessTokenClaims,
+    id: String,
+    access: WorkerAccess,
+) -> Result<worker::Model, ServerError> {
+    let worker = WorkerQuery::find_worker_by_id(&state.db, id)
+        .await
+        .map_err(|err| {
+            tracing::error!("Failed to get worker: {:?}", err);
+            ServerError::InternalServerError
+        })?
+        .ok_or(ServerError::NotFound)?;
+
+    authorize_worker(state, claims, &worker, access).await?;
+
+    Ok(worker)
+}
+
+/// Hashes the parts of a deployment, length prefixed so that moving bytes
+/// between parts changes the hash.
+fn content_hash(parts: &[&str]) -> String {
+    let mut hasher = Sha256::new();
+    for part in parts {
+        hasher.update(part.len().to_le_bytes());
+        hasher.update(part);
+    }
+    format!("{:x}", hasher.finalize())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_content_hash() {
+        let hash = content_hash(&["entry.js", "code", "capfile"]);
+        assert_eq!(hash, content_hash(&["entry.js", "code", "capfile"]));
+        assert_ne!(hash, content_hash(&["entry.js", "code", "capfile2"]));
+        assert_ne!(
+            content_hash(&["a", "bc", "capfile"]),
+            content_hash(&["ab", "c", "capfile"])
+        );
+    }
+}
@@ -11,6 +11,7 @@ pub enum ConfigError {
     FailedParseEnvironment,
     FailedDatabaseConnection,
     FailedRedisConnection,
+    FailedLoadKeys,
 }
 
 #[derive(Debug)]
@@ -33,11 +34,58 @@ pub enum ServerError {
     WorkerNotRunning,
     WorkerNotFound,
     FailedStartWorker,
+    RouteNotFound,
+    BadGateway,
+    InvalidCronExpression,
+    RolloutInProgress,
+    InvalidRollout,
+    InvalidBundle,
+    InvalidManifest,
+    UnsafeBundlePath,
+    BundleTooLarge,
+    InvalidWorkspaceManifest,
+    InvalidRole,
+    CannotRevokeOwnAdmin,
+    SetupUnavailable,
+    InvalidSetupToken,
+    RegistrationClosed,
+    InvitationRequired,
+    InvalidInvitation,
+    InvalidInvitationExpiry,
+    UserNotActive,
+    CannotSuspendSelf,
+    InvalidTokenRequest,
+    MissingWorkersReadScope,
+    MissingWorkersWriteScope,
+    MissingWorkersExecScope,
+    MissingUsersReadScope,
+    MissingUsersWriteScope,
+    MissingUsersAdminScope,
+    InvalidTeamName,
+    TeamNameTaken,
+    LastTeamOwner,
+    InvalidRoleName,
+    OidcNotConfigured,
+    OidcFailed,
+    OidcUnknownUser,
+    OidcLinkRequired,
+    OidcIdentityTaken,
+    InvalidTwoFactorChallenge,
+    InvalidTwoFactorCode,
+    TotpAlreadyEnabled,
+    TotpNotEnrolled,
+    TwoFactorRequired,
+    InvalidHostName,
+    InvalidEntry,
+    InvalidTemplate,
+    NoFreePort,
+    InvalidSchedulePath,
 }
 
-impl IntoResponse for ServerError {
-    fn into_response(self) -> Response {
-        let (status, error_message) = match self {
+impl ServerError {
+    /// The status and message the error is answered with.
+    pub fn status_and_message(&self) -> (StatusCode, &'static str) {
+        match self {
             ServerError::WrongCredentials => (StatusCode::UNAUTHORIZED, "Wrong credentials"),
             ServerError::MissingCredentials => (StatusCode::BAD_REQUEST, "Missing credentials"),
             ServerError::TokenCreation => {
@@ -81,7 +129,133 @@ impl IntoResponse for ServerError {
             ServerError::FailedStartWorker => {
                 (StatusCode::INTERNAL_SERVER_ERROR, "Failed to start worker")
             }
-        };
+            ServerError::RouteNotFound => (
+                StatusCode::NOT_FOUND,
+                "No worker is routed for this host and path",
+            ),
+            ServerError::BadGateway => (StatusCode::BAD_GATEWAY, "Worker is unreachable"),
+            ServerError::InvalidCronExpression => {
+                (StatusCode::BAD_REQUEST, "Invalid cron expression")
+            }
+            ServerError::RolloutInProgress => {
+                (StatusCode::CONFLICT, "A rollout is already in progress")
+            }
+            ServerError::InvalidRollout => {
+                (StatusCode::BAD_REQUEST, "Invalid rollout configuration")
+            }
+            ServerError::InvalidBundle => (StatusCode::BAD_REQUEST, "Invalid bundle archive"),
+            ServerError::InvalidManifest => (StatusCode::BAD_REQUEST, "Invalid bundle manifest"),
+            ServerError::UnsafeBundlePath => {
+                (StatusCode::BAD_REQUEST, "Bundle contains an unsafe path")
+            }
+            ServerError::BundleTooLarge => (StatusCode::PAYLOAD_TOO_LARGE, "Bundle is too large"),
+            ServerError::InvalidWorkspaceManifest => {
+                (StatusCode::BAD_REQUEST, "Invalid workspace manifest")
+            }
+            ServerError::InvalidRole => (StatusCode::BAD_REQUEST, "Unknown role"),
+            ServerError::CannotRevokeOwnAdmin => (
+                StatusCode::BAD_REQUEST,
+                "Admins cannot revoke their own admin role",
+            ),
+            ServerError::SetupUnavailable => {
+                (StatusCode::CONFLICT, "Setup has already been completed")
+            }
+            ServerError::InvalidSetupToken => (StatusCode::UNAUTHORIZED, "Invalid setup token"),
+            ServerError::RegistrationClosed => (StatusCode::FORBIDDEN, "Registration is closed"),
+            ServerError::InvitationRequired => {
+                (StatusCode::FORBIDDEN, "An invitation code is required")
+            }
+            ServerError::InvalidInvitation => {
+                (StatusCode::FORBIDDEN, "Invalid or expired invitation code")
+            }
+            ServerError::InvalidInvitationExpiry => {
+                (StatusCode::BAD_REQUEST, "Invalid invitation expiry")
+            }
+            ServerError::UserNotActive => (StatusCode::FORBIDDEN, "User is not active"),
+            ServerError::CannotSuspendSelf => {
+                (StatusCode::BAD_REQUEST, "Admins cannot suspend themselves")
+            }
+            ServerError::InvalidTokenRequest => (
+                StatusCode::BAD_REQUEST,
+                "Invalid token name, expiry or scopes",
+            ),
+            ServerError::MissingWorkersReadScope => (
+                StatusCode::FORBIDDEN,
+                "Token is missing the workers:read scope",
+            ),
+            ServerError::MissingWorkersWriteScope => (
+                StatusCode::FORBIDDEN,
+                "Token is missing the workers:write scope",
+            ),
+            ServerError::MissingWorkersExecScope => (
+                StatusCode::FORBIDDEN,
+                "Token is missing the workers:exec scope",
+            ),
+            ServerError::MissingUsersReadScope => (
+                StatusCode::FORBIDDEN,
+                "Token is missing the users:read scope",
+            ),
+            ServerError::MissingUsersWriteScope => (
+                StatusCode::FORBIDDEN,
+                "Token is missing the users:write scope",
+            ),
+            ServerError::MissingUsersAdminScope => (
+                StatusCode::FORBIDDEN,
+                "Token is missing the users:admin scope",
+            ),
+            ServerError::InvalidTeamName => (StatusCode::BAD_REQUEST, "Invalid team name"),
+            ServerError::TeamNameTaken => (StatusCode::CONFLICT, "Team name is already taken"),
+            ServerError::LastTeamOwner => (
+                StatusCode::BAD_REQUEST,
+                "A team must keep at least one owner",
+            ),
+            ServerError::InvalidRoleName => (StatusCode::BAD_REQUEST, "Invalid role name"),
+            ServerError::OidcNotConfigured => {
+                (StatusCode::NOT_FOUND, "Single sign-on is not configured")
+            }
+            ServerError::OidcFailed => (StatusCode::UNAUTHORIZED, "Single sign-on failed"),
+            ServerError::OidcUnknownUser => {
+                (StatusCode::FORBIDDEN, "No user is linked to this identity")
+            }
+            ServerError::OidcLinkRequired => (
+                StatusCode::FORBIDDEN,
+                "A user has this email, log in to link the identity to it",
+            ),
+            ServerError::OidcIdentityTaken => (
+                StatusCode::CONFLICT,
+                "This identity is linked to another user",
+            ),
+            ServerError::InvalidTwoFactorChallenge => (
+                StatusCode::UNAUTHORIZED,
+                "Invalid or expired two-factor challenge",
+            ),
+            ServerError::InvalidTwoFactorCode => {
+                (StatusCode::UNAUTHORIZED, "Invalid two-factor code")
+            }
+            ServerError::TotpAlreadyEnabled => (
+                StatusCode::CONFLICT,
+                "Two-factor authentication is already enabled",
+            ),
+            ServerError::TotpNotEnrolled => (
+                StatusCode::BAD_REQUEST,
+                "Two-factor authentication is not set up",
+            ),
+            ServerError::TwoFactorRequired => (
+                StatusCode::BAD_REQUEST,
+                "Two-factor authentication is required for this account",
+            ),
+            ServerError::InvalidHostName => (StatusCode::BAD_REQUEST, "Invalid host name"),
+            ServerError::InvalidEntry => (StatusCode::BAD_REQUEST, "Invalid entry module"),
+            ServerError::InvalidTemplate => (StatusCode::BAD_REQUEST, "Invalid Capfile template"),
+            ServerError::NoFreePort => (StatusCode::CONFLICT, "No port is free for the worker"),
+            ServerError::InvalidSchedulePath => (StatusCode::BAD_REQUEST, "Invalid schedule path"),
+        }
+    }
+}
+
+impl IntoResponse for ServerError {
+    fn into_response(self) -> Response {
+        let (status, error_message) = self.status_and_message();
         let body = Json(json!({
             "message": error_message,
         }));
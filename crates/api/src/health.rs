@@ -0,0 +1,129 @@
+use std::{
+    collections::BTreeMap,
+    future::Future,
+    os::unix::fs::PermissionsExt,
+    sync::atomic::{AtomicU64, Ordering},
+    time::{Duration, Instant},
+};
+
+use axum::{extract::State, http::StatusCode, Json};
+use serde::Serialize;
+use serde_json::{json, Value};
+use tokio::fs;
+
+use crate::config::AppState;
+
+/// How long a single check may take before it counts as failed.
+const CHECK_TIMEOUT: Duration = Duration::from_secs(5);
+
+static PROBE_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+#[derive(Debug, Serialize)]
+pub struct CheckResult {
+    pub ok: bool,
+    pub latency_ms: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ReadinessResponse {
+    pub ready: bool,
+    pub checks: BTreeMap<&'static str, CheckResult>,
+}
+
+/// Liveness probe: answers as long as the process is able to serve requests.
+pub async fn healthz() -> Json<Value> {
+    Json(json!({ "status": "ok" }))
+}
+
+/// Readiness probe: checks every dependency the manager needs to run workers
+/// and answers `503 Service Unavailable` if any of them fails.
+pub async fn readyz(State(state): State<AppState>) -> (StatusCode, Json<ReadinessResponse>) {
+    let (database, redis, workerd_bin, workerd_dir) = tokio::join!(
+        timed(check_database(&state)),
+        timed(check_redis(&state)),
+        timed(check_workerd_bin(&state)),
+        timed(check_workerd_dir(&state)),
+    );
+
+    let checks = BTreeMap::from([
+        ("database", database),
+        ("redis", redis),
+        ("workerd_bin", workerd_bin),
+        ("workerd_dir", workerd_dir),
+    ]);
+    let ready = checks.values().all(|check| check.ok);
+    let status = if ready {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+
+    (status, Json(ReadinessResponse { ready, checks }))
+}
+
+async fn timed(check: impl Future<Output = Result<(), String>>) -> CheckResult {
+    let started_at = Instant::now();
+    let result = match tokio::time::timeout(CHECK_TIMEOUT, check).await {
+        Ok(result) => result,
+        Err(_) => Err(format!("timed out after {}s", CHECK_TIMEOUT.as_secs())),
+    };
+    let latency_ms = started_at.elapsed().as_secs_f64() * 1000.0;
+
+    if let Err(err) = &result {
+        tracing::warn!("Readiness check failed: {}", err);
+    }
+
+    CheckResult {
+        ok: result.is_ok(),
+        latency_ms,
+        error: result.err(),
+    }
+}
+
+async fn check_database(state: &AppState) -> Result<(), String> {
+    state.db.ping().await.map_err(|err| err.to_string())
+}
+
+async fn check_redis(state: &AppState) -> Result<(), String> {
+    let redis_client = state.redis_client.clone();
+    tokio::task::spawn_blocking(move || {
+        let mut con = redis_client.get_connection()?;
+        redis::cmd("PING").query::<String>(&mut con)
+    })
+    .await
+    .map_err(|err| err.to_string())?
+    .map(|_| ())
+    .map_err(|err| err.to_string())
+}
+
+async fn check_workerd_bin(state: &AppState) -> Result<(), String> {
+    let path = state.env.workerd_bin_path.to_string();
+    let metadata = fs::metadata(&path)
+        .await
+        .map_err(|err| format!("{path}: {err}"))?;
+
+    if !metadata.is_file() {
+        return Err(format!("{path} is not a file"));
+    }
+    if metadata.permissions().mode() & 0o111 == 0 {
+        return Err(format!("{path} is not executable"));
+    }
+    Ok(())
+}
+
+async fn check_workerd_dir(state: &AppState) -> Result<(), String> {
+    let path = std::path::PathBuf::from(state.env.workerd_dir.to_string()).join(format!(
+        ".readyz-{}-{}",
+        std::process::id(),
+        PROBE_COUNTER.fetch_add(1, Ordering::Relaxed)
+    ));
+
+    fs::write(&path, b"")
+        .await
+        .map_err(|err| format!("{}: {err}", path.display()))?;
+    fs::remove_file(&path)
+        .await
+        .map_err(|err| format!("{}: {err}", path.display()))
+}
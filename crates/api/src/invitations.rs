@@ -0,0 +1,275 @@
+use std::str::FromStr;
+
+use axum::{
+    debug_handler,
+    extract::{Path, State},
+    Json,
+};
+use chrono::{Duration, Utc};
+use entity::invitation;
+use serde::{Deserialize, Serialize};
+use service::{
+    invitations::{Mutation, Query},
+    settings::{Mutation as SettingMutation, Query as SettingQuery},
+};
+
+use crate::{
+    auth::{generate_secret, hash_secret},
+    config::AppState,
+    errors::ServerError,
+    policy::{authorize, Permission},
+    scopes::{Scoped, UsersAdmin},
+    users::MessageResponse,
+};
+
+const REGISTRATION_MODE_KEY: &str = "registration_mode";
+const DEFAULT_INVITATION_HOURS: u32 = 72;
+const MAX_INVITATION_HOURS: u32 = 30 * 24;
+
+/// Who may create an account through `POST /users`. Admins always can.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RegistrationMode {
+    Open,
+    InviteOnly,
+    Closed,
+}
+
+impl RegistrationMode {
+    fn as_str(self) -> &'static str {
+        match self {
+            RegistrationMode::Open => "open",
+            RegistrationMode::InviteOnly => "invite_only",
+            RegistrationMode::Closed => "closed",
+        }
+    }
+}
+
+impl FromStr for RegistrationMode {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "open" => Ok(RegistrationMode::Open),
+            "invite_only" => Ok(RegistrationMode::InviteOnly),
+            "closed" => Ok(RegistrationMode::Closed),
+            _ => Err(()),
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct RegistrationSettings {
+    pub mode: RegistrationMode,
+}
+
+#[derive(Deserialize)]
+pub struct InvitationCreateRequest {
+    /// Restricts the invitation to this email address.
+    pub email: Option<String>,
+    pub expires_in_hours: Option<u32>,
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct InvitationResponse {
+    pub id: String,
+    /// Only returned when the invitation is created.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub code: Option<String>,
+    pub email: Option<String>,
+    pub created_by: String,
+    pub used_by: Option<String>,
+    pub expires_at: String,
+    pub used_at: Option<String>,
+    pub created_at: String,
+}
+
+impl From<invitation::Model> for InvitationResponse {
+    fn from(invitation: invitation::Model) -> Self {
+        Self {
+            id: invitation.id.to_string(),
+            code: None,
+            email: invitation.email,
+            created_by: invitation.created_by.to_string(),
+            used_by: invitation.used_by.map(|id| id.to_string()),
+            expires_at: invitation.expires_at.to_rfc3339(),
+            used_at: invitation.used_at.map(|at| at.to_rfc3339()),
+            created_at: invitation.created_at.to_rfc3339(),
+        }
+    }
+}
+
+/// Returns the stored registration mode, or the one configured with
+/// `REGISTRATION_MODE` until an admin changes it.
+pub async fn registration_mode(state: &AppState) -> Result<RegistrationMode, ServerError> {
+    let value = SettingQuery::find_setting(&state.db, REGISTRATION_MODE_KEY)
+        .await
+        .map_err(|err| {
+            tracing::error!("Failed to get registration mode: {:?}", err);
+            ServerError::InternalServerError
+        })?;
+
+    Ok(match value {
+        Some(value) => value.parse().unwrap_or_else(|_| {
+            tracing::warn!("Invalid registration mode {:?}", value);
+            state.env.registration_mode
+        }),
+        None => state.env.registration_mode,
+    })
+}
+
+/// Finds the invitation a code belongs to, if it can still create an
+/// account for the email address.
+pub async fn find_usable_invitation(
+    state: &AppState,
+    code: Option<&str>,
+    email: &str,
+) -> Result<invitation::Model, ServerError> {
+    let code = code.ok_or(ServerError::InvitationRequired)?;
+    let invitation = Query::find_invitation_by_code_hash(&state.db, hash_secret(code))
+        .await
+        .map_err(|err| {
+            tracing::error!("Failed to get invitation: {:?}", err);
+            ServerError::InternalServerError
+        })?
+        .ok_or(ServerError::InvalidInvitation)?;
+
+    let usable = invitation.used_at.is_none()
+        && invitation.expires_at > Utc::now()
+        && invitation
+            .email
+            .as_ref()
+            .is_none_or(|invited| invited.eq_ignore_ascii_case(email));
+    if !usable {
+        return Err(ServerError::InvalidInvitation);
+    }
+    Ok(invitation)
+}
+
+#[debug_handler]
+pub async fn get_registration(
+    State(state): State<AppState>,
+    Scoped(claims, _): Scoped<UsersAdmin>,
+) -> Result<Json<RegistrationSettings>, ServerError> {
+    authorize(&claims, Permission::SettingsRead)?;
+
+    Ok(Json(RegistrationSettings {
+        mode: registration_mode(&state).await?,
+    }))
+}
+
+#[debug_handler]
+pub async fn update_registration(
+    State(state): State<AppState>,
+    Scoped(claims, _): Scoped<UsersAdmin>,
+    Json(settings): Json<RegistrationSettings>,
+) -> Result<Json<RegistrationSettings>, ServerError> {
+    authorize(&claims, Permission::SettingsManage)?;
+
+    SettingMutation::set_setting(
+        &state.db,
+        REGISTRATION_MODE_KEY,
+        settings.mode.as_str().to_owned(),
+    )
+    .await
+    .map_err(|err| {
+        tracing::error!("Failed to update registration mode: {:?}", err);
+        ServerError::InternalServerError
+    })?;
+
+    Ok(Json(settings))
+}
+
+#[debug_handler]
+pub async fn create_invitation(
+    State(state): State<AppState>,
+    Scoped(claims, _): Scoped<UsersAdmin>,
+    Json(request): Json<InvitationCreateRequest>,
+) -> Result<Json<InvitationResponse>, ServerError> {
+    authorize(&claims, Permission::UsersManage)?;
+
+    let hours = request.expires_in_hours.unwrap_or(DEFAULT_INVITATION_HOURS);
+    if hours == 0 || hours > MAX_INVITATION_HOURS {
+        return Err(ServerError::InvalidInvitationExpiry);
+    }
+
+    let code = generate_secret();
+    let invitation = Mutation::create_invitation(
+        &state.db,
+        hash_secret(&code),
+        request.email.filter(|email| !email.is_empty()),
+        claims.sub,
+        (Utc::now() + Duration::hours(hours.into())).into(),
+    )
+    .await
+    .map_err(|err| {
+        tracing::error!("Failed to create invitation: {:?}", err);
+        ServerError::InternalServerError
+    })?;
+
+    Ok(Json(InvitationResponse {
+        code: Some(code),
+        ..invitation.into()
+    }))
+}
+
+#[debug_handler]
+pub async fn get_invitations(
+    State(state): State<AppState>,
+    Scoped(claims, _): Scoped<UsersAdmin>,
+) -> Result<Json<Vec<InvitationResponse>>, ServerError> {
+    authorize(&claims, Permission::UsersRead)?;
+
+    let invitations = Query::find_all_invitations(&state.db)
+        .await
+        .map_err(|err| {
+            tracing::error!("Failed to get invitations: {:?}", err);
+            ServerError::InternalServerError
+        })?;
+
+    Ok(Json(invitations.into_iter().map(Into::into).collect()))
+}
+
+#[debug_handler]
+pub async fn delete_invitation(
+    State(state): State<AppState>,
+    Scoped(claims, _): Scoped<UsersAdmin>,
+    Path(id): Path<String>,
+) -> Result<Json<MessageResponse>, ServerError> {
+    authorize(&claims, Permission::UsersManage)?;
+
+    let result = Mutation::delete_invitation(&state.db, id)
+        .await
+        .map_err(|err| {
+            tracing::error!("Failed to delete invitation: {:?}", err);
+            ServerError::InternalServerError
+        })?;
+    if result.rows_affected == 0 {
+        return Err(ServerError::NotFound);
+    }
+
+    Ok(Json(MessageResponse {
+        message: "Invitation deleted successfully".to_owned(),
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_registration_mode() {
+        for mode in [
+            RegistrationMode::Open,
+            RegistrationMode::InviteOnly,
+            RegistrationMode::Closed,
+        ] {
+            assert_eq!(mode.as_str().parse(), Ok(mode));
+            assert_eq!(
+                serde_json::to_string(&mode).unwrap(),
+                format!("\"{}\"", mode.as_str())
+            );
+        }
+        assert!("invite-only".parse::<RegistrationMode>().is_err());
+    }
+}
@@ -1,21 +1,78 @@
+pub mod apply;
 pub mod auth;
+pub mod bundle;
 pub mod config;
+pub mod deployments;
 pub mod errors;
+pub mod health;
+pub mod invitations;
+pub mod keys;
+pub mod logs;
+pub mod metrics;
+pub mod oidc;
+pub mod outbound;
+pub mod policy;
+pub mod proxy;
+pub mod roles;
+pub mod rollouts;
+pub mod scheduler;
+pub mod schedules;
+pub mod scopes;
+pub mod security_events;
+pub mod sessions;
+pub mod setup;
+pub mod teams;
+pub mod tokens;
+pub mod transfer;
+pub mod two_factor;
 pub mod users;
 pub mod workerd;
 pub mod workers;
 
 use crate::config::AppState;
 use crate::errors::ServerError;
-use auth::{login, refresh_token};
+use apply::apply;
+use auth::{get_jwks, login, logout, logout_all, refresh_token};
 use axum::{
     http::{self, Method},
-    routing::{delete, get, post},
+    middleware,
+    routing::{delete, get, patch, post, put},
     Router,
 };
+use bundle::upload_bundle;
+use deployments::{create_deployment, get_deployment_diff, get_deployments, rollback_deployment};
+use health::{healthz, readyz};
+use invitations::{
+    create_invitation, delete_invitation, get_invitations, get_registration, update_registration,
+};
+use logs::get_worker_logs;
+use metrics::{get_metrics, get_worker_metrics, track_http_metrics};
+use oidc::{oidc_callback, oidc_link, oidc_login};
+use roles::{delete_role, get_roles, set_role};
+use rollouts::{abort_rollout, create_rollout, get_rollout, promote_rollout, update_rollout_split};
+use schedules::{
+    create_schedule, delete_schedule, get_schedule_runs, get_schedules, update_schedule,
+};
+use security_events::get_security_events;
+use sessions::{delete_session, get_sessions};
+use setup::setup;
+use std::net::SocketAddr;
+use teams::{
+    create_team, delete_team, get_team, get_teams, remove_team_member, set_team_member,
+    set_worker_team,
+};
+use tokens::{create_token, delete_token, get_tokens};
 use tower_http::cors::{Any, CorsLayer};
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
-use users::{create_user, delete_user, get_all_users, get_user, update_user};
+use transfer::{export_worker, import_worker};
+use two_factor::{
+    complete_login, confirm_totp, delete_totp, enroll_login, get_totp, get_two_factor_settings,
+    regenerate_recovery_codes, start_totp, update_two_factor_settings,
+};
+use users::{
+    create_user, delete_user, get_all_users, get_user, grant_role, reactivate_user, revoke_role,
+    suspend_user, update_user,
+};
 use workerd::{delete_file, exit_cmd, run_cmd, write_worker_code, write_worker_config_capfile};
 use workers::{create_worker, delete_worker, get_all_workers, get_worker, update_worker};
 
@@ -43,16 +100,66 @@ pub async fn start() {
     let state = AppState::from_env()
         .await
         .expect("Failed to load configuration");
+    setup::bootstrap(&state)
+        .await
+        .expect("Failed to create the first admin");
 
     let app = Router::new()
         .route("/", get(index))
+        .route("/.well-known/jwks.json", get(get_jwks))
+        .route("/healthz", get(healthz))
+        .route("/readyz", get(readyz))
+        .route("/metrics", get(get_metrics))
         .route("/auth/login", post(login))
+        .route("/auth/login/two-factor", post(complete_login))
+        .route("/auth/login/two-factor/enroll", post(enroll_login))
         .route("/auth/refresh-tokens", post(refresh_token))
+        .route("/auth/logout", post(logout))
+        .route("/auth/logout-all", post(logout_all))
+        .route("/auth/sessions", get(get_sessions))
+        .route("/auth/sessions/:id", delete(delete_session))
+        .route("/auth/oidc/login", get(oidc_login))
+        .route("/auth/oidc/callback", get(oidc_callback))
+        .route("/auth/oidc/link", post(oidc_link))
+        .route(
+            "/auth/totp",
+            get(get_totp).post(start_totp).delete(delete_totp),
+        )
+        .route("/auth/totp/confirm", post(confirm_totp))
+        .route("/auth/totp/recovery-codes", post(regenerate_recovery_codes))
+        .route("/setup", post(setup))
         .route("/users", get(get_all_users).post(create_user))
         .route(
             "/users/:id",
             get(get_user).patch(update_user).delete(delete_user),
         )
+        .route(
+            "/users/:id/roles/:role",
+            put(grant_role).delete(revoke_role),
+        )
+        .route("/users/:id/suspend", post(suspend_user))
+        .route("/users/:id/reactivate", post(reactivate_user))
+        .route("/users/:id/security-events", get(get_security_events))
+        .route("/users/:id/tokens", get(get_tokens).post(create_token))
+        .route("/users/:id/tokens/:token_id", delete(delete_token))
+        .route("/roles", get(get_roles))
+        .route("/roles/:name", put(set_role).delete(delete_role))
+        .route("/teams", get(get_teams).post(create_team))
+        .route("/teams/:id", get(get_team).delete(delete_team))
+        .route(
+            "/teams/:id/members/:user_id",
+            put(set_team_member).delete(remove_team_member),
+        )
+        .route(
+            "/settings/registration",
+            get(get_registration).put(update_registration),
+        )
+        .route(
+            "/settings/two-factor",
+            get(get_two_factor_settings).put(update_two_factor_settings),
+        )
+        .route("/invitations", get(get_invitations).post(create_invitation))
+        .route("/invitations/:id", delete(delete_invitation))
         .route("/workers", get(get_all_workers).post(create_worker))
         .route(
             "/workers/:id",
@@ -62,9 +169,63 @@ pub async fn start() {
         .route("/workers/:id/code", post(write_worker_code))
         .route("/workers/:id/file", delete(delete_file))
         .route("/workers/:id/exec", post(run_cmd).delete(exit_cmd))
+        .route("/workers/:id/metrics", get(get_worker_metrics))
+        .route("/workers/:id/logs", get(get_worker_logs))
+        .route("/workers/:id/bundle", put(upload_bundle))
+        .route("/workers/:id/export", get(export_worker))
+        .route("/workers/:id/team", put(set_worker_team))
+        .route("/workers/import", post(import_worker))
+        .route("/apply", post(apply))
+        .route(
+            "/workers/:id/deployments",
+            get(get_deployments).post(create_deployment),
+        )
+        .route(
+            "/workers/:id/deployments/:did/diff/:other",
+            get(get_deployment_diff),
+        )
+        .route(
+            "/workers/:id/deployments/:did/rollback",
+            post(rollback_deployment),
+        )
+        .route(
+            "/workers/:id/rollout",
+            get(get_rollout).post(create_rollout),
+        )
+        .route("/workers/:id/rollout/split", put(update_rollout_split))
+        .route("/workers/:id/rollout/promote", post(promote_rollout))
+        .route("/workers/:id/rollout/abort", post(abort_rollout))
+        .route(
+            "/workers/:id/schedules",
+            get(get_schedules).post(create_schedule),
+        )
+        .route(
+            "/workers/:id/schedules/:sid",
+            patch(update_schedule).delete(delete_schedule),
+        )
+        .route("/workers/:id/schedules/:sid/runs", get(get_schedule_runs))
+        .route_layer(middleware::from_fn_with_state(
+            state.clone(),
+            track_http_metrics,
+        ))
         .layer(cors)
         .with_state(state.clone());
 
+    if let Some(proxy_port) = state.env.proxy_port {
+        let proxy_listener = tokio::net::TcpListener::bind(format!(
+            "{}:{}",
+            state.env.proxy_listen_addr, proxy_port
+        ))
+        .await
+        .unwrap();
+
+        tokio::spawn(proxy::serve(state.clone(), proxy_listener));
+    }
+
+    tokio::spawn(scheduler::run(state.clone()));
+    tokio::spawn(keys::maintain(state.clone()));
+    tokio::spawn(rollouts::monitor(state.clone()));
+
     let listener = tokio::net::TcpListener::bind(format!(
         "{}:{}",
         state.env.api_listen_addr, state.env.api_port
@@ -73,9 +234,14 @@ pub async fn start() {
     .unwrap();
 
     tracing::debug!("listening on {}", listener.local_addr().unwrap());
-    axum::serve(listener, app).await.unwrap();
+    axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<SocketAddr>(),
+    )
+    .await
+    .unwrap();
 }
 
 async fn index() -> Result<String, ServerError> {
-    Ok(format!("Hello, World!",))
+    Ok("Hello, World!".to_string())
 }
@@ -0,0 +1,126 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::{Arc, Mutex},
+};
+
+use axum::{
+    debug_handler,
+    extract::{Path, Query, State},
+    http::header,
+    response::IntoResponse,
+};
+use serde::Deserialize;
+use tokio::io::{AsyncBufReadExt, AsyncRead, BufReader};
+
+use crate::{
+    config::AppState,
+    errors::ServerError,
+    scopes::{Scoped, WorkersRead},
+    teams::WorkerAccess,
+    workerd::get_worker_with_id,
+};
+
+/// Number of output lines kept for each worker.
+pub const MAX_LOG_LINES: usize = 1000;
+const DEFAULT_LOG_LINES: usize = 100;
+
+/// Recent output of the workerd processes, kept across restarts.
+#[derive(Debug, Default)]
+pub struct WorkerLogs {
+    workers: Mutex<HashMap<String, VecDeque<String>>>,
+}
+
+impl WorkerLogs {
+    pub fn push(&self, worker_id: &str, line: String) {
+        let mut workers = self.workers.lock().unwrap();
+        let lines = workers.entry(worker_id.to_owned()).or_default();
+        if lines.len() == MAX_LOG_LINES {
+            lines.pop_front();
+        }
+        lines.push_back(line);
+    }
+
+    /// Returns the last `count` lines of output of a worker, oldest first.
+    pub fn tail(&self, worker_id: &str, count: usize) -> Vec<String> {
+        let workers = self.workers.lock().unwrap();
+        let Some(lines) = workers.get(worker_id) else {
+            return Vec::new();
+        };
+        lines
+            .iter()
+            .skip(lines.len().saturating_sub(count))
+            .cloned()
+            .collect()
+    }
+
+    pub fn remove(&self, worker_id: &str) {
+        self.workers.lock().unwrap().remove(worker_id);
+    }
+}
+
+/// Forwards the output of a workerd process to the logs of the worker and
+/// to the log of the manager.
+pub fn capture(
+    logs: Arc<WorkerLogs>,
+    worker_id: String,
+    output: impl AsyncRead + Unpin + Send + 'static,
+) {
+    tokio::spawn(async move {
+        let mut lines = BufReader::new(output).lines();
+        while let Ok(Some(line)) = lines.next_line().await {
+            tracing::info!(target: "workerd", "[{}] {}", worker_id, line);
+            logs.push(&worker_id, line);
+        }
+    });
+}
+
+#[derive(Deserialize)]
+pub struct LogsQuery {
+    pub lines: Option<usize>,
+}
+
+#[debug_handler]
+pub async fn get_worker_logs(
+    State(state): State<AppState>,
+    Scoped(claims, _): Scoped<WorkersRead>,
+    Path(id): Path<String>,
+    Query(query): Query<LogsQuery>,
+) -> Result<impl IntoResponse, ServerError> {
+    let worker = get_worker_with_id(state.to_owned(), claims, id, WorkerAccess::Read).await?;
+
+    let count = query.lines.unwrap_or(DEFAULT_LOG_LINES).min(MAX_LOG_LINES);
+    let mut body = state.worker_logs.tail(&worker.id, count).join("\n");
+    if !body.is_empty() {
+        body.push('\n');
+    }
+
+    Ok(([(header::CONTENT_TYPE, "text/plain; charset=utf-8")], body))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_worker_logs() {
+        let logs = WorkerLogs::default();
+        for line in 0..MAX_LOG_LINES + 5 {
+            logs.push("a", line.to_string());
+        }
+        logs.push("b", "other".to_string());
+
+        assert_eq!(
+            logs.tail("a", 2),
+            [
+                (MAX_LOG_LINES + 3).to_string(),
+                (MAX_LOG_LINES + 4).to_string()
+            ]
+        );
+        assert_eq!(logs.tail("a", usize::MAX).len(), MAX_LOG_LINES);
+        assert_eq!(logs.tail("a", usize::MAX)[0], "5");
+        assert_eq!(logs.tail("b", 10), ["other"]);
+
+        logs.remove("a");
+        assert!(logs.tail("a", 10).is_empty());
+    }
+}
@@ -0,0 +1,653 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    fmt::Write,
+    sync::Mutex,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use axum::{
+    debug_handler,
+    extract::{MatchedPath, Path, Query, Request, State},
+    http::{header, HeaderMap},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use service::workers::Query as WorkerQuery;
+
+use crate::{
+    auth::{extract_jwt_from_headers, secrets_match},
+    config::AppState,
+    errors::ServerError,
+    scopes::{Scoped, WorkersRead},
+    teams::WorkerAccess,
+    workerd::get_worker_with_id,
+};
+
+/// Upper bounds, in seconds, of the latency histogram buckets.
+pub const LATENCY_BUCKETS: [f64; 11] = [
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+
+/// How many one-minute buckets are kept per worker.
+const RETAINED_MINUTES: u64 = 60;
+
+pub const PROMETHEUS_CONTENT_TYPE: &str = "text/plain; version=0.0.4";
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Histogram {
+    pub buckets: Vec<u64>,
+    pub count: u64,
+    pub sum: f64,
+}
+
+impl Default for Histogram {
+    fn default() -> Self {
+        Self {
+            buckets: vec![0; LATENCY_BUCKETS.len()],
+            count: 0,
+            sum: 0.0,
+        }
+    }
+}
+
+impl Histogram {
+    pub fn observe(&mut self, value: f64) {
+        for (bucket, bound) in self.buckets.iter_mut().zip(LATENCY_BUCKETS) {
+            if value <= bound {
+                *bucket += 1;
+            }
+        }
+        self.count += 1;
+        self.sum += value;
+    }
+
+    pub fn render(&self, out: &mut String, name: &str, labels: &str) {
+        let separator = if labels.is_empty() { "" } else { "," };
+        for (bucket, bound) in self.buckets.iter().zip(LATENCY_BUCKETS) {
+            let _ = writeln!(
+                out,
+                "{name}_bucket{{{labels}{separator}le=\"{bound}\"}} {bucket}"
+            );
+        }
+        let _ = writeln!(
+            out,
+            "{name}_bucket{{{labels}{separator}le=\"+Inf\"}} {}",
+            self.count
+        );
+        let labels = if labels.is_empty() {
+            String::new()
+        } else {
+            format!("{{{labels}}}")
+        };
+        let _ = writeln!(out, "{name}_sum{labels} {}", self.sum);
+        let _ = writeln!(out, "{name}_count{labels} {}", self.count);
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct StatusBuckets {
+    #[serde(rename = "1xx")]
+    pub informational: u64,
+    #[serde(rename = "2xx")]
+    pub success: u64,
+    #[serde(rename = "3xx")]
+    pub redirection: u64,
+    #[serde(rename = "4xx")]
+    pub client_error: u64,
+    #[serde(rename = "5xx")]
+    pub server_error: u64,
+}
+
+impl StatusBuckets {
+    fn record(&mut self, status: u16) {
+        match status {
+            100..=199 => self.informational += 1,
+            200..=299 => self.success += 1,
+            300..=399 => self.redirection += 1,
+            400..=499 => self.client_error += 1,
+            _ => self.server_error += 1,
+        }
+    }
+
+    fn iter(&self) -> [(&'static str, u64); 5] {
+        [
+            ("1xx", self.informational),
+            ("2xx", self.success),
+            ("3xx", self.redirection),
+            ("4xx", self.client_error),
+            ("5xx", self.server_error),
+        ]
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct RequestSample {
+    pub status: u16,
+    pub bytes_in: u64,
+    pub bytes_out: u64,
+    pub latency: Duration,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct RequestStats {
+    pub requests: u64,
+    pub status: StatusBuckets,
+    pub bytes_in: u64,
+    pub bytes_out: u64,
+    pub latency: Histogram,
+}
+
+impl RequestStats {
+    fn record(&mut self, sample: &RequestSample) {
+        self.requests += 1;
+        self.status.record(sample.status);
+        self.bytes_in += sample.bytes_in;
+        self.bytes_out += sample.bytes_out;
+        self.latency.observe(sample.latency.as_secs_f64());
+    }
+
+    /// Share of the requests that ended with a 5xx status.
+    pub fn error_rate(&self) -> f64 {
+        if self.requests == 0 {
+            0.0
+        } else {
+            self.status.server_error as f64 / self.requests as f64
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct MinuteStats {
+    /// Unix timestamp, in seconds, of the start of the minute.
+    pub timestamp: u64,
+    #[serde(flatten)]
+    pub stats: RequestStats,
+}
+
+#[derive(Debug, Default)]
+pub struct WorkerMetrics {
+    total: RequestStats,
+    minutes: VecDeque<(u64, RequestStats)>,
+}
+
+impl WorkerMetrics {
+    pub fn record(&mut self, minute: u64, sample: &RequestSample) {
+        self.total.record(sample);
+
+        match self.minutes.back_mut() {
+            Some((last, stats)) if *last == minute => stats.record(sample),
+            _ => {
+                let mut stats = RequestStats::default();
+                stats.record(sample);
+                self.minutes.push_back((minute, stats));
+            }
+        }
+
+        while self
+            .minutes
+            .front()
+            .is_some_and(|(first, _)| first + RETAINED_MINUTES <= minute)
+        {
+            self.minutes.pop_front();
+        }
+    }
+
+    pub fn total(&self) -> &RequestStats {
+        &self.total
+    }
+
+    /// Returns one entry per minute of the hour ending at `minute`, oldest
+    /// first, with empty minutes filled in.
+    pub fn last_hour(&self, minute: u64) -> Vec<MinuteStats> {
+        (minute + 1 - RETAINED_MINUTES.min(minute + 1)..=minute)
+            .map(|m| MinuteStats {
+                timestamp: m * 60,
+                stats: self
+                    .minutes
+                    .iter()
+                    .find(|(recorded, _)| *recorded == m)
+                    .map(|(_, stats)| stats.clone())
+                    .unwrap_or_default(),
+            })
+            .collect()
+    }
+}
+
+/// Per-worker request metrics collected by the front proxy.
+#[derive(Debug, Default)]
+pub struct ProxyMetrics {
+    workers: Mutex<HashMap<String, WorkerMetrics>>,
+}
+
+impl ProxyMetrics {
+    pub fn record(&self, worker_id: &str, sample: RequestSample) {
+        self.workers
+            .lock()
+            .unwrap()
+            .entry(worker_id.to_owned())
+            .or_default()
+            .record(current_minute(), &sample);
+    }
+
+    pub fn with_worker<T>(&self, worker_id: &str, f: impl FnOnce(&WorkerMetrics) -> T) -> T {
+        let workers = self.workers.lock().unwrap();
+        match workers.get(worker_id) {
+            Some(metrics) => f(metrics),
+            None => f(&WorkerMetrics::default()),
+        }
+    }
+
+    pub fn remove(&self, worker_id: &str) {
+        self.workers.lock().unwrap().remove(worker_id);
+    }
+
+    /// Renders the metrics of the given worker, or of every worker when
+    /// `worker_id` is `None`, in the Prometheus text exposition format.
+    pub fn render_prometheus(&self, worker_id: Option<&str>) -> String {
+        let workers = self.workers.lock().unwrap();
+        let mut workers = workers
+            .iter()
+            .filter(|(id, _)| worker_id.is_none_or(|worker_id| worker_id == id.as_str()))
+            .collect::<Vec<_>>();
+        workers.sort_by_key(|(id, _)| *id);
+
+        let mut out = String::new();
+        out.push_str("# HELP workerd_proxy_requests_total Requests proxied to the worker.\n");
+        out.push_str("# TYPE workerd_proxy_requests_total counter\n");
+        for (id, metrics) in &workers {
+            for (class, count) in metrics.total.status.iter() {
+                let _ = writeln!(
+                    out,
+                    "workerd_proxy_requests_total{{worker_id=\"{id}\",status=\"{class}\"}} {count}"
+                );
+            }
+        }
+        out.push_str(
+            "# HELP workerd_proxy_request_bytes_total Request body bytes sent to the worker.\n",
+        );
+        out.push_str("# TYPE workerd_proxy_request_bytes_total counter\n");
+        for (id, metrics) in &workers {
+            let _ = writeln!(
+                out,
+                "workerd_proxy_request_bytes_total{{worker_id=\"{id}\"}} {}",
+                metrics.total.bytes_in
+            );
+        }
+        out.push_str(
+            "# HELP workerd_proxy_response_bytes_total Response body bytes received from the worker.\n",
+        );
+        out.push_str("# TYPE workerd_proxy_response_bytes_total counter\n");
+        for (id, metrics) in &workers {
+            let _ = writeln!(
+                out,
+                "workerd_proxy_response_bytes_total{{worker_id=\"{id}\"}} {}",
+                metrics.total.bytes_out
+            );
+        }
+        out.push_str(
+            "# HELP workerd_proxy_request_duration_seconds Time until the worker responded.\n",
+        );
+        out.push_str("# TYPE workerd_proxy_request_duration_seconds histogram\n");
+        for (id, metrics) in &workers {
+            metrics.total.latency.render(
+                &mut out,
+                "workerd_proxy_request_duration_seconds",
+                &format!("worker_id=\"{id}\""),
+            );
+        }
+        out
+    }
+}
+
+#[derive(Debug, Default)]
+struct CallStats {
+    latency: Histogram,
+    errors: u64,
+}
+
+impl CallStats {
+    fn observe(&mut self, elapsed: Duration, failed: bool) {
+        self.latency.observe(elapsed.as_secs_f64());
+        if failed {
+            self.errors += 1;
+        }
+    }
+}
+
+/// Metrics about the manager process itself.
+#[derive(Debug, Default)]
+pub struct ManagerMetrics {
+    http_requests: Mutex<HashMap<(String, String, u16), u64>>,
+    http_latency: Mutex<HashMap<(String, String), Histogram>>,
+    db: Mutex<CallStats>,
+    redis: Mutex<CallStats>,
+    worker_starts: Mutex<HashMap<String, u64>>,
+}
+
+impl ManagerMetrics {
+    pub fn observe_http(&self, method: &str, route: &str, status: u16, elapsed: Duration) {
+        *self
+            .http_requests
+            .lock()
+            .unwrap()
+            .entry((method.to_owned(), route.to_owned(), status))
+            .or_default() += 1;
+        self.http_latency
+            .lock()
+            .unwrap()
+            .entry((method.to_owned(), route.to_owned()))
+            .or_default()
+            .observe(elapsed.as_secs_f64());
+    }
+
+    pub fn observe_db(&self, elapsed: Duration, failed: bool) {
+        self.db.lock().unwrap().observe(elapsed, failed);
+    }
+
+    pub fn observe_redis(&self, elapsed: Duration, failed: bool) {
+        self.redis.lock().unwrap().observe(elapsed, failed);
+    }
+
+    /// Runs a Redis command, observing how long it took and whether it
+    /// failed.
+    pub fn time_redis<T>(
+        &self,
+        command: impl FnOnce() -> redis::RedisResult<T>,
+    ) -> redis::RedisResult<T> {
+        let started_at = Instant::now();
+        let result = command();
+        self.observe_redis(started_at.elapsed(), result.is_err());
+        result
+    }
+
+    pub fn record_worker_start(&self, worker_id: &str) {
+        *self
+            .worker_starts
+            .lock()
+            .unwrap()
+            .entry(worker_id.to_owned())
+            .or_default() += 1;
+    }
+
+    pub fn render_prometheus(&self, out: &mut String) {
+        out.push_str(
+            "# HELP workerd_manager_http_requests_total HTTP requests handled by the API.\n",
+        );
+        out.push_str("# TYPE workerd_manager_http_requests_total counter\n");
+        let http_requests = self.http_requests.lock().unwrap();
+        let mut keys = http_requests.keys().collect::<Vec<_>>();
+        keys.sort();
+        for key @ (method, route, status) in keys {
+            let _ = writeln!(
+                out,
+                "workerd_manager_http_requests_total{{method=\"{method}\",route=\"{route}\",status=\"{status}\"}} {}",
+                http_requests[key]
+            );
+        }
+        drop(http_requests);
+
+        out.push_str(
+            "# HELP workerd_manager_http_request_duration_seconds Time spent handling API requests.\n",
+        );
+        out.push_str("# TYPE workerd_manager_http_request_duration_seconds histogram\n");
+        let http_latency = self.http_latency.lock().unwrap();
+        let mut keys = http_latency.keys().collect::<Vec<_>>();
+        keys.sort();
+        for key @ (method, route) in keys {
+            http_latency[key].render(
+                out,
+                "workerd_manager_http_request_duration_seconds",
+                &format!("method=\"{method}\",route=\"{route}\""),
+            );
+        }
+        drop(http_latency);
+
+        for (name, stats, help) in [
+            ("db_query", &self.db, "database queries"),
+            ("redis_command", &self.redis, "Redis commands"),
+        ] {
+            let stats = stats.lock().unwrap();
+            let _ = writeln!(
+                out,
+                "# HELP workerd_manager_{name}_duration_seconds Latency of {help}."
+            );
+            let _ = writeln!(
+                out,
+                "# TYPE workerd_manager_{name}_duration_seconds histogram"
+            );
+            stats
+                .latency
+                .render(out, &format!("workerd_manager_{name}_duration_seconds"), "");
+            let _ = writeln!(
+                out,
+                "# HELP workerd_manager_{name}_errors_total Failed {help}."
+            );
+            let _ = writeln!(out, "# TYPE workerd_manager_{name}_errors_total counter");
+            let _ = writeln!(out, "workerd_manager_{name}_errors_total {}", stats.errors);
+        }
+
+        out.push_str(
+            "# HELP workerd_manager_worker_restarts_total Times a worker was started again after its first start.\n",
+        );
+        out.push_str("# TYPE workerd_manager_worker_restarts_total counter\n");
+        let worker_starts = self.worker_starts.lock().unwrap();
+        let mut ids = worker_starts.keys().collect::<Vec<_>>();
+        ids.sort();
+        for id in ids {
+            let _ = writeln!(
+                out,
+                "workerd_manager_worker_restarts_total{{worker_id=\"{id}\"}} {}",
+                worker_starts[id] - 1
+            );
+        }
+    }
+}
+
+pub fn current_minute() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+        / 60
+}
+
+/// Records the count and latency of API requests by matched route.
+pub async fn track_http_metrics(
+    State(state): State<AppState>,
+    req: Request,
+    next: Next,
+) -> Response {
+    let method = req.method().to_string();
+    let route = req.extensions().get::<MatchedPath>().map_or_else(
+        || req.uri().path().to_owned(),
+        |path| path.as_str().to_owned(),
+    );
+
+    let started_at = Instant::now();
+    let res = next.run(req).await;
+    state.manager_metrics.observe_http(
+        &method,
+        &route,
+        res.status().as_u16(),
+        started_at.elapsed(),
+    );
+    res
+}
+
+#[debug_handler]
+pub async fn get_metrics(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Response, ServerError> {
+    if let Some(token) = &state.env.metrics_token {
+        let jwt = extract_jwt_from_headers(headers)?;
+        if !secrets_match(token, &jwt) {
+            return Err(ServerError::Unauthorized);
+        }
+    }
+
+    let total_workers = WorkerQuery::count_workers(&state.db).await.map_err(|err| {
+        tracing::error!("Failed to count workers: {:?}", err);
+        ServerError::InternalServerError
+    })?;
+    let running_workers = state.chan_map.lock().await.len() as u64;
+    let template_cache_size = state.template_cache.lock().await.len();
+
+    let mut out = String::new();
+    state.manager_metrics.render_prometheus(&mut out);
+    out.push_str("# HELP workerd_manager_workers Workers known to the manager by state.\n");
+    out.push_str("# TYPE workerd_manager_workers gauge\n");
+    let _ = writeln!(
+        out,
+        "workerd_manager_workers{{state=\"running\"}} {running_workers}"
+    );
+    let _ = writeln!(
+        out,
+        "workerd_manager_workers{{state=\"stopped\"}} {}",
+        total_workers.saturating_sub(running_workers)
+    );
+    out.push_str(
+        "# HELP workerd_manager_template_cache_size Compiled Capfile templates held in memory.\n",
+    );
+    out.push_str("# TYPE workerd_manager_template_cache_size gauge\n");
+    let _ = writeln!(
+        out,
+        "workerd_manager_template_cache_size {template_cache_size}"
+    );
+    out.push_str(&state.proxy_metrics.render_prometheus(None));
+
+    Ok(([(header::CONTENT_TYPE, PROMETHEUS_CONTENT_TYPE)], out).into_response())
+}
+
+#[derive(Debug, Deserialize)]
+pub struct MetricsQuery {
+    pub format: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct WorkerMetricsResponse {
+    pub worker_id: String,
+    pub total: RequestStats,
+    pub minutes: Vec<MinuteStats>,
+}
+
+#[debug_handler]
+pub async fn get_worker_metrics(
+    State(state): State<AppState>,
+    Scoped(claims, _): Scoped<WorkersRead>,
+    Path(id): Path<String>,
+    Query(query): Query<MetricsQuery>,
+) -> Result<Response, ServerError> {
+    let worker = get_worker_with_id(state.to_owned(), claims, id.to_owned(), WorkerAccess::Read)
+        .await
+        .map_err(|err| {
+            tracing::error!("Failed to get worker: {:?}", err);
+            ServerError::WorkerNotFound
+        })?;
+
+    if query.format.as_deref() == Some("prometheus") {
+        return Ok((
+            [(header::CONTENT_TYPE, PROMETHEUS_CONTENT_TYPE)],
+            state.proxy_metrics.render_prometheus(Some(&worker.id)),
+        )
+            .into_response());
+    }
+
+    let minute = current_minute();
+    let (total, minutes) = state.proxy_metrics.with_worker(&worker.id, |metrics| {
+        (metrics.total().clone(), metrics.last_hour(minute))
+    });
+
+    Ok(Json(WorkerMetricsResponse {
+        worker_id: id,
+        total,
+        minutes,
+    })
+    .into_response())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample(status: u16, millis: u64) -> RequestSample {
+        RequestSample {
+            status,
+            bytes_in: 10,
+            bytes_out: 100,
+            latency: Duration::from_millis(millis),
+        }
+    }
+
+    #[test]
+    fn test_record_minutes() {
+        let mut metrics = WorkerMetrics::default();
+        metrics.record(1000, &sample(200, 3));
+        metrics.record(1000, &sample(404, 30));
+        metrics.record(1002, &sample(502, 300));
+
+        assert_eq!(metrics.total().requests, 3);
+        assert_eq!(metrics.total().bytes_in, 30);
+        assert_eq!(metrics.total().bytes_out, 300);
+        assert_eq!(metrics.total().status.client_error, 1);
+        assert_eq!(metrics.total().latency.buckets[0], 1);
+        assert_eq!(metrics.total().latency.buckets[6], 3);
+
+        let hour = metrics.last_hour(1002);
+        assert_eq!(hour.len(), 60);
+        assert_eq!(hour[57].timestamp, 1000 * 60);
+        assert_eq!(hour[57].stats.requests, 2);
+        assert_eq!(hour[58].stats.requests, 0);
+        assert_eq!(hour[59].stats.status.server_error, 1);
+
+        metrics.record(1060, &sample(200, 1));
+        let hour = metrics.last_hour(1060);
+        assert_eq!(hour.iter().map(|m| m.stats.requests).sum::<u64>(), 2);
+        assert_eq!(metrics.total().requests, 4);
+    }
+
+    #[test]
+    fn test_render_manager_metrics() {
+        let metrics = ManagerMetrics::default();
+        metrics.observe_http("GET", "/workers/:id", 200, Duration::from_millis(20));
+        metrics.observe_http("GET", "/workers/:id", 200, Duration::from_millis(20));
+        metrics.observe_db(Duration::from_millis(2), true);
+        metrics.record_worker_start("a");
+        metrics.record_worker_start("a");
+        metrics.record_worker_start("b");
+
+        let mut out = String::new();
+        metrics.render_prometheus(&mut out);
+        assert!(out.contains(
+            "workerd_manager_http_requests_total{method=\"GET\",route=\"/workers/:id\",status=\"200\"} 2\n"
+        ));
+        assert!(out.contains(
+            "workerd_manager_http_request_duration_seconds_count{method=\"GET\",route=\"/workers/:id\"} 2\n"
+        ));
+        assert!(out.contains("workerd_manager_db_query_errors_total 1\n"));
+        assert!(out.contains("workerd_manager_db_query_duration_seconds_bucket{le=\"0.005\"} 1\n"));
+        assert!(out.contains("workerd_manager_redis_command_errors_total 0\n"));
+        assert!(out.contains("workerd_manager_worker_restarts_total{worker_id=\"a\"} 1\n"));
+        assert!(out.contains("workerd_manager_worker_restarts_total{worker_id=\"b\"} 0\n"));
+    }
+
+    #[test]
+    fn test_render_prometheus() {
+        let metrics = ProxyMetrics::default();
+        metrics.record("a", sample(200, 20));
+        metrics.record("b", sample(500, 20));
+
+        let out = metrics.render_prometheus(Some("a"));
+        assert!(out.contains("workerd_proxy_requests_total{worker_id=\"a\",status=\"2xx\"} 1\n"));
+        assert!(out.contains(
+            "workerd_proxy_request_duration_seconds_bucket{worker_id=\"a\",le=\"0.025\"} 1\n"
+        ));
+        assert!(out.contains("workerd_proxy_request_duration_seconds_count{worker_id=\"a\"} 1\n"));
+        assert!(!out.contains("worker_id=\"b\""));
+        assert!(metrics
+            .render_prometheus(None)
+            .contains("workerd_proxy_requests_total{worker_id=\"b\",status=\"5xx\"} 1\n"));
+    }
+}
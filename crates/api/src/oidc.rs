@@ -0,0 +1,1062 @@
+//! OpenID Connect single sign-on. Users log in at an identity provider with
+//! the authorization code flow and PKCE, and get the usual token pair for
+//! the user linked to their identity there.
+//!
+//! Identities are linked to users by the subject at the issuer. Users link
+//! their identity while logged in, through `/auth/oidc/link`, since an
+//! email at the provider does not prove owning the user with that email
+//! here. The first login of an identity whose verified email nobody uses
+//! creates a user when `OIDC_AUTO_PROVISION` is set.
+//!
+//! A second factor of the manager is still asked for, as with `login`, so
+//! that the identity provider is not the only factor of admins.
+
+use std::{
+    collections::{BTreeSet, HashMap, HashSet},
+    sync::Arc,
+};
+
+use argon2::password_hash::rand_core::{OsRng, RngCore};
+use axum::{
+    debug_handler,
+    extract::{Query, State},
+    response::Redirect,
+    Json,
+};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use entity::{
+    sea_orm_active_enums::{RoleEnum, UserStatusEnum},
+    user,
+};
+use jsonwebtoken::{decode, decode_header, jwk::JwkSet, Algorithm, DecodingKey, Validation};
+use redis::Commands;
+use serde::{Deserialize, Deserializer, Serialize};
+use service::{
+    identities::{Mutation as IdentityMutation, Query as IdentityQuery},
+    roles::Mutation as RoleMutation,
+    sea_orm::ActiveEnum,
+    users::{Mutation as UserMutation, Query as UserQuery},
+};
+use sha2::{Digest, Sha256};
+use tokio::sync::RwLock;
+
+use crate::{
+    auth::{
+        bump_token_generation, generate_secret, generate_token_pair, hash_password, hash_secret,
+        secrets_match, token_pair_error, AuthBody, LoginResponse,
+    },
+    config::AppState,
+    errors::{ConfigError, ServerError},
+    outbound::{self, OutboundError},
+    scopes::{Scoped, UsersWrite},
+    sessions::ClientInfo,
+    two_factor::second_factor_challenge,
+};
+
+/// How long a login may take at the identity provider.
+pub const LOGIN_TTL_SECS: u64 = 10 * 60;
+const DEFAULT_SCOPES: &str = "openid email profile";
+const DEFAULT_GROUPS_CLAIM: &str = "groups";
+
+#[derive(Clone, Debug)]
+pub struct OidcConfig {
+    pub issuer: String,
+    pub client_id: String,
+    /// Unset for public clients, which rely on PKCE alone.
+    pub client_secret: Option<String>,
+    /// The URL of `/auth/oidc/callback`, as registered at the provider.
+    pub redirect_url: String,
+    pub scopes: String,
+    /// Creates a user for identities that match none.
+    pub auto_provision: bool,
+    /// Claim of the ID token that lists the groups of the user.
+    pub groups_claim: String,
+    /// Pairs of a group and the role granted to its members. The roles
+    /// named here are managed by the provider, see `sync_group_roles`.
+    pub group_roles: Vec<(String, String)>,
+}
+
+impl OidcConfig {
+    /// Reads the `OIDC_*` variables, when `OIDC_ISSUER` is set.
+    pub fn from_env() -> Result<Option<Self>, ConfigError> {
+        let Ok(issuer) = dotenv::var("OIDC_ISSUER") else {
+            return Ok(None);
+        };
+
+        fn get_env_var(key: &str) -> Result<String, ConfigError> {
+            dotenv::var(key).map_err(|err| {
+                tracing::error!("missing {key}: {err}");
+                ConfigError::FailedReadEnvironment
+            })
+        }
+
+        Ok(Some(Self {
+            issuer,
+            client_id: get_env_var("OIDC_CLIENT_ID")?,
+            client_secret: dotenv::var("OIDC_CLIENT_SECRET").ok(),
+            redirect_url: get_env_var("OIDC_REDIRECT_URL")?,
+            scopes: dotenv::var("OIDC_SCOPES").unwrap_or(DEFAULT_SCOPES.to_owned()),
+            auto_provision: match dotenv::var("OIDC_AUTO_PROVISION") {
+                Ok(s) => s.parse().map_err(|_| ConfigError::FailedParseEnvironment)?,
+                _ => false,
+            },
+            groups_claim: dotenv::var("OIDC_GROUPS_CLAIM")
+                .unwrap_or(DEFAULT_GROUPS_CLAIM.to_owned()),
+            group_roles: match dotenv::var("OIDC_GROUP_ROLES") {
+                Ok(s) => parse_group_roles(&s).ok_or(ConfigError::FailedParseEnvironment)?,
+                _ => Vec::new(),
+            },
+        }))
+    }
+}
+
+/// Parses `group=role` pairs separated by commas.
+pub fn parse_group_roles(s: &str) -> Option<Vec<(String, String)>> {
+    s.split(',')
+        .map(str::trim)
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (group, role) = pair.split_once('=')?;
+            let (group, role) = (group.trim(), role.trim());
+            (!group.is_empty() && !role.is_empty()).then(|| (group.to_owned(), role.to_owned()))
+        })
+        .collect()
+}
+
+#[derive(Debug)]
+pub enum OidcError {
+    Request(OutboundError),
+    /// The provider answered with an error status or an unexpected body.
+    InvalidResponse(u16),
+    /// The discovery document names another issuer.
+    IssuerMismatch(String),
+    /// The discovery document names a plain http endpoint of an https
+    /// issuer.
+    InsecureEndpoint(String),
+    InvalidIdToken(jsonwebtoken::errors::Error),
+    UnknownKey,
+    NonceMismatch,
+}
+
+impl From<OutboundError> for OidcError {
+    fn from(err: OutboundError) -> Self {
+        OidcError::Request(err)
+    }
+}
+
+impl From<jsonwebtoken::errors::Error> for OidcError {
+    fn from(err: jsonwebtoken::errors::Error) -> Self {
+        OidcError::InvalidIdToken(err)
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+struct ProviderMetadata {
+    issuer: String,
+    authorization_endpoint: String,
+    token_endpoint: String,
+    jwks_uri: String,
+}
+
+impl ProviderMetadata {
+    /// Finds an endpoint that does not use https, which only issuers that
+    /// do not either may have.
+    fn insecure_endpoint(&self) -> Option<&str> {
+        if has_scheme(&self.issuer, "http") {
+            return None;
+        }
+        [
+            &self.authorization_endpoint,
+            &self.token_endpoint,
+            &self.jwks_uri,
+        ]
+        .into_iter()
+        .find(|endpoint| !has_scheme(endpoint, "https"))
+        .map(String::as_str)
+    }
+}
+
+fn has_scheme(url: &str, scheme: &str) -> bool {
+    url.split_once("://")
+        .is_some_and(|(prefix, _)| prefix.eq_ignore_ascii_case(scheme))
+}
+
+struct Provider {
+    metadata: ProviderMetadata,
+    jwks: JwkSet,
+}
+
+/// A login in progress, kept until the provider redirects back.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct LoginRequest {
+    pub state: String,
+    pub nonce: String,
+    pub code_verifier: String,
+    /// The user to link the identity to, when started by `oidc_link`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub link_user: Option<String>,
+}
+
+impl LoginRequest {
+    pub fn new() -> Self {
+        let mut verifier = [0u8; 32];
+        OsRng.fill_bytes(&mut verifier);
+
+        Self {
+            state: generate_secret(),
+            nonce: generate_secret(),
+            code_verifier: URL_SAFE_NO_PAD.encode(verifier),
+            link_user: None,
+        }
+    }
+
+    /// The S256 challenge of the verifier, see RFC 7636.
+    pub fn code_challenge(&self) -> String {
+        URL_SAFE_NO_PAD.encode(Sha256::digest(self.code_verifier.as_bytes()))
+    }
+}
+
+impl Default for LoginRequest {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct IdTokenClaims {
+    pub sub: String,
+    pub email: Option<String>,
+    #[serde(default, deserialize_with = "deserialize_flag")]
+    pub email_verified: bool,
+    pub preferred_username: Option<String>,
+    pub nonce: Option<String>,
+    #[serde(flatten)]
+    pub other: HashMap<String, serde_json::Value>,
+}
+
+impl IdTokenClaims {
+    /// Returns the groups listed in `claim`, a list or a single name.
+    pub fn groups(&self, claim: &str) -> Vec<String> {
+        match self.other.get(claim) {
+            Some(serde_json::Value::Array(groups)) => groups
+                .iter()
+                .filter_map(|group| group.as_str().map(str::to_owned))
+                .collect(),
+            Some(serde_json::Value::String(group)) => vec![group.to_owned()],
+            _ => Vec::new(),
+        }
+    }
+}
+
+/// Some providers send flags as the strings `"true"` and `"false"`.
+fn deserialize_flag<'de, D: Deserializer<'de>>(deserializer: D) -> Result<bool, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Flag {
+        Bool(bool),
+        String(String),
+    }
+
+    Ok(match Flag::deserialize(deserializer)? {
+        Flag::Bool(flag) => flag,
+        Flag::String(flag) => flag == "true",
+    })
+}
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    id_token: String,
+}
+
+/// Talks to the identity provider. The discovery document and the keys of
+/// the provider are fetched on first use, and the keys again when an ID
+/// token names a key that is not known yet.
+#[derive(Clone)]
+pub struct OidcClient {
+    config: Arc<OidcConfig>,
+    provider: Arc<RwLock<Option<Arc<Provider>>>>,
+}
+
+impl OidcClient {
+    pub fn new(config: OidcConfig) -> Self {
+        Self {
+            config: Arc::new(config),
+            provider: Arc::new(RwLock::new(None)),
+        }
+    }
+
+    pub fn config(&self) -> &OidcConfig {
+        &self.config
+    }
+
+    pub async fn authorization_url(&self, login: &LoginRequest) -> Result<String, OidcError> {
+        let provider = self.provider().await?;
+        let endpoint = &provider.metadata.authorization_endpoint;
+        let query = serde_urlencoded::to_string([
+            ("response_type", "code"),
+            ("client_id", &self.config.client_id),
+            ("redirect_uri", &self.config.redirect_url),
+            ("scope", &self.config.scopes),
+            ("state", &login.state),
+            ("nonce", &login.nonce),
+            ("code_challenge", &login.code_challenge()),
+            ("code_challenge_method", "S256"),
+        ])
+        .map_err(|_| OidcError::InvalidResponse(0))?;
+
+        let separator = if endpoint.contains('?') { '&' } else { '?' };
+        Ok(format!("{endpoint}{separator}{query}"))
+    }
+
+    /// Redeems the authorization code of `login` and returns the claims of
+    /// the verified ID token.
+    pub async fn exchange_code(
+        &self,
+        code: &str,
+        login: &LoginRequest,
+    ) -> Result<IdTokenClaims, OidcError> {
+        let provider = self.provider().await?;
+        let mut params = vec![
+            ("grant_type", "authorization_code"),
+            ("code", code),
+            ("redirect_uri", &self.config.redirect_url),
+            ("client_id", &self.config.client_id),
+            ("code_verifier", &login.code_verifier),
+        ];
+        if let Some(secret) = &self.config.client_secret {
+            params.push(("client_secret", secret));
+        }
+
+        let response = outbound::post_form(&provider.metadata.token_endpoint, &params).await?;
+        if !response.is_success() {
+            return Err(OidcError::InvalidResponse(response.status));
+        }
+        let token: TokenResponse = serde_json::from_slice(&response.body)
+            .map_err(|_| OidcError::InvalidResponse(response.status))?;
+
+        let claims = match self.verify_id_token(&provider, &token.id_token) {
+            Err(OidcError::UnknownKey) => {
+                let provider = self.refresh_keys(&provider).await?;
+                self.verify_id_token(&provider, &token.id_token)?
+            }
+            result => result?,
+        };
+        if !claims
+            .nonce
+            .as_deref()
+            .is_some_and(|nonce| secrets_match(&login.nonce, nonce))
+        {
+            return Err(OidcError::NonceMismatch);
+        }
+        Ok(claims)
+    }
+
+    fn verify_id_token(
+        &self,
+        provider: &Provider,
+        token: &str,
+    ) -> Result<IdTokenClaims, OidcError> {
+        let header = decode_header(token)?;
+        // Tokens signed with the client secret are not supported, and
+        // accepting them would let anyone knowing it sign in.
+        if matches!(
+            header.alg,
+            Algorithm::HS256 | Algorithm::HS384 | Algorithm::HS512
+        ) {
+            return Err(OidcError::InvalidIdToken(
+                jsonwebtoken::errors::ErrorKind::InvalidAlgorithm.into(),
+            ));
+        }
+
+        let jwk = match &header.kid {
+            Some(kid) => provider.jwks.find(kid),
+            None if provider.jwks.keys.len() == 1 => provider.jwks.keys.first(),
+            None => None,
+        }
+        .ok_or(OidcError::UnknownKey)?;
+
+        let mut validation = Validation::new(header.alg);
+        validation.set_issuer(&[&provider.metadata.issuer]);
+        validation.set_audience(&[&self.config.client_id]);
+        validation.set_required_spec_claims(&["exp", "iss", "aud", "sub"]);
+
+        Ok(decode::<IdTokenClaims>(token, &DecodingKey::from_jwk(jwk)?, &validation)?.claims)
+    }
+
+    async fn provider(&self) -> Result<Arc<Provider>, OidcError> {
+        if let Some(provider) = self.provider.read().await.as_ref() {
+            return Ok(provider.clone());
+        }
+
+        let url = format!(
+            "{}/.well-known/openid-configuration",
+            self.config.issuer.trim_end_matches('/')
+        );
+        let metadata: ProviderMetadata = fetch_json(&url).await?;
+        if metadata.issuer != self.config.issuer {
+            return Err(OidcError::IssuerMismatch(metadata.issuer));
+        }
+        if let Some(endpoint) = metadata.insecure_endpoint() {
+            return Err(OidcError::InsecureEndpoint(endpoint.to_owned()));
+        }
+        let jwks = fetch_json(&metadata.jwks_uri).await?;
+
+        let provider = Arc::new(Provider { metadata, jwks });
+        *self.provider.write().await = Some(provider.clone());
+        Ok(provider)
+    }
+
+    async fn refresh_keys(&self, provider: &Provider) -> Result<Arc<Provider>, OidcError> {
+        let provider = Arc::new(Provider {
+            metadata: provider.metadata.clone(),
+            jwks: fetch_json(&provider.metadata.jwks_uri).await?,
+        });
+        *self.provider.write().await = Some(provider.clone());
+        Ok(provider)
+    }
+}
+
+async fn fetch_json<T: serde::de::DeserializeOwned>(url: &str) -> Result<T, OidcError> {
+    let response = outbound::get(url).await?;
+    if !response.is_success() {
+        return Err(OidcError::InvalidResponse(response.status));
+    }
+    serde_json::from_slice(&response.body).map_err(|_| OidcError::InvalidResponse(response.status))
+}
+
+/// Starts a login by sending the user to the identity provider.
+#[debug_handler]
+pub async fn oidc_login(State(state): State<AppState>) -> Result<Redirect, ServerError> {
+    let oidc = state.oidc.as_ref().ok_or(ServerError::OidcNotConfigured)?;
+
+    let login = LoginRequest::new();
+    let url = oidc.authorization_url(&login).await.map_err(oidc_error)?;
+    save_login(&state, &login)?;
+
+    Ok(Redirect::to(&url))
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct LinkResponse {
+    pub authorization_url: String,
+}
+
+/// Starts linking an identity at the provider to the logged-in user. The
+/// client sends the user to the returned URL, and the callback then logs
+/// them in as usual.
+#[debug_handler]
+pub async fn oidc_link(
+    State(state): State<AppState>,
+    Scoped(claims, _): Scoped<UsersWrite>,
+) -> Result<Json<LinkResponse>, ServerError> {
+    let oidc = state.oidc.as_ref().ok_or(ServerError::OidcNotConfigured)?;
+
+    let login = LoginRequest {
+        link_user: Some(claims.sub),
+        ..LoginRequest::new()
+    };
+    let authorization_url = oidc.authorization_url(&login).await.map_err(oidc_error)?;
+    save_login(&state, &login)?;
+
+    Ok(Json(LinkResponse { authorization_url }))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CallbackQuery {
+    pub code: Option<String>,
+    pub state: Option<String>,
+    pub error: Option<String>,
+}
+
+/// Completes a login where the identity provider redirects back to. Users
+/// with a second factor, or admins that must have one, get a challenge as
+/// with `login`.
+#[debug_handler]
+pub async fn oidc_callback(
+    State(state): State<AppState>,
+    client: ClientInfo,
+    Query(query): Query<CallbackQuery>,
+) -> Result<Json<LoginResponse>, ServerError> {
+    let oidc = state.oidc.as_ref().ok_or(ServerError::OidcNotConfigured)?;
+    if let Some(error) = &query.error {
+        tracing::warn!("Identity provider refused the login: {}", error);
+        return Err(ServerError::OidcFailed);
+    }
+    let (Some(code), Some(login_state)) = (&query.code, &query.state) else {
+        return Err(ServerError::OidcFailed);
+    };
+
+    let login = take_login(&state, login_state)?.ok_or(ServerError::OidcFailed)?;
+    let claims = oidc.exchange_code(code, &login).await.map_err(oidc_error)?;
+    let user = match &login.link_user {
+        Some(user_id) => link_identity(&state, oidc.config(), user_id, &claims).await?,
+        None => find_or_create_user(&state, oidc.config(), &claims).await?,
+    };
+    let user = sync_group_roles(&state, oidc.config(), user, &claims).await?;
+    if user.status != UserStatusEnum::Active {
+        return Err(ServerError::UserNotActive);
+    }
+
+    if let Some(challenge) = second_factor_challenge(&state, &user).await? {
+        return Ok(Json(LoginResponse::SecondFactorRequired(challenge)));
+    }
+    let (access_token, refresh_token) =
+        generate_token_pair(&state, &user.id.to_string(), &client, None)
+            .await
+            .map_err(token_pair_error)?;
+
+    Ok(Json(LoginResponse::Tokens(AuthBody::new(
+        access_token,
+        refresh_token,
+    ))))
+}
+
+fn oidc_error(err: OidcError) -> ServerError {
+    tracing::error!("Single sign-on failed: {:?}", err);
+    ServerError::OidcFailed
+}
+
+fn login_key(state: &str) -> String {
+    format!("oidc_login:{}", hash_secret(state))
+}
+
+fn save_login(state: &AppState, login: &LoginRequest) -> Result<(), ServerError> {
+    let value = serde_json::to_string(login).map_err(|_| ServerError::InternalServerError)?;
+    let mut con = state.redis_client.get_connection().map_err(|err| {
+        tracing::error!("Failed to connect to Redis: {:?}", err);
+        ServerError::InternalServerError
+    })?;
+    let result: redis::RedisResult<()> = state
+        .manager_metrics
+        .time_redis(|| con.set_ex(login_key(&login.state), value, LOGIN_TTL_SECS));
+
+    result.map_err(|err| {
+        tracing::error!("Failed to save login: {:?}", err);
+        ServerError::InternalServerError
+    })
+}
+
+/// Returns the login started with `login_state`, at most once.
+fn take_login(state: &AppState, login_state: &str) -> Result<Option<LoginRequest>, ServerError> {
+    let mut con = state.redis_client.get_connection().map_err(|err| {
+        tracing::error!("Failed to connect to Redis: {:?}", err);
+        ServerError::InternalServerError
+    })?;
+    let result: redis::RedisResult<Option<String>> = state
+        .manager_metrics
+        .time_redis(|| con.get_del(login_key(login_state)));
+
+    let value = result.map_err(|err| {
+        tracing::error!("Failed to get login: {:?}", err);
+        ServerError::InternalServerError
+    })?;
+    Ok(value.and_then(|value| serde_json::from_str(&value).ok()))
+}
+
+/// Links the identity to the user who started `oidc_link`, unless another
+/// user has it already.
+async fn link_identity(
+    state: &AppState,
+    config: &OidcConfig,
+    user_id: &str,
+    claims: &IdTokenClaims,
+) -> Result<user::Model, ServerError> {
+    let identity =
+        IdentityQuery::find_identity(&state.db, config.issuer.to_owned(), claims.sub.to_owned())
+            .await
+            .map_err(|err| {
+                tracing::error!("Failed to get identity: {:?}", err);
+                ServerError::InternalServerError
+            })?;
+    let user = UserQuery::find_user_by_id(&state.db, user_id.to_owned())
+        .await
+        .map_err(|err| {
+            tracing::error!("Failed to get user: {:?}", err);
+            ServerError::InternalServerError
+        })?
+        .ok_or(ServerError::OidcUnknownUser)?;
+
+    match identity {
+        Some(identity) if identity.user_id == user.id => {}
+        Some(_) => return Err(ServerError::OidcIdentityTaken),
+        None => {
+            IdentityMutation::create_identity(
+                &state.db,
+                user.id,
+                config.issuer.to_owned(),
+                claims.sub.to_owned(),
+            )
+            .await
+            .map_err(|err| {
+                tracing::error!("Failed to link identity: {:?}", err);
+                ServerError::InternalServerError
+            })?;
+        }
+    }
+    Ok(user)
+}
+
+/// Finds the user linked to the identity, or creates one on the first
+/// login. Users with the email of the identity must link it themselves, as
+/// the provider may vouch for addresses it does not own. Emails are only
+/// used when the provider verified them.
+async fn find_or_create_user(
+    state: &AppState,
+    config: &OidcConfig,
+    claims: &IdTokenClaims,
+) -> Result<user::Model, ServerError> {
+    let identity =
+        IdentityQuery::find_identity(&state.db, config.issuer.to_owned(), claims.sub.to_owned())
+            .await
+            .map_err(|err| {
+                tracing::error!("Failed to get identity: {:?}", err);
+                ServerError::InternalServerError
+            })?;
+    if let Some(identity) = identity {
+        return UserQuery::find_user_by_id(&state.db, identity.user_id.to_string())
+            .await
+            .map_err(|err| {
+                tracing::error!("Failed to get user: {:?}", err);
+                ServerError::InternalServerError
+            })?
+            .ok_or(ServerError::OidcUnknownUser);
+    }
+
+    let email = claims
+        .email
+        .as_deref()
+        .filter(|_| claims.email_verified)
+        .ok_or(ServerError::OidcUnknownUser)?;
+    let user = UserQuery::find_user_by_email(&state.db, email.to_owned())
+        .await
+        .map_err(|err| {
+            tracing::error!("Failed to get user: {:?}", err);
+            ServerError::InternalServerError
+        })?;
+    if user.is_some() {
+        return Err(ServerError::OidcLinkRequired);
+    }
+
+    if !config.auto_provision {
+        return Err(ServerError::OidcUnknownUser);
+    }
+    let username = available_username(state, claims, email).await?;
+    // Nobody knows this password, so the user logs in through the provider.
+    let password = hash_password(&generate_secret()).map_err(|err| {
+        tracing::error!("Failed to hash password: {:?}", err);
+        ServerError::InternalServerError
+    })?;
+    IdentityMutation::create_user_with_identity(
+        &state.db,
+        email.to_owned(),
+        username,
+        password,
+        config.issuer.to_owned(),
+        claims.sub.to_owned(),
+    )
+    .await
+    .map_err(|err| {
+        tracing::error!("Failed to create user: {:?}", err);
+        ServerError::InternalServerError
+    })
+}
+
+/// Picks the preferred username of the identity, or the local part of its
+/// email, suffixed with a hash of the subject when taken.
+async fn available_username(
+    state: &AppState,
+    claims: &IdTokenClaims,
+    email: &str,
+) -> Result<String, ServerError> {
+    let username = claims
+        .preferred_username
+        .to_owned()
+        .filter(|username| !username.is_empty())
+        .unwrap_or_else(|| email.split('@').next().unwrap_or(email).to_owned());
+
+    let taken = UserQuery::find_user_by_username(&state.db, username.to_owned())
+        .await
+        .map_err(|err| {
+            tracing::error!("Failed to get user: {:?}", err);
+            ServerError::InternalServerError
+        })?
+        .is_some();
+    if taken {
+        return Ok(format!("{}-{}", username, &hash_secret(&claims.sub)[..8]));
+    }
+    Ok(username)
+}
+
+/// Splits the roles of `group_roles` into those granted through `groups`
+/// and the others.
+fn mapped_roles(
+    group_roles: &[(String, String)],
+    groups: &HashSet<String>,
+) -> (BTreeSet<String>, BTreeSet<String>) {
+    let granted: BTreeSet<String> = group_roles
+        .iter()
+        .filter(|(group, _)| groups.contains(group))
+        .map(|(_, role)| role.to_owned())
+        .collect();
+    let revoked = group_roles
+        .iter()
+        .map(|(_, role)| role.to_owned())
+        .filter(|role| !granted.contains(role))
+        .collect();
+    (granted, revoked)
+}
+
+/// Grants the roles mapped from the groups of the user, and revokes the
+/// mapped roles of the groups the user left, so the provider decides who
+/// holds them. Roles not named in `OIDC_GROUP_ROLES` are left alone.
+async fn sync_group_roles(
+    state: &AppState,
+    config: &OidcConfig,
+    user: user::Model,
+    claims: &IdTokenClaims,
+) -> Result<user::Model, ServerError> {
+    if config.group_roles.is_empty() {
+        return Ok(user);
+    }
+    let groups = claims.groups(&config.groups_claim).into_iter().collect();
+    let (granted, revoked) = mapped_roles(&config.group_roles, &groups);
+
+    let mut roles = user.roles.to_owned();
+    let mut changed = false;
+    for name in granted {
+        match RoleEnum::try_from_value(&name) {
+            Ok(role) => {
+                if !roles.contains(&role) {
+                    roles.push(role);
+                }
+            }
+            Err(_) => match RoleMutation::assign_role(&state.db, user.id, name).await {
+                Ok(assigned) => changed |= assigned,
+                Err(err) => tracing::warn!("Failed to assign role from group: {:?}", err),
+            },
+        }
+    }
+    for name in revoked {
+        match RoleEnum::try_from_value(&name) {
+            Ok(role) => roles.retain(|current| *current != role),
+            Err(_) => {
+                let result = RoleMutation::unassign_role(&state.db, user.id, name)
+                    .await
+                    .map_err(|err| {
+                        tracing::error!("Failed to unassign role: {:?}", err);
+                        ServerError::InternalServerError
+                    })?;
+                changed |= result.rows_affected > 0;
+            }
+        }
+    }
+
+    let user = if roles == user.roles {
+        user
+    } else {
+        changed = true;
+        UserMutation::update_user_roles(&state.db, user.id.to_string(), roles)
+            .await
+            .map_err(|err| {
+                tracing::error!("Failed to update user roles: {:?}", err);
+                ServerError::InternalServerError
+            })?
+    };
+    // Tokens issued before carry the previous roles.
+    if changed {
+        bump_token_generation(state, &user.id.to_string())?;
+    }
+    Ok(user)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{extract::Form, http::StatusCode, routing::get, routing::post, Router};
+    use jsonwebtoken::{encode, get_current_timestamp, EncodingKey, Header};
+    use ring::{
+        rand::SystemRandom,
+        signature::{Ed25519KeyPair, KeyPair},
+    };
+    use serde_json::json;
+    use std::sync::Mutex;
+
+    const CLIENT_ID: &str = "manager";
+    const CLIENT_SECRET: &str = "secret";
+    const REDIRECT_URL: &str = "http://manager.test/auth/oidc/callback";
+
+    /// An identity provider on a local port, which issues ID tokens for a
+    /// single identity.
+    struct MockIssuer {
+        url: String,
+        key: EncodingKey,
+        /// Pending codes with their PKCE challenge and nonce.
+        codes: Mutex<HashMap<String, (String, String)>>,
+    }
+
+    impl MockIssuer {
+        async fn start() -> Arc<Self> {
+            let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let pkcs8 = Ed25519KeyPair::generate_pkcs8(&SystemRandom::new()).unwrap();
+            let public_key = Ed25519KeyPair::from_pkcs8(pkcs8.as_ref())
+                .unwrap()
+                .public_key()
+                .as_ref()
+                .to_vec();
+
+            let issuer = Arc::new(Self {
+                url: format!("http://{}", listener.local_addr().unwrap()),
+                key: EncodingKey::from_ed_der(pkcs8.as_ref()),
+                codes: Mutex::new(HashMap::new()),
+            });
+            let jwks = json!({
+                "keys": [{
+                    "kty": "OKP",
+                    "crv": "Ed25519",
+                    "x": URL_SAFE_NO_PAD.encode(public_key),
+                    "kid": "mock",
+                    "alg": "EdDSA",
+                    "use": "sig",
+                }],
+            });
+
+            let app = Router::new()
+                .route(
+                    "/.well-known/openid-configuration",
+                    get({
+                        let url = issuer.url.to_owned();
+                        move || async move {
+                            Json(json!({
+                                "issuer": url,
+                                "authorization_endpoint": format!("{url}/authorize"),
+                                "token_endpoint": format!("{url}/token"),
+                                "jwks_uri": format!("{url}/jwks"),
+                            }))
+                        }
+                    }),
+                )
+                .route("/jwks", get(move || async move { Json(jwks) }))
+                .route(
+                    "/token",
+                    post({
+                        let issuer = issuer.clone();
+                        move |Form(form): Form<HashMap<String, String>>| async move {
+                            issuer.token(form)
+                        }
+                    }),
+                );
+            tokio::spawn(async move { axum::serve(listener, app).await });
+
+            issuer
+        }
+
+        fn config(&self) -> OidcConfig {
+            OidcConfig {
+                issuer: self.url.to_owned(),
+                client_id: CLIENT_ID.to_owned(),
+                client_secret: Some(CLIENT_SECRET.to_owned()),
+                redirect_url: REDIRECT_URL.to_owned(),
+                scopes: DEFAULT_SCOPES.to_owned(),
+                auto_provision: false,
+                groups_claim: DEFAULT_GROUPS_CLAIM.to_owned(),
+                group_roles: Vec::new(),
+            }
+        }
+
+        /// Issues a code, as the provider does once the user logged in.
+        fn authorize(&self, code_challenge: &str, nonce: &str) -> String {
+            let code = generate_secret();
+            self.codes.lock().unwrap().insert(
+                code.to_owned(),
+                (code_challenge.to_owned(), nonce.to_owned()),
+            );
+            code
+        }
+
+        fn token(&self, form: HashMap<String, String>) -> (StatusCode, Json<serde_json::Value>) {
+            let param = |name: &str| form.get(name).map(String::as_str).unwrap_or_default();
+            let pending = self.codes.lock().unwrap().remove(param("code"));
+            let verified = param("grant_type") == "authorization_code"
+                && param("client_id") == CLIENT_ID
+                && param("client_secret") == CLIENT_SECRET
+                && param("redirect_uri") == REDIRECT_URL;
+            let Some((_, nonce)) = pending.filter(|(challenge, _)| {
+                verified
+                    && *challenge == URL_SAFE_NO_PAD.encode(Sha256::digest(param("code_verifier")))
+            }) else {
+                return (
+                    StatusCode::BAD_REQUEST,
+                    Json(json!({ "error": "invalid_grant" })),
+                );
+            };
+
+            let mut header = Header::new(Algorithm::EdDSA);
+            header.kid = Some("mock".to_owned());
+            let now = get_current_timestamp();
+            let id_token = encode(
+                &header,
+                &json!({
+                    "iss": self.url,
+                    "aud": CLIENT_ID,
+                    "sub": "248289761001",
+                    "email": "ada@example.com",
+                    "email_verified": true,
+                    "preferred_username": "ada",
+                    "groups": ["platform-admins", "sre"],
+                    "nonce": nonce,
+                    "iat": now,
+                    "exp": now + 300,
+                }),
+                &self.key,
+            )
+            .unwrap();
+
+            (
+                StatusCode::OK,
+                Json(json!({
+                    "access_token": "mock",
+                    "token_type": "Bearer",
+                    "id_token": id_token,
+                })),
+            )
+        }
+    }
+
+    fn query_params(url: &str) -> HashMap<String, String> {
+        serde_urlencoded::from_str(url.split_once('?').unwrap().1).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_login_with_mock_issuer() {
+        let issuer = MockIssuer::start().await;
+        let client = OidcClient::new(issuer.config());
+
+        let login = LoginRequest::new();
+        let url = client.authorization_url(&login).await.unwrap();
+        assert!(url.starts_with(&format!("{}/authorize?", issuer.url)));
+        let params = query_params(&url);
+        assert_eq!(params["response_type"], "code");
+        assert_eq!(params["client_id"], CLIENT_ID);
+        assert_eq!(params["redirect_uri"], REDIRECT_URL);
+        assert_eq!(params["state"], login.state);
+        assert_eq!(params["code_challenge_method"], "S256");
+        assert_ne!(params["code_challenge"], login.code_verifier);
+
+        let code = issuer.authorize(&params["code_challenge"], &params["nonce"]);
+        let claims = client.exchange_code(&code, &login).await.unwrap();
+        assert_eq!(claims.sub, "248289761001");
+        assert_eq!(claims.email.as_deref(), Some("ada@example.com"));
+        assert!(claims.email_verified);
+        assert_eq!(claims.groups("groups"), ["platform-admins", "sre"]);
+
+        // Codes are redeemed once.
+        assert!(matches!(
+            client.exchange_code(&code, &login).await,
+            Err(OidcError::InvalidResponse(400))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_reject_mismatched_login() {
+        let issuer = MockIssuer::start().await;
+        let client = OidcClient::new(issuer.config());
+        let login = LoginRequest::new();
+        let other = LoginRequest::new();
+
+        // The verifier of another login does not match the challenge.
+        let code = issuer.authorize(&login.code_challenge(), &login.nonce);
+        assert!(matches!(
+            client.exchange_code(&code, &other).await,
+            Err(OidcError::InvalidResponse(400))
+        ));
+
+        // Nor does an ID token issued for another login.
+        let code = issuer.authorize(&login.code_challenge(), &other.nonce);
+        assert!(matches!(
+            client.exchange_code(&code, &login).await,
+            Err(OidcError::NonceMismatch)
+        ));
+
+        // Nor does a provider naming another issuer.
+        let mut config = issuer.config();
+        config.issuer.push('/');
+        assert!(matches!(
+            OidcClient::new(config).authorization_url(&login).await,
+            Err(OidcError::IssuerMismatch(_))
+        ));
+    }
+
+    #[test]
+    fn test_login_request_link_user() {
+        let login = LoginRequest {
+            link_user: Some("user-id".to_owned()),
+            ..LoginRequest::new()
+        };
+        let value = serde_json::to_string(&login).unwrap();
+        let parsed: LoginRequest = serde_json::from_str(&value).unwrap();
+        assert_eq!(parsed.link_user.as_deref(), Some("user-id"));
+
+        let value = serde_json::to_string(&LoginRequest::new()).unwrap();
+        assert!(!value.contains("link_user"));
+        let parsed: LoginRequest = serde_json::from_str(&value).unwrap();
+        assert_eq!(parsed.link_user, None);
+    }
+
+    #[test]
+    fn test_insecure_endpoint() {
+        let metadata = |issuer: &str, token_endpoint: &str| ProviderMetadata {
+            issuer: issuer.to_owned(),
+            authorization_endpoint: format!("{}/authorize", issuer),
+            token_endpoint: token_endpoint.to_owned(),
+            jwks_uri: format!("{}/jwks", issuer),
+        };
+
+        let secure = metadata("https://id.example.com", "HTTPS://id.example.com/token");
+        assert_eq!(secure.insecure_endpoint(), None);
+        let insecure = metadata("https://id.example.com", "http://id.example.com/token");
+        assert_eq!(
+            insecure.insecure_endpoint(),
+            Some("http://id.example.com/token")
+        );
+        let local = metadata("http://localhost:8080", "http://localhost:8080/token");
+        assert_eq!(local.insecure_endpoint(), None);
+    }
+
+    #[test]
+    fn test_parse_group_roles() {
+        assert_eq!(
+            parse_group_roles("platform-admins=admin, sre=operator,,auditors = auditor"),
+            Some(vec![
+                ("platform-admins".to_owned(), "admin".to_owned()),
+                ("sre".to_owned(), "operator".to_owned()),
+                ("auditors".to_owned(), "auditor".to_owned()),
+            ])
+        );
+        assert_eq!(parse_group_roles(""), Some(Vec::new()));
+        assert_eq!(parse_group_roles("sre"), None);
+        assert_eq!(parse_group_roles("sre="), None);
+    }
+
+    #[test]
+    fn test_mapped_roles() {
+        let group_roles = parse_group_roles("admins=admin,sre=operator,oncall=operator").unwrap();
+        let groups = HashSet::from(["oncall".to_owned(), "other".to_owned()]);
+
+        let (granted, revoked) = mapped_roles(&group_roles, &groups);
+        assert_eq!(granted, BTreeSet::from(["operator".to_owned()]));
+        assert_eq!(revoked, BTreeSet::from(["admin".to_owned()]));
+    }
+
+    #[test]
+    fn test_id_token_claims() {
+        let claims: IdTokenClaims = serde_json::from_value(json!({
+            "sub": "1",
+            "email_verified": "true",
+            "roles": "sre",
+        }))
+        .unwrap();
+        assert!(claims.email_verified);
+        assert_eq!(claims.groups("roles"), ["sre"]);
+        assert!(claims.groups("groups").is_empty());
+    }
+}
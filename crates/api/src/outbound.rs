@@ -0,0 +1,275 @@
+//! Requests from the manager to other services, such as an identity
+//! provider. Unlike the proxy these go to any host, so they speak HTTPS as
+//! well, verified against the bundled web PKI roots.
+//!
+//! Calls are rare and small, so each one is made on a blocking thread with
+//! a connection of its own.
+
+use std::{
+    io::{self, Read, Write},
+    net::{TcpStream, ToSocketAddrs},
+    sync::Arc,
+    time::Duration,
+};
+
+use axum::http::Uri;
+use once_cell::sync::Lazy;
+use rustls::{ClientConfig, ClientConnection, OwnedTrustAnchor, RootCertStore, ServerName};
+
+const TIMEOUT: Duration = Duration::from_secs(10);
+const MAX_RESPONSE_LEN: u64 = 1024 * 1024;
+const MAX_HEADERS: usize = 64;
+
+static TLS_CONFIG: Lazy<Arc<ClientConfig>> = Lazy::new(|| {
+    let mut roots = RootCertStore::empty();
+    roots.add_trust_anchors(webpki_roots::TLS_SERVER_ROOTS.iter().map(|anchor| {
+        OwnedTrustAnchor::from_subject_spki_name_constraints(
+            anchor.subject,
+            anchor.spki,
+            anchor.name_constraints,
+        )
+    }));
+
+    Arc::new(
+        ClientConfig::builder()
+            .with_safe_defaults()
+            .with_root_certificates(roots)
+            .with_no_client_auth(),
+    )
+});
+
+#[derive(Debug)]
+pub enum OutboundError {
+    Io(io::Error),
+    InvalidUrl(String),
+    Tls(rustls::Error),
+    InvalidResponse,
+}
+
+impl From<io::Error> for OutboundError {
+    fn from(err: io::Error) -> Self {
+        OutboundError::Io(err)
+    }
+}
+
+#[derive(Debug)]
+pub struct Response {
+    pub status: u16,
+    pub body: Vec<u8>,
+}
+
+impl Response {
+    pub fn is_success(&self) -> bool {
+        (200..300).contains(&self.status)
+    }
+}
+
+pub async fn get(url: &str) -> Result<Response, OutboundError> {
+    send("GET", url, None).await
+}
+
+/// Posts `params` as an `application/x-www-form-urlencoded` body.
+pub async fn post_form(url: &str, params: &[(&str, &str)]) -> Result<Response, OutboundError> {
+    let body = serde_urlencoded::to_string(params)
+        .map_err(|_| OutboundError::InvalidUrl(url.to_owned()))?;
+    send("POST", url, Some(body)).await
+}
+
+async fn send(method: &str, url: &str, form: Option<String>) -> Result<Response, OutboundError> {
+    let method = method.to_owned();
+    let url = url.to_owned();
+    tokio::task::spawn_blocking(move || send_blocking(&method, &url, form.as_deref()))
+        .await
+        .map_err(|err| OutboundError::Io(io::Error::other(err)))?
+}
+
+fn send_blocking(method: &str, url: &str, form: Option<&str>) -> Result<Response, OutboundError> {
+    let invalid_url = || OutboundError::InvalidUrl(url.to_owned());
+    let uri: Uri = url.parse().map_err(|_| invalid_url())?;
+    let https = match uri.scheme_str() {
+        Some("https") => true,
+        Some("http") => false,
+        _ => return Err(invalid_url()),
+    };
+    let host = uri
+        .host()
+        .ok_or_else(invalid_url)?
+        .trim_start_matches('[')
+        .trim_end_matches(']');
+    let port = uri.port_u16().unwrap_or(if https { 443 } else { 80 });
+    let authority = uri.authority().ok_or_else(invalid_url)?.as_str();
+    let path = uri.path_and_query().map_or("/", |path| path.as_str());
+
+    let mut request = format!(
+        "{method} {path} HTTP/1.1\r\nHost: {authority}\r\nAccept: application/json\r\nConnection: close\r\nUser-Agent: {}/{}\r\n",
+        env!("CARGO_PKG_NAME"),
+        env!("CARGO_PKG_VERSION"),
+    );
+    if let Some(form) = form {
+        request.push_str(&format!(
+            "Content-Type: application/x-www-form-urlencoded\r\nContent-Length: {}\r\n",
+            form.len()
+        ));
+    }
+    request.push_str("\r\n");
+    request.push_str(form.unwrap_or_default());
+
+    let tcp = connect(host, port)?;
+    let (raw, closed) = if https {
+        let server_name = ServerName::try_from(host).map_err(|_| invalid_url())?;
+        let connection =
+            ClientConnection::new(TLS_CONFIG.clone(), server_name).map_err(OutboundError::Tls)?;
+        exchange(rustls::StreamOwned::new(connection, tcp), &request)?
+    } else {
+        exchange(tcp, &request)?
+    };
+
+    parse_response(&raw, closed)
+}
+
+fn connect(host: &str, port: u16) -> Result<TcpStream, OutboundError> {
+    let mut last_err = io::Error::new(io::ErrorKind::NotFound, "no addresses");
+    for addr in (host, port).to_socket_addrs()? {
+        match TcpStream::connect_timeout(&addr, TIMEOUT) {
+            Ok(stream) => {
+                stream.set_read_timeout(Some(TIMEOUT))?;
+                stream.set_write_timeout(Some(TIMEOUT))?;
+                return Ok(stream);
+            }
+            Err(err) => last_err = err,
+        }
+    }
+    Err(last_err.into())
+}
+
+/// Writes the request and reads the response until the server closes the
+/// connection. Also returns whether it closed it cleanly, which TLS servers
+/// commonly skip by not sending a close_notify.
+fn exchange(
+    mut stream: impl Read + Write,
+    request: &str,
+) -> Result<(Vec<u8>, bool), OutboundError> {
+    stream.write_all(request.as_bytes())?;
+    stream.flush()?;
+
+    let mut raw = Vec::new();
+    let closed = match stream.take(MAX_RESPONSE_LEN + 1).read_to_end(&mut raw) {
+        Ok(_) => true,
+        Err(err) if err.kind() == io::ErrorKind::UnexpectedEof && !raw.is_empty() => false,
+        Err(err) => return Err(err.into()),
+    };
+    if raw.len() as u64 > MAX_RESPONSE_LEN {
+        return Err(OutboundError::InvalidResponse);
+    }
+    Ok((raw, closed))
+}
+
+/// Parses a response read until the connection ended. Unless it was closed
+/// cleanly, only bodies whose length is known are complete, since anyone on
+/// the path may cut the connection short.
+fn parse_response(raw: &[u8], closed: bool) -> Result<Response, OutboundError> {
+    let mut headers = [httparse::EMPTY_HEADER; MAX_HEADERS];
+    let mut response = httparse::Response::new(&mut headers);
+    let httparse::Status::Complete(head_len) = response
+        .parse(raw)
+        .map_err(|_| OutboundError::InvalidResponse)?
+    else {
+        return Err(OutboundError::InvalidResponse);
+    };
+
+    let header = |name: &str| {
+        response
+            .headers
+            .iter()
+            .find(|header| header.name.eq_ignore_ascii_case(name))
+            .and_then(|header| std::str::from_utf8(header.value).ok())
+    };
+    let body = &raw[head_len..];
+    let body = if header("transfer-encoding").is_some_and(|value| value.contains("chunked")) {
+        decode_chunked(body)?
+    } else if let Some(length) = header("content-length") {
+        let length = length
+            .trim()
+            .parse::<usize>()
+            .map_err(|_| OutboundError::InvalidResponse)?;
+        body.get(..length)
+            .ok_or(OutboundError::InvalidResponse)?
+            .to_vec()
+    } else if closed {
+        body.to_vec()
+    } else {
+        return Err(OutboundError::InvalidResponse);
+    };
+
+    Ok(Response {
+        status: response.code.ok_or(OutboundError::InvalidResponse)?,
+        body,
+    })
+}
+
+fn decode_chunked(mut body: &[u8]) -> Result<Vec<u8>, OutboundError> {
+    let mut decoded = Vec::new();
+    loop {
+        let httparse::Status::Complete((offset, len)) =
+            httparse::parse_chunk_size(body).map_err(|_| OutboundError::InvalidResponse)?
+        else {
+            return Err(OutboundError::InvalidResponse);
+        };
+        if len == 0 {
+            return Ok(decoded);
+        }
+        let end = offset + len as usize;
+        decoded.extend_from_slice(
+            body.get(offset..end)
+                .ok_or(OutboundError::InvalidResponse)?,
+        );
+        body = body
+            .get(end..)
+            .and_then(|rest| rest.strip_prefix(b"\r\n"))
+            .ok_or(OutboundError::InvalidResponse)?;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_response() {
+        let response = parse_response(
+            b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\nContent-Type: application/json\r\n\r\n{}",
+            false,
+        )
+        .unwrap();
+        assert!(response.is_success());
+        assert_eq!(response.body, b"{}");
+
+        let response = parse_response(
+            b"HTTP/1.1 400 Bad Request\r\nTransfer-Encoding: chunked\r\n\r\n4\r\n{\"a\"\r\n3\r\n:1}\r\n0\r\n\r\n",
+            false,
+        )
+        .unwrap();
+        assert_eq!(response.status, 400);
+        assert_eq!(response.body, b"{\"a\":1}");
+
+        assert!(parse_response(b"HTTP/1.1 200 OK\r\nContent-Length: 5\r\n\r\n{}", true).is_err());
+        assert!(parse_response(b"HTTP/1.1 200 OK\r\nContent-Le", true).is_err());
+
+        let unframed = b"HTTP/1.1 200 OK\r\nContent-Type: application/json\r\n\r\n{}";
+        assert_eq!(parse_response(unframed, true).unwrap().body, b"{}");
+        assert!(parse_response(unframed, false).is_err());
+        assert!(parse_response(
+            b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n2\r\n{}\r\n",
+            false
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_send_rejects_other_schemes() {
+        assert!(matches!(
+            send_blocking("GET", "ftp://example.com/", None),
+            Err(OutboundError::InvalidUrl(_))
+        ));
+    }
+}
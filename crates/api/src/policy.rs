@@ -0,0 +1,179 @@
+//! Named permissions and the roles granting them. Handlers check
+//! permissions with `authorize`, never roles. Users can always reach their
+//! own resources; permissions extend that to everyone else's.
+
+use std::str::FromStr;
+
+use entity::{sea_orm_active_enums::RoleEnum, user};
+use serde::{Deserialize, Serialize};
+use service::roles::Query;
+
+use crate::{auth::AccessTokenClaims, config::AppState, errors::ServerError};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+pub enum Permission {
+    #[serde(rename = "workers.read")]
+    WorkersRead,
+    /// Starting and stopping workers.
+    #[serde(rename = "workers.operate")]
+    WorkersOperate,
+    #[serde(rename = "workers.write")]
+    WorkersWrite,
+    #[serde(rename = "users.read")]
+    UsersRead,
+    #[serde(rename = "users.manage")]
+    UsersManage,
+    #[serde(rename = "teams.read")]
+    TeamsRead,
+    #[serde(rename = "teams.manage")]
+    TeamsManage,
+    #[serde(rename = "settings.read")]
+    SettingsRead,
+    #[serde(rename = "settings.manage")]
+    SettingsManage,
+    #[serde(rename = "roles.read")]
+    RolesRead,
+    #[serde(rename = "roles.manage")]
+    RolesManage,
+}
+
+impl Permission {
+    pub const ALL: [Permission; 11] = [
+        Permission::WorkersRead,
+        Permission::WorkersOperate,
+        Permission::WorkersWrite,
+        Permission::UsersRead,
+        Permission::UsersManage,
+        Permission::TeamsRead,
+        Permission::TeamsManage,
+        Permission::SettingsRead,
+        Permission::SettingsManage,
+        Permission::RolesRead,
+        Permission::RolesManage,
+    ];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Permission::WorkersRead => "workers.read",
+            Permission::WorkersOperate => "workers.operate",
+            Permission::WorkersWrite => "workers.write",
+            Permission::UsersRead => "users.read",
+            Permission::UsersManage => "users.manage",
+            Permission::TeamsRead => "teams.read",
+            Permission::TeamsManage => "teams.manage",
+            Permission::SettingsRead => "settings.read",
+            Permission::SettingsManage => "settings.manage",
+            Permission::RolesRead => "roles.read",
+            Permission::RolesManage => "roles.manage",
+        }
+    }
+}
+
+impl FromStr for Permission {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Permission::ALL
+            .into_iter()
+            .find(|permission| permission.as_str() == s)
+            .ok_or(())
+    }
+}
+
+/// Permissions of the roles built into `RoleEnum`. Plain users only reach
+/// their own resources.
+pub fn builtin_permissions(role: &RoleEnum) -> &'static [Permission] {
+    match role {
+        RoleEnum::Admin => &Permission::ALL,
+        RoleEnum::User => &[],
+    }
+}
+
+/// Parses stored permissions, skipping any this version does not know.
+pub fn parse_permissions(permissions: &[String]) -> Vec<Permission> {
+    permissions
+        .iter()
+        .filter_map(|permission| permission.parse().ok())
+        .collect()
+}
+
+/// Collects the permissions of the built-in and stored roles of a user.
+pub async fn user_permissions(
+    state: &AppState,
+    user: &user::Model,
+) -> Result<Vec<Permission>, ServerError> {
+    let roles = Query::find_roles_by_user_id(&state.db, user.id)
+        .await
+        .map_err(|err| {
+            tracing::error!("Failed to get roles: {:?}", err);
+            ServerError::InternalServerError
+        })?;
+
+    let mut permissions = Vec::new();
+    let granted = user
+        .roles
+        .iter()
+        .flat_map(|role| builtin_permissions(role).iter().copied())
+        .chain(
+            roles
+                .iter()
+                .flat_map(|role| parse_permissions(&role.permissions)),
+        );
+    for permission in granted {
+        if !permissions.contains(&permission) {
+            permissions.push(permission);
+        }
+    }
+    Ok(permissions)
+}
+
+pub fn allows(claims: &AccessTokenClaims, permission: Permission) -> bool {
+    claims.permissions.contains(&permission)
+}
+
+/// Checks that the caller holds a permission.
+pub fn authorize(claims: &AccessTokenClaims, permission: Permission) -> Result<(), ServerError> {
+    if !allows(claims, permission) {
+        tracing::error!("Missing permission {}: {:?}", permission.as_str(), claims);
+        return Err(ServerError::Unauthorized);
+    }
+    Ok(())
+}
+
+/// Checks that the caller is the user, or holds a permission over others.
+pub fn authorize_self_or(
+    claims: &AccessTokenClaims,
+    user_id: &str,
+    permission: Permission,
+) -> Result<(), ServerError> {
+    if claims.sub == user_id {
+        return Ok(());
+    }
+    authorize(claims, permission)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_permission_names() {
+        for permission in Permission::ALL {
+            assert_eq!(permission.as_str().parse(), Ok(permission));
+            assert_eq!(
+                serde_json::to_string(&permission).unwrap(),
+                format!("\"{}\"", permission.as_str())
+            );
+        }
+        assert_eq!(
+            parse_permissions(&["workers.read".to_owned(), "workers".to_owned()]),
+            [Permission::WorkersRead]
+        );
+    }
+
+    #[test]
+    fn test_builtin_permissions() {
+        assert_eq!(builtin_permissions(&RoleEnum::Admin), Permission::ALL);
+        assert!(builtin_permissions(&RoleEnum::User).is_empty());
+    }
+}
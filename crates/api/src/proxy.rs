@@ -0,0 +1,468 @@
+use std::{
+    collections::HashMap,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    task::{Context, Poll},
+    time::{Duration, Instant},
+};
+
+use axum::{
+    body::{Body, Bytes, HttpBody},
+    extract::{Request, State},
+    http::{header, HeaderMap, HeaderName, HeaderValue, StatusCode, Uri},
+    response::Response,
+    Router,
+};
+use http_body::{Frame, SizeHint};
+use hyper_util::{
+    client::legacy::{connect::HttpConnector, Client},
+    rt::TokioExecutor,
+};
+use service::{rollouts::Query as RolloutQuery, workers::Query};
+use tokio::net::TcpListener;
+
+use crate::{
+    config::AppState,
+    errors::ServerError,
+    metrics::{ProxyMetrics, RequestSample},
+    rollouts::{candidate_id, traffic_split},
+};
+
+pub type HttpClient = Client<HttpConnector, Body>;
+
+pub fn http_client() -> HttpClient {
+    Client::builder(TokioExecutor::new()).build_http()
+}
+
+const HOP_BY_HOP_HEADERS: [HeaderName; 8] = [
+    header::CONNECTION,
+    HeaderName::from_static("keep-alive"),
+    header::PROXY_AUTHENTICATE,
+    header::PROXY_AUTHORIZATION,
+    header::TE,
+    header::TRAILER,
+    header::TRANSFER_ENCODING,
+    header::UPGRADE,
+];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Route {
+    pub worker_id: String,
+    pub external_path: String,
+    pub upstream: String,
+    pub candidate: Option<Candidate>,
+}
+
+/// Candidate version of a worker taking part of its traffic during a rollout.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Candidate {
+    pub worker_id: String,
+    pub upstream: String,
+    pub split: TrafficSplit,
+}
+
+/// Decides which requests go to the candidate: those carrying the header or
+/// cookie, if configured, and `percentage` percent of the others.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TrafficSplit {
+    pub percentage: u64,
+    pub header: Option<(String, String)>,
+    pub cookie: Option<(String, String)>,
+}
+
+impl TrafficSplit {
+    /// `roll` is a per-request counter spreading requests over 100 slots.
+    pub fn matches(&self, headers: &HeaderMap, roll: u64) -> bool {
+        if let Some((name, value)) = &self.header {
+            if headers
+                .get_all(name.as_str())
+                .iter()
+                .any(|header| header.as_bytes() == value.as_bytes())
+            {
+                return true;
+            }
+        }
+
+        if let Some((name, value)) = &self.cookie {
+            let found = headers
+                .get_all(header::COOKIE)
+                .iter()
+                .filter_map(|header| header.to_str().ok())
+                .flat_map(|cookies| cookies.split(';'))
+                .filter_map(|cookie| cookie.trim().split_once('='))
+                .any(|(cookie_name, cookie_value)| cookie_name == name && cookie_value == value);
+            if found {
+                return true;
+            }
+        }
+
+        roll % 100 < self.percentage
+    }
+}
+
+/// Spreads requests between the active and candidate versions.
+static SPLIT_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+/// Routes of the running workers, grouped by host name and ordered from the
+/// longest `external_path` to the shortest so the first match wins.
+#[derive(Debug, Default)]
+pub struct RouteTable {
+    hosts: HashMap<String, Vec<Route>>,
+}
+
+impl RouteTable {
+    pub fn new(routes: Vec<(String, Route)>) -> Self {
+        let mut hosts: HashMap<String, Vec<Route>> = HashMap::new();
+        for (host_name, route) in routes {
+            hosts
+                .entry(host_name.to_ascii_lowercase())
+                .or_default()
+                .push(route);
+        }
+        for routes in hosts.values_mut() {
+            routes.sort_by(|a, b| {
+                normalize_prefix(&b.external_path)
+                    .len()
+                    .cmp(&normalize_prefix(&a.external_path).len())
+            });
+        }
+        Self { hosts }
+    }
+
+    pub fn lookup(&self, host: &str, path: &str) -> Option<&Route> {
+        let host = host
+            .rsplit_once(':')
+            .filter(|(_, port)| port.chars().all(|c| c.is_ascii_digit()))
+            .map_or(host, |(name, _)| name)
+            .to_ascii_lowercase();
+
+        self.hosts
+            .get(&host)?
+            .iter()
+            .find(|route| path_matches(&route.external_path, path))
+    }
+
+    pub fn len(&self) -> usize {
+        self.hosts.values().map(Vec::len).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+fn normalize_prefix(prefix: &str) -> &str {
+    prefix.trim_end_matches('/')
+}
+
+fn path_matches(prefix: &str, path: &str) -> bool {
+    let prefix = normalize_prefix(prefix);
+    prefix.is_empty()
+        || path
+            .strip_prefix(prefix)
+            .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+}
+
+/// Rebuilds the routing table from the workers that currently have a running
+/// workerd process. Failures are logged and the previous table is kept.
+pub async fn rebuild_routes(state: &AppState) {
+    let workers = match Query::find_all_workers(&state.db).await {
+        Ok(workers) => workers,
+        Err(err) => {
+            tracing::error!("Failed to rebuild proxy routes: {:?}", err);
+            return;
+        }
+    };
+
+    let rollouts = match RolloutQuery::find_active_rollouts(&state.db).await {
+        Ok(rollouts) => rollouts,
+        Err(err) => {
+            tracing::error!("Failed to rebuild proxy routes: {:?}", err);
+            return;
+        }
+    };
+    let mut rollouts = rollouts
+        .into_iter()
+        .map(|rollout| (rollout.worker_id, rollout))
+        .collect::<HashMap<_, _>>();
+
+    let running = state.chan_map.lock().await;
+    let routes = workers
+        .into_iter()
+        .map(|worker| (worker.id.to_string().replace('-', ""), worker))
+        .filter(|(id, _)| running.contains_key(id))
+        .map(|(id, worker)| {
+            let candidate = rollouts
+                .remove(&worker.id)
+                .map(|rollout| (candidate_id(&id), rollout))
+                .filter(|(candidate_id, _)| running.contains_key(candidate_id))
+                .map(|(candidate_id, rollout)| Candidate {
+                    worker_id: candidate_id,
+                    upstream: format!("{}:{}", worker.host_name, rollout.port),
+                    split: traffic_split(&rollout),
+                });
+            (
+                worker.host_name.clone(),
+                Route {
+                    worker_id: id,
+                    external_path: worker.external_path,
+                    upstream: format!("{}:{}", worker.host_name, worker.port),
+                    candidate,
+                },
+            )
+        })
+        .collect::<Vec<_>>();
+    drop(running);
+
+    let table = RouteTable::new(routes);
+    tracing::debug!("proxy routing table rebuilt with {} routes", table.len());
+    *state.routes.write().await = table;
+}
+
+pub async fn serve(state: AppState, listener: TcpListener) {
+    let app = Router::new().fallback(proxy).with_state(state);
+
+    tracing::debug!("proxy listening on {}", listener.local_addr().unwrap());
+    axum::serve(listener, app).await.unwrap();
+}
+
+async fn proxy(State(state): State<AppState>, mut req: Request) -> Result<Response, ServerError> {
+    let host = req
+        .headers()
+        .get(header::HOST)
+        .and_then(|host| host.to_str().ok())
+        .or_else(|| req.uri().host())
+        .ok_or(ServerError::RouteNotFound)?
+        .to_owned();
+
+    let route = state
+        .routes
+        .read()
+        .await
+        .lookup(&host, req.uri().path())
+        .cloned()
+        .ok_or(ServerError::RouteNotFound)?;
+
+    let (worker_id, upstream) = match route.candidate {
+        Some(candidate)
+            if candidate
+                .split
+                .matches(req.headers(), SPLIT_COUNTER.fetch_add(1, Ordering::Relaxed)) =>
+        {
+            (candidate.worker_id, candidate.upstream)
+        }
+        _ => (route.worker_id, route.upstream),
+    };
+
+    let path_and_query = req
+        .uri()
+        .path_and_query()
+        .map_or("/", |path_and_query| path_and_query.as_str());
+    *req.uri_mut() = format!("http://{}{}", upstream, path_and_query)
+        .parse::<Uri>()
+        .map_err(|err| {
+            tracing::error!("Invalid upstream for worker {}: {:?}", worker_id, err);
+            ServerError::BadGateway
+        })?;
+
+    let headers = req.headers_mut();
+    for name in HOP_BY_HOP_HEADERS {
+        headers.remove(name);
+    }
+    if let Ok(value) = HeaderValue::from_str(&host) {
+        headers.insert("x-forwarded-host", value);
+    }
+
+    let started_at = Instant::now();
+    let bytes_in = Arc::new(AtomicU64::new(0));
+    let req = req.map(|body| {
+        Body::new(MeteredBody {
+            inner: body,
+            bytes: bytes_in.clone(),
+            recorder: None,
+        })
+    });
+
+    let res = match state.http_client.request(req).await {
+        Ok(res) => res,
+        Err(err) => {
+            tracing::error!("Failed to proxy to worker {}: {:?}", worker_id, err);
+            state.proxy_metrics.record(
+                &worker_id,
+                RequestSample {
+                    status: StatusCode::BAD_GATEWAY.as_u16(),
+                    bytes_in: bytes_in.load(Ordering::Relaxed),
+                    bytes_out: 0,
+                    latency: started_at.elapsed(),
+                },
+            );
+            return Err(ServerError::BadGateway);
+        }
+    };
+
+    let recorder = Recorder {
+        metrics: state.proxy_metrics.clone(),
+        worker_id,
+        status: res.status().as_u16(),
+        latency: started_at.elapsed(),
+        bytes_in,
+    };
+    Ok(res.map(|body| {
+        Body::new(MeteredBody {
+            inner: Body::new(body),
+            bytes: Arc::new(AtomicU64::new(0)),
+            recorder: Some(recorder),
+        })
+    }))
+}
+
+/// Records a proxied request once its response body has been fully sent or
+/// dropped, so that the response size is known.
+struct Recorder {
+    metrics: Arc<ProxyMetrics>,
+    worker_id: String,
+    status: u16,
+    latency: Duration,
+    bytes_in: Arc<AtomicU64>,
+}
+
+/// Body wrapper counting the data bytes that flow through it.
+struct MeteredBody {
+    inner: Body,
+    bytes: Arc<AtomicU64>,
+    recorder: Option<Recorder>,
+}
+
+impl HttpBody for MeteredBody {
+    type Data = Bytes;
+    type Error = axum::Error;
+
+    fn poll_frame(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
+        let poll = Pin::new(&mut self.inner).poll_frame(cx);
+        if let Poll::Ready(Some(Ok(frame))) = &poll {
+            if let Some(data) = frame.data_ref() {
+                self.bytes.fetch_add(data.len() as u64, Ordering::Relaxed);
+            }
+        }
+        poll
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.inner.is_end_stream()
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        self.inner.size_hint()
+    }
+}
+
+impl Drop for MeteredBody {
+    fn drop(&mut self) {
+        if let Some(recorder) = self.recorder.take() {
+            recorder.metrics.record(
+                &recorder.worker_id,
+                RequestSample {
+                    status: recorder.status,
+                    bytes_in: recorder.bytes_in.load(Ordering::Relaxed),
+                    bytes_out: self.bytes.load(Ordering::Relaxed),
+                    latency: recorder.latency,
+                },
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn route(worker_id: &str, external_path: &str) -> Route {
+        Route {
+            worker_id: worker_id.to_string(),
+            external_path: external_path.to_string(),
+            upstream: "localhost:8080".to_string(),
+            candidate: None,
+        }
+    }
+
+    #[test]
+    fn test_lookup_longest_prefix() {
+        let table = RouteTable::new(vec![
+            ("example.com".to_string(), route("root", "/")),
+            ("example.com".to_string(), route("api", "/api")),
+            ("example.com".to_string(), route("v2", "/api/v2/")),
+        ]);
+
+        assert_eq!(table.lookup("example.com", "/").unwrap().worker_id, "root");
+        assert_eq!(
+            table.lookup("example.com", "/apix").unwrap().worker_id,
+            "root"
+        );
+        assert_eq!(
+            table.lookup("example.com", "/api").unwrap().worker_id,
+            "api"
+        );
+        assert_eq!(
+            table.lookup("example.com", "/api/v1").unwrap().worker_id,
+            "api"
+        );
+        assert_eq!(
+            table.lookup("example.com", "/api/v2").unwrap().worker_id,
+            "v2"
+        );
+        assert_eq!(
+            table.lookup("example.com", "/api/v2/x").unwrap().worker_id,
+            "v2"
+        );
+    }
+
+    #[test]
+    fn test_lookup_host() {
+        let table = RouteTable::new(vec![
+            ("Example.com".to_string(), route("a", "/")),
+            ("other.com".to_string(), route("b", "/only")),
+        ]);
+
+        assert_eq!(
+            table.lookup("example.COM:8443", "/").unwrap().worker_id,
+            "a"
+        );
+        assert_eq!(table.lookup("other.com", "/only/x").unwrap().worker_id, "b");
+        assert!(table.lookup("other.com", "/").is_none());
+        assert!(table.lookup("unknown.com", "/").is_none());
+        assert_eq!(table.len(), 2);
+    }
+
+    #[test]
+    fn test_traffic_split() {
+        let split = TrafficSplit {
+            percentage: 25,
+            header: Some(("x-canary".to_string(), "1".to_string())),
+            cookie: Some(("canary".to_string(), "yes".to_string())),
+        };
+        let empty = HeaderMap::new();
+
+        assert_eq!(
+            (0..100).filter(|roll| split.matches(&empty, *roll)).count(),
+            25
+        );
+
+        let mut headers = HeaderMap::new();
+        headers.insert("x-canary", HeaderValue::from_static("1"));
+        assert!(split.matches(&headers, 99));
+
+        let mut headers = HeaderMap::new();
+        headers.insert(header::COOKIE, HeaderValue::from_static("a=b; canary=yes"));
+        assert!(split.matches(&headers, 99));
+
+        let mut headers = HeaderMap::new();
+        headers.insert(header::COOKIE, HeaderValue::from_static("canary=no"));
+        assert!(!split.matches(&headers, 99));
+    }
+}
@@ -0,0 +1,217 @@
+//! Roles stored in the database, next to the ones built into `RoleEnum`.
+
+use axum::{
+    debug_handler,
+    extract::{Path, State},
+    Json,
+};
+use entity::{role, sea_orm_active_enums::RoleEnum};
+use serde::{Deserialize, Serialize};
+use service::{
+    roles::{Mutation, Query},
+    sea_orm::{prelude::Uuid, ActiveEnum},
+};
+
+use crate::{
+    auth::bump_token_generation,
+    config::AppState,
+    errors::ServerError,
+    policy::{authorize, builtin_permissions, parse_permissions, Permission},
+    scopes::{Scoped, UsersAdmin, UsersRead},
+    users::MessageResponse,
+};
+
+const MAX_ROLE_NAME_LEN: usize = 50;
+
+#[derive(Deserialize)]
+pub struct RoleUpdateRequest {
+    pub description: Option<String>,
+    pub permissions: Vec<Permission>,
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct RoleResponse {
+    pub name: String,
+    pub description: Option<String>,
+    pub permissions: Vec<Permission>,
+    /// Built-in roles cannot be changed or deleted.
+    pub builtin: bool,
+}
+
+impl From<role::Model> for RoleResponse {
+    fn from(role: role::Model) -> Self {
+        Self {
+            name: role.name,
+            description: role.description,
+            permissions: parse_permissions(&role.permissions),
+            builtin: false,
+        }
+    }
+}
+
+/// Stored role names are lowercase words that do not shadow built-in roles.
+fn is_valid_role_name(name: &str) -> bool {
+    !name.is_empty()
+        && name.len() <= MAX_ROLE_NAME_LEN
+        && name
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-' || c == '_')
+        && RoleEnum::try_from_value(&name.to_owned()).is_err()
+}
+
+#[debug_handler]
+pub async fn get_roles(
+    State(state): State<AppState>,
+    Scoped(claims, _): Scoped<UsersRead>,
+) -> Result<Json<Vec<RoleResponse>>, ServerError> {
+    authorize(&claims, Permission::RolesRead)?;
+
+    let roles = Query::find_all_roles(&state.db).await.map_err(|err| {
+        tracing::error!("Failed to get roles: {:?}", err);
+        ServerError::InternalServerError
+    })?;
+
+    let builtin = [RoleEnum::Admin, RoleEnum::User].map(|role| RoleResponse {
+        name: role.to_value(),
+        description: None,
+        permissions: builtin_permissions(&role).to_vec(),
+        builtin: true,
+    });
+    Ok(Json(
+        builtin
+            .into_iter()
+            .chain(roles.into_iter().map(Into::into))
+            .collect(),
+    ))
+}
+
+/// Creates or replaces a role. The caller must hold every permission it
+/// grants, so that nobody can define a role above their own.
+#[debug_handler]
+pub async fn set_role(
+    State(state): State<AppState>,
+    Scoped(claims, _): Scoped<UsersAdmin>,
+    Path(name): Path<String>,
+    Json(request): Json<RoleUpdateRequest>,
+) -> Result<Json<RoleResponse>, ServerError> {
+    authorize(&claims, Permission::RolesManage)?;
+    if !is_valid_role_name(&name) {
+        return Err(ServerError::InvalidRoleName);
+    }
+    let current = find_role(&state, &name).await?;
+    if let Some(role) = &current {
+        for permission in parse_permissions(&role.permissions) {
+            authorize(&claims, permission)?;
+        }
+    }
+    let mut permissions = Vec::new();
+    for permission in request.permissions {
+        authorize(&claims, permission)?;
+        if !permissions.contains(&permission) {
+            permissions.push(permission);
+        }
+    }
+
+    Mutation::set_role(
+        &state.db,
+        name.to_owned(),
+        request.description.to_owned(),
+        permissions
+            .iter()
+            .map(|permission| permission.as_str().to_owned())
+            .collect(),
+    )
+    .await
+    .map_err(|err| {
+        tracing::error!("Failed to set role: {:?}", err);
+        ServerError::InternalServerError
+    })?;
+    if current.is_some_and(|role| parse_permissions(&role.permissions) != permissions) {
+        bump_holders(&state, &name).await?;
+    }
+
+    Ok(Json(RoleResponse {
+        name,
+        description: request.description,
+        permissions,
+        builtin: false,
+    }))
+}
+
+#[debug_handler]
+pub async fn delete_role(
+    State(state): State<AppState>,
+    Scoped(claims, _): Scoped<UsersAdmin>,
+    Path(name): Path<String>,
+) -> Result<Json<MessageResponse>, ServerError> {
+    authorize(&claims, Permission::RolesManage)?;
+    let role = find_role(&state, &name)
+        .await?
+        .ok_or(ServerError::NotFound)?;
+    for permission in parse_permissions(&role.permissions) {
+        authorize(&claims, permission)?;
+    }
+    // Assignments go with the role, so its holders are found beforehand.
+    let holders = find_holders(&state, &name).await?;
+
+    let result = Mutation::delete_role(&state.db, name)
+        .await
+        .map_err(|err| {
+            tracing::error!("Failed to delete role: {:?}", err);
+            ServerError::InternalServerError
+        })?;
+    if result.rows_affected == 0 {
+        return Err(ServerError::NotFound);
+    }
+    for user_id in holders {
+        bump_token_generation(&state, &user_id.to_string())?;
+    }
+
+    Ok(Json(MessageResponse {
+        message: "Role deleted successfully".to_owned(),
+    }))
+}
+
+async fn find_role(state: &AppState, name: &str) -> Result<Option<role::Model>, ServerError> {
+    Query::find_role_by_name(&state.db, name.to_owned())
+        .await
+        .map_err(|err| {
+            tracing::error!("Failed to get role: {:?}", err);
+            ServerError::InternalServerError
+        })
+}
+
+async fn find_holders(state: &AppState, name: &str) -> Result<Vec<Uuid>, ServerError> {
+    Query::find_user_ids_by_role(&state.db, name.to_owned())
+        .await
+        .map_err(|err| {
+            tracing::error!("Failed to get role holders: {:?}", err);
+            ServerError::InternalServerError
+        })
+}
+
+/// Rejects the tokens of the holders of a role, which carry its previous
+/// permissions.
+async fn bump_holders(state: &AppState, name: &str) -> Result<(), ServerError> {
+    for user_id in find_holders(state, name).await? {
+        bump_token_generation(state, &user_id.to_string())?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_valid_role_name() {
+        assert!(is_valid_role_name("operator"));
+        assert!(is_valid_role_name("release-manager_2"));
+        assert!(!is_valid_role_name(""));
+        assert!(!is_valid_role_name("Operator"));
+        assert!(!is_valid_role_name("on call"));
+        assert!(!is_valid_role_name("admin"));
+        assert!(!is_valid_role_name("user"));
+        assert!(!is_valid_role_name(&"a".repeat(MAX_ROLE_NAME_LEN + 1)));
+    }
+}
@@ -0,0 +1,493 @@
+use std::time::Duration;
+
+use axum::{
+    debug_handler,
+    extract::{Path, State},
+    Json,
+};
+use chrono::Utc;
+use entity::{rollout, sea_orm_active_enums::RolloutStatusEnum, worker};
+use service::{
+    deployments::{Mutation as DeploymentMutation, Query as DeploymentQuery},
+    rollouts::{Mutation, Query},
+};
+
+use crate::{
+    config::AppState,
+    deployments::{get_owned_worker, go_live, record_deployment},
+    errors::ServerError,
+    metrics::RequestStats,
+    proxy::{rebuild_routes, TrafficSplit},
+    scopes::{Scoped, WorkersRead, WorkersWrite},
+    teams::WorkerAccess,
+    workerd::{start_worker, stop_worker, write_worker_files, Worker},
+    workers::MessageResponse,
+};
+
+/// How often the error rate of the candidates is checked.
+const MONITOR_INTERVAL: Duration = Duration::from_secs(10);
+
+const DEFAULT_MAX_ERROR_RATE: f64 = 0.05;
+const DEFAULT_MIN_REQUESTS: i32 = 20;
+
+#[derive(Clone, serde::Deserialize, serde::Serialize)]
+pub struct MatchRule {
+    pub name: String,
+    pub value: String,
+}
+
+#[derive(serde::Deserialize, serde::Serialize)]
+pub struct TrafficSplitRequest {
+    pub percentage: i32,
+    pub header: Option<MatchRule>,
+    pub cookie: Option<MatchRule>,
+}
+
+#[derive(serde::Deserialize, serde::Serialize)]
+pub struct RolloutCreateRequest {
+    /// Existing deployment to roll out. When missing, a new deployment is
+    /// recorded from `entry`, `code` and `template`.
+    pub deployment_id: Option<String>,
+    pub entry: Option<String>,
+    pub code: Option<String>,
+    pub template: Option<String>,
+    /// Port of the candidate's workerd socket.
+    pub port: i32,
+    pub split: TrafficSplitRequest,
+    pub max_error_rate: Option<f64>,
+    pub min_requests: Option<i32>,
+}
+
+#[derive(serde::Serialize)]
+pub struct CandidateStats {
+    pub requests: u64,
+    pub server_errors: u64,
+    pub error_rate: f64,
+}
+
+#[derive(serde::Serialize)]
+pub struct RolloutInfoResponse {
+    pub id: String,
+    pub worker_id: String,
+    pub deployment_id: String,
+    pub port: i32,
+    pub split: TrafficSplitRequest,
+    pub max_error_rate: f64,
+    pub min_requests: i32,
+    pub status: RolloutStatusEnum,
+    pub reason: Option<String>,
+    pub created_at: String,
+    pub ended_at: Option<String>,
+    pub candidate: CandidateStats,
+}
+
+impl RolloutInfoResponse {
+    fn new(rollout: rollout::Model, stats: &RequestStats) -> Self {
+        Self {
+            id: rollout.id.to_string(),
+            worker_id: rollout.worker_id.to_string(),
+            deployment_id: rollout.deployment_id.to_string(),
+            port: rollout.port,
+            split: TrafficSplitRequest {
+                percentage: rollout.percentage,
+                header: rollout
+                    .header_name
+                    .zip(rollout.header_value)
+                    .map(|(name, value)| MatchRule { name, value }),
+                cookie: rollout
+                    .cookie_name
+                    .zip(rollout.cookie_value)
+                    .map(|(name, value)| MatchRule { name, value }),
+            },
+            max_error_rate: rollout.max_error_rate,
+            min_requests: rollout.min_requests,
+            status: rollout.status,
+            reason: rollout.reason,
+            created_at: rollout.created_at.to_rfc3339(),
+            ended_at: rollout.ended_at.map(|ended_at| ended_at.to_rfc3339()),
+            candidate: CandidateStats {
+                requests: stats.requests,
+                server_errors: stats.status.server_error,
+                error_rate: stats.error_rate(),
+            },
+        }
+    }
+}
+
+/// Id under which the candidate of a worker runs and records its metrics.
+/// It has to stay a valid Cap'n Proto identifier once used in the Capfile.
+pub fn candidate_id(worker_id: &str) -> String {
+    format!("{}candidate", worker_id.replace('-', ""))
+}
+
+pub fn traffic_split(rollout: &rollout::Model) -> TrafficSplit {
+    TrafficSplit {
+        percentage: rollout.percentage.clamp(0, 100) as u64,
+        header: rollout
+            .header_name
+            .clone()
+            .zip(rollout.header_value.clone()),
+        cookie: rollout
+            .cookie_name
+            .clone()
+            .zip(rollout.cookie_value.clone()),
+    }
+}
+
+#[debug_handler]
+pub async fn get_rollout(
+    State(state): State<AppState>,
+    Scoped(claims, _): Scoped<WorkersRead>,
+    Path(id): Path<String>,
+) -> Result<Json<RolloutInfoResponse>, ServerError> {
+    get_owned_worker(&state, &claims, id.to_owned(), WorkerAccess::Read).await?;
+    let rollout = get_active_rollout(&state, id).await?;
+
+    let stats = candidate_stats(&state, &rollout);
+    Ok(Json(RolloutInfoResponse::new(rollout, &stats)))
+}
+
+#[debug_handler]
+pub async fn create_rollout(
+    State(state): State<AppState>,
+    Scoped(claims, _): Scoped<WorkersWrite>,
+    Path(id): Path<String>,
+    Json(rollout_request): Json<RolloutCreateRequest>,
+) -> Result<Json<RolloutInfoResponse>, ServerError> {
+    let worker = get_owned_worker(&state, &claims, id.to_owned(), WorkerAccess::Write).await?;
+
+    let max_error_rate = rollout_request
+        .max_error_rate
+        .unwrap_or(DEFAULT_MAX_ERROR_RATE);
+    let min_requests = rollout_request.min_requests.unwrap_or(DEFAULT_MIN_REQUESTS);
+    if rollout_request.port == worker.port
+        || !(0.0..=1.0).contains(&max_error_rate)
+        || min_requests < 0
+    {
+        return Err(ServerError::InvalidRollout);
+    }
+    let (percentage, header, cookie) = validate_split(rollout_request.split)?;
+
+    let active = Query::find_active_rollout_by_worker_id(&state.db, id.to_owned())
+        .await
+        .map_err(|err| {
+            tracing::error!("Failed to get rollout: {:?}", err);
+            ServerError::InternalServerError
+        })?;
+    if active.is_some() {
+        return Err(ServerError::RolloutInProgress);
+    }
+
+    let deployment = match rollout_request.deployment_id {
+        Some(deployment_id) => DeploymentQuery::find_deployment_by_id(&state.db, deployment_id)
+            .await
+            .map_err(|err| {
+                tracing::error!("Failed to get deployment: {:?}", err);
+                ServerError::InternalServerError
+            })?
+            .filter(|deployment| deployment.worker_id == worker.id)
+            .ok_or(ServerError::NotFound)?,
+        None => {
+            let mut candidate: Worker = worker.clone().into();
+            if rollout_request.entry.is_some() || rollout_request.code.is_some() {
+                candidate.modules = None;
+            }
+            candidate.entry = rollout_request.entry.unwrap_or(candidate.entry);
+            candidate.code = rollout_request.code.unwrap_or(candidate.code);
+            candidate.template = rollout_request.template.or(candidate.template);
+            record_deployment(&state, claims.sub, &candidate, false).await?
+        }
+    };
+
+    let rollout = Mutation::create_rollout(
+        &state.db,
+        id,
+        deployment.id.to_string(),
+        rollout_request.port,
+        percentage,
+        header,
+        cookie,
+        max_error_rate,
+        min_requests,
+    )
+    .await
+    .map_err(|err| {
+        tracing::error!("Failed to create rollout: {:?}", err);
+        ServerError::InternalServerError
+    })?;
+
+    let mut candidate: Worker = worker::Model {
+        entry: deployment.entry,
+        code: deployment.code,
+        template: deployment.template,
+        modules: deployment.modules,
+        bindings: deployment.bindings,
+        compatibility_date: deployment.compatibility_date,
+        team_id: None,
+        ..worker
+    }
+    .into();
+    candidate.id = candidate_id(&candidate.id);
+    candidate.port = rollout.port.to_string();
+    state.proxy_metrics.remove(&candidate.id);
+
+    let started = match write_worker_files(&state, &candidate).await {
+        Ok(()) => start_worker(&state, &candidate).await,
+        Err(err) => Err(err),
+    };
+    if let Err(err) = started {
+        tracing::error!("Failed to start candidate {}: {:?}", candidate.id, err);
+        finish_rollout(
+            &state,
+            &rollout,
+            RolloutStatusEnum::Aborted,
+            Some("Candidate failed to start".to_owned()),
+        )
+        .await?;
+        return Err(err);
+    }
+
+    Ok(Json(RolloutInfoResponse::new(
+        rollout,
+        &RequestStats::default(),
+    )))
+}
+
+#[debug_handler]
+pub async fn update_rollout_split(
+    State(state): State<AppState>,
+    Scoped(claims, _): Scoped<WorkersWrite>,
+    Path(id): Path<String>,
+    Json(split_request): Json<TrafficSplitRequest>,
+) -> Result<Json<RolloutInfoResponse>, ServerError> {
+    get_owned_worker(&state, &claims, id.to_owned(), WorkerAccess::Write).await?;
+    let rollout = get_active_rollout(&state, id).await?;
+    let (percentage, header, cookie) = validate_split(split_request)?;
+
+    let rollout = Mutation::update_rollout_split(
+        &state.db,
+        rollout.id.to_string(),
+        percentage,
+        header,
+        cookie,
+    )
+    .await
+    .map_err(|err| {
+        tracing::error!("Failed to update rollout: {:?}", err);
+        ServerError::InternalServerError
+    })?;
+    rebuild_routes(&state).await;
+
+    let stats = candidate_stats(&state, &rollout);
+    Ok(Json(RolloutInfoResponse::new(rollout, &stats)))
+}
+
+#[debug_handler]
+pub async fn promote_rollout(
+    State(state): State<AppState>,
+    Scoped(claims, _): Scoped<WorkersWrite>,
+    Path(id): Path<String>,
+) -> Result<Json<MessageResponse>, ServerError> {
+    get_owned_worker(&state, &claims, id.to_owned(), WorkerAccess::Write).await?;
+    let rollout = get_active_rollout(&state, id.to_owned()).await?;
+
+    let deployment =
+        DeploymentQuery::find_deployment_by_id(&state.db, rollout.deployment_id.to_string())
+            .await
+            .map_err(|err| {
+                tracing::error!("Failed to get deployment: {:?}", err);
+                ServerError::InternalServerError
+            })?
+            .ok_or(ServerError::NotFound)?;
+
+    let worker = DeploymentMutation::activate_deployment(&state.db, &deployment)
+        .await
+        .map_err(|err| {
+            tracing::error!("Failed to activate deployment: {:?}", err);
+            ServerError::InternalServerError
+        })?;
+    finish_rollout(&state, &rollout, RolloutStatusEnum::Promoted, None).await?;
+    go_live(&state, &worker.into()).await?;
+
+    Ok(Json(MessageResponse {
+        message: format!("Deployment {} promoted", deployment.id),
+    }))
+}
+
+#[debug_handler]
+pub async fn abort_rollout(
+    State(state): State<AppState>,
+    Scoped(claims, _): Scoped<WorkersWrite>,
+    Path(id): Path<String>,
+) -> Result<Json<MessageResponse>, ServerError> {
+    get_owned_worker(&state, &claims, id.to_owned(), WorkerAccess::Write).await?;
+    let rollout = get_active_rollout(&state, id).await?;
+
+    finish_rollout(
+        &state,
+        &rollout,
+        RolloutStatusEnum::Aborted,
+        Some("Aborted manually".to_owned()),
+    )
+    .await?;
+
+    Ok(Json(MessageResponse {
+        message: "Rollout aborted".to_owned(),
+    }))
+}
+
+/// Aborts the rollouts whose candidate answers with too many server errors.
+pub async fn monitor(state: AppState) {
+    let mut interval = tokio::time::interval(MONITOR_INTERVAL);
+
+    loop {
+        interval.tick().await;
+
+        let rollouts = match Query::find_active_rollouts(&state.db).await {
+            Ok(rollouts) => rollouts,
+            Err(err) => {
+                tracing::error!("Failed to load rollouts: {:?}", err);
+                continue;
+            }
+        };
+
+        for rollout in rollouts {
+            let stats = candidate_stats(&state, &rollout);
+            if !exceeds_error_rate(&rollout, &stats) {
+                continue;
+            }
+
+            let reason = format!(
+                "Candidate error rate {:.1}% exceeded {:.1}% over {} requests",
+                stats.error_rate() * 100.0,
+                rollout.max_error_rate * 100.0,
+                stats.requests
+            );
+            tracing::warn!("Aborting rollout {}: {}", rollout.id, reason);
+            if let Err(err) =
+                finish_rollout(&state, &rollout, RolloutStatusEnum::Aborted, Some(reason)).await
+            {
+                tracing::error!("Failed to abort rollout {}: {:?}", rollout.id, err);
+            }
+        }
+    }
+}
+
+fn exceeds_error_rate(rollout: &rollout::Model, stats: &RequestStats) -> bool {
+    stats.requests >= rollout.min_requests.max(1) as u64
+        && stats.error_rate() > rollout.max_error_rate
+}
+
+fn candidate_stats(state: &AppState, rollout: &rollout::Model) -> RequestStats {
+    state
+        .proxy_metrics
+        .with_worker(&candidate_id(&rollout.worker_id.to_string()), |metrics| {
+            metrics.total().clone()
+        })
+}
+
+type Split = (i32, Option<(String, String)>, Option<(String, String)>);
+
+fn validate_split(split: TrafficSplitRequest) -> Result<Split, ServerError> {
+    if !(0..=100).contains(&split.percentage) {
+        return Err(ServerError::InvalidRollout);
+    }
+
+    Ok((
+        split.percentage,
+        split
+            .header
+            .map(|rule| (rule.name.to_ascii_lowercase(), rule.value)),
+        split.cookie.map(|rule| (rule.name, rule.value)),
+    ))
+}
+
+async fn get_active_rollout(state: &AppState, id: String) -> Result<rollout::Model, ServerError> {
+    Query::find_active_rollout_by_worker_id(&state.db, id)
+        .await
+        .map_err(|err| {
+            tracing::error!("Failed to get rollout: {:?}", err);
+            ServerError::InternalServerError
+        })?
+        .ok_or(ServerError::NotFound)
+}
+
+/// Marks the rollout as finished and stops its candidate.
+async fn finish_rollout(
+    state: &AppState,
+    rollout: &rollout::Model,
+    status: RolloutStatusEnum,
+    reason: Option<String>,
+) -> Result<(), ServerError> {
+    Mutation::finish_rollout(
+        &state.db,
+        rollout.id.to_string(),
+        status,
+        reason,
+        Utc::now().into(),
+    )
+    .await
+    .map_err(|err| {
+        tracing::error!("Failed to finish rollout: {:?}", err);
+        ServerError::InternalServerError
+    })?;
+
+    let candidate_id = candidate_id(&rollout.worker_id.to_string());
+    match stop_worker(state, &candidate_id).await {
+        Ok(()) | Err(ServerError::WorkerNotRunning) => {}
+        Err(err) => return Err(err),
+    }
+    state.proxy_metrics.remove(&candidate_id);
+    rebuild_routes(state).await;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rollout(max_error_rate: f64, min_requests: i32) -> rollout::Model {
+        rollout::Model {
+            id: Default::default(),
+            worker_id: Default::default(),
+            deployment_id: Default::default(),
+            port: 8081,
+            percentage: 10,
+            header_name: None,
+            header_value: None,
+            cookie_name: None,
+            cookie_value: None,
+            max_error_rate,
+            min_requests,
+            status: RolloutStatusEnum::Active,
+            reason: None,
+            created_at: "2024-01-01T00:00:00+00:00".parse().unwrap(),
+            ended_at: None,
+        }
+    }
+
+    fn stats(requests: u64, server_errors: u64) -> RequestStats {
+        let mut stats = RequestStats {
+            requests,
+            ..Default::default()
+        };
+        stats.status.server_error = server_errors;
+        stats
+    }
+
+    #[test]
+    fn test_exceeds_error_rate() {
+        assert!(!exceeds_error_rate(&rollout(0.05, 20), &stats(10, 10)));
+        assert!(!exceeds_error_rate(&rollout(0.05, 20), &stats(100, 5)));
+        assert!(exceeds_error_rate(&rollout(0.05, 20), &stats(100, 6)));
+        assert!(!exceeds_error_rate(&rollout(0.05, 0), &stats(0, 0)));
+    }
+
+    #[test]
+    fn test_candidate_id() {
+        assert_eq!(
+            candidate_id("00000000-0000-0000-0000-000000000001"),
+            "00000000000000000000000000000001candidate"
+        );
+    }
+}
@@ -0,0 +1,254 @@
+use std::time::{Duration, Instant};
+
+use axum::{
+    body::Body,
+    http::{Request, Uri},
+};
+use chrono::Utc;
+use cron::Schedule;
+use entity::{sea_orm_active_enums::RunStatusEnum, worker, worker_schedule};
+use http_body_util::BodyExt;
+use service::schedules::{Mutation, Query};
+
+use crate::config::AppState;
+
+/// Longest time the scheduler sleeps before reloading the schedules.
+const MAX_SLEEP: Duration = Duration::from_secs(60);
+
+/// How long a worker gets to answer a scheduled invocation.
+const RUN_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// How much of the worker's response is kept in the run history.
+const MAX_OUTPUT_BYTES: usize = 64 * 1024;
+
+/// Parses a cron expression, accepting both the five-field syntax used by
+/// Cloudflare cron triggers and the six/seven-field syntax with seconds.
+pub fn parse_cron(expression: &str) -> Result<Schedule, cron::error::Error> {
+    let expression = expression.trim();
+    if expression.split_whitespace().count() == 5 {
+        format!("0 {expression}").parse()
+    } else {
+        expression.parse()
+    }
+}
+
+/// Checks the path a schedule requests from its worker. It must stay a path
+/// on the worker, so it starts with a single `/` and has nothing that could
+/// be read as userinfo or another authority.
+pub fn is_valid_path(path: &str) -> bool {
+    path.starts_with('/')
+        && !path.starts_with("//")
+        && !path
+            .chars()
+            .any(|c| c == '@' || c == '\\' || c.is_whitespace() || c.is_control())
+}
+
+/// Fires the enabled schedules of every worker. The loop sleeps until the
+/// next schedule is due, or until `AppState::schedule_notify` signals that the
+/// schedules changed.
+pub async fn run(state: AppState) {
+    let mut last_tick = Utc::now();
+
+    loop {
+        let schedules = match Query::find_enabled_schedules_with_worker(&state.db).await {
+            Ok(schedules) => schedules,
+            Err(err) => {
+                tracing::error!("Failed to load schedules: {:?}", err);
+                vec![]
+            }
+        };
+        let schedules = schedules
+            .into_iter()
+            .filter_map(|(schedule, worker)| {
+                let cron = parse_cron(&schedule.cron)
+                    .map_err(|err| {
+                        tracing::error!("Invalid cron expression in {}: {:?}", schedule.id, err)
+                    })
+                    .ok()?;
+                Some((schedule, worker?, cron))
+            })
+            .collect::<Vec<_>>();
+
+        let now = Utc::now();
+        for (schedule, worker, cron) in &schedules {
+            if cron
+                .after(&last_tick)
+                .next()
+                .is_some_and(|next| next <= now)
+            {
+                tokio::spawn(fire(state.clone(), schedule.clone(), worker.clone()));
+            }
+        }
+        last_tick = now;
+
+        let sleep_for = schedules
+            .iter()
+            .filter_map(|(_, _, cron)| cron.after(&now).next())
+            .min()
+            .and_then(|next| (next - now).to_std().ok())
+            .map_or(MAX_SLEEP, |duration| duration.min(MAX_SLEEP));
+
+        tokio::select! {
+            _ = tokio::time::sleep(sleep_for) => {}
+            _ = state.schedule_notify.notified() => {}
+        }
+    }
+}
+
+async fn fire(state: AppState, schedule: worker_schedule::Model, worker: worker::Model) {
+    let started_at = Utc::now();
+    let started = Instant::now();
+    let (status, status_code, output) = invoke(&state, &schedule, &worker).await;
+
+    if let Err(err) = Mutation::create_run(
+        &state.db,
+        schedule.id.to_string(),
+        started_at.into(),
+        started.elapsed().as_millis() as i64,
+        status,
+        status_code,
+        output,
+    )
+    .await
+    {
+        tracing::error!("Failed to record run of {}: {:?}", schedule.id, err);
+    }
+}
+
+async fn invoke(
+    state: &AppState,
+    schedule: &worker_schedule::Model,
+    worker: &worker::Model,
+) -> (RunStatusEnum, Option<i32>, String) {
+    let worker_id = worker.id.to_string().replace('-', "");
+    if !state.chan_map.lock().await.contains_key(&worker_id) {
+        return (
+            RunStatusEnum::Skipped,
+            None,
+            "Worker is not running".to_owned(),
+        );
+    }
+
+    let path = schedule.path.clone().unwrap_or_else(|| {
+        format!(
+            "/__scheduled?{}",
+            serde_urlencoded::to_string([("cron", &schedule.cron)]).unwrap_or_default()
+        )
+    });
+    if !is_valid_path(&path) {
+        return (
+            RunStatusEnum::Failure,
+            None,
+            format!("Invalid schedule path {path:?}"),
+        );
+    }
+    let uri = Uri::builder()
+        .scheme("http")
+        .authority(format!("{}:{}", worker.host_name, worker.port))
+        .path_and_query(path)
+        .build();
+    let req = match uri.and_then(|uri| Request::get(uri).body(Body::empty())) {
+        Ok(req) => req,
+        Err(err) => return (RunStatusEnum::Failure, None, err.to_string()),
+    };
+
+    let res = match tokio::time::timeout(RUN_TIMEOUT, state.http_client.request(req)).await {
+        Ok(Ok(res)) => res,
+        Ok(Err(err)) => return (RunStatusEnum::Failure, None, err.to_string()),
+        Err(_) => {
+            return (
+                RunStatusEnum::Failure,
+                None,
+                format!("Timed out after {}s", RUN_TIMEOUT.as_secs()),
+            )
+        }
+    };
+
+    let status = if res.status().is_success() {
+        RunStatusEnum::Success
+    } else {
+        RunStatusEnum::Failure
+    };
+    let status_code = Some(res.status().as_u16() as i32);
+    let output = match read_truncated(Body::new(res.into_body()), MAX_OUTPUT_BYTES).await {
+        Ok(bytes) => String::from_utf8_lossy(&bytes).into_owned(),
+        Err(err) => format!("Failed to read response: {err}"),
+    };
+
+    (status, status_code, output)
+}
+
+/// Reads at most `limit` bytes of a body, dropping the rest.
+async fn read_truncated(mut body: Body, limit: usize) -> Result<Vec<u8>, axum::Error> {
+    let mut bytes = Vec::new();
+    while bytes.len() < limit {
+        let Some(frame) = body.frame().await else {
+            break;
+        };
+        if let Ok(data) = frame?.into_data() {
+            let len = data.len().min(limit - bytes.len());
+            bytes.extend_from_slice(&data[..len]);
+        }
+    }
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeZone, Timelike};
+
+    use super::*;
+
+    #[test]
+    fn test_is_valid_path() {
+        assert!(is_valid_path("/"));
+        assert!(is_valid_path("/tasks/cleanup?force=1"));
+
+        for path in [
+            "",
+            "tasks",
+            "@169.254.169.254/latest/meta-data/",
+            "/@169.254.169.254/",
+            "//169.254.169.254/",
+            "/\\evil",
+            "/a b",
+            "/a\tb",
+            "/a\r\nHost: evil",
+        ] {
+            assert!(!is_valid_path(path), "{path:?}");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_read_truncated() {
+        let body = Body::from("a".repeat(MAX_OUTPUT_BYTES + 10));
+        let bytes = read_truncated(body, MAX_OUTPUT_BYTES).await.unwrap();
+        assert_eq!(bytes.len(), MAX_OUTPUT_BYTES);
+
+        let bytes = read_truncated(Body::from("ok"), MAX_OUTPUT_BYTES)
+            .await
+            .unwrap();
+        assert_eq!(bytes, b"ok");
+    }
+
+    #[test]
+    fn test_parse_cron() {
+        let start = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 30).unwrap();
+
+        let next = parse_cron("*/5 * * * *")
+            .unwrap()
+            .after(&start)
+            .next()
+            .unwrap();
+        assert_eq!((next.minute(), next.second()), (5, 0));
+
+        let next = parse_cron("15 * * * * *")
+            .unwrap()
+            .after(&start)
+            .next()
+            .unwrap();
+        assert_eq!((next.minute(), next.second()), (1, 15));
+
+        assert!(parse_cron("not a cron").is_err());
+    }
+}
@@ -0,0 +1,229 @@
+use axum::{
+    debug_handler,
+    extract::{Path, State},
+    Json,
+};
+use entity::{sea_orm_active_enums::RunStatusEnum, worker_schedule};
+use service::schedules::{Mutation, Query};
+
+use crate::{
+    auth::AccessTokenClaims,
+    config::AppState,
+    errors::ServerError,
+    scheduler::{is_valid_path, parse_cron},
+    scopes::{Scoped, WorkersRead, WorkersWrite},
+    teams::WorkerAccess,
+    workerd::get_worker_with_id,
+    workers::MessageResponse,
+};
+
+/// Number of runs returned by the run history endpoint.
+const RUN_HISTORY_LIMIT: u64 = 100;
+
+#[derive(serde::Deserialize, serde::Serialize)]
+pub struct ScheduleCreateRequest {
+    pub cron: String,
+    pub path: Option<String>,
+}
+
+#[derive(serde::Deserialize, serde::Serialize)]
+pub struct ScheduleUpdateRequest {
+    pub cron: Option<String>,
+    pub path: Option<String>,
+    pub enabled: Option<bool>,
+}
+
+#[derive(serde::Deserialize, serde::Serialize)]
+pub struct ScheduleInfoResponse {
+    pub id: String,
+    pub worker_id: String,
+    pub cron: String,
+    pub path: Option<String>,
+    pub enabled: bool,
+    pub created_at: String,
+}
+
+impl From<worker_schedule::Model> for ScheduleInfoResponse {
+    fn from(schedule: worker_schedule::Model) -> Self {
+        Self {
+            id: schedule.id.to_string(),
+            worker_id: schedule.worker_id.to_string(),
+            cron: schedule.cron,
+            path: schedule.path,
+            enabled: schedule.enabled,
+            created_at: schedule.created_at.to_rfc3339(),
+        }
+    }
+}
+
+#[derive(serde::Deserialize, serde::Serialize)]
+pub struct ScheduleRunResponse {
+    pub id: String,
+    pub started_at: String,
+    pub duration_ms: i64,
+    pub status: RunStatusEnum,
+    pub status_code: Option<i32>,
+    pub output: String,
+}
+
+#[debug_handler]
+pub async fn get_schedules(
+    State(state): State<AppState>,
+    Scoped(claims, _): Scoped<WorkersRead>,
+    Path(id): Path<String>,
+) -> Result<Json<Vec<ScheduleInfoResponse>>, ServerError> {
+    get_worker_with_id(state.to_owned(), claims, id.to_owned(), WorkerAccess::Read).await?;
+
+    let schedules = Query::find_schedules_by_worker_id(&state.db, id)
+        .await
+        .map_err(|err| {
+            tracing::error!("Failed to get schedules: {:?}", err);
+            ServerError::InternalServerError
+        })?;
+
+    Ok(Json(schedules.into_iter().map(Into::into).collect()))
+}
+
+#[debug_handler]
+pub async fn create_schedule(
+    State(state): State<AppState>,
+    Scoped(claims, _): Scoped<WorkersWrite>,
+    Path(id): Path<String>,
+    Json(schedule): Json<ScheduleCreateRequest>,
+) -> Result<Json<MessageResponse>, ServerError> {
+    get_worker_with_id(state.to_owned(), claims, id.to_owned(), WorkerAccess::Write).await?;
+
+    parse_cron(&schedule.cron).map_err(|_| ServerError::InvalidCronExpression)?;
+    check_path(schedule.path.as_deref())?;
+
+    Mutation::create_schedule(&state.db, id, schedule.cron, schedule.path)
+        .await
+        .map_err(|err| {
+            tracing::error!("Failed to create schedule: {:?}", err);
+            ServerError::InternalServerError
+        })?;
+
+    state.schedule_notify.notify_one();
+
+    Ok(Json(MessageResponse {
+        message: "Schedule created successfully".to_owned(),
+    }))
+}
+
+#[debug_handler]
+pub async fn update_schedule(
+    State(state): State<AppState>,
+    Scoped(claims, _): Scoped<WorkersWrite>,
+    Path((id, sid)): Path<(String, String)>,
+    Json(schedule_request): Json<ScheduleUpdateRequest>,
+) -> Result<Json<MessageResponse>, ServerError> {
+    let schedule =
+        get_schedule_of_worker(&state, claims, id, sid.to_owned(), WorkerAccess::Write).await?;
+
+    if let Some(cron) = &schedule_request.cron {
+        parse_cron(cron).map_err(|_| ServerError::InvalidCronExpression)?;
+    }
+    check_path(schedule_request.path.as_deref())?;
+
+    Mutation::update_schedule(
+        &state.db,
+        sid,
+        schedule_request.cron.unwrap_or(schedule.cron),
+        schedule_request.path.or(schedule.path),
+        schedule_request.enabled.unwrap_or(schedule.enabled),
+    )
+    .await
+    .map_err(|err| {
+        tracing::error!("Failed to update schedule: {:?}", err);
+        ServerError::InternalServerError
+    })?;
+
+    state.schedule_notify.notify_one();
+
+    Ok(Json(MessageResponse {
+        message: "Schedule updated successfully".to_owned(),
+    }))
+}
+
+#[debug_handler]
+pub async fn delete_schedule(
+    State(state): State<AppState>,
+    Scoped(claims, _): Scoped<WorkersWrite>,
+    Path((id, sid)): Path<(String, String)>,
+) -> Result<Json<MessageResponse>, ServerError> {
+    get_schedule_of_worker(&state, claims, id, sid.to_owned(), WorkerAccess::Write).await?;
+
+    Mutation::delete_schedule(&state.db, sid)
+        .await
+        .map_err(|err| {
+            tracing::error!("Failed to delete schedule: {:?}", err);
+            ServerError::InternalServerError
+        })?;
+
+    state.schedule_notify.notify_one();
+
+    Ok(Json(MessageResponse {
+        message: "Schedule deleted successfully".to_owned(),
+    }))
+}
+
+#[debug_handler]
+pub async fn get_schedule_runs(
+    State(state): State<AppState>,
+    Scoped(claims, _): Scoped<WorkersRead>,
+    Path((id, sid)): Path<(String, String)>,
+) -> Result<Json<Vec<ScheduleRunResponse>>, ServerError> {
+    get_schedule_of_worker(&state, claims, id, sid.to_owned(), WorkerAccess::Read).await?;
+
+    let runs = Query::find_runs_by_schedule_id(&state.db, sid, RUN_HISTORY_LIMIT)
+        .await
+        .map_err(|err| {
+            tracing::error!("Failed to get schedule runs: {:?}", err);
+            ServerError::InternalServerError
+        })?;
+
+    Ok(Json(
+        runs.into_iter()
+            .map(|run| ScheduleRunResponse {
+                id: run.id.to_string(),
+                started_at: run.started_at.to_rfc3339(),
+                duration_ms: run.duration_ms,
+                status: run.status,
+                status_code: run.status_code,
+                output: run.output,
+            })
+            .collect(),
+    ))
+}
+
+/// Rejects schedule paths that could point the request at another host.
+pub(crate) fn check_path(path: Option<&str>) -> Result<(), ServerError> {
+    if path.is_some_and(|path| !is_valid_path(path)) {
+        return Err(ServerError::InvalidSchedulePath);
+    }
+    Ok(())
+}
+
+async fn get_schedule_of_worker(
+    state: &AppState,
+    claims: AccessTokenClaims,
+    id: String,
+    sid: String,
+    access: WorkerAccess,
+) -> Result<worker_schedule::Model, ServerError> {
+    let worker = get_worker_with_id(state.to_owned(), claims, id, access).await?;
+
+    let schedule = Query::find_schedule_by_id(&state.db, sid)
+        .await
+        .map_err(|err| {
+            tracing::error!("Failed to get schedule: {:?}", err);
+            ServerError::InternalServerError
+        })?
+        .ok_or(ServerError::NotFound)?;
+
+    if schedule.worker_id.to_string().replace('-', "") != worker.id {
+        return Err(ServerError::NotFound);
+    }
+
+    Ok(schedule)
+}
@@ -0,0 +1,148 @@
+//! Scopes narrow what a token may do, on top of the roles of its user.
+//! Handlers declare the scope they need by extracting `Scoped<S>` instead of
+//! `AccessTokenClaims`.
+
+use std::{marker::PhantomData, str::FromStr};
+
+use axum::{
+    async_trait,
+    extract::{FromRef, FromRequestParts},
+    http::request::Parts,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{auth::AccessTokenClaims, config::AppState, errors::ServerError};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+pub enum Scope {
+    #[serde(rename = "workers:read")]
+    WorkersRead,
+    #[serde(rename = "workers:write")]
+    WorkersWrite,
+    #[serde(rename = "workers:exec")]
+    WorkersExec,
+    #[serde(rename = "users:read")]
+    UsersRead,
+    #[serde(rename = "users:write")]
+    UsersWrite,
+    #[serde(rename = "users:admin")]
+    UsersAdmin,
+}
+
+impl Scope {
+    /// Every scope, as carried by the tokens issued on login.
+    pub const ALL: [Scope; 6] = [
+        Scope::WorkersRead,
+        Scope::WorkersWrite,
+        Scope::WorkersExec,
+        Scope::UsersRead,
+        Scope::UsersWrite,
+        Scope::UsersAdmin,
+    ];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Scope::WorkersRead => "workers:read",
+            Scope::WorkersWrite => "workers:write",
+            Scope::WorkersExec => "workers:exec",
+            Scope::UsersRead => "users:read",
+            Scope::UsersWrite => "users:write",
+            Scope::UsersAdmin => "users:admin",
+        }
+    }
+
+    /// The error for a token that lacks this scope.
+    pub fn missing(self) -> ServerError {
+        match self {
+            Scope::WorkersRead => ServerError::MissingWorkersReadScope,
+            Scope::WorkersWrite => ServerError::MissingWorkersWriteScope,
+            Scope::WorkersExec => ServerError::MissingWorkersExecScope,
+            Scope::UsersRead => ServerError::MissingUsersReadScope,
+            Scope::UsersWrite => ServerError::MissingUsersWriteScope,
+            Scope::UsersAdmin => ServerError::MissingUsersAdminScope,
+        }
+    }
+}
+
+impl FromStr for Scope {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Scope::ALL
+            .into_iter()
+            .find(|scope| scope.as_str() == s)
+            .ok_or(())
+    }
+}
+
+/// Checks that the claims carry a scope.
+pub fn require_scope(claims: &AccessTokenClaims, scope: Scope) -> Result<(), ServerError> {
+    if !claims.scopes.contains(&scope) {
+        tracing::error!("Missing scope {}: {:?}", scope.as_str(), claims);
+        return Err(scope.missing());
+    }
+    Ok(())
+}
+
+/// Names the scope a handler requires, for use as `Scoped<S>`.
+pub trait RequiredScope: Send + Sync {
+    const SCOPE: Scope;
+}
+
+macro_rules! required_scopes {
+    ($($name:ident),* $(,)?) => {
+        $(
+            pub struct $name;
+
+            impl RequiredScope for $name {
+                const SCOPE: Scope = Scope::$name;
+            }
+        )*
+    };
+}
+
+required_scopes!(
+    WorkersRead,
+    WorkersWrite,
+    WorkersExec,
+    UsersRead,
+    UsersWrite,
+    UsersAdmin,
+);
+
+/// Claims of a token that carries the scope `S`.
+pub struct Scoped<S>(pub AccessTokenClaims, pub PhantomData<S>);
+
+#[async_trait]
+impl<S, St> FromRequestParts<St> for Scoped<S>
+where
+    S: RequiredScope,
+    AppState: FromRef<St>,
+    St: Send + Sync,
+{
+    type Rejection = ServerError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &St) -> Result<Self, Self::Rejection> {
+        let claims = AccessTokenClaims::from_request_parts(parts, state).await?;
+        require_scope(&claims, S::SCOPE)?;
+
+        Ok(Scoped(claims, PhantomData))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scope_names() {
+        for scope in Scope::ALL {
+            assert_eq!(scope.as_str().parse(), Ok(scope));
+            assert_eq!(
+                serde_json::to_string(&scope).unwrap(),
+                format!("\"{}\"", scope.as_str())
+            );
+        }
+        assert!("workers".parse::<Scope>().is_err());
+    }
+}
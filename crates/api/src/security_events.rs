@@ -0,0 +1,133 @@
+//! Security events, recorded for users to review what happened to their
+//! account.
+
+use axum::{
+    debug_handler,
+    extract::{Path, State},
+    Json,
+};
+use entity::security_event;
+use serde::{Deserialize, Serialize};
+use service::{
+    sea_orm::prelude::Uuid,
+    security_events::{Mutation, Query},
+};
+
+use crate::{
+    config::AppState,
+    errors::ServerError,
+    policy::{authorize_self_or, Permission},
+    scopes::{Scoped, UsersRead},
+    sessions::ClientInfo,
+};
+
+/// Number of events returned, the most recent ones.
+const MAX_EVENTS: u64 = 100;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SecurityEventKind {
+    /// A refresh token was presented after it had been rotated, so a copy
+    /// of it is in other hands. The session was ended.
+    RefreshTokenReused,
+    /// A recovery code stood in for a one-time password.
+    RecoveryCodeUsed,
+    TwoFactorDisabled,
+}
+
+impl SecurityEventKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            SecurityEventKind::RefreshTokenReused => "refresh_token_reused",
+            SecurityEventKind::RecoveryCodeUsed => "recovery_code_used",
+            SecurityEventKind::TwoFactorDisabled => "two_factor_disabled",
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct SecurityEventResponse {
+    pub id: String,
+    pub kind: String,
+    pub ip_address: Option<String>,
+    pub user_agent: Option<String>,
+    pub created_at: String,
+}
+
+impl From<security_event::Model> for SecurityEventResponse {
+    fn from(event: security_event::Model) -> Self {
+        Self {
+            id: event.id.to_string(),
+            kind: event.kind,
+            ip_address: event.ip_address,
+            user_agent: event.user_agent,
+            created_at: event.created_at.to_rfc3339(),
+        }
+    }
+}
+
+/// Records an event for a user, from the client of the request causing it.
+/// Failing to store it does not fail the request.
+pub async fn record_event(
+    state: &AppState,
+    user_id: &str,
+    kind: SecurityEventKind,
+    client: &ClientInfo,
+) {
+    tracing::warn!(
+        "Security event {} for user {} from {:?}",
+        kind.as_str(),
+        user_id,
+        client
+    );
+
+    let Ok(user_id) = Uuid::parse_str(user_id) else {
+        return;
+    };
+    if let Err(err) = Mutation::create_event(
+        &state.db,
+        user_id,
+        kind.as_str().to_owned(),
+        client.ip_address.to_owned(),
+        client.user_agent.to_owned(),
+    )
+    .await
+    {
+        tracing::error!("Failed to record security event: {:?}", err);
+    }
+}
+
+#[debug_handler]
+pub async fn get_security_events(
+    State(state): State<AppState>,
+    Scoped(claims, _): Scoped<UsersRead>,
+    Path(id): Path<String>,
+) -> Result<Json<Vec<SecurityEventResponse>>, ServerError> {
+    authorize_self_or(&claims, &id, Permission::UsersRead)?;
+    if Uuid::parse_str(&id).is_err() {
+        return Err(ServerError::NotFound);
+    }
+
+    let events = Query::find_events_by_user_id(&state.db, id, MAX_EVENTS)
+        .await
+        .map_err(|err| {
+            tracing::error!("Failed to get security events: {:?}", err);
+            ServerError::InternalServerError
+        })?;
+
+    Ok(Json(events.into_iter().map(Into::into).collect()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_event_kind_names() {
+        let kind = SecurityEventKind::RefreshTokenReused;
+        assert_eq!(
+            serde_json::to_string(&kind).unwrap(),
+            format!("\"{}\"", kind.as_str())
+        );
+    }
+}
@@ -0,0 +1,274 @@
+//! Login sessions. Every refresh token names its session in `jti`, and
+//! rotating the token keeps the session, so users can see where they are
+//! logged in and end a session on one device.
+
+use std::{convert::Infallible, net::SocketAddr};
+
+use axum::{
+    async_trait, debug_handler,
+    extract::{ConnectInfo, FromRequestParts, Path, State},
+    http::{header::USER_AGENT, request::Parts},
+    Json,
+};
+use chrono::{Duration, Utc};
+use entity::session;
+use serde::{Deserialize, Serialize};
+use service::{
+    sea_orm::prelude::Uuid,
+    sessions::{Mutation, Query},
+};
+
+use crate::{
+    auth::REFRESH_TOKEN_SECS,
+    config::AppState,
+    errors::ServerError,
+    scopes::{Scoped, UsersRead, UsersWrite},
+    users::MessageResponse,
+};
+
+const MAX_USER_AGENT_LEN: usize = 512;
+
+/// Describes the client of a request, as shown in the session list. Both
+/// are informational only: the user agent and `X-Forwarded-For` are up to
+/// the client.
+#[derive(Debug, Default)]
+pub struct ClientInfo {
+    pub user_agent: Option<String>,
+    pub ip_address: Option<String>,
+}
+
+#[async_trait]
+impl<S> FromRequestParts<S> for ClientInfo
+where
+    S: Send + Sync,
+{
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let header = |name| {
+            parts
+                .headers
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+        };
+
+        let user_agent = header(USER_AGENT.as_str())
+            .map(|user_agent| user_agent.chars().take(MAX_USER_AGENT_LEN).collect());
+        let ip_address = header("x-forwarded-for")
+            .and_then(|forwarded| forwarded.split(',').next())
+            .map(|ip| ip.trim().to_owned())
+            .filter(|ip| !ip.is_empty())
+            .or_else(|| {
+                parts
+                    .extensions
+                    .get::<ConnectInfo<SocketAddr>>()
+                    .map(|ConnectInfo(addr)| addr.ip().to_string())
+            });
+
+        Ok(Self {
+            user_agent,
+            ip_address,
+        })
+    }
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct SessionResponse {
+    pub id: String,
+    pub user_agent: Option<String>,
+    pub ip_address: Option<String>,
+    pub created_at: String,
+    pub last_used_at: String,
+    pub expires_at: String,
+    /// Whether the request was made from this session.
+    pub current: bool,
+}
+
+impl From<session::Model> for SessionResponse {
+    fn from(session: session::Model) -> Self {
+        Self {
+            id: session.id.to_string(),
+            user_agent: session.user_agent,
+            ip_address: session.ip_address,
+            created_at: session.created_at.to_rfc3339(),
+            last_used_at: session.last_used_at.to_rfc3339(),
+            expires_at: session.expires_at.to_rfc3339(),
+            current: false,
+        }
+    }
+}
+
+fn session_expiry() -> chrono::DateTime<Utc> {
+    Utc::now() + Duration::seconds(REFRESH_TOKEN_SECS as i64)
+}
+
+/// Starts a session on login, clearing the expired ones of the user.
+pub async fn start_session(
+    state: &AppState,
+    user_id: &str,
+    refresh_token_id: Uuid,
+    client: &ClientInfo,
+) -> Result<Uuid, ServerError> {
+    if let Err(err) =
+        Mutation::delete_expired_sessions(&state.db, user_id.to_owned(), Utc::now().into()).await
+    {
+        tracing::warn!("Failed to delete expired sessions: {:?}", err);
+    }
+
+    let session = Mutation::create_session(
+        &state.db,
+        user_id.to_owned(),
+        client.user_agent.to_owned(),
+        client.ip_address.to_owned(),
+        session_expiry().into(),
+        refresh_token_id,
+    )
+    .await
+    .map_err(|err| {
+        tracing::error!("Failed to create session: {:?}", err);
+        ServerError::InternalServerError
+    })?;
+    Ok(session.id)
+}
+
+/// Continues a session when its refresh token is rotated, from
+/// `current_token_id` to `next_token_id`. Fails for sessions that were
+/// ended or have expired, and returns false when `current_token_id` was
+/// already rotated.
+pub async fn rotate_session(
+    state: &AppState,
+    user_id: &str,
+    id: &str,
+    current_token_id: Option<Uuid>,
+    next_token_id: Uuid,
+    client: &ClientInfo,
+) -> Result<bool, ServerError> {
+    let session = Query::find_session_by_id(&state.db, id.to_owned())
+        .await
+        .map_err(|err| {
+            tracing::error!("Failed to get session: {:?}", err);
+            ServerError::InternalServerError
+        })?
+        .filter(|session| session.user_id.to_string() == user_id)
+        .filter(|session| session.expires_at > Utc::now())
+        .ok_or(ServerError::InvalidToken)?;
+
+    Mutation::rotate_session(
+        &state.db,
+        session.id,
+        current_token_id,
+        next_token_id,
+        client.user_agent.to_owned(),
+        client.ip_address.to_owned(),
+        Utc::now().into(),
+        session_expiry().into(),
+    )
+    .await
+    .map_err(|err| {
+        tracing::error!("Failed to rotate session: {:?}", err);
+        ServerError::InternalServerError
+    })
+}
+
+pub async fn end_session(state: &AppState, user_id: &str, id: &str) -> Result<u64, ServerError> {
+    if Uuid::parse_str(id).is_err() {
+        return Ok(0);
+    }
+
+    let result = Mutation::delete_session(&state.db, user_id.to_owned(), id.to_owned())
+        .await
+        .map_err(|err| {
+            tracing::error!("Failed to delete session: {:?}", err);
+            ServerError::InternalServerError
+        })?;
+    Ok(result.rows_affected)
+}
+
+pub async fn end_all_sessions(state: &AppState, user_id: &str) -> Result<(), ServerError> {
+    Mutation::delete_sessions_by_user_id(&state.db, user_id.to_owned())
+        .await
+        .map_err(|err| {
+            tracing::error!("Failed to delete sessions: {:?}", err);
+            ServerError::InternalServerError
+        })?;
+    Ok(())
+}
+
+#[debug_handler]
+pub async fn get_sessions(
+    State(state): State<AppState>,
+    Scoped(claims, _): Scoped<UsersRead>,
+) -> Result<Json<Vec<SessionResponse>>, ServerError> {
+    let sessions =
+        Query::find_sessions_by_user_id(&state.db, claims.sub.to_owned(), Utc::now().into())
+            .await
+            .map_err(|err| {
+                tracing::error!("Failed to get sessions: {:?}", err);
+                ServerError::InternalServerError
+            })?;
+
+    Ok(Json(
+        sessions
+            .into_iter()
+            .map(|session| {
+                let current = claims.sid == Some(session.id.to_string());
+                SessionResponse {
+                    current,
+                    ..session.into()
+                }
+            })
+            .collect(),
+    ))
+}
+
+/// Ends a session of the caller, after which its refresh token is
+/// rejected. Access tokens already issued to it last until they expire.
+#[debug_handler]
+pub async fn delete_session(
+    State(state): State<AppState>,
+    Scoped(claims, _): Scoped<UsersWrite>,
+    Path(id): Path<String>,
+) -> Result<Json<MessageResponse>, ServerError> {
+    if end_session(&state, &claims.sub, &id).await? == 0 {
+        return Err(ServerError::NotFound);
+    }
+
+    Ok(Json(MessageResponse {
+        message: "Session deleted successfully".to_owned(),
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::Request;
+
+    async fn client_info(request: Request<()>) -> ClientInfo {
+        let (mut parts, _) = request.into_parts();
+        ClientInfo::from_request_parts(&mut parts, &())
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_client_info() {
+        let client = client_info(
+            Request::builder()
+                .header(USER_AGENT, "curl/8.0")
+                .header("x-forwarded-for", "203.0.113.7, 10.0.0.1")
+                .body(())
+                .unwrap(),
+        )
+        .await;
+        assert_eq!(client.user_agent.as_deref(), Some("curl/8.0"));
+        assert_eq!(client.ip_address.as_deref(), Some("203.0.113.7"));
+
+        let mut request = Request::builder().body(()).unwrap();
+        request
+            .extensions_mut()
+            .insert(ConnectInfo(SocketAddr::from(([127, 0, 0, 1], 8000))));
+        let client = client_info(request).await;
+        assert_eq!(client.user_agent, None);
+        assert_eq!(client.ip_address.as_deref(), Some("127.0.0.1"));
+    }
+}
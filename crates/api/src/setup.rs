@@ -0,0 +1,111 @@
+//! Creation of the first admin of a fresh installation.
+
+use axum::{debug_handler, extract::State, Json};
+use entity::{sea_orm_active_enums::RoleEnum, user};
+use serde::Deserialize;
+use service::users::{Mutation, Query};
+
+use crate::{
+    auth::{generate_secret, hash_password, secrets_match},
+    config::AppState,
+    errors::ServerError,
+    users::UserInfoResponse,
+};
+
+const DEFAULT_ADMIN_USERNAME: &str = "admin";
+
+#[derive(Deserialize)]
+pub struct SetupRequest {
+    pub token: String,
+    pub email: String,
+    pub username: String,
+    pub password: String,
+}
+
+/// Creates the first admin when there are no users yet, from `ADMIN_EMAIL`
+/// and `ADMIN_PASSWORD` when they are set. Otherwise logs a one-time token
+/// that lets `POST /setup` create it.
+pub async fn bootstrap(state: &AppState) -> Result<(), ServerError> {
+    if count_users(state).await? > 0 {
+        return Ok(());
+    }
+
+    if let (Some(email), Some(password)) = (&state.env.admin_email, &state.env.admin_password) {
+        let username = state
+            .env
+            .admin_username
+            .as_deref()
+            .unwrap_or(DEFAULT_ADMIN_USERNAME);
+        create_admin(state, email.to_string(), username.to_owned(), password).await?;
+        tracing::info!("Created the admin {}", email);
+        return Ok(());
+    }
+
+    let token = generate_secret();
+    tracing::warn!(
+        "No users exist yet. Create the first admin with POST /setup and the setup token {}",
+        token
+    );
+    *state.setup_token.lock().await = Some(token);
+    Ok(())
+}
+
+#[debug_handler]
+pub async fn setup(
+    State(state): State<AppState>,
+    Json(request): Json<SetupRequest>,
+) -> Result<Json<UserInfoResponse>, ServerError> {
+    let mut setup_token = state.setup_token.lock().await;
+    let Some(token) = setup_token.as_deref() else {
+        return Err(ServerError::SetupUnavailable);
+    };
+    if !secrets_match(token, &request.token) {
+        return Err(ServerError::InvalidSetupToken);
+    }
+    if request.email.is_empty() || request.username.is_empty() || request.password.is_empty() {
+        return Err(ServerError::MissingCredentials);
+    }
+
+    // Users may have been created in the database since startup.
+    if count_users(&state).await? > 0 {
+        *setup_token = None;
+        return Err(ServerError::SetupUnavailable);
+    }
+
+    let user = create_admin(&state, request.email, request.username, &request.password).await?;
+    *setup_token = None;
+
+    Ok(Json(user.into()))
+}
+
+async fn count_users(state: &AppState) -> Result<u64, ServerError> {
+    Query::count_users(&state.db).await.map_err(|err| {
+        tracing::error!("Failed to count users: {:?}", err);
+        ServerError::InternalServerError
+    })
+}
+
+async fn create_admin(
+    state: &AppState,
+    email: String,
+    username: String,
+    password: &str,
+) -> Result<user::Model, ServerError> {
+    let hashed_password = hash_password(password).map_err(|err| {
+        tracing::error!("Failed to hash password: {:?}", err);
+        ServerError::InternalServerError
+    })?;
+
+    Mutation::create_user_with_roles(
+        &state.db,
+        email,
+        username,
+        hashed_password,
+        vec![RoleEnum::User, RoleEnum::Admin],
+    )
+    .await
+    .map_err(|err| {
+        tracing::error!("Failed to create admin: {:?}", err);
+        ServerError::InternalServerError
+    })
+}
@@ -0,0 +1,469 @@
+//! Teams share workers between their members, so that a worker outlives the
+//! involvement of the user who created it.
+
+use axum::{
+    debug_handler,
+    extract::{Path, State},
+    Json,
+};
+use entity::{sea_orm_active_enums::TeamRoleEnum, team, worker};
+use serde::{Deserialize, Serialize};
+use service::{
+    sea_orm::{prelude::Uuid, DbErr, SqlErr},
+    teams::{Mutation, Query},
+    users::Query as UserQuery,
+    workers::{Mutation as WorkerMutation, Query as WorkerQuery},
+};
+
+use crate::{
+    auth::AccessTokenClaims,
+    config::AppState,
+    errors::ServerError,
+    policy::{allows, authorize_self_or, Permission},
+    scopes::{Scoped, UsersRead, UsersWrite, WorkersWrite},
+    users::MessageResponse,
+};
+
+const MAX_TEAM_NAME_LEN: usize = 100;
+
+/// What a caller wants to do with a worker.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WorkerAccess {
+    Read,
+    /// Starting and stopping, without changing the code or configuration.
+    Operate,
+    Write,
+}
+
+impl WorkerAccess {
+    /// The permission granting this access to every worker.
+    fn permission(self) -> Permission {
+        match self {
+            WorkerAccess::Read => Permission::WorkersRead,
+            WorkerAccess::Operate => Permission::WorkersOperate,
+            WorkerAccess::Write => Permission::WorkersWrite,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct TeamCreateRequest {
+    pub name: String,
+}
+
+#[derive(Deserialize)]
+pub struct TeamMemberRequest {
+    pub role: TeamRoleEnum,
+}
+
+#[derive(Deserialize)]
+pub struct WorkerTeamRequest {
+    /// Returns the worker to its user alone when unset.
+    pub team_id: Option<String>,
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct TeamResponse {
+    pub id: String,
+    pub name: String,
+    pub created_at: String,
+}
+
+impl From<team::Model> for TeamResponse {
+    fn from(team: team::Model) -> Self {
+        Self {
+            id: team.id.to_string(),
+            name: team.name,
+            created_at: team.created_at.to_rfc3339(),
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct TeamMemberResponse {
+    pub user_id: String,
+    pub username: Option<String>,
+    pub role: TeamRoleEnum,
+    pub created_at: String,
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct TeamDetailResponse {
+    #[serde(flatten)]
+    pub team: TeamResponse,
+    pub members: Vec<TeamMemberResponse>,
+}
+
+/// Whether a team role grants an access to the workers of the team.
+fn role_allows(role: &TeamRoleEnum, access: WorkerAccess) -> bool {
+    match access {
+        WorkerAccess::Read => true,
+        WorkerAccess::Operate | WorkerAccess::Write => *role != TeamRoleEnum::Viewer,
+    }
+}
+
+fn parse_id(id: &str) -> Result<Uuid, ServerError> {
+    Uuid::parse_str(id).map_err(|_| ServerError::NotFound)
+}
+
+async fn find_member_role(
+    state: &AppState,
+    team_id: Uuid,
+    user_id: &str,
+) -> Result<Option<TeamRoleEnum>, ServerError> {
+    let Ok(user_id) = Uuid::parse_str(user_id) else {
+        return Ok(None);
+    };
+
+    let member = Query::find_team_member(&state.db, team_id, user_id)
+        .await
+        .map_err(|err| {
+            tracing::error!("Failed to get team member: {:?}", err);
+            ServerError::InternalServerError
+        })?;
+    Ok(member.map(|member| member.role))
+}
+
+/// Checks that the caller may access a worker, as its user, through a
+/// permission over every worker or through the team owning it.
+pub async fn authorize_worker(
+    state: &AppState,
+    claims: &AccessTokenClaims,
+    worker: &worker::Model,
+    access: WorkerAccess,
+) -> Result<(), ServerError> {
+    if claims.sub == worker.user_id.to_string() || allows(claims, access.permission()) {
+        return Ok(());
+    }
+
+    if let Some(team_id) = worker.team_id {
+        let role = find_member_role(state, team_id, &claims.sub).await?;
+        if role.is_some_and(|role| role_allows(&role, access)) {
+            return Ok(());
+        }
+    }
+
+    tracing::error!("Unauthorized access: {:?}", claims);
+    Err(ServerError::Unauthorized)
+}
+
+/// Checks that the caller holds one of the roles in a team, or a permission
+/// over every team.
+async fn require_team_role(
+    state: &AppState,
+    claims: &AccessTokenClaims,
+    team_id: Uuid,
+    roles: &[TeamRoleEnum],
+    permission: Permission,
+) -> Result<(), ServerError> {
+    if allows(claims, permission) {
+        return Ok(());
+    }
+
+    let role = find_member_role(state, team_id, &claims.sub).await?;
+    if !role.is_some_and(|role| roles.contains(&role)) {
+        tracing::error!("Unauthorized access: {:?}", claims);
+        return Err(ServerError::Unauthorized);
+    }
+    Ok(())
+}
+
+async fn find_team(state: &AppState, id: String) -> Result<team::Model, ServerError> {
+    Query::find_team_by_id(&state.db, id)
+        .await
+        .map_err(|err| {
+            tracing::error!("Failed to get team: {:?}", err);
+            ServerError::InternalServerError
+        })?
+        .ok_or(ServerError::NotFound)
+}
+
+/// Fails when a change would leave the team without an owner.
+async fn keep_an_owner(
+    state: &AppState,
+    team_id: Uuid,
+    user_id: Uuid,
+    role: Option<&TeamRoleEnum>,
+) -> Result<(), ServerError> {
+    if role == Some(&TeamRoleEnum::Owner) {
+        return Ok(());
+    }
+    let current = Query::find_team_member(&state.db, team_id, user_id)
+        .await
+        .map_err(|err| {
+            tracing::error!("Failed to get team member: {:?}", err);
+            ServerError::InternalServerError
+        })?;
+    if current.is_none_or(|member| member.role != TeamRoleEnum::Owner) {
+        return Ok(());
+    }
+
+    let owners = Query::count_team_owners(&state.db, team_id)
+        .await
+        .map_err(|err| {
+            tracing::error!("Failed to count team owners: {:?}", err);
+            ServerError::InternalServerError
+        })?;
+    if owners <= 1 {
+        return Err(ServerError::LastTeamOwner);
+    }
+    Ok(())
+}
+
+#[debug_handler]
+pub async fn create_team(
+    State(state): State<AppState>,
+    Scoped(claims, _): Scoped<UsersWrite>,
+    Json(request): Json<TeamCreateRequest>,
+) -> Result<Json<TeamResponse>, ServerError> {
+    let name = request.name.trim();
+    if name.is_empty() || name.len() > MAX_TEAM_NAME_LEN {
+        return Err(ServerError::InvalidTeamName);
+    }
+
+    let team = Mutation::create_team(&state.db, name.to_owned(), claims.sub)
+        .await
+        .map_err(|err| match err.sql_err() {
+            Some(SqlErr::UniqueConstraintViolation(_)) => ServerError::TeamNameTaken,
+            _ => {
+                tracing::error!("Failed to create team: {:?}", err);
+                ServerError::InternalServerError
+            }
+        })?;
+
+    Ok(Json(team.into()))
+}
+
+/// Lists the teams of the caller, or every team for those who may read them.
+#[debug_handler]
+pub async fn get_teams(
+    State(state): State<AppState>,
+    Scoped(claims, _): Scoped<UsersRead>,
+) -> Result<Json<Vec<TeamResponse>>, ServerError> {
+    let teams = if allows(&claims, Permission::TeamsRead) {
+        Query::find_all_teams(&state.db).await
+    } else {
+        Query::find_teams_by_user_id(&state.db, claims.sub).await
+    }
+    .map_err(|err| {
+        tracing::error!("Failed to get teams: {:?}", err);
+        ServerError::InternalServerError
+    })?;
+
+    Ok(Json(teams.into_iter().map(Into::into).collect()))
+}
+
+#[debug_handler]
+pub async fn get_team(
+    State(state): State<AppState>,
+    Scoped(claims, _): Scoped<UsersRead>,
+    Path(id): Path<String>,
+) -> Result<Json<TeamDetailResponse>, ServerError> {
+    let team_id = parse_id(&id)?;
+    require_team_role(
+        &state,
+        &claims,
+        team_id,
+        &[
+            TeamRoleEnum::Owner,
+            TeamRoleEnum::Maintainer,
+            TeamRoleEnum::Viewer,
+        ],
+        Permission::TeamsRead,
+    )
+    .await?;
+    let team = find_team(&state, id).await?;
+
+    let members = Query::find_team_members(&state.db, team.id)
+        .await
+        .map_err(|err| {
+            tracing::error!("Failed to get team members: {:?}", err);
+            ServerError::InternalServerError
+        })?;
+
+    Ok(Json(TeamDetailResponse {
+        team: team.into(),
+        members: members
+            .into_iter()
+            .map(|(member, user)| TeamMemberResponse {
+                user_id: member.user_id.to_string(),
+                username: user.map(|user| user.username),
+                role: member.role,
+                created_at: member.created_at.to_rfc3339(),
+            })
+            .collect(),
+    }))
+}
+
+/// Deletes a team. Its workers go back to the users who created them.
+#[debug_handler]
+pub async fn delete_team(
+    State(state): State<AppState>,
+    Scoped(claims, _): Scoped<UsersWrite>,
+    Path(id): Path<String>,
+) -> Result<Json<MessageResponse>, ServerError> {
+    let team_id = parse_id(&id)?;
+    require_team_role(
+        &state,
+        &claims,
+        team_id,
+        &[TeamRoleEnum::Owner],
+        Permission::TeamsManage,
+    )
+    .await?;
+
+    let result = Mutation::delete_team(&state.db, id).await.map_err(|err| {
+        tracing::error!("Failed to delete team: {:?}", err);
+        ServerError::InternalServerError
+    })?;
+    if result.rows_affected == 0 {
+        return Err(ServerError::NotFound);
+    }
+
+    Ok(Json(MessageResponse {
+        message: "Team deleted successfully".to_owned(),
+    }))
+}
+
+#[debug_handler]
+pub async fn set_team_member(
+    State(state): State<AppState>,
+    Scoped(claims, _): Scoped<UsersWrite>,
+    Path((id, user_id)): Path<(String, String)>,
+    Json(request): Json<TeamMemberRequest>,
+) -> Result<Json<MessageResponse>, ServerError> {
+    let team_id = parse_id(&id)?;
+    require_team_role(
+        &state,
+        &claims,
+        team_id,
+        &[TeamRoleEnum::Owner],
+        Permission::TeamsManage,
+    )
+    .await?;
+    find_team(&state, id).await?;
+
+    let user = UserQuery::find_user_by_id(&state.db, user_id)
+        .await
+        .map_err(|err| {
+            tracing::error!("Failed to get user: {:?}", err);
+            ServerError::InternalServerError
+        })?
+        .ok_or(ServerError::NotFound)?;
+    keep_an_owner(&state, team_id, user.id, Some(&request.role)).await?;
+
+    Mutation::set_team_member(&state.db, team_id, user.id, request.role)
+        .await
+        .map_err(|err| {
+            tracing::error!("Failed to set team member: {:?}", err);
+            ServerError::InternalServerError
+        })?;
+
+    Ok(Json(MessageResponse {
+        message: "Team member updated successfully".to_owned(),
+    }))
+}
+
+/// Removes a member from a team. Members may also leave on their own.
+#[debug_handler]
+pub async fn remove_team_member(
+    State(state): State<AppState>,
+    Scoped(claims, _): Scoped<UsersWrite>,
+    Path((id, user_id)): Path<(String, String)>,
+) -> Result<Json<MessageResponse>, ServerError> {
+    let team_id = parse_id(&id)?;
+    if claims.sub != user_id {
+        require_team_role(
+            &state,
+            &claims,
+            team_id,
+            &[TeamRoleEnum::Owner],
+            Permission::TeamsManage,
+        )
+        .await?;
+    }
+    let user_id = parse_id(&user_id)?;
+    keep_an_owner(&state, team_id, user_id, None).await?;
+
+    let result = Mutation::remove_team_member(&state.db, team_id, user_id)
+        .await
+        .map_err(|err| {
+            tracing::error!("Failed to remove team member: {:?}", err);
+            ServerError::InternalServerError
+        })?;
+    if result.rows_affected == 0 {
+        return Err(ServerError::NotFound);
+    }
+
+    Ok(Json(MessageResponse {
+        message: "Team member removed successfully".to_owned(),
+    }))
+}
+
+/// Moves a worker into a team, or out of it. Only the user of the worker
+/// and admins may do so, and only into teams they maintain.
+#[debug_handler]
+pub async fn set_worker_team(
+    State(state): State<AppState>,
+    Scoped(claims, _): Scoped<WorkersWrite>,
+    Path(id): Path<String>,
+    Json(request): Json<WorkerTeamRequest>,
+) -> Result<Json<MessageResponse>, ServerError> {
+    let worker = WorkerQuery::find_worker_by_id(&state.db, id.to_owned())
+        .await
+        .map_err(|err| {
+            tracing::error!("Failed to get worker: {:?}", err);
+            ServerError::InternalServerError
+        })?
+        .ok_or(ServerError::NotFound)?;
+    authorize_self_or(
+        &claims,
+        &worker.user_id.to_string(),
+        Permission::WorkersWrite,
+    )?;
+
+    let team_id = match request.team_id {
+        Some(team_id) => {
+            let team_id = parse_id(&team_id)?;
+            require_team_role(
+                &state,
+                &claims,
+                team_id,
+                &[TeamRoleEnum::Owner, TeamRoleEnum::Maintainer],
+                Permission::TeamsManage,
+            )
+            .await?;
+            Some(team_id)
+        }
+        None => None,
+    };
+
+    WorkerMutation::set_worker_team(&state.db, id, team_id)
+        .await
+        .map_err(|err: DbErr| match err.sql_err() {
+            Some(SqlErr::ForeignKeyConstraintViolation(_)) => ServerError::NotFound,
+            _ => {
+                tracing::error!("Failed to set worker team: {:?}", err);
+                ServerError::InternalServerError
+            }
+        })?;
+
+    Ok(Json(MessageResponse {
+        message: "Worker team updated successfully".to_owned(),
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_role_allows() {
+        for role in [TeamRoleEnum::Owner, TeamRoleEnum::Maintainer] {
+            assert!(role_allows(&role, WorkerAccess::Read));
+            assert!(role_allows(&role, WorkerAccess::Write));
+        }
+        assert!(role_allows(&TeamRoleEnum::Viewer, WorkerAccess::Read));
+        assert!(!role_allows(&TeamRoleEnum::Viewer, WorkerAccess::Write));
+    }
+}
@@ -0,0 +1,261 @@
+//! Personal access tokens, long-lived credentials for automation that are
+//! accepted wherever an access token is.
+
+use axum::{
+    debug_handler,
+    extract::{Path, State},
+    Json,
+};
+use chrono::{Duration, Utc};
+use entity::{personal_access_token, sea_orm_active_enums::UserStatusEnum};
+use serde::{Deserialize, Serialize};
+use service::{
+    tokens::{Mutation, Query},
+    users::Query as UserQuery,
+};
+
+use crate::{
+    auth::{generate_secret, hash_secret, AccessTokenClaims},
+    config::AppState,
+    errors::ServerError,
+    policy::{authorize_self_or, user_permissions, Permission},
+    scopes::{require_scope, Scope, Scoped, UsersRead, UsersWrite},
+    users::MessageResponse,
+};
+
+/// Marks personal access tokens, so that they are told apart from JWTs
+/// and recognizable when leaked.
+pub const TOKEN_PREFIX: &str = "wmpat_";
+const MAX_TOKEN_NAME_LEN: usize = 100;
+const MAX_TOKEN_DAYS: u32 = 3650;
+/// Spares a write on every request by recording uses this often at most.
+const LAST_USED_INTERVAL_SECS: i64 = 60;
+
+#[derive(Deserialize)]
+pub struct TokenCreateRequest {
+    pub name: String,
+    /// The token never expires when unset.
+    pub expires_in_days: Option<u32>,
+    /// Must be a subset of the scopes of the token creating it.
+    pub scopes: Vec<Scope>,
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct TokenResponse {
+    pub id: String,
+    pub name: String,
+    /// Only returned when the token is created.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub token: Option<String>,
+    pub expires_at: Option<String>,
+    pub last_used_at: Option<String>,
+    pub created_at: String,
+    pub scopes: Vec<Scope>,
+}
+
+impl From<personal_access_token::Model> for TokenResponse {
+    fn from(token: personal_access_token::Model) -> Self {
+        Self {
+            id: token.id.to_string(),
+            name: token.name,
+            token: None,
+            expires_at: token.expires_at.map(|at| at.to_rfc3339()),
+            last_used_at: token.last_used_at.map(|at| at.to_rfc3339()),
+            created_at: token.created_at.to_rfc3339(),
+            scopes: parse_scopes(&token.scopes),
+        }
+    }
+}
+
+/// Resolves a personal access token to the claims of its user.
+pub async fn authenticate(state: &AppState, token: &str) -> Result<AccessTokenClaims, ServerError> {
+    let record = Query::find_token_by_hash(&state.db, hash_secret(token))
+        .await
+        .map_err(|err| {
+            tracing::error!("Failed to get personal access token: {:?}", err);
+            ServerError::InternalServerError
+        })?
+        .ok_or(ServerError::InvalidToken)?;
+
+    let now = Utc::now();
+    if record
+        .expires_at
+        .is_some_and(|expires_at| expires_at <= now)
+    {
+        return Err(ServerError::InvalidToken);
+    }
+
+    let user = UserQuery::find_user_by_id(&state.db, record.user_id.to_string())
+        .await
+        .map_err(|err| {
+            tracing::error!("Failed to get user: {:?}", err);
+            ServerError::InternalServerError
+        })?
+        .ok_or(ServerError::InvalidToken)?;
+    if user.status != UserStatusEnum::Active {
+        return Err(ServerError::UserNotActive);
+    }
+
+    let stale = record.last_used_at.is_none_or(|last_used_at| {
+        now.signed_duration_since(last_used_at) >= Duration::seconds(LAST_USED_INTERVAL_SECS)
+    });
+    if stale {
+        if let Err(err) = Mutation::touch_token(&state.db, record.id, now.into()).await {
+            tracing::warn!("Failed to record use of personal access token: {:?}", err);
+        }
+    }
+
+    let permissions = user_permissions(state, &user).await?;
+    Ok(AccessTokenClaims {
+        sub: user.id.to_string(),
+        username: user.username,
+        roles: user.roles,
+        status: user.status,
+        scopes: parse_scopes(&record.scopes),
+        permissions,
+        // Not a session, so `logout_all` leaves it alone.
+        generation: 0,
+        sid: None,
+        exp: record
+            .expires_at
+            .map_or(u64::MAX, |expires_at| expires_at.timestamp() as u64),
+    })
+}
+
+/// Parses stored scopes, skipping any this version does not know.
+fn parse_scopes(scopes: &[String]) -> Vec<Scope> {
+    scopes
+        .iter()
+        .filter_map(|scope| scope.parse().ok())
+        .collect()
+}
+
+#[debug_handler]
+pub async fn get_tokens(
+    State(state): State<AppState>,
+    Scoped(claims, _): Scoped<UsersRead>,
+    Path(id): Path<String>,
+) -> Result<Json<Vec<TokenResponse>>, ServerError> {
+    authorize_self_or(&claims, &id, Permission::UsersRead)?;
+
+    let tokens = Query::find_tokens_by_user_id(&state.db, id)
+        .await
+        .map_err(|err| {
+            tracing::error!("Failed to get personal access tokens: {:?}", err);
+            ServerError::InternalServerError
+        })?;
+
+    Ok(Json(tokens.into_iter().map(Into::into).collect()))
+}
+
+/// Creates a token. Admins cannot create tokens for other users, as that
+/// would let them act as those users.
+#[debug_handler]
+pub async fn create_token(
+    State(state): State<AppState>,
+    Scoped(claims, _): Scoped<UsersWrite>,
+    Path(id): Path<String>,
+    Json(request): Json<TokenCreateRequest>,
+) -> Result<Json<TokenResponse>, ServerError> {
+    if claims.sub != id {
+        tracing::error!("Unauthorized access: {:?}", claims);
+        return Err(ServerError::Unauthorized);
+    }
+
+    let name = request.name.trim();
+    let valid = !name.is_empty()
+        && name.len() <= MAX_TOKEN_NAME_LEN
+        && request
+            .expires_in_days
+            .is_none_or(|days| (1..=MAX_TOKEN_DAYS).contains(&days));
+    if !valid || request.scopes.is_empty() {
+        return Err(ServerError::InvalidTokenRequest);
+    }
+    // A token cannot grant more than the token it was created with.
+    for scope in &request.scopes {
+        require_scope(&claims, *scope)?;
+    }
+    let mut scopes = Vec::new();
+    for scope in request.scopes {
+        if !scopes.contains(&scope) {
+            scopes.push(scope);
+        }
+    }
+
+    let token = format!("{}{}", TOKEN_PREFIX, generate_secret());
+    let record = Mutation::create_token(
+        &state.db,
+        id,
+        name.to_owned(),
+        hash_secret(&token),
+        request
+            .expires_in_days
+            .map(|days| (Utc::now() + Duration::days(days.into())).into()),
+        scopes
+            .iter()
+            .map(|scope| scope.as_str().to_owned())
+            .collect(),
+    )
+    .await
+    .map_err(|err| {
+        tracing::error!("Failed to create personal access token: {:?}", err);
+        ServerError::InternalServerError
+    })?;
+
+    Ok(Json(TokenResponse {
+        token: Some(token),
+        ..record.into()
+    }))
+}
+
+#[debug_handler]
+pub async fn delete_token(
+    State(state): State<AppState>,
+    Scoped(claims, _): Scoped<UsersWrite>,
+    Path((id, token_id)): Path<(String, String)>,
+) -> Result<Json<MessageResponse>, ServerError> {
+    authorize_self_or(&claims, &id, Permission::UsersManage)?;
+
+    let result = Mutation::delete_token(&state.db, id, token_id)
+        .await
+        .map_err(|err| {
+            tracing::error!("Failed to delete personal access token: {:?}", err);
+            ServerError::InternalServerError
+        })?;
+    if result.rows_affected == 0 {
+        return Err(ServerError::NotFound);
+    }
+
+    Ok(Json(MessageResponse {
+        message: "Token deleted successfully".to_owned(),
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::FixedOffset;
+    use uuid::Uuid;
+
+    #[test]
+    fn test_token_response_hides_token() {
+        let created_at = Utc::now().with_timezone(&FixedOffset::east_opt(0).unwrap());
+        let response: TokenResponse = personal_access_token::Model {
+            id: Uuid::new_v4(),
+            user_id: Uuid::new_v4(),
+            name: "ci".to_owned(),
+            token_hash: hash_secret("wmpat_secret"),
+            expires_at: None,
+            last_used_at: None,
+            created_at,
+            scopes: vec!["workers:read".to_owned(), "unknown".to_owned()],
+        }
+        .into();
+
+        let json = serde_json::to_value(&response).unwrap();
+        assert!(json.get("token").is_none());
+        assert!(json.get("token_hash").is_none());
+        assert_eq!(json["name"], "ci");
+        assert_eq!(json["scopes"], serde_json::json!(["workers:read"]));
+    }
+}
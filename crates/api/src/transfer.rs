@@ -0,0 +1,544 @@
+use std::collections::{BTreeMap, HashSet};
+
+use axum::{
+    body::Body,
+    debug_handler,
+    extract::{Path, State},
+    http::header,
+    response::IntoResponse,
+    Json,
+};
+use flate2::{write::GzEncoder, Compression};
+use serde::{Deserialize, Serialize};
+use service::{
+    rollouts::Query as RolloutQuery,
+    schedules::{Mutation as ScheduleMutation, Query as ScheduleQuery},
+    workers::{Mutation, Query},
+};
+
+use crate::{
+    auth::AccessTokenClaims,
+    bundle::{build_bundle, read_body, unpack, Manifest, MANIFEST_NAME},
+    config::AppState,
+    deployments::{get_owned_worker, go_live, record_deployment},
+    errors::ServerError,
+    scheduler::parse_cron,
+    schedules::check_path,
+    scopes::{Scoped, WorkersRead, WorkersWrite},
+    teams::WorkerAccess,
+    workerd::{
+        remove_worker_files, validate_host_name, validate_template, Binding, Module, ModuleKind,
+        Worker,
+    },
+};
+
+pub const METADATA_NAME: &str = "worker.json";
+pub const EXPORT_VERSION: u32 = 1;
+
+/// `worker.json` of an export, describing everything but the code which is
+/// stored as a regular bundle next to it.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct WorkerExport {
+    pub version: u32,
+    pub name: String,
+    pub external_path: String,
+    pub host_name: String,
+    pub node_name: String,
+    pub port: i32,
+    pub template: Option<String>,
+    /// Whether the entry is a service worker script rather than the main
+    /// module of a bundle.
+    #[serde(default)]
+    pub service_worker: bool,
+    #[serde(default)]
+    pub schedules: Vec<ScheduleExport>,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct ScheduleExport {
+    pub cron: String,
+    pub path: Option<String>,
+    pub enabled: bool,
+}
+
+#[derive(serde::Deserialize, serde::Serialize)]
+pub struct WorkerImportResponse {
+    pub id: String,
+    pub name: String,
+    pub port: i32,
+    pub deployment_id: String,
+    /// Secret bindings that were exported without their value.
+    pub missing_secrets: Vec<String>,
+}
+
+#[debug_handler]
+pub async fn export_worker(
+    State(state): State<AppState>,
+    Scoped(claims, _): Scoped<WorkersRead>,
+    Path(id): Path<String>,
+) -> Result<impl IntoResponse, ServerError> {
+    let worker = get_owned_worker(&state, &claims, id.to_owned(), WorkerAccess::Read).await?;
+
+    let schedules = ScheduleQuery::find_schedules_by_worker_id(&state.db, id)
+        .await
+        .map_err(|err| {
+            tracing::error!("Failed to get schedules: {:?}", err);
+            ServerError::InternalServerError
+        })?;
+
+    let export = WorkerExport {
+        version: EXPORT_VERSION,
+        name: worker.name.to_owned(),
+        external_path: worker.external_path.to_owned(),
+        host_name: worker.host_name.to_owned(),
+        node_name: worker.node_name.to_owned(),
+        port: worker.port,
+        template: worker.template.to_owned(),
+        service_worker: worker.modules.is_none(),
+        schedules: schedules
+            .into_iter()
+            .map(|schedule| ScheduleExport {
+                cron: schedule.cron,
+                path: schedule.path,
+                enabled: schedule.enabled,
+            })
+            .collect(),
+    };
+    let file_name = format!("worker-{}.tar.gz", worker.id);
+    let archive = write_export(&export, &worker.into())?;
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, "application/gzip".to_owned()),
+            (
+                header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"{file_name}\""),
+            ),
+        ],
+        archive,
+    ))
+}
+
+#[debug_handler]
+pub async fn import_worker(
+    State(state): State<AppState>,
+    Scoped(claims, _): Scoped<WorkersWrite>,
+    body: Body,
+) -> Result<Json<WorkerImportResponse>, ServerError> {
+    let bytes = read_body(body).await?;
+    let (export, code) = tokio::task::spawn_blocking(move || read_export(&bytes))
+        .await
+        .map_err(|err| {
+            tracing::error!("Failed to unpack export: {:?}", err);
+            ServerError::InternalServerError
+        })??;
+
+    let (names, ports) = taken_names_and_ports(&state, &claims.sub).await?;
+    let name = unique_name(&export.name, &names);
+    let port = free_port(export.port, &ports).ok_or(ServerError::NoFreePort)?;
+
+    let created = Mutation::create_worker(
+        &state.db,
+        name.to_owned(),
+        port,
+        code.code.to_owned(),
+        claims.sub.to_owned(),
+    )
+    .await
+    .map_err(|err| {
+        tracing::error!("Failed to create worker: {:?}", err);
+        ServerError::InternalServerError
+    })?;
+    let id = created.id.as_ref().to_string();
+
+    // Remove the worker again rather than leave a half imported one behind.
+    let imported = restore_worker(&state, &claims, &id, &name, port, &export, code).await;
+    if imported.is_err() {
+        let _ = remove_worker_files(&state, &id.replace('-', "")).await;
+        if let Err(err) = Mutation::delete_worker(&state.db, id).await {
+            tracing::error!("Failed to delete partially imported worker: {:?}", err);
+        }
+    }
+    imported.map(Json)
+}
+
+/// Fills a freshly created worker with the configuration, code and
+/// schedules of an export.
+async fn restore_worker(
+    state: &AppState,
+    claims: &AccessTokenClaims,
+    id: &str,
+    name: &str,
+    port: i32,
+    export: &WorkerExport,
+    code: Worker,
+) -> Result<WorkerImportResponse, ServerError> {
+    let updated = Mutation::update_worker(
+        &state.db,
+        id.to_owned(),
+        export.external_path.to_owned(),
+        export.host_name.to_owned(),
+        export.node_name.to_owned(),
+        port,
+        code.code.to_owned(),
+        name.to_owned(),
+        None,
+        export.template.to_owned(),
+    )
+    .await
+    .map_err(|err| {
+        tracing::error!("Failed to update worker: {:?}", err);
+        ServerError::InternalServerError
+    })?;
+
+    let worker = Worker {
+        entry: code.entry,
+        code: code.code,
+        modules: code.modules,
+        bindings: code.bindings,
+        compatibility_date: code.compatibility_date,
+        ..updated.into()
+    };
+    let deployment = record_deployment(state, claims.sub.to_owned(), &worker, true).await?;
+    go_live(state, &worker).await?;
+
+    for schedule in &export.schedules {
+        let created = ScheduleMutation::create_schedule(
+            &state.db,
+            id.to_owned(),
+            schedule.cron.to_owned(),
+            schedule.path.to_owned(),
+        )
+        .await
+        .map_err(|err| {
+            tracing::error!("Failed to create schedule: {:?}", err);
+            ServerError::InternalServerError
+        })?;
+
+        if !schedule.enabled {
+            ScheduleMutation::update_schedule(
+                &state.db,
+                created.id.as_ref().to_string(),
+                schedule.cron.to_owned(),
+                schedule.path.to_owned(),
+                false,
+            )
+            .await
+            .map_err(|err| {
+                tracing::error!("Failed to update schedule: {:?}", err);
+                ServerError::InternalServerError
+            })?;
+        }
+    }
+    state.schedule_notify.notify_one();
+
+    Ok(WorkerImportResponse {
+        id: id.to_owned(),
+        name: name.to_owned(),
+        port,
+        deployment_id: deployment.id.to_string(),
+        missing_secrets: missing_secrets(&worker.bindings),
+    })
+}
+
+/// Names of the workers of the user and ports used by any worker or
+/// rollout candidate.
+async fn taken_names_and_ports(
+    state: &AppState,
+    user_id: &str,
+) -> Result<(HashSet<String>, HashSet<i32>), ServerError> {
+    let map_err = |err| {
+        tracing::error!("Failed to get workers: {:?}", err);
+        ServerError::InternalServerError
+    };
+
+    let names = Query::find_user_workers_with_user_id(&state.db, user_id.to_owned())
+        .await
+        .map_err(map_err)?
+        .into_iter()
+        .map(|worker| worker.name)
+        .collect();
+    let mut ports = Query::find_all_workers(&state.db)
+        .await
+        .map_err(map_err)?
+        .into_iter()
+        .map(|worker| worker.port)
+        .collect::<HashSet<_>>();
+    ports.extend(
+        RolloutQuery::find_active_rollouts(&state.db)
+            .await
+            .map_err(map_err)?
+            .into_iter()
+            .map(|rollout| rollout.port),
+    );
+
+    Ok((names, ports))
+}
+
+/// Packs the metadata and code of a worker into a `tar.gz` archive which is
+/// also a valid bundle. Secret values are left out.
+fn write_export(export: &WorkerExport, worker: &Worker) -> Result<Vec<u8>, ServerError> {
+    let modules = match &worker.modules {
+        Some(modules) => modules.to_owned(),
+        None => BTreeMap::from([(
+            worker.entry.to_owned(),
+            Module {
+                kind: ModuleKind::from_name(&worker.entry),
+                content: worker.code.to_owned(),
+            },
+        )]),
+    };
+    let bindings = worker
+        .bindings
+        .iter()
+        .map(|(name, binding)| {
+            let binding = match binding {
+                Binding::Secret { .. } => Binding::Secret {
+                    value: String::new(),
+                },
+                binding => binding.to_owned(),
+            };
+            (name.to_owned(), binding)
+        })
+        .collect();
+    let manifest = Manifest {
+        entry: worker.entry.to_owned(),
+        modules: Some(modules.keys().cloned().collect()),
+        compatibility_date: worker.compatibility_date.to_owned(),
+        bindings,
+    };
+
+    let mut files = vec![
+        (METADATA_NAME.to_owned(), to_json(export)?),
+        (MANIFEST_NAME.to_owned(), to_json(&manifest)?),
+    ];
+    for (name, module) in modules {
+        files.push((name, module.bytes()?));
+    }
+
+    pack(files).map_err(|err| {
+        tracing::error!("Failed to write export: {:?}", err);
+        ServerError::InternalServerError
+    })
+}
+
+fn to_json(value: &impl Serialize) -> Result<Vec<u8>, ServerError> {
+    serde_json::to_vec_pretty(value).map_err(|err| {
+        tracing::error!("Failed to serialize export: {:?}", err);
+        ServerError::InternalServerError
+    })
+}
+
+fn pack(files: Vec<(String, Vec<u8>)>) -> std::io::Result<Vec<u8>> {
+    let mut builder = tar::Builder::new(GzEncoder::new(Vec::new(), Compression::default()));
+    for (path, content) in files {
+        let mut header = tar::Header::new_gnu();
+        header.set_size(content.len() as u64);
+        header.set_mode(0o644);
+        builder.append_data(&mut header, path, content.as_slice())?;
+    }
+    builder.into_inner()?.finish()
+}
+
+/// Reads an archive written by [`write_export`]. The returned worker only
+/// carries the code, modules and bindings of the export.
+fn read_export(archive: &[u8]) -> Result<(WorkerExport, Worker), ServerError> {
+    let mut files = unpack(archive)?;
+
+    let export = files
+        .remove(METADATA_NAME)
+        .ok_or(ServerError::InvalidManifest)?;
+    let export: WorkerExport = serde_json::from_slice(&export).map_err(|err| {
+        tracing::warn!("Failed to parse worker metadata: {:?}", err);
+        ServerError::InvalidManifest
+    })?;
+    if export.version != EXPORT_VERSION
+        || export
+            .schedules
+            .iter()
+            .any(|schedule| parse_cron(&schedule.cron).is_err())
+    {
+        return Err(ServerError::InvalidManifest);
+    }
+    for schedule in &export.schedules {
+        check_path(schedule.path.as_deref())?;
+    }
+
+    let bundle = build_bundle(files)?;
+    let mut worker = Worker {
+        id: String::new(),
+        host_name: export.host_name.to_owned(),
+        port: export.port.to_string(),
+        entry: String::new(),
+        code: String::new(),
+        template: export.template.to_owned(),
+        modules: None,
+        bindings: BTreeMap::new(),
+        compatibility_date: None,
+    };
+    worker = bundle.apply(worker);
+    if export.service_worker {
+        worker.modules = None;
+    }
+    validate_host_name(&worker.host_name)?;
+    if let Some(template) = &worker.template {
+        validate_template(template)?;
+    }
+
+    Ok((export, worker))
+}
+
+fn missing_secrets(bindings: &BTreeMap<String, Binding>) -> Vec<String> {
+    bindings
+        .iter()
+        .filter(|(_, binding)| matches!(binding, Binding::Secret { value } if value.is_empty()))
+        .map(|(name, _)| name.to_owned())
+        .collect()
+}
+
+/// Returns `name`, or the first of `name-2`, `name-3`, ... not in `taken`.
+fn unique_name(name: &str, taken: &HashSet<String>) -> String {
+    if !taken.contains(name) {
+        return name.to_owned();
+    }
+    (2..)
+        .map(|n| format!("{name}-{n}"))
+        .find(|candidate| !taken.contains(candidate))
+        .unwrap()
+}
+
+/// Returns `port`, or the next port after it not in `taken`, wrapping
+/// around to the first unprivileged port.
+fn free_port(port: i32, taken: &HashSet<i32>) -> Option<i32> {
+    let start = port.clamp(1024, 65535);
+    (start..=65535)
+        .chain(1024..start)
+        .find(|port| !taken.contains(port))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn export() -> WorkerExport {
+        WorkerExport {
+            version: EXPORT_VERSION,
+            name: "api".to_string(),
+            external_path: "/api".to_string(),
+            host_name: "localhost".to_string(),
+            node_name: "default".to_string(),
+            port: 8080,
+            template: None,
+            service_worker: false,
+            schedules: vec![ScheduleExport {
+                cron: "0 */5 * * * *".to_string(),
+                path: Some("/tick".to_string()),
+                enabled: false,
+            }],
+        }
+    }
+
+    fn worker() -> Worker {
+        Worker {
+            id: "0f8e".to_string(),
+            host_name: "localhost".to_string(),
+            port: "8080".to_string(),
+            entry: "index.js".to_string(),
+            code: "export default {}".to_string(),
+            template: None,
+            modules: Some(BTreeMap::from([
+                (
+                    "index.js".to_string(),
+                    Module::new(ModuleKind::EsModule, b"export default {}".to_vec()).unwrap(),
+                ),
+                (
+                    "add.wasm".to_string(),
+                    Module::new(ModuleKind::Wasm, vec![0, 97, 115, 109]).unwrap(),
+                ),
+            ])),
+            bindings: BTreeMap::from([
+                (
+                    "MODE".to_string(),
+                    Binding::Text {
+                        value: "prod".to_string(),
+                    },
+                ),
+                (
+                    "TOKEN".to_string(),
+                    Binding::Secret {
+                        value: "hunter2".to_string(),
+                    },
+                ),
+            ]),
+            compatibility_date: Some("2024-09-01".to_string()),
+        }
+    }
+
+    #[test]
+    fn test_export_round_trip() {
+        let archive = write_export(&export(), &worker()).unwrap();
+        let (imported, code) = read_export(&archive).unwrap();
+
+        assert_eq!(imported, export());
+        assert_eq!(code.entry, "index.js");
+        assert_eq!(code.code, "export default {}");
+        assert_eq!(code.modules, worker().modules);
+        assert_eq!(code.compatibility_date.as_deref(), Some("2024-09-01"));
+        assert_eq!(code.bindings["MODE"], worker().bindings["MODE"]);
+        assert_eq!(missing_secrets(&code.bindings), ["TOKEN"]);
+    }
+
+    #[test]
+    fn test_import_rejects_schedule_paths() {
+        let mut export = export();
+        export.schedules[0].path = Some("@169.254.169.254/latest/meta-data/".to_string());
+        let archive = write_export(&export, &worker()).unwrap();
+
+        assert!(matches!(
+            read_export(&archive),
+            Err(ServerError::InvalidSchedulePath)
+        ));
+    }
+
+    #[test]
+    fn test_export_leaves_out_secrets() {
+        let archive = write_export(&export(), &worker()).unwrap();
+        let files = unpack(&archive).unwrap();
+
+        let manifest = String::from_utf8(files[MANIFEST_NAME].to_owned()).unwrap();
+        assert!(!manifest.contains("hunter2"));
+        assert!(manifest.contains(r#""type": "secret""#));
+    }
+
+    #[test]
+    fn test_export_service_worker() {
+        let mut worker = worker();
+        worker.modules = None;
+        worker.bindings.clear();
+        let export = WorkerExport {
+            service_worker: true,
+            ..export()
+        };
+
+        let (_, code) = read_export(&write_export(&export, &worker).unwrap()).unwrap();
+        assert_eq!(code.modules, None);
+        assert_eq!(code.entry, "index.js");
+        assert_eq!(code.code, "export default {}");
+    }
+
+    #[test]
+    fn test_unique_name() {
+        let taken = HashSet::from(["api".to_string(), "api-2".to_string()]);
+        assert_eq!(unique_name("web", &taken), "web");
+        assert_eq!(unique_name("api", &taken), "api-3");
+    }
+
+    #[test]
+    fn test_free_port() {
+        let taken = HashSet::from([8080, 8081, 65535]);
+        assert_eq!(free_port(9000, &taken), Some(9000));
+        assert_eq!(free_port(8080, &taken), Some(8082));
+        assert_eq!(free_port(65535, &taken), Some(1024));
+        assert_eq!(free_port(80, &taken), Some(1024));
+    }
+}
@@ -0,0 +1,748 @@
+//! Two-factor authentication with time-based one-time passwords (RFC 6238),
+//! as shown by authenticator apps, and recovery codes for when the device
+//! is lost.
+//!
+//! Once a user confirmed an enrollment, `login` answers with a challenge
+//! instead of tokens, which takes a code at `/auth/login/two-factor` to
+//! complete. Admins that must use a second factor and have none enroll
+//! through the challenge as well, and single sign-on asks for it the same
+//! way.
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use argon2::password_hash::rand_core::{OsRng, RngCore};
+use axum::{debug_handler, extract::State, Json};
+use chrono::Utc;
+use entity::{user, user_totp};
+use redis::Commands;
+use ring::hmac;
+use serde::{Deserialize, Serialize};
+use service::{
+    settings::{Mutation as SettingMutation, Query as SettingQuery},
+    two_factor::{Mutation, Query},
+    users::Query as UserQuery,
+};
+
+use crate::{
+    auth::{
+        generate_secret, generate_token_pair, hash_secret, secrets_match, token_pair_error,
+        AuthBody,
+    },
+    config::AppState,
+    errors::ServerError,
+    policy::{authorize, user_permissions, Permission},
+    scopes::{Scoped, UsersAdmin, UsersRead, UsersWrite},
+    security_events::{record_event, SecurityEventKind},
+    sessions::ClientInfo,
+    users::MessageResponse,
+};
+
+const REQUIRE_ADMIN_TWO_FACTOR_KEY: &str = "require_admin_two_factor";
+/// Names the manager in authenticator apps.
+const TOTP_ISSUER: &str = "workerd-manager";
+const TOTP_STEP_SECS: u64 = 30;
+const TOTP_DIGITS: usize = 6;
+/// Steps accepted before and after the current one, for clocks that drift.
+const TOTP_SKEW_STEPS: u64 = 1;
+/// 160 bits, the length of SHA-1 as RFC 4226 recommends.
+const TOTP_SECRET_LEN: usize = 20;
+const RECOVERY_CODE_COUNT: usize = 10;
+/// How long a password login may wait for its second factor.
+pub const CHALLENGE_TTL_SECS: u64 = 5 * 60;
+/// Wrong codes accepted per challenge, after which the login starts over.
+const MAX_CHALLENGE_ATTEMPTS: u64 = 5;
+
+const BASE32_ALPHABET: &[u8; 32] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ234567";
+
+/// Encodes bytes in unpadded base32 (RFC 4648), as authenticator apps take
+/// secrets.
+pub fn base32_encode(bytes: &[u8]) -> String {
+    let mut encoded = String::with_capacity((bytes.len() * 8).div_ceil(5));
+    let (mut buffer, mut bits) = (0u32, 0);
+    for byte in bytes {
+        buffer = (buffer << 8) | u32::from(*byte);
+        bits += 8;
+        while bits >= 5 {
+            bits -= 5;
+            encoded.push(BASE32_ALPHABET[((buffer >> bits) & 31) as usize] as char);
+        }
+    }
+    if bits > 0 {
+        encoded.push(BASE32_ALPHABET[((buffer << (5 - bits)) & 31) as usize] as char);
+    }
+    encoded
+}
+
+pub fn base32_decode(encoded: &str) -> Option<Vec<u8>> {
+    let mut bytes = Vec::with_capacity(encoded.len() * 5 / 8);
+    let (mut buffer, mut bits) = (0u32, 0);
+    for c in encoded.chars().filter(|c| !c.is_whitespace() && *c != '=') {
+        let value = BASE32_ALPHABET
+            .iter()
+            .position(|&a| a as char == c.to_ascii_uppercase())?;
+        buffer = (buffer << 5) | value as u32;
+        bits += 5;
+        if bits >= 8 {
+            bits -= 8;
+            bytes.push((buffer >> bits) as u8);
+        }
+    }
+    Some(bytes)
+}
+
+/// The one-time password of a counter (RFC 4226).
+fn hotp(secret: &[u8], counter: u64) -> u32 {
+    let key = hmac::Key::new(hmac::HMAC_SHA1_FOR_LEGACY_USE_ONLY, secret);
+    let tag = hmac::sign(&key, &counter.to_be_bytes());
+    let digest = tag.as_ref();
+    let offset = (digest[digest.len() - 1] & 0x0f) as usize;
+    let code = u32::from_be_bytes([
+        digest[offset] & 0x7f,
+        digest[offset + 1],
+        digest[offset + 2],
+        digest[offset + 3],
+    ]);
+    code % 10u32.pow(TOTP_DIGITS as u32)
+}
+
+/// Returns the time step `code` is valid for at `now`, if any.
+fn verify_totp(secret: &[u8], code: &str, now: u64) -> Option<u64> {
+    let current = now / TOTP_STEP_SECS;
+    (current.saturating_sub(TOTP_SKEW_STEPS)..=current + TOTP_SKEW_STEPS).find(|step| {
+        secrets_match(
+            &format!("{:0width$}", hotp(secret, *step), width = TOTP_DIGITS),
+            code,
+        )
+    })
+}
+
+fn unix_time() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_secs())
+}
+
+/// The `otpauth://` URI of a secret, which authenticator apps read from a
+/// QR code.
+pub fn provisioning_uri(account: &str, secret: &str) -> String {
+    // Spaces are `%20` in paths, where form encoding makes them `+`.
+    let label = serde_urlencoded::to_string([("", format!("{TOTP_ISSUER}:{account}"))])
+        .unwrap_or_default()
+        .trim_start_matches('=')
+        .replace('+', "%20");
+    let query = serde_urlencoded::to_string([
+        ("secret", secret),
+        ("issuer", TOTP_ISSUER),
+        ("algorithm", "SHA1"),
+        ("digits", &TOTP_DIGITS.to_string()),
+        ("period", &TOTP_STEP_SECS.to_string()),
+    ])
+    .unwrap_or_default();
+    format!("otpauth://totp/{label}?{query}")
+}
+
+/// Returns fresh recovery codes, formatted as `xxxx-xxxx-xxxx`.
+pub fn generate_recovery_codes() -> Vec<String> {
+    (0..RECOVERY_CODE_COUNT)
+        .map(|_| {
+            let secret = generate_secret();
+            format!("{}-{}-{}", &secret[..4], &secret[4..8], &secret[8..12])
+        })
+        .collect()
+}
+
+/// Hashes a recovery code as typed, ignoring case and separators.
+pub fn hash_recovery_code(code: &str) -> String {
+    let normalized: String = code
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .map(|c| c.to_ascii_lowercase())
+        .collect();
+    hash_secret(&normalized)
+}
+
+fn is_totp_code(code: &str) -> bool {
+    code.len() == TOTP_DIGITS && code.bytes().all(|byte| byte.is_ascii_digit())
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TwoFactorSettings {
+    /// Whether admins must use a second factor to log in with a password.
+    pub require_for_admins: bool,
+}
+
+pub async fn admins_require_two_factor(state: &AppState) -> Result<bool, ServerError> {
+    let value = SettingQuery::find_setting(&state.db, REQUIRE_ADMIN_TWO_FACTOR_KEY)
+        .await
+        .map_err(|err| {
+            tracing::error!("Failed to get two-factor setting: {:?}", err);
+            ServerError::InternalServerError
+        })?;
+    Ok(value.is_some_and(|value| value == "true"))
+}
+
+/// Holders of any of these count as admins for the two-factor requirement,
+/// whether granted by a built-in or a stored role.
+const ADMIN_PERMISSIONS: [Permission; 3] = [
+    Permission::UsersManage,
+    Permission::RolesManage,
+    Permission::SettingsManage,
+];
+
+async fn is_two_factor_required(state: &AppState, user: &user::Model) -> Result<bool, ServerError> {
+    if !admins_require_two_factor(state).await? {
+        return Ok(false);
+    }
+    let permissions = user_permissions(state, user).await?;
+    Ok(ADMIN_PERMISSIONS
+        .iter()
+        .any(|permission| permissions.contains(permission)))
+}
+
+async fn find_totp(
+    state: &AppState,
+    user_id: &str,
+) -> Result<Option<user_totp::Model>, ServerError> {
+    Query::find_totp_by_user_id(&state.db, user_id.to_owned())
+        .await
+        .map_err(|err| {
+            tracing::error!("Failed to get TOTP: {:?}", err);
+            ServerError::InternalServerError
+        })
+}
+
+/// Answer of `login` for users with a second factor, in place of tokens.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SecondFactorChallenge {
+    pub second_factor_required: bool,
+    pub challenge: String,
+    /// Set when the user must enroll first, see `enroll_login`.
+    pub enrollment_required: bool,
+    pub expires_in: u64,
+}
+
+/// Starts a challenge when `user` needs a second factor to log in.
+pub async fn second_factor_challenge(
+    state: &AppState,
+    user: &user::Model,
+) -> Result<Option<SecondFactorChallenge>, ServerError> {
+    let user_id = user.id.to_string();
+    let enrolled = find_totp(state, &user_id)
+        .await?
+        .is_some_and(|totp| totp.confirmed_at.is_some());
+    if !enrolled && !is_two_factor_required(state, user).await? {
+        return Ok(None);
+    }
+
+    let challenge = generate_secret();
+    let mut con = redis_connection(state)?;
+    let result: redis::RedisResult<()> = state
+        .manager_metrics
+        .time_redis(|| con.set_ex(challenge_key(&challenge), &user_id, CHALLENGE_TTL_SECS));
+    result.map_err(|err| {
+        tracing::error!("Failed to save two-factor challenge: {:?}", err);
+        ServerError::InternalServerError
+    })?;
+
+    Ok(Some(SecondFactorChallenge {
+        second_factor_required: true,
+        challenge,
+        enrollment_required: !enrolled,
+        expires_in: CHALLENGE_TTL_SECS,
+    }))
+}
+
+fn challenge_key(challenge: &str) -> String {
+    format!("two_factor_challenge:{}", hash_secret(challenge))
+}
+
+fn challenge_attempts_key(challenge: &str) -> String {
+    format!("two_factor_attempts:{}", hash_secret(challenge))
+}
+
+fn redis_connection(state: &AppState) -> Result<redis::Connection, ServerError> {
+    state.redis_client.get_connection().map_err(|err| {
+        tracing::error!("Failed to connect to Redis: {:?}", err);
+        ServerError::InternalServerError
+    })
+}
+
+/// Returns the user a challenge was issued to, counting an attempt at it
+/// when `attempt` is set. The challenge is dropped after too many.
+fn challenge_user(state: &AppState, challenge: &str, attempt: bool) -> Result<String, ServerError> {
+    let mut con = redis_connection(state)?;
+    let result: redis::RedisResult<Option<String>> = state
+        .manager_metrics
+        .time_redis(|| con.get(challenge_key(challenge)));
+    let user_id = result
+        .map_err(|err| {
+            tracing::error!("Failed to get two-factor challenge: {:?}", err);
+            ServerError::InternalServerError
+        })?
+        .ok_or(ServerError::InvalidTwoFactorChallenge)?;
+    if !attempt {
+        return Ok(user_id);
+    }
+
+    let result: redis::RedisResult<u64> = state.manager_metrics.time_redis(|| {
+        redis::pipe()
+            .atomic()
+            .incr(challenge_attempts_key(challenge), 1)
+            .expire(challenge_attempts_key(challenge), CHALLENGE_TTL_SECS as i64)
+            .ignore()
+            .query(&mut con)
+            .map(|(attempts,): (u64,)| attempts)
+    });
+    let attempts = result.map_err(|err| {
+        tracing::error!("Failed to count two-factor attempts: {:?}", err);
+        ServerError::InternalServerError
+    })?;
+    if attempts > MAX_CHALLENGE_ATTEMPTS {
+        end_challenge(state, challenge)?;
+        return Err(ServerError::InvalidTwoFactorChallenge);
+    }
+    Ok(user_id)
+}
+
+/// Ends a challenge, and returns false when it had already ended, so that
+/// each is completed once.
+fn end_challenge(state: &AppState, challenge: &str) -> Result<bool, ServerError> {
+    let mut con = redis_connection(state)?;
+    let result: redis::RedisResult<u64> = state
+        .manager_metrics
+        .time_redis(|| con.del(&[challenge_key(challenge), challenge_attempts_key(challenge)]));
+
+    result.map(|deleted| deleted > 0).map_err(|err| {
+        tracing::error!("Failed to end two-factor challenge: {:?}", err);
+        ServerError::InternalServerError
+    })
+}
+
+/// Checks a code of a confirmed enrollment: a one-time password, or else
+/// a recovery code. Either works once.
+async fn verify_code(
+    state: &AppState,
+    totp: &user_totp::Model,
+    code: &str,
+    client: &ClientInfo,
+) -> Result<bool, ServerError> {
+    let code = code.trim();
+    if is_totp_code(code) {
+        let secret = base32_decode(&totp.secret).ok_or(ServerError::InternalServerError)?;
+        let Some(step) = verify_totp(&secret, code, unix_time()) else {
+            return Ok(false);
+        };
+        return Mutation::use_totp_step(&state.db, totp.user_id, step as i64)
+            .await
+            .map_err(|err| {
+                tracing::error!("Failed to use TOTP step: {:?}", err);
+                ServerError::InternalServerError
+            });
+    }
+
+    let used = Mutation::use_recovery_code(
+        &state.db,
+        totp.user_id,
+        hash_recovery_code(code),
+        Utc::now().into(),
+    )
+    .await
+    .map_err(|err| {
+        tracing::error!("Failed to use recovery code: {:?}", err);
+        ServerError::InternalServerError
+    })?;
+    if used {
+        record_event(
+            state,
+            &totp.user_id.to_string(),
+            SecurityEventKind::RecoveryCodeUsed,
+            client,
+        )
+        .await;
+    }
+    Ok(used)
+}
+
+/// Confirms an enrollment with a one-time password of its secret, and
+/// returns the recovery codes.
+async fn confirm_enrollment(
+    state: &AppState,
+    totp: &user_totp::Model,
+    code: &str,
+) -> Result<Vec<String>, ServerError> {
+    let secret = base32_decode(&totp.secret).ok_or(ServerError::InternalServerError)?;
+    let step =
+        verify_totp(&secret, code.trim(), unix_time()).ok_or(ServerError::InvalidTwoFactorCode)?;
+
+    let recovery_codes = generate_recovery_codes();
+    let confirmed = Mutation::confirm_totp(
+        &state.db,
+        totp.user_id,
+        Utc::now().into(),
+        recovery_codes
+            .iter()
+            .map(|code| hash_recovery_code(code))
+            .collect(),
+    )
+    .await
+    .map_err(|err| {
+        tracing::error!("Failed to confirm TOTP: {:?}", err);
+        ServerError::InternalServerError
+    })?;
+    if !confirmed {
+        return Err(ServerError::TotpAlreadyEnabled);
+    }
+    if let Err(err) = Mutation::use_totp_step(&state.db, totp.user_id, step as i64).await {
+        tracing::warn!("Failed to use TOTP step: {:?}", err);
+    }
+    Ok(recovery_codes)
+}
+
+/// Generates a secret for a user, replacing an unconfirmed one.
+async fn start_enrollment(state: &AppState, user_id: &str) -> Result<TotpEnrollment, ServerError> {
+    let user = UserQuery::find_user_by_id(&state.db, user_id.to_owned())
+        .await
+        .map_err(|err| {
+            tracing::error!("Failed to get user: {:?}", err);
+            ServerError::InternalServerError
+        })?
+        .ok_or(ServerError::NotFound)?;
+
+    let mut secret = [0u8; TOTP_SECRET_LEN];
+    OsRng.fill_bytes(&mut secret);
+    let secret = base32_encode(&secret);
+    let started = Mutation::start_totp(&state.db, user.id, secret.to_owned())
+        .await
+        .map_err(|err| {
+            tracing::error!("Failed to start TOTP: {:?}", err);
+            ServerError::InternalServerError
+        })?;
+    if !started {
+        return Err(ServerError::TotpAlreadyEnabled);
+    }
+
+    Ok(TotpEnrollment {
+        provisioning_uri: provisioning_uri(&user.email, &secret),
+        secret,
+    })
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TotpEnrollment {
+    /// For typing into the authenticator app when scanning is no option.
+    pub secret: String,
+    /// For showing as a QR code.
+    pub provisioning_uri: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TotpStatus {
+    pub enabled: bool,
+    pub required: bool,
+    pub recovery_codes_left: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RecoveryCodesResponse {
+    /// Shown once, only their hashes are stored.
+    pub recovery_codes: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CodeRequest {
+    pub code: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ChallengeRequest {
+    pub challenge: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ChallengeResponse {
+    pub challenge: String,
+    pub code: String,
+}
+
+/// Completes a password login with a one-time password or a recovery
+/// code. Completing an enrollment started with `enroll_login` confirms it,
+/// and the tokens come with the recovery codes.
+#[debug_handler]
+pub async fn complete_login(
+    State(state): State<AppState>,
+    client: ClientInfo,
+    Json(payload): Json<ChallengeResponse>,
+) -> Result<Json<AuthBody>, ServerError> {
+    let user_id = challenge_user(&state, &payload.challenge, true)?;
+    let totp = find_totp(&state, &user_id)
+        .await?
+        .ok_or(ServerError::TotpNotEnrolled)?;
+
+    let recovery_codes = match totp.confirmed_at {
+        Some(_) => {
+            if !verify_code(&state, &totp, &payload.code, &client).await? {
+                return Err(ServerError::InvalidTwoFactorCode);
+            }
+            None
+        }
+        None => Some(confirm_enrollment(&state, &totp, &payload.code).await?),
+    };
+    if !end_challenge(&state, &payload.challenge)? {
+        return Err(ServerError::InvalidTwoFactorChallenge);
+    }
+
+    let (access_token, refresh_token) = generate_token_pair(&state, &user_id, &client, None)
+        .await
+        .map_err(token_pair_error)?;
+
+    Ok(Json(AuthBody {
+        recovery_codes,
+        ..AuthBody::new(access_token, refresh_token)
+    }))
+}
+
+/// Starts an enrollment during a login that requires one.
+#[debug_handler]
+pub async fn enroll_login(
+    State(state): State<AppState>,
+    Json(payload): Json<ChallengeRequest>,
+) -> Result<Json<TotpEnrollment>, ServerError> {
+    let user_id = challenge_user(&state, &payload.challenge, false)?;
+    Ok(Json(start_enrollment(&state, &user_id).await?))
+}
+
+#[debug_handler]
+pub async fn get_totp(
+    State(state): State<AppState>,
+    Scoped(claims, _): Scoped<UsersRead>,
+) -> Result<Json<TotpStatus>, ServerError> {
+    let user = UserQuery::find_user_by_id(&state.db, claims.sub.to_owned())
+        .await
+        .map_err(|err| {
+            tracing::error!("Failed to get user: {:?}", err);
+            ServerError::InternalServerError
+        })?
+        .ok_or(ServerError::NotFound)?;
+    let enabled = find_totp(&state, &claims.sub)
+        .await?
+        .is_some_and(|totp| totp.confirmed_at.is_some());
+    let recovery_codes_left = Query::count_recovery_codes(&state.db, claims.sub.to_owned())
+        .await
+        .map_err(|err| {
+            tracing::error!("Failed to count recovery codes: {:?}", err);
+            ServerError::InternalServerError
+        })?;
+
+    Ok(Json(TotpStatus {
+        enabled,
+        required: is_two_factor_required(&state, &user).await?,
+        recovery_codes_left,
+    }))
+}
+
+/// Starts an enrollment of the caller, to confirm with `confirm_totp`.
+#[debug_handler]
+pub async fn start_totp(
+    State(state): State<AppState>,
+    Scoped(claims, _): Scoped<UsersWrite>,
+) -> Result<Json<TotpEnrollment>, ServerError> {
+    Ok(Json(start_enrollment(&state, &claims.sub).await?))
+}
+
+#[debug_handler]
+pub async fn confirm_totp(
+    State(state): State<AppState>,
+    Scoped(claims, _): Scoped<UsersWrite>,
+    Json(payload): Json<CodeRequest>,
+) -> Result<Json<RecoveryCodesResponse>, ServerError> {
+    let totp = find_totp(&state, &claims.sub)
+        .await?
+        .ok_or(ServerError::TotpNotEnrolled)?;
+    if totp.confirmed_at.is_some() {
+        return Err(ServerError::TotpAlreadyEnabled);
+    }
+
+    Ok(Json(RecoveryCodesResponse {
+        recovery_codes: confirm_enrollment(&state, &totp, &payload.code).await?,
+    }))
+}
+
+/// Replaces the recovery codes of the caller, for when they ran out or
+/// leaked.
+#[debug_handler]
+pub async fn regenerate_recovery_codes(
+    State(state): State<AppState>,
+    Scoped(claims, _): Scoped<UsersWrite>,
+    client: ClientInfo,
+    Json(payload): Json<CodeRequest>,
+) -> Result<Json<RecoveryCodesResponse>, ServerError> {
+    let totp = find_totp(&state, &claims.sub)
+        .await?
+        .filter(|totp| totp.confirmed_at.is_some())
+        .ok_or(ServerError::TotpNotEnrolled)?;
+    if !verify_code(&state, &totp, &payload.code, &client).await? {
+        return Err(ServerError::InvalidTwoFactorCode);
+    }
+
+    let recovery_codes = generate_recovery_codes();
+    Mutation::replace_recovery_codes(
+        &state.db,
+        totp.user_id,
+        recovery_codes
+            .iter()
+            .map(|code| hash_recovery_code(code))
+            .collect(),
+    )
+    .await
+    .map_err(|err| {
+        tracing::error!("Failed to replace recovery codes: {:?}", err);
+        ServerError::InternalServerError
+    })?;
+
+    Ok(Json(RecoveryCodesResponse { recovery_codes }))
+}
+
+/// Turns the second factor of the caller off, which takes a code of it.
+/// Admins cannot while it is required for them.
+#[debug_handler]
+pub async fn delete_totp(
+    State(state): State<AppState>,
+    Scoped(claims, _): Scoped<UsersWrite>,
+    client: ClientInfo,
+    Json(payload): Json<CodeRequest>,
+) -> Result<Json<MessageResponse>, ServerError> {
+    let user = UserQuery::find_user_by_id(&state.db, claims.sub.to_owned())
+        .await
+        .map_err(|err| {
+            tracing::error!("Failed to get user: {:?}", err);
+            ServerError::InternalServerError
+        })?
+        .ok_or(ServerError::NotFound)?;
+    if is_two_factor_required(&state, &user).await? {
+        return Err(ServerError::TwoFactorRequired);
+    }
+    let totp = find_totp(&state, &claims.sub)
+        .await?
+        .filter(|totp| totp.confirmed_at.is_some())
+        .ok_or(ServerError::TotpNotEnrolled)?;
+    if !verify_code(&state, &totp, &payload.code, &client).await? {
+        return Err(ServerError::InvalidTwoFactorCode);
+    }
+
+    Mutation::delete_totp(&state.db, user.id)
+        .await
+        .map_err(|err| {
+            tracing::error!("Failed to delete TOTP: {:?}", err);
+            ServerError::InternalServerError
+        })?;
+    record_event(
+        &state,
+        &claims.sub,
+        SecurityEventKind::TwoFactorDisabled,
+        &client,
+    )
+    .await;
+
+    Ok(Json(MessageResponse {
+        message: "Two-factor authentication disabled successfully".to_owned(),
+    }))
+}
+
+#[debug_handler]
+pub async fn get_two_factor_settings(
+    State(state): State<AppState>,
+    Scoped(claims, _): Scoped<UsersAdmin>,
+) -> Result<Json<TwoFactorSettings>, ServerError> {
+    authorize(&claims, Permission::SettingsRead)?;
+
+    Ok(Json(TwoFactorSettings {
+        require_for_admins: admins_require_two_factor(&state).await?,
+    }))
+}
+
+/// Sets whether admins need a second factor. Those without one enroll on
+/// their next password login, sessions already started are left alone.
+#[debug_handler]
+pub async fn update_two_factor_settings(
+    State(state): State<AppState>,
+    Scoped(claims, _): Scoped<UsersAdmin>,
+    Json(settings): Json<TwoFactorSettings>,
+) -> Result<Json<TwoFactorSettings>, ServerError> {
+    authorize(&claims, Permission::SettingsManage)?;
+
+    SettingMutation::set_setting(
+        &state.db,
+        REQUIRE_ADMIN_TWO_FACTOR_KEY,
+        settings.require_for_admins.to_string(),
+    )
+    .await
+    .map_err(|err| {
+        tracing::error!("Failed to update two-factor setting: {:?}", err);
+        ServerError::InternalServerError
+    })?;
+
+    Ok(Json(settings))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_base32() {
+        let secret = b"12345678901234567890";
+        let encoded = base32_encode(secret);
+        assert_eq!(encoded, "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ");
+        assert_eq!(base32_decode(&encoded).unwrap(), secret);
+        assert_eq!(base32_decode("gezd gnbv").unwrap(), b"12345");
+        assert_eq!(base32_encode(b"f"), "MY");
+        assert_eq!(base32_decode("MY======").unwrap(), b"f");
+        assert_eq!(base32_decode("M1"), None);
+    }
+
+    #[test]
+    fn test_totp() {
+        // The SHA-1 test vectors of RFC 6238, cut to six digits.
+        let secret = b"12345678901234567890";
+        assert_eq!(verify_totp(secret, "287082", 59), Some(1));
+        assert_eq!(verify_totp(secret, "081804", 1111111109), Some(37037036));
+        assert_eq!(verify_totp(secret, "005924", 1234567890), Some(41152263));
+
+        // A step of drift either way is accepted, more is not.
+        assert_eq!(
+            verify_totp(secret, "081804", 1111111109 + 30),
+            Some(37037036)
+        );
+        assert_eq!(
+            verify_totp(secret, "081804", 1111111109 - 30),
+            Some(37037036)
+        );
+        assert_eq!(verify_totp(secret, "081804", 1111111109 + 60), None);
+        assert_eq!(verify_totp(secret, "81804", 1111111109), None);
+    }
+
+    #[test]
+    fn test_provisioning_uri() {
+        assert_eq!(
+            provisioning_uri("ada lovelace@example.com", "GEZDGNBV"),
+            "otpauth://totp/workerd-manager%3Aada%20lovelace%40example.com?secret=GEZDGNBV&issuer=workerd-manager&algorithm=SHA1&digits=6&period=30"
+        );
+    }
+
+    #[test]
+    fn test_recovery_codes() {
+        let codes = generate_recovery_codes();
+        assert_eq!(codes.len(), RECOVERY_CODE_COUNT);
+        assert!(codes
+            .iter()
+            .all(|code| code.len() == 14 && !is_totp_code(code)));
+        assert_ne!(codes[0], codes[1]);
+
+        let code = &codes[0];
+        assert_eq!(
+            hash_recovery_code(&code.to_uppercase().replace('-', " ")),
+            hash_recovery_code(code)
+        );
+        assert_ne!(hash_recovery_code(code), hash_recovery_code(&codes[1]));
+    }
+}
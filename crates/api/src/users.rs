@@ -1,7 +1,14 @@
 use crate::{
-    auth::{hash_password, AccessTokenClaims},
+    auth::{bump_token_generation, end_all_logins, hash_password, AccessTokenClaims},
     config::AppState,
     errors::ServerError,
+    invitations::{find_usable_invitation, registration_mode, RegistrationMode},
+    policy::{
+        allows, authorize, authorize_self_or, builtin_permissions, parse_permissions, Permission,
+    },
+    rollouts::candidate_id,
+    scopes::{Scope, Scoped, UsersAdmin, UsersRead, UsersWrite},
+    workerd::stop_worker,
 };
 
 use axum::{
@@ -9,14 +16,27 @@ use axum::{
     extract::{Path, State},
     Json,
 };
-use entity::sea_orm_active_enums::RoleEnum;
-use service::users::{Mutation, Query};
+use chrono::Utc;
+use entity::{
+    sea_orm_active_enums::{RoleEnum, UserStatusEnum},
+    user,
+};
+use service::{
+    invitations::Mutation as InvitationMutation,
+    roles::{Mutation as RoleMutation, Query as RoleQuery},
+    sea_orm::ActiveEnum,
+    users::{Mutation, Query},
+    workers::Query as WorkerQuery,
+};
 
 #[derive(serde::Deserialize, serde::Serialize)]
 pub struct UserCreateRequest {
     pub email: String,
     pub username: String,
     pub password: String,
+    /// Required to sign up while registration is invite-only.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub invitation_code: Option<String>,
 }
 
 #[derive(serde::Deserialize, serde::Serialize)]
@@ -25,7 +45,7 @@ pub struct UserInfoResponse {
     pub email: String,
     pub username: String,
     pub roles: Vec<RoleEnum>,
-    pub status: i32,
+    pub status: UserStatusEnum,
 }
 
 #[derive(serde::Deserialize, serde::Serialize)]
@@ -36,8 +56,22 @@ pub struct MessageResponse {
 #[debug_handler]
 pub async fn create_user(
     State(state): State<AppState>,
+    claims: Option<AccessTokenClaims>,
     Json(new_user): Json<UserCreateRequest>,
 ) -> Result<Json<MessageResponse>, ServerError> {
+    let can_manage = claims.is_some_and(|claims| {
+        allows(&claims, Permission::UsersManage) && claims.scopes.contains(&Scope::UsersAdmin)
+    });
+    let invitation = match registration_mode(&state).await? {
+        _ if can_manage => None,
+        RegistrationMode::Open => None,
+        RegistrationMode::InviteOnly => Some(
+            find_usable_invitation(&state, new_user.invitation_code.as_deref(), &new_user.email)
+                .await?,
+        ),
+        RegistrationMode::Closed => return Err(ServerError::RegistrationClosed),
+    };
+
     let hashed_password = match hash_password(&new_user.password) {
         Ok(hash) => hash,
         Err(err) => {
@@ -46,34 +80,46 @@ pub async fn create_user(
         }
     };
 
-    Mutation::create_user(
-        &state.db,
-        new_user.email,
-        new_user.username,
-        hashed_password,
-    )
-    .await
-    .map(|_| {
-        Json(MessageResponse {
-            message: "User created successfully".to_owned(),
-        })
-    })
+    let created = match invitation {
+        Some(invitation) => InvitationMutation::redeem_invitation(
+            &state.db,
+            invitation.id,
+            Utc::now().into(),
+            new_user.email,
+            new_user.username,
+            hashed_password,
+        )
+        .await
+        .map(|user| user.is_some()),
+        None => Mutation::create_user(
+            &state.db,
+            new_user.email,
+            new_user.username,
+            hashed_password,
+        )
+        .await
+        .map(|_| true),
+    }
     .map_err(|err| {
         tracing::error!("Failed to create user: {:?}", err);
         ServerError::InternalServerError
-    })
+    })?;
+    if !created {
+        return Err(ServerError::InvalidInvitation);
+    }
+
+    Ok(Json(MessageResponse {
+        message: "User created successfully".to_owned(),
+    }))
 }
 
 #[debug_handler]
 pub async fn get_user(
     State(state): State<AppState>,
-    claims: AccessTokenClaims,
+    Scoped(claims, _): Scoped<UsersRead>,
     Path(id): Path<String>,
 ) -> Result<Json<UserInfoResponse>, ServerError> {
-    if claims.sub != id && !claims.roles.contains(&RoleEnum::Admin) {
-        tracing::error!("Unauthorized access: {:?}", claims);
-        return Err(ServerError::Unauthorized);
-    }
+    authorize_self_or(&claims, &id, Permission::UsersRead)?;
     let user = Query::find_user_by_id(&state.db, id)
         .await
         .map_err(|err| {
@@ -94,12 +140,9 @@ pub async fn get_user(
 #[debug_handler]
 pub async fn get_all_users(
     State(state): State<AppState>,
-    claims: AccessTokenClaims,
+    Scoped(claims, _): Scoped<UsersAdmin>,
 ) -> Result<Json<Vec<UserInfoResponse>>, ServerError> {
-    if !claims.roles.contains(&RoleEnum::Admin) {
-        tracing::error!("Unauthorized access: {:?}", claims);
-        return Err(ServerError::Unauthorized);
-    }
+    authorize(&claims, Permission::UsersRead)?;
 
     let users = Query::find_all_users(&state.db).await.map_err(|err| {
         tracing::error!("Failed to get all users: {:?}", err);
@@ -123,14 +166,11 @@ pub async fn get_all_users(
 #[debug_handler]
 pub async fn update_user(
     State(state): State<AppState>,
-    claims: AccessTokenClaims,
+    Scoped(claims, _): Scoped<UsersWrite>,
     Path(id): Path<String>,
     Json(user): Json<UserCreateRequest>,
 ) -> Result<Json<MessageResponse>, ServerError> {
-    if claims.sub != id && !claims.roles.contains(&RoleEnum::Admin) {
-        tracing::error!("Unauthorized access: {:?}", claims);
-        return Err(ServerError::Unauthorized);
-    }
+    authorize_self_or(&claims, &id, Permission::UsersManage)?;
 
     let hashed_password = match hash_password(&user.password) {
         Ok(hash) => hash,
@@ -156,13 +196,10 @@ pub async fn update_user(
 #[debug_handler]
 pub async fn delete_user(
     State(state): State<AppState>,
-    claims: AccessTokenClaims,
+    Scoped(claims, _): Scoped<UsersWrite>,
     Path(id): Path<String>,
 ) -> Result<Json<MessageResponse>, ServerError> {
-    if claims.sub != id && !claims.roles.contains(&RoleEnum::Admin) {
-        tracing::error!("Unauthorized access: {:?}", claims);
-        return Err(ServerError::Unauthorized);
-    }
+    authorize_self_or(&claims, &id, Permission::UsersManage)?;
     Mutation::delete_user(&state.db, id)
         .await
         .map(|_| {
@@ -175,3 +212,226 @@ pub async fn delete_user(
             ServerError::InternalServerError
         })
 }
+
+#[debug_handler]
+pub async fn grant_role(
+    State(state): State<AppState>,
+    Scoped(claims, _): Scoped<UsersAdmin>,
+    Path((id, role)): Path<(String, String)>,
+) -> Result<Json<UserInfoResponse>, ServerError> {
+    let role = find_grantable_role(&state, &claims, &role).await?;
+    let user = find_user(&state, id).await?;
+
+    match role {
+        NamedRole::Builtin(role) => {
+            let mut roles = user.roles.to_owned();
+            if !roles.contains(&role) {
+                roles.push(role);
+            }
+            set_roles(&state, user, roles).await
+        }
+        NamedRole::Stored(name) => {
+            let assigned = RoleMutation::assign_role(&state.db, user.id, name)
+                .await
+                .map_err(|err| {
+                    tracing::error!("Failed to assign role: {:?}", err);
+                    ServerError::InternalServerError
+                })?;
+            if assigned {
+                bump_token_generation(&state, &user.id.to_string())?;
+            }
+            Ok(Json(user.into()))
+        }
+    }
+}
+
+#[debug_handler]
+pub async fn revoke_role(
+    State(state): State<AppState>,
+    Scoped(claims, _): Scoped<UsersAdmin>,
+    Path((id, role)): Path<(String, String)>,
+) -> Result<Json<UserInfoResponse>, ServerError> {
+    let role = find_grantable_role(&state, &claims, &role).await?;
+    // Keeps at least one admin around, the one making the request.
+    if role == NamedRole::Builtin(RoleEnum::Admin) && claims.sub == id {
+        return Err(ServerError::CannotRevokeOwnAdmin);
+    }
+    let user = find_user(&state, id).await?;
+
+    match role {
+        NamedRole::Builtin(role) => {
+            let roles = user
+                .roles
+                .iter()
+                .filter(|current| **current != role)
+                .cloned()
+                .collect();
+            set_roles(&state, user, roles).await
+        }
+        NamedRole::Stored(name) => {
+            let result = RoleMutation::unassign_role(&state.db, user.id, name)
+                .await
+                .map_err(|err| {
+                    tracing::error!("Failed to unassign role: {:?}", err);
+                    ServerError::InternalServerError
+                })?;
+            if result.rows_affected > 0 {
+                bump_token_generation(&state, &user.id.to_string())?;
+            }
+            Ok(Json(user.into()))
+        }
+    }
+}
+
+/// A role as named in `/users/:id/roles/:role`.
+#[derive(Debug, PartialEq, Eq)]
+enum NamedRole {
+    Builtin(RoleEnum),
+    Stored(String),
+}
+
+/// Finds a role the caller may grant and revoke. That takes holding every
+/// permission of the role, so that nobody can hand out more than they have.
+async fn find_grantable_role(
+    state: &AppState,
+    claims: &AccessTokenClaims,
+    name: &str,
+) -> Result<NamedRole, ServerError> {
+    authorize(claims, Permission::RolesManage)?;
+
+    let (role, permissions) = match RoleEnum::try_from_value(&name.to_owned()) {
+        Ok(role) => {
+            let permissions = builtin_permissions(&role).to_vec();
+            (NamedRole::Builtin(role), permissions)
+        }
+        Err(_) => {
+            let role = RoleQuery::find_role_by_name(&state.db, name.to_owned())
+                .await
+                .map_err(|err| {
+                    tracing::error!("Failed to get role: {:?}", err);
+                    ServerError::InternalServerError
+                })?
+                .ok_or(ServerError::InvalidRole)?;
+            let permissions = parse_permissions(&role.permissions);
+            (NamedRole::Stored(role.name), permissions)
+        }
+    };
+    for permission in permissions {
+        authorize(claims, permission)?;
+    }
+    Ok(role)
+}
+
+async fn find_user(state: &AppState, id: String) -> Result<user::Model, ServerError> {
+    Query::find_user_by_id(&state.db, id)
+        .await
+        .map_err(|err| {
+            tracing::error!("Failed to get user: {:?}", err);
+            ServerError::InternalServerError
+        })?
+        .ok_or(ServerError::NotFound)
+}
+
+async fn set_roles(
+    state: &AppState,
+    user: user::Model,
+    roles: Vec<RoleEnum>,
+) -> Result<Json<UserInfoResponse>, ServerError> {
+    let user = if roles == user.roles {
+        user
+    } else {
+        let user = Mutation::update_user_roles(&state.db, user.id.to_string(), roles)
+            .await
+            .map_err(|err| {
+                tracing::error!("Failed to update user roles: {:?}", err);
+                ServerError::InternalServerError
+            })?;
+        bump_token_generation(state, &user.id.to_string())?;
+        user
+    };
+
+    Ok(Json(user.into()))
+}
+
+/// Keeps a user from logging in, logs them out everywhere and stops their
+/// workers.
+#[debug_handler]
+pub async fn suspend_user(
+    State(state): State<AppState>,
+    Scoped(claims, _): Scoped<UsersAdmin>,
+    Path(id): Path<String>,
+) -> Result<Json<UserInfoResponse>, ServerError> {
+    authorize(&claims, Permission::UsersManage)?;
+    if claims.sub == id {
+        return Err(ServerError::CannotSuspendSelf);
+    }
+    find_user(&state, id.to_owned()).await?;
+
+    let user = set_status(&state, id, UserStatusEnum::Suspended).await?;
+    end_all_logins(&state, &user.id.to_string()).await?;
+    stop_user_workers(&state, &user.id.to_string()).await?;
+
+    Ok(Json(user.into()))
+}
+
+/// Lets a user log in again. Their workers stay stopped until started.
+#[debug_handler]
+pub async fn reactivate_user(
+    State(state): State<AppState>,
+    Scoped(claims, _): Scoped<UsersAdmin>,
+    Path(id): Path<String>,
+) -> Result<Json<UserInfoResponse>, ServerError> {
+    authorize(&claims, Permission::UsersManage)?;
+    find_user(&state, id.to_owned()).await?;
+
+    let user = set_status(&state, id, UserStatusEnum::Active).await?;
+
+    Ok(Json(user.into()))
+}
+
+async fn set_status(
+    state: &AppState,
+    id: String,
+    status: UserStatusEnum,
+) -> Result<user::Model, ServerError> {
+    Mutation::update_user_status(&state.db, id, status)
+        .await
+        .map_err(|err| {
+            tracing::error!("Failed to update user status: {:?}", err);
+            ServerError::InternalServerError
+        })
+}
+
+/// Stops every running worker of a user, along with the candidates of
+/// their rollouts.
+async fn stop_user_workers(state: &AppState, user_id: &str) -> Result<(), ServerError> {
+    let workers = WorkerQuery::find_user_workers_with_user_id(&state.db, user_id.to_owned())
+        .await
+        .map_err(|err| {
+            tracing::error!("Failed to get workers: {:?}", err);
+            ServerError::InternalServerError
+        })?;
+
+    for worker in workers {
+        let id = worker.id.to_string().replace('-', "");
+        for id in [candidate_id(&id), id] {
+            match stop_worker(state, &id).await {
+                Ok(()) | Err(ServerError::WorkerNotRunning) => {}
+                Err(err) => tracing::error!("Failed to stop {}: {:?}", id, err),
+            }
+        }
+    }
+    Ok(())
+}
+
+impl From<user::Model> for UserInfoResponse {
+    fn from(user: user::Model) -> Self {
+        Self {
+            id: user.id.to_string(),
+            email: user.email,
+            username: user.username,
+            roles: user.roles,
+            status: user.status,
+        }
+    }
+}
@@ -1,4 +1,8 @@
-use std::{collections::HashMap, path::PathBuf};
+use std::{
+    collections::{hash_map::Entry, BTreeMap},
+    path::PathBuf,
+    process::Stdio,
+};
 
 use axum::{
     debug_handler,
@@ -7,15 +11,25 @@ use axum::{
     response::IntoResponse,
     Json,
 };
-use entity::sea_orm_active_enums::RoleEnum;
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use entity::worker;
 use handlebars::Handlebars;
 use serde::{Deserialize, Serialize};
-use serde_json::json;
-use service::workers::Query;
+use serde_json::{json, Value as JsonValue};
+use service::{deployments::MASKED_SECRET, workers::Query};
 use sha2::{Digest, Sha256};
 use tokio::{fs, process::Command, sync::oneshot};
 
-use crate::{auth::AccessTokenClaims, config::AppState, errors::ServerError};
+use crate::{
+    auth::AccessTokenClaims,
+    bundle::safe_path,
+    config::AppState,
+    errors::ServerError,
+    logs::capture,
+    proxy::rebuild_routes,
+    scopes::{Scoped, WorkersExec, WorkersWrite},
+    teams::{authorize_worker, WorkerAccess},
+};
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct Worker {
@@ -25,40 +39,178 @@ pub struct Worker {
     pub entry: String,
     pub code: String,
     pub template: Option<String>,
+    /// Modules of an uploaded bundle, keyed by their path under `src/`.
+    /// Workers without modules run `code` as a service worker script.
+    pub modules: Option<BTreeMap<String, Module>>,
+    pub bindings: BTreeMap<String, Binding>,
+    pub compatibility_date: Option<String>,
+}
+
+impl From<worker::Model> for Worker {
+    fn from(worker: worker::Model) -> Self {
+        let id = worker.id.to_string().replace('-', "");
+        let modules = worker.modules.and_then(|modules| {
+            serde_json::from_value(modules)
+                .map_err(|err| tracing::warn!("Ignoring invalid modules of {}: {:?}", id, err))
+                .ok()
+        });
+        let bindings = serde_json::from_value(worker.bindings).unwrap_or_else(|err| {
+            tracing::warn!("Ignoring invalid bindings of {}: {:?}", id, err);
+            BTreeMap::new()
+        });
+
+        Self {
+            id,
+            host_name: worker.host_name,
+            port: worker.port.to_string(),
+            entry: worker.entry,
+            code: worker.code,
+            template: worker.template,
+            modules,
+            bindings,
+            compatibility_date: worker.compatibility_date,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ModuleKind {
+    EsModule,
+    CommonJsModule,
+    Text,
+    Data,
+    Wasm,
+    Json,
+}
+
+impl ModuleKind {
+    /// Guesses the kind of a module from the extension of its name.
+    pub fn from_name(name: &str) -> Self {
+        let extension = name
+            .rsplit_once('.')
+            .map(|(_, ext)| ext.to_ascii_lowercase());
+        match extension.as_deref() {
+            Some("js" | "mjs") => ModuleKind::EsModule,
+            Some("cjs") => ModuleKind::CommonJsModule,
+            Some("json") => ModuleKind::Json,
+            Some("wasm") => ModuleKind::Wasm,
+            Some("txt" | "html" | "css" | "md" | "svg" | "csv") => ModuleKind::Text,
+            _ => ModuleKind::Data,
+        }
+    }
+
+    pub fn is_script(self) -> bool {
+        matches!(self, ModuleKind::EsModule | ModuleKind::CommonJsModule)
+    }
+
+    /// Whether the content is stored base64 encoded.
+    pub fn is_binary(self) -> bool {
+        matches!(self, ModuleKind::Data | ModuleKind::Wasm)
+    }
+
+    /// Name of the field of a `Workerd.Worker.Module` holding the content.
+    fn field(self) -> &'static str {
+        match self {
+            ModuleKind::EsModule => "esModule",
+            ModuleKind::CommonJsModule => "commonJsModule",
+            ModuleKind::Text => "text",
+            ModuleKind::Data => "data",
+            ModuleKind::Wasm => "wasm",
+            ModuleKind::Json => "json",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Module {
+    #[serde(rename = "type")]
+    pub kind: ModuleKind,
+    pub content: String,
+}
+
+impl Module {
+    /// Stores `bytes` as a module of the given kind. Returns `None` when a
+    /// textual module is not valid UTF-8.
+    pub fn new(kind: ModuleKind, bytes: Vec<u8>) -> Option<Self> {
+        let content = if kind.is_binary() {
+            BASE64.encode(bytes)
+        } else {
+            String::from_utf8(bytes).ok()?
+        };
+        Some(Self { kind, content })
+    }
+
+    pub fn bytes(&self) -> Result<Vec<u8>, ServerError> {
+        if !self.kind.is_binary() {
+            return Ok(self.content.clone().into_bytes());
+        }
+        BASE64.decode(&self.content).map_err(|err| {
+            tracing::error!("Failed to decode module: {:?}", err);
+            ServerError::InternalServerError
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum Binding {
+    Text {
+        value: String,
+    },
+    Json {
+        value: JsonValue,
+    },
+    /// A text binding whose value is masked in deployment snapshots.
+    Secret {
+        value: String,
+    },
+}
+
+impl Binding {
+    /// Renders the `Workerd.Worker.Binding` field and value of the binding.
+    fn render(&self, mask_secrets: bool) -> (&'static str, String) {
+        match self {
+            Binding::Text { value } => ("text", capnp_string(value)),
+            Binding::Json { value } => ("json", capnp_string(&value.to_string())),
+            Binding::Secret { .. } if mask_secrets => ("text", capnp_string(MASKED_SECRET)),
+            Binding::Secret { value } => ("text", capnp_string(value)),
+        }
+    }
+}
+
+/// Quotes a value as a Cap'n Proto text literal.
+fn capnp_string(value: &str) -> String {
+    let mut literal = String::with_capacity(value.len() + 2);
+    literal.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => literal.push_str("\\\""),
+            '\\' => literal.push_str("\\\\"),
+            '\n' => literal.push_str("\\n"),
+            '\t' => literal.push_str("\\t"),
+            c if c.is_ascii_control() => literal.push_str(&format!("\\x{:02x}", c as u32)),
+            c => literal.push(c),
+        }
+    }
+    literal.push('"');
+    literal
 }
 
 #[debug_handler]
 pub async fn write_worker_config_capfile(
     State(state): State<AppState>,
-    claims: AccessTokenClaims,
+    Scoped(claims, _): Scoped<WorkersWrite>,
     Path(id): Path<String>,
 ) -> Result<impl IntoResponse, ServerError> {
-    let worker = get_worker_with_id(state.to_owned(), claims, id.to_owned())
+    let worker = get_worker_with_id(state.to_owned(), claims, id.to_owned(), WorkerAccess::Write)
         .await
         .map_err(|err| {
             tracing::error!("Failed to get worker: {:?}", err);
             ServerError::WorkerNotFound
         })?;
 
-    let file_map = generate_worker_configs(&state, vec![worker.clone()]).await;
-    let file_content = file_map.get(&worker.id).unwrap().clone();
-
-    let path = PathBuf::from(state.env.workerd_dir.to_string())
-        .join("worker-info")
-        .join(worker.id)
-        .join("Capfile");
-
-    fs::create_dir_all(path.parent().unwrap())
-        .await
-        .map_err(|err| {
-            tracing::error!("Failed to create directories: {:?}", err);
-            ServerError::InternalServerError
-        })?;
-
-    fs::write(path, file_content).await.map_err(|err| {
-        tracing::error!("Failed to write file: {:?}", err);
-        ServerError::InternalServerError
-    })?;
+    write_capfile(&state, &worker).await?;
 
     Ok((
         StatusCode::OK,
@@ -69,33 +221,17 @@ pub async fn write_worker_config_capfile(
 #[debug_handler]
 pub async fn write_worker_code(
     State(state): State<AppState>,
-    claims: AccessTokenClaims,
+    Scoped(claims, _): Scoped<WorkersWrite>,
     Path(id): Path<String>,
 ) -> Result<impl IntoResponse, ServerError> {
-    let worker = get_worker_with_id(state.to_owned(), claims, id.to_owned())
+    let worker = get_worker_with_id(state.to_owned(), claims, id.to_owned(), WorkerAccess::Write)
         .await
         .map_err(|err| {
             tracing::error!("Failed to get worker: {:?}", err);
             ServerError::WorkerNotFound
         })?;
 
-    let path = PathBuf::from(&state.env.workerd_dir.to_string())
-        .join(state.env.worker_info_dir.to_string())
-        .join(worker.id)
-        .join("src")
-        .join(worker.entry);
-
-    fs::create_dir_all(path.parent().unwrap())
-        .await
-        .map_err(|err| {
-            tracing::error!("Failed to create directories: {:?}", err);
-            ServerError::InternalServerError
-        })?;
-
-    fs::write(path, worker.code).await.map_err(|err| {
-        tracing::error!("Failed to write file: {:?}", err);
-        ServerError::InternalServerError
-    })?;
+    write_code(&state, &worker).await?;
 
     Ok((
         StatusCode::OK,
@@ -106,24 +242,22 @@ pub async fn write_worker_code(
 #[debug_handler]
 pub async fn delete_file(
     State(state): State<AppState>,
-    claims: AccessTokenClaims,
+    Scoped(claims, _): Scoped<WorkersWrite>,
     Path(id): Path<String>,
 ) -> Result<impl IntoResponse, ServerError> {
-    let worker = get_worker_with_id(state.to_owned(), claims, id.to_owned())
+    let worker = get_worker_with_id(state.to_owned(), claims, id.to_owned(), WorkerAccess::Write)
         .await
         .map_err(|err| {
             tracing::error!("Failed to get worker: {:?}", err);
             ServerError::WorkerNotFound
         })?;
 
-    let path = PathBuf::from(state.env.workerd_dir.to_string())
-        .join(state.env.worker_info_dir.to_string())
-        .join(worker.id);
-
-    fs::remove_dir_all(path).await.map_err(|err| {
-        tracing::error!("Failed to delete file: {:?}", err);
-        ServerError::InternalServerError
-    })?;
+    fs::remove_dir_all(worker_dir(&state, &worker.id))
+        .await
+        .map_err(|err| {
+            tracing::error!("Failed to delete file: {:?}", err);
+            ServerError::InternalServerError
+        })?;
 
     Ok((
         StatusCode::OK,
@@ -134,56 +268,25 @@ pub async fn delete_file(
 #[debug_handler]
 pub async fn run_cmd(
     State(state): State<AppState>,
-    claims: AccessTokenClaims,
+    Scoped(claims, _): Scoped<WorkersExec>,
     Path(id): Path<String>,
 ) -> Result<impl IntoResponse, ServerError> {
-    let worker = get_worker_with_id(state.to_owned(), claims, id.to_owned())
-        .await
-        .map_err(|err| {
-            tracing::error!("Failed to get worker: {:?}", err);
-            ServerError::WorkerNotFound
-        })?;
-
-    let mut chan_map = state.chan_map.lock().await;
-
-    if chan_map.contains_key(&worker.id) {
-        tracing::error!("{} is still running!", id);
-        return Err(ServerError::WorkerStillRunning);
-    }
-
-    let (tx, rx) = oneshot::channel();
-    chan_map.insert(worker.id.clone(), tx);
-
-    tokio::spawn(async move {
-        let worker_dir = PathBuf::from(state.env.workerd_dir.to_string())
-            .join("worker-info")
-            .join(worker.id.clone());
-
-        let args = vec![
-            "serve".to_string(),
-            worker_dir.join("Capfile").to_str().unwrap().to_string(),
-            "--watch".to_string(),
-            "--verbose".to_string(),
-        ]
-        .into_iter()
-        .collect::<Vec<_>>();
-
-        let child = Command::new(state.env.workerd_bin_path.to_string())
-            .args(&args)
-            .spawn()
-            .map_err(|err| {
-                tracing::error!("Failed to start subprocess: {:?}", err);
-                ServerError::FailedStartWorker
-            })
-            .unwrap();
-
-        let mut child_map = state.child_map.lock().await;
-        child_map.insert(worker.id.clone(), child);
+    let worker = get_worker_with_id(
+        state.to_owned(),
+        claims,
+        id.to_owned(),
+        WorkerAccess::Operate,
+    )
+    .await
+    .map_err(|err| {
+        tracing::error!("Failed to get worker: {:?}", err);
+        ServerError::WorkerNotFound
+    })?;
 
-        let _ = rx.await;
-        let mut child = child_map.remove(&worker.id).unwrap();
-        let _ = child.kill().await;
-    });
+    start_worker(&state, &worker).await.map_err(|err| {
+        tracing::error!("Failed to start {}: {:?}", id, err);
+        err
+    })?;
 
     Ok((
         StatusCode::OK,
@@ -194,23 +297,22 @@ pub async fn run_cmd(
 #[debug_handler]
 pub async fn exit_cmd(
     State(state): State<AppState>,
-    claims: AccessTokenClaims,
+    Scoped(claims, _): Scoped<WorkersExec>,
     Path(id): Path<String>,
 ) -> Result<impl IntoResponse, ServerError> {
-    let worker = get_worker_with_id(state.to_owned(), claims, id.to_owned())
-        .await
-        .map_err(|err| {
-            tracing::error!("Failed to get worker: {:?}", err);
-            ServerError::WorkerNotFound
-        })?;
-
-    let mut chan_map = state.chan_map.lock().await;
+    let worker = get_worker_with_id(
+        state.to_owned(),
+        claims,
+        id.to_owned(),
+        WorkerAccess::Operate,
+    )
+    .await
+    .map_err(|err| {
+        tracing::error!("Failed to get worker: {:?}", err);
+        ServerError::WorkerNotFound
+    })?;
 
-    if let Some(tx) = chan_map.remove(&worker.id) {
-        let _ = tx.send(());
-    } else {
-        return Err(ServerError::WorkerNotRunning);
-    }
+    stop_worker(&state, &worker.id).await?;
 
     Ok((
         StatusCode::OK,
@@ -225,54 +327,338 @@ pub async fn exit_all_cmd(State(state): State<AppState>) -> Result<impl IntoResp
     for (_, tx) in chan_map.drain() {
         let _ = tx.send(());
     }
+    drop(chan_map);
+    for (_, handle) in state.child_map.lock().await.drain() {
+        let _ = handle.await;
+    }
+
+    rebuild_routes(&state).await;
 
     Ok((StatusCode::OK, "All commands exited").into_response())
 }
 
+/// Removes the directory of a worker, if it has one.
+pub(crate) async fn remove_worker_files(state: &AppState, id: &str) -> Result<(), ServerError> {
+    match fs::remove_dir_all(worker_dir(state, id)).await {
+        Ok(()) => Ok(()),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(()),
+        Err(err) => {
+            tracing::error!("Failed to remove worker files: {:?}", err);
+            Err(ServerError::InternalServerError)
+        }
+    }
+}
+
+fn worker_dir(state: &AppState, id: &str) -> PathBuf {
+    PathBuf::from(state.env.workerd_dir.to_string())
+        .join(state.env.worker_info_dir.to_string())
+        .join(id)
+}
+
+async fn write_file(path: PathBuf, content: impl AsRef<[u8]>) -> Result<(), ServerError> {
+    fs::create_dir_all(path.parent().unwrap())
+        .await
+        .map_err(|err| {
+            tracing::error!("Failed to create directories: {:?}", err);
+            ServerError::InternalServerError
+        })?;
+
+    fs::write(path, content).await.map_err(|err| {
+        tracing::error!("Failed to write file: {:?}", err);
+        ServerError::InternalServerError
+    })
+}
+
+pub async fn write_capfile(state: &AppState, worker: &Worker) -> Result<(), ServerError> {
+    let capfile = render_capfile(state, worker, false).await?;
+    write_file(worker_dir(state, &worker.id).join("Capfile"), capfile).await
+}
+
+/// Writes the code of a worker under `src/`. The directory is replaced as a
+/// whole for bundles so that modules removed from them do not linger.
+pub async fn write_code(state: &AppState, worker: &Worker) -> Result<(), ServerError> {
+    let src = worker_dir(state, &worker.id).join("src");
+
+    let Some(modules) = &worker.modules else {
+        return write_file(src.join(&worker.entry), &worker.code).await;
+    };
+
+    match fs::remove_dir_all(&src).await {
+        Ok(()) => {}
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => {}
+        Err(err) => {
+            tracing::error!("Failed to clear worker code: {:?}", err);
+            return Err(ServerError::InternalServerError);
+        }
+    }
+    for (name, module) in modules {
+        write_file(src.join(name), module.bytes()?).await?;
+    }
+    Ok(())
+}
+
+/// Writes the Capfile and the code of a worker, as the `config` and `code`
+/// endpoints do.
+pub async fn write_worker_files(state: &AppState, worker: &Worker) -> Result<(), ServerError> {
+    write_capfile(state, worker).await?;
+    write_code(state, worker).await
+}
+
+pub async fn is_worker_running(state: &AppState, id: &str) -> bool {
+    state.chan_map.lock().await.contains_key(id)
+}
+
+pub async fn start_worker(state: &AppState, worker: &Worker) -> Result<(), ServerError> {
+    let mut chan_map = state.chan_map.lock().await;
+
+    if chan_map.contains_key(&worker.id) {
+        return Err(ServerError::WorkerStillRunning);
+    }
+
+    let args = vec![
+        "serve".to_string(),
+        worker_dir(state, &worker.id)
+            .join("Capfile")
+            .to_str()
+            .unwrap()
+            .to_string(),
+        "--watch".to_string(),
+        "--verbose".to_string(),
+    ];
+
+    let mut child = Command::new(state.env.workerd_bin_path.to_string())
+        .args(&args)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|err| {
+            tracing::error!("Failed to start subprocess: {:?}", err);
+            ServerError::FailedStartWorker
+        })?;
+    if let Some(stdout) = child.stdout.take() {
+        capture(state.worker_logs.clone(), worker.id.clone(), stdout);
+    }
+    if let Some(stderr) = child.stderr.take() {
+        capture(state.worker_logs.clone(), worker.id.clone(), stderr);
+    }
+
+    let (tx, mut rx) = oneshot::channel();
+    let task_state = state.clone();
+    let id = worker.id.clone();
+    let handle = tokio::spawn(async move {
+        tokio::select! {
+            _ = &mut rx => {
+                let _ = child.kill().await;
+            }
+            status = child.wait() => {
+                tracing::warn!("workerd of worker {} exited: {:?}", id, status);
+                drop(rx);
+                forget_exited_worker(&task_state, &id).await;
+                rebuild_routes(&task_state).await;
+            }
+        }
+    });
+
+    state
+        .child_map
+        .lock()
+        .await
+        .insert(worker.id.clone(), handle);
+    chan_map.insert(worker.id.clone(), tx);
+    drop(chan_map);
+    state.manager_metrics.record_worker_start(&worker.id);
+
+    rebuild_routes(state).await;
+    Ok(())
+}
+
+/// Removes a worker whose process exited on its own from the running
+/// workers, unless it was stopped or started again in the meantime.
+async fn forget_exited_worker(state: &AppState, id: &str) {
+    let mut chan_map = state.chan_map.lock().await;
+    if chan_map.get(id).is_some_and(|tx| tx.is_closed()) {
+        chan_map.remove(id);
+        state.child_map.lock().await.remove(id);
+    }
+}
+
+/// Stops the workerd process of a worker and waits for it to exit.
+pub async fn stop_worker(state: &AppState, id: &str) -> Result<(), ServerError> {
+    let tx = state
+        .chan_map
+        .lock()
+        .await
+        .remove(id)
+        .ok_or(ServerError::WorkerNotRunning)?;
+
+    let handle = state.child_map.lock().await.remove(id);
+    let _ = tx.send(());
+    if let Some(handle) = handle {
+        let _ = handle.await;
+    }
+
+    rebuild_routes(state).await;
+    Ok(())
+}
+
+/// Restarts a worker if it is running so that it picks up freshly written
+/// files. Returns whether the worker was restarted.
+pub async fn restart_worker(state: &AppState, worker: &Worker) -> Result<bool, ServerError> {
+    match stop_worker(state, &worker.id).await {
+        Ok(()) => start_worker(state, worker).await.map(|_| true),
+        Err(ServerError::WorkerNotRunning) => Ok(false),
+        Err(err) => Err(err),
+    }
+}
+
 fn get_template_hash(template: &str) -> String {
     let mut hasher = Sha256::new();
     hasher.update(template);
     format!("{:x}", hasher.finalize())
 }
 
-async fn generate_worker_configs(
-    state: &AppState,
-    workers: Vec<Worker>,
-) -> HashMap<String, String> {
-    let mut results = HashMap::new();
-    for worker in workers {
-        let config = generate_worker_config(state, worker.clone()).await;
-        results.insert(worker.id.clone(), config);
+#[derive(Serialize)]
+struct CapfileContext<'a> {
+    id: String,
+    host_name: &'a str,
+    port: &'a str,
+    entry: &'a str,
+    compatibility_date: &'a str,
+    modules: Vec<CapfileModule<'a>>,
+    bindings: Vec<CapfileBinding<'a>>,
+}
+
+#[derive(Serialize)]
+struct CapfileModule<'a> {
+    name: &'a str,
+    kind: &'static str,
+}
+
+#[derive(Serialize)]
+struct CapfileBinding<'a> {
+    name: &'a str,
+    kind: &'static str,
+    /// Already quoted Cap'n Proto literal.
+    value: String,
+}
+
+impl<'a> CapfileContext<'a> {
+    fn new(worker: &'a Worker, mask_secrets: bool) -> Result<Self, ServerError> {
+        validate_host_name(&worker.host_name)?;
+        validate_entry(&worker.entry)?;
+
+        // workerd treats the first module as the main one.
+        let mut modules = worker
+            .modules
+            .iter()
+            .flatten()
+            .map(|(name, module)| CapfileModule {
+                name,
+                kind: module.kind.field(),
+            })
+            .collect::<Vec<_>>();
+        modules.sort_by_key(|module| module.name != worker.entry);
+
+        let bindings = worker
+            .bindings
+            .iter()
+            .map(|(name, binding)| {
+                let (kind, value) = binding.render(mask_secrets);
+                CapfileBinding { name, kind, value }
+            })
+            .collect();
+
+        Ok(Self {
+            id: worker.id.replace('-', ""),
+            host_name: &worker.host_name,
+            port: &worker.port,
+            entry: &worker.entry,
+            compatibility_date: worker
+                .compatibility_date
+                .as_deref()
+                .unwrap_or(DEFAULT_COMPATIBILITY_DATE),
+            modules,
+            bindings,
+        })
+    }
+}
+
+/// Checks the host name a worker listens on, which goes into its Capfile
+/// unquoted.
+pub(crate) fn validate_host_name(host_name: &str) -> Result<(), ServerError> {
+    let valid = !host_name.is_empty()
+        && host_name.len() <= 253
+        && host_name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | ':' | '[' | ']'));
+    if !valid {
+        return Err(ServerError::InvalidHostName);
     }
-    results
+    Ok(())
 }
 
-async fn generate_worker_config(state: &AppState, mut worker: Worker) -> String {
-    worker.id = worker.id.replace("-", "");
-    let template = worker
-        .clone()
-        .template
-        .unwrap_or_else(|| DEFAULT_TEMPLATE.to_string());
-    let template_hash = get_template_hash(&template);
+/// Checks the entry module of a worker, a normalized path under `src/`.
+pub(crate) fn validate_entry(entry: &str) -> Result<(), ServerError> {
+    if safe_path(entry).as_deref() != Some(entry) {
+        return Err(ServerError::InvalidEntry);
+    }
+    Ok(())
+}
+
+/// Checks that a template compiles, so that it is rejected before it is
+/// stored.
+pub(crate) fn validate_template(template: &str) -> Result<(), ServerError> {
+    compile_template("template", template).map(|_| ())
+}
+
+fn compile_template(name: &str, template: &str) -> Result<Handlebars<'static>, ServerError> {
+    let mut handlebars = Handlebars::new();
+    // Values are either validated or quoted as Cap'n Proto literals already.
+    handlebars.register_escape_fn(handlebars::no_escape);
+    handlebars
+        .register_template_string(name, template)
+        .map_err(|err| {
+            tracing::warn!("Failed to compile template: {}", err);
+            ServerError::InvalidTemplate
+        })?;
+    Ok(handlebars)
+}
+
+/// Renders the Capfile of a worker. With `mask_secrets`, the values of
+/// secret bindings are replaced so that the result can be stored.
+pub async fn render_capfile(
+    state: &AppState,
+    worker: &Worker,
+    mask_secrets: bool,
+) -> Result<String, ServerError> {
+    let template = match (&worker.template, &worker.modules) {
+        (Some(template), _) => template.as_str(),
+        (None, Some(_)) => MODULES_TEMPLATE,
+        (None, None) => DEFAULT_TEMPLATE,
+    };
+    let template_hash = get_template_hash(template);
+
+    let context = CapfileContext::new(worker, mask_secrets)?;
 
     let mut template_cache = state.template_cache.lock().await;
-    let compiled_template = template_cache
-        .entry(template_hash.clone())
-        .or_insert_with(|| {
-            let mut handlebars = Handlebars::new();
-            handlebars
-                .register_template_string(&template_hash, &template)
-                .unwrap();
-            handlebars
-        });
+    let compiled_template = match template_cache.entry(template_hash.clone()) {
+        Entry::Occupied(entry) => entry.into_mut(),
+        Entry::Vacant(entry) => entry.insert(compile_template(&template_hash, template)?),
+    };
 
-    compiled_template.render(&template_hash, &worker).unwrap()
+    compiled_template
+        .render(&template_hash, &context)
+        .map_err(|err| {
+            tracing::warn!("Failed to render template: {}", err);
+            ServerError::InvalidTemplate
+        })
 }
 
 pub async fn get_worker_with_id(
     state: AppState,
     claims: AccessTokenClaims,
     id: String,
+    access: WorkerAccess,
 ) -> Result<Worker, ServerError> {
     let worker_in_db = Query::find_worker_by_id(&state.db, id.clone())
         .await
@@ -282,24 +668,13 @@ pub async fn get_worker_with_id(
         })?
         .ok_or(ServerError::NotFound)?;
 
-    if claims.sub != worker_in_db.user_id.to_string() && !claims.roles.contains(&RoleEnum::Admin) {
-        tracing::error!("Unauthorized access: {:?}", claims);
-        return Err(ServerError::Unauthorized);
-    }
-
-    let mut worker = Worker {
-        id,
-        host_name: worker_in_db.host_name.to_string(),
-        port: worker_in_db.port.to_string(),
-        entry: worker_in_db.entry.to_string(),
-        code: worker_in_db.code.to_string(),
-        template: worker_in_db.template.map(|id| id.to_string()),
-    };
+    authorize_worker(&state, &claims, &worker_in_db, access).await?;
 
-    worker.id = worker.id.replace("-", "");
-    Ok(worker)
+    Ok(worker_in_db.into())
 }
 
+const DEFAULT_COMPATIBILITY_DATE: &str = "2024-06-03";
+
 const DEFAULT_TEMPLATE: &str = r#"using Workerd = import "/workerd/workerd.capnp";
 
 const config :Workerd.Config = (
@@ -319,6 +694,162 @@ const config :Workerd.Config = (
 
 const worker{{id}} :Workerd.Worker = (
   serviceWorkerScript = embed "src/{{entry}}",
-  compatibilityDate = "2024-06-03",
+  compatibilityDate = "{{compatibility_date}}",
+{{#if bindings}}
+  bindings = [
+{{#each bindings}}
+    (name = "{{name}}", {{kind}} = {{{value}}}),
+{{/each}}
+  ],
+{{/if}}
 );
 "#;
+
+const MODULES_TEMPLATE: &str = r#"using Workerd = import "/workerd/workerd.capnp";
+
+const config :Workerd.Config = (
+  services = [
+    (name = "{{id}}", worker = .worker{{id}}),
+  ],
+
+  sockets = [
+    (
+      name = "{{id}}",
+      address = "{{host_name}}:{{port}}",
+      http = (),
+      service = "{{id}}"
+    ),
+  ]
+);
+
+const worker{{id}} :Workerd.Worker = (
+  modules = [
+{{#each modules}}
+    (name = "{{name}}", {{kind}} = embed "src/{{name}}"),
+{{/each}}
+  ],
+  compatibilityDate = "{{compatibility_date}}",
+{{#if bindings}}
+  bindings = [
+{{#each bindings}}
+    (name = "{{name}}", {{kind}} = {{{value}}}),
+{{/each}}
+  ],
+{{/if}}
+);
+"#;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn worker() -> Worker {
+        Worker {
+            id: "0f8e".to_string(),
+            host_name: "localhost".to_string(),
+            port: "8080".to_string(),
+            entry: "index.js".to_string(),
+            code: "export default {}".to_string(),
+            template: None,
+            modules: None,
+            bindings: BTreeMap::from([
+                (
+                    "GREETING".to_string(),
+                    Binding::Text {
+                        value: "say \"hi\"".to_string(),
+                    },
+                ),
+                (
+                    "TOKEN".to_string(),
+                    Binding::Secret {
+                        value: "hunter2".to_string(),
+                    },
+                ),
+            ]),
+            compatibility_date: Some("2024-09-01".to_string()),
+        }
+    }
+
+    fn render(template: &str, worker: &Worker, mask_secrets: bool) -> String {
+        compile_template("test", template)
+            .unwrap()
+            .render("test", &CapfileContext::new(worker, mask_secrets).unwrap())
+            .unwrap()
+    }
+
+    #[test]
+    fn test_invalid_capfile_values() {
+        let mut quoted_host = worker();
+        quoted_host.host_name = "localhost\", x = \"".to_string();
+        assert!(matches!(
+            CapfileContext::new(&quoted_host, false),
+            Err(ServerError::InvalidHostName)
+        ));
+
+        let mut quoted_entry = worker();
+        quoted_entry.entry = "index.js\"".to_string();
+        assert!(matches!(
+            CapfileContext::new(&quoted_entry, false),
+            Err(ServerError::InvalidEntry)
+        ));
+        assert!(validate_entry("../index.js").is_err());
+
+        assert!(validate_host_name("[::1]").is_ok());
+        assert!(matches!(
+            validate_template("{{#if}}"),
+            Err(ServerError::InvalidTemplate)
+        ));
+    }
+
+    #[test]
+    fn test_capnp_string() {
+        assert_eq!(capnp_string("plain"), r#""plain""#);
+        assert_eq!(capnp_string("a\"b\\c\nd\u{1}"), r#""a\"b\\c\nd\x01""#);
+    }
+
+    #[test]
+    fn test_render_bindings() {
+        let rendered = render(DEFAULT_TEMPLATE, &worker(), false);
+        assert!(rendered.contains(r#"compatibilityDate = "2024-09-01","#));
+        assert!(rendered.contains(
+            r#"
+  bindings = [
+    (name = "GREETING", text = "say \"hi\""),
+    (name = "TOKEN", text = "hunter2"),
+  ],
+"#
+        ));
+
+        let masked = render(DEFAULT_TEMPLATE, &worker(), true);
+        assert!(masked.contains(&format!(r#"(name = "TOKEN", text = "{MASKED_SECRET}"),"#)));
+        assert!(!masked.contains("hunter2"));
+    }
+
+    #[test]
+    fn test_render_modules() {
+        let mut worker = worker();
+        worker.bindings.clear();
+        worker.modules = Some(BTreeMap::from([
+            (
+                "add.wasm".to_string(),
+                Module::new(ModuleKind::Wasm, vec![0, 97, 115, 109]).unwrap(),
+            ),
+            (
+                "index.js".to_string(),
+                Module::new(ModuleKind::EsModule, b"export default {}".to_vec()).unwrap(),
+            ),
+        ]));
+
+        let rendered = render(MODULES_TEMPLATE, &worker, false);
+        assert!(rendered.contains(
+            r#"
+  modules = [
+    (name = "index.js", esModule = embed "src/index.js"),
+    (name = "add.wasm", wasm = embed "src/add.wasm"),
+  ],
+  compatibilityDate = "2024-09-01",
+);
+"#
+        ));
+    }
+}
@@ -3,10 +3,19 @@ use axum::{
     extract::{Path, State},
     Json,
 };
-use entity::{sea_orm_active_enums::RoleEnum, worker};
+use entity::worker;
 use service::workers::{Mutation, Query};
 
-use crate::{auth::AccessTokenClaims, config::AppState, errors::ServerError};
+use crate::{
+    config::AppState,
+    deployments::record_deployment,
+    errors::ServerError,
+    policy::{allows, Permission},
+    proxy::rebuild_routes,
+    scopes::{Scoped, WorkersRead, WorkersWrite},
+    teams::{authorize_worker, WorkerAccess},
+    workerd::{validate_host_name, validate_template, Worker},
+};
 
 #[derive(serde::Deserialize, serde::Serialize)]
 pub struct WorkerCreateRequest {
@@ -40,6 +49,8 @@ pub struct WorkerInfoResponse {
     pub tunnel_id: Option<String>,
     pub template: Option<String>,
     pub user_id: String,
+    pub deployment_id: Option<String>,
+    pub team_id: Option<String>,
 }
 
 #[derive(serde::Deserialize, serde::Serialize)]
@@ -50,7 +61,7 @@ pub struct MessageResponse {
 #[debug_handler]
 pub async fn create_worker(
     State(state): State<AppState>,
-    claims: AccessTokenClaims,
+    Scoped(claims, _): Scoped<WorkersWrite>,
     Json(worker): Json<WorkerCreateRequest>,
 ) -> Result<Json<MessageResponse>, ServerError> {
     Mutation::create_worker(
@@ -75,7 +86,7 @@ pub async fn create_worker(
 #[debug_handler]
 pub async fn get_worker(
     State(state): State<AppState>,
-    claims: AccessTokenClaims,
+    Scoped(claims, _): Scoped<WorkersRead>,
     Path(id): Path<String>,
 ) -> Result<Json<WorkerInfoResponse>, ServerError> {
     let worker = Query::find_worker_by_id(&state.db, id)
@@ -86,10 +97,7 @@ pub async fn get_worker(
         })?
         .ok_or(ServerError::NotFound)?;
 
-    if claims.sub != worker.user_id.to_string() && !claims.roles.contains(&RoleEnum::Admin) {
-        tracing::error!("Unauthorized access: {:?}", claims);
-        return Err(ServerError::Unauthorized);
-    }
+    authorize_worker(&state, &claims, &worker, WorkerAccess::Read).await?;
 
     Ok(Json(WorkerInfoResponse {
         id: worker.id.to_string(),
@@ -102,29 +110,29 @@ pub async fn get_worker(
         tunnel_id: worker.tunnel_id.map(|id| id.to_string()),
         template: worker.template.map(|id| id.to_string()),
         user_id: worker.user_id.to_string(),
+        deployment_id: worker.deployment_id.map(|id| id.to_string()),
+        team_id: worker.team_id.map(|id| id.to_string()),
     }))
 }
 
 #[debug_handler]
 pub async fn get_all_workers(
     State(state): State<AppState>,
-    claims: AccessTokenClaims,
+    Scoped(claims, _): Scoped<WorkersRead>,
 ) -> Result<Json<Vec<WorkerInfoResponse>>, ServerError> {
-    let workers: Vec<worker::Model>;
-
-    if claims.roles.contains(&RoleEnum::Admin) {
-        workers = Query::find_all_workers(&state.db).await.map_err(|err| {
+    let workers: Vec<worker::Model> = if allows(&claims, Permission::WorkersRead) {
+        Query::find_all_workers(&state.db).await.map_err(|err| {
             tracing::error!("Failed to get all workers: {:?}", err);
             ServerError::InternalServerError
-        })?;
+        })?
     } else {
-        workers = Query::find_user_workers_with_user_id(&state.db, claims.sub)
+        Query::find_workers_accessible_by_user_id(&state.db, claims.sub)
             .await
             .map_err(|err| {
                 tracing::error!("Failed to get all workers: {:?}", err);
                 ServerError::InternalServerError
-            })?;
-    }
+            })?
+    };
 
     Ok(Json(
         workers
@@ -140,6 +148,8 @@ pub async fn get_all_workers(
                 tunnel_id: worker.tunnel_id.map(|id| id.to_string()),
                 template: worker.template.map(|id| id.to_string()),
                 user_id: worker.user_id.to_string(),
+                deployment_id: worker.deployment_id.map(|id| id.to_string()),
+                team_id: worker.team_id.map(|id| id.to_string()),
             })
             .collect(),
     ))
@@ -148,7 +158,7 @@ pub async fn get_all_workers(
 #[debug_handler]
 pub async fn update_worker(
     State(state): State<AppState>,
-    claims: AccessTokenClaims,
+    Scoped(claims, _): Scoped<WorkersWrite>,
     Path(id): Path<String>,
     Json(worker_request): Json<WorkerUpdateRequest>,
 ) -> Result<Json<MessageResponse>, ServerError> {
@@ -160,12 +170,19 @@ pub async fn update_worker(
         })?
         .ok_or(ServerError::NotFound)?;
 
-    if claims.sub != worker.user_id.to_string() && !claims.roles.contains(&RoleEnum::Admin) {
-        tracing::error!("Unauthorized access: {:?}", claims);
-        return Err(ServerError::Unauthorized);
+    authorize_worker(&state, &claims, &worker, WorkerAccess::Write).await?;
+
+    if let Some(host_name) = &worker_request.host_name {
+        validate_host_name(host_name)?;
     }
+    if let Some(template) = &worker_request.template {
+        validate_template(template)?;
+    }
+
+    let code_replaced = worker_request.code.is_some();
+    let code_changed = code_replaced || worker_request.template.is_some();
 
-    Mutation::update_worker(
+    let updated_worker = Mutation::update_worker(
         &state.db,
         id,
         worker_request.external_path.unwrap_or(worker.external_path),
@@ -178,21 +195,30 @@ pub async fn update_worker(
         worker_request.template,
     )
     .await
-    .map(|_| {
-        Json(MessageResponse {
-            message: "Worker updated successfully".to_owned(),
-        })
-    })
     .map_err(|err| {
         tracing::error!("Failed to update worker: {:?}", err);
         ServerError::InternalServerError
-    })
+    })?;
+
+    if code_changed {
+        let mut updated_worker: Worker = updated_worker.into();
+        if code_replaced {
+            updated_worker.modules = None;
+        }
+        record_deployment(&state, claims.sub, &updated_worker, true).await?;
+    }
+
+    rebuild_routes(&state).await;
+
+    Ok(Json(MessageResponse {
+        message: "Worker updated successfully".to_owned(),
+    }))
 }
 
 #[debug_handler]
 pub async fn delete_worker(
     State(state): State<AppState>,
-    claims: AccessTokenClaims,
+    Scoped(claims, _): Scoped<WorkersWrite>,
     Path(id): Path<String>,
 ) -> Result<Json<MessageResponse>, ServerError> {
     let worker = Query::find_worker_by_id(&state.db, id.to_owned())
@@ -203,20 +229,21 @@ pub async fn delete_worker(
         })?
         .ok_or(ServerError::NotFound)?;
 
-    if claims.sub != worker.user_id.to_string() && !claims.roles.contains(&RoleEnum::Admin) {
-        tracing::error!("Unauthorized access: {:?}", claims);
-        return Err(ServerError::Unauthorized);
-    }
+    authorize_worker(&state, &claims, &worker, WorkerAccess::Write).await?;
 
     Mutation::delete_worker(&state.db, id)
         .await
-        .map(|_| {
-            Json(MessageResponse {
-                message: "Worker deleted successfully".to_owned(),
-            })
-        })
         .map_err(|err| {
             tracing::error!("Failed to delete worker: {:?}", err);
             ServerError::InternalServerError
-        })
+        })?;
+
+    rebuild_routes(&state).await;
+    let id = worker.id.to_string().replace('-', "");
+    state.proxy_metrics.remove(&id);
+    state.worker_logs.remove(&id);
+
+    Ok(Json(MessageResponse {
+        message: "Worker deleted successfully".to_owned(),
+    }))
 }
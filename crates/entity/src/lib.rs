@@ -2,6 +2,22 @@
 
 pub mod prelude;
 
+pub mod deployment;
+pub mod invitation;
+pub mod personal_access_token;
+pub mod recovery_code;
+pub mod role;
+pub mod rollout;
+pub mod schedule_run;
 pub mod sea_orm_active_enums;
+pub mod security_event;
+pub mod session;
+pub mod setting;
+pub mod team;
+pub mod team_member;
 pub mod user;
+pub mod user_identity;
+pub mod user_role;
+pub mod user_totp;
 pub mod worker;
+pub mod worker_schedule;
@@ -1,4 +1,20 @@
 //! `SeaORM` Entity, @generated by sea-orm-codegen 1.0.0
 
+pub use super::deployment::Entity as Deployment;
+pub use super::invitation::Entity as Invitation;
+pub use super::personal_access_token::Entity as PersonalAccessToken;
+pub use super::recovery_code::Entity as RecoveryCode;
+pub use super::role::Entity as Role;
+pub use super::rollout::Entity as Rollout;
+pub use super::schedule_run::Entity as ScheduleRun;
+pub use super::security_event::Entity as SecurityEvent;
+pub use super::session::Entity as Session;
+pub use super::setting::Entity as Setting;
+pub use super::team::Entity as Team;
+pub use super::team_member::Entity as TeamMember;
 pub use super::user::Entity as User;
+pub use super::user_identity::Entity as UserIdentity;
+pub use super::user_role::Entity as UserRole;
+pub use super::user_totp::Entity as UserTotp;
 pub use super::worker::Entity as Worker;
+pub use super::worker_schedule::Entity as WorkerSchedule;
@@ -11,3 +11,49 @@ pub enum RoleEnum {
     #[sea_orm(string_value = "user")]
     User,
 }
+#[derive(Debug, Clone, PartialEq, Eq, EnumIter, DeriveActiveEnum, Serialize, Deserialize)]
+#[sea_orm(
+    rs_type = "String",
+    db_type = "Enum",
+    enum_name = "rollout_status_enum"
+)]
+pub enum RolloutStatusEnum {
+    #[sea_orm(string_value = "aborted")]
+    Aborted,
+    #[sea_orm(string_value = "active")]
+    Active,
+    #[sea_orm(string_value = "promoted")]
+    Promoted,
+}
+#[derive(Debug, Clone, PartialEq, Eq, EnumIter, DeriveActiveEnum, Serialize, Deserialize)]
+#[sea_orm(rs_type = "String", db_type = "Enum", enum_name = "run_status_enum")]
+pub enum RunStatusEnum {
+    #[sea_orm(string_value = "failure")]
+    Failure,
+    #[sea_orm(string_value = "skipped")]
+    Skipped,
+    #[sea_orm(string_value = "success")]
+    Success,
+}
+#[derive(Debug, Clone, PartialEq, Eq, EnumIter, DeriveActiveEnum, Serialize, Deserialize)]
+#[sea_orm(rs_type = "String", db_type = "Enum", enum_name = "team_role_enum")]
+pub enum TeamRoleEnum {
+    #[sea_orm(string_value = "maintainer")]
+    Maintainer,
+    #[sea_orm(string_value = "owner")]
+    Owner,
+    #[sea_orm(string_value = "viewer")]
+    Viewer,
+}
+#[derive(Debug, Clone, PartialEq, Eq, EnumIter, DeriveActiveEnum, Serialize, Deserialize)]
+#[sea_orm(rs_type = "String", db_type = "Enum", enum_name = "user_status_enum")]
+pub enum UserStatusEnum {
+    #[sea_orm(string_value = "active")]
+    Active,
+    #[sea_orm(string_value = "deleted")]
+    Deleted,
+    #[sea_orm(string_value = "pending_verification")]
+    PendingVerification,
+    #[sea_orm(string_value = "suspended")]
+    Suspended,
+}
@@ -19,10 +19,35 @@ pub struct Model {
     pub tunnel_id: Option<String>,
     pub template: Option<String>,
     pub user_id: Uuid,
+    pub deployment_id: Option<Uuid>,
+    #[sea_orm(column_type = "JsonBinary", nullable)]
+    pub modules: Option<Json>,
+    #[sea_orm(column_type = "JsonBinary")]
+    pub bindings: Json,
+    pub compatibility_date: Option<String>,
+    pub team_id: Option<Uuid>,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
 pub enum Relation {
+    #[sea_orm(
+        belongs_to = "super::deployment::Entity",
+        from = "Column::DeploymentId",
+        to = "super::deployment::Column::Id",
+        on_update = "Cascade",
+        on_delete = "SetNull"
+    )]
+    Deployment,
+    #[sea_orm(has_many = "super::rollout::Entity")]
+    Rollout,
+    #[sea_orm(
+        belongs_to = "super::team::Entity",
+        from = "Column::TeamId",
+        to = "super::team::Column::Id",
+        on_update = "Cascade",
+        on_delete = "SetNull"
+    )]
+    Team,
     #[sea_orm(
         belongs_to = "super::user::Entity",
         from = "Column::UserId",
@@ -31,6 +56,20 @@ pub enum Relation {
         on_delete = "Restrict"
     )]
     User,
+    #[sea_orm(has_many = "super::worker_schedule::Entity")]
+    WorkerSchedule,
+}
+
+impl Related<super::rollout::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Rollout.def()
+    }
+}
+
+impl Related<super::team::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Team.def()
+    }
 }
 
 impl Related<super::user::Entity> for Entity {
@@ -39,4 +78,10 @@ impl Related<super::user::Entity> for Entity {
     }
 }
 
+impl Related<super::worker_schedule::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::WorkerSchedule.def()
+    }
+}
+
 impl ActiveModelBehavior for ActiveModel {}
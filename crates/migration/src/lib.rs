@@ -1,12 +1,44 @@
 pub use sea_orm_migration::prelude::*;
 
 mod m20240814_000001_create_table;
+mod m20261018_000001_create_worker_schedule_table;
+mod m20261018_000002_create_deployment_table;
+mod m20261018_000003_create_rollout_table;
+mod m20261018_000004_add_bundle_columns;
+mod m20261018_000005_create_setting_table;
+mod m20261018_000006_create_invitation_table;
+mod m20261018_000007_add_user_status_enum;
+mod m20261018_000008_create_personal_access_token_table;
+mod m20261018_000009_add_personal_access_token_scopes;
+mod m20261018_000010_create_team_tables;
+mod m20261018_000011_create_role_tables;
+mod m20261018_000012_create_session_table;
+mod m20261018_000013_add_refresh_token_families;
+mod m20261018_000014_create_user_identity_table;
+mod m20261018_000015_create_two_factor_tables;
 
 pub struct Migrator;
 
 #[async_trait::async_trait]
 impl MigratorTrait for Migrator {
     fn migrations() -> Vec<Box<dyn MigrationTrait>> {
-        vec![Box::new(m20240814_000001_create_table::Migration)]
+        vec![
+            Box::new(m20240814_000001_create_table::Migration),
+            Box::new(m20261018_000001_create_worker_schedule_table::Migration),
+            Box::new(m20261018_000002_create_deployment_table::Migration),
+            Box::new(m20261018_000003_create_rollout_table::Migration),
+            Box::new(m20261018_000004_add_bundle_columns::Migration),
+            Box::new(m20261018_000005_create_setting_table::Migration),
+            Box::new(m20261018_000006_create_invitation_table::Migration),
+            Box::new(m20261018_000007_add_user_status_enum::Migration),
+            Box::new(m20261018_000008_create_personal_access_token_table::Migration),
+            Box::new(m20261018_000009_add_personal_access_token_scopes::Migration),
+            Box::new(m20261018_000010_create_team_tables::Migration),
+            Box::new(m20261018_000011_create_role_tables::Migration),
+            Box::new(m20261018_000012_create_session_table::Migration),
+            Box::new(m20261018_000013_add_refresh_token_families::Migration),
+            Box::new(m20261018_000014_create_user_identity_table::Migration),
+            Box::new(m20261018_000015_create_two_factor_tables::Migration),
+        ]
     }
 }
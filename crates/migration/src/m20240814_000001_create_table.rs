@@ -35,9 +35,7 @@ impl MigrationTrait for Migration {
                             User::Roles,
                             ColumnType::Enum {
                                 name: SeaRc::new(RoleEnum),
-                                variants: RoleVariants::iter()
-                                    .map(|v| SeaRc::new(v))
-                                    .collect::<Vec<_>>(),
+                                variants: RoleVariants::iter().map(SeaRc::new).collect::<Vec<_>>(),
                             },
                         )
                         .default(Expr::value(r#"{user}"#)),
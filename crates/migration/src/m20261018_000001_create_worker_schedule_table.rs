@@ -0,0 +1,146 @@
+use extension::postgres::Type;
+use sea_orm::{EnumIter, Iterable};
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_type(
+                Type::create()
+                    .as_enum(RunStatusEnum)
+                    .values(RunStatusVariants::iter())
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_table(
+                Table::create()
+                    .table(WorkerSchedule::Table)
+                    .if_not_exists()
+                    .col(
+                        uuid(WorkerSchedule::Id)
+                            .primary_key()
+                            .default(Expr::cust("gen_random_uuid()")),
+                    )
+                    .col(uuid(WorkerSchedule::WorkerId))
+                    .col(string(WorkerSchedule::Cron))
+                    .col(string_null(WorkerSchedule::Path))
+                    .col(boolean(WorkerSchedule::Enabled).default(true))
+                    .col(
+                        timestamp_with_time_zone(WorkerSchedule::CreatedAt)
+                            .default(Expr::current_timestamp()),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("worker_schedule_worker_id_fkey")
+                            .from(WorkerSchedule::Table, WorkerSchedule::WorkerId)
+                            .to(Worker::Table, Worker::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_table(
+                Table::create()
+                    .table(ScheduleRun::Table)
+                    .if_not_exists()
+                    .col(
+                        uuid(ScheduleRun::Id)
+                            .primary_key()
+                            .default(Expr::cust("gen_random_uuid()")),
+                    )
+                    .col(uuid(ScheduleRun::ScheduleId))
+                    .col(timestamp_with_time_zone(ScheduleRun::StartedAt))
+                    .col(big_integer(ScheduleRun::DurationMs))
+                    .col(enumeration(
+                        ScheduleRun::Status,
+                        RunStatusEnum,
+                        RunStatusVariants::iter(),
+                    ))
+                    .col(integer_null(ScheduleRun::StatusCode))
+                    .col(text(ScheduleRun::Output).default(""))
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("schedule_run_schedule_id_fkey")
+                            .from(ScheduleRun::Table, ScheduleRun::ScheduleId)
+                            .to(WorkerSchedule::Table, WorkerSchedule::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("schedule_run_schedule_id_started_at_idx")
+                    .table(ScheduleRun::Table)
+                    .col(ScheduleRun::ScheduleId)
+                    .col(ScheduleRun::StartedAt)
+                    .to_owned(),
+            )
+            .await?;
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(ScheduleRun::Table).to_owned())
+            .await?;
+        manager
+            .drop_table(Table::drop().table(WorkerSchedule::Table).to_owned())
+            .await?;
+        manager
+            .drop_type(Type::drop().name(RunStatusEnum).to_owned())
+            .await?;
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+enum Worker {
+    Table,
+    Id,
+}
+
+#[derive(DeriveIden)]
+enum WorkerSchedule {
+    Table,
+    Id,
+    WorkerId,
+    Cron,
+    Path,
+    Enabled,
+    CreatedAt,
+}
+
+#[derive(DeriveIden)]
+enum ScheduleRun {
+    Table,
+    Id,
+    ScheduleId,
+    StartedAt,
+    DurationMs,
+    Status,
+    StatusCode,
+    Output,
+}
+
+#[derive(DeriveIden)]
+struct RunStatusEnum;
+
+#[derive(DeriveIden, EnumIter)]
+enum RunStatusVariants {
+    Success,
+    Failure,
+    Skipped,
+}
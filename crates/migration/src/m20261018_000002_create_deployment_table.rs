@@ -0,0 +1,124 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(Deployment::Table)
+                    .if_not_exists()
+                    .col(
+                        uuid(Deployment::Id)
+                            .primary_key()
+                            .default(Expr::cust("gen_random_uuid()")),
+                    )
+                    .col(uuid(Deployment::WorkerId))
+                    .col(uuid_null(Deployment::AuthorId))
+                    .col(string(Deployment::Entry))
+                    .col(text(Deployment::Code))
+                    .col(text_null(Deployment::Template))
+                    .col(text(Deployment::Capfile))
+                    .col(string(Deployment::ContentHash))
+                    .col(
+                        timestamp_with_time_zone(Deployment::CreatedAt)
+                            .default(Expr::current_timestamp()),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("deployment_worker_id_fkey")
+                            .from(Deployment::Table, Deployment::WorkerId)
+                            .to(Worker::Table, Worker::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Cascade),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("deployment_author_id_fkey")
+                            .from(Deployment::Table, Deployment::AuthorId)
+                            .to(User::Table, User::Id)
+                            .on_delete(ForeignKeyAction::SetNull)
+                            .on_update(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("deployment_worker_id_created_at_idx")
+                    .table(Deployment::Table)
+                    .col(Deployment::WorkerId)
+                    .col(Deployment::CreatedAt)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Worker::Table)
+                    .add_column(uuid_null(Worker::DeploymentId))
+                    .add_foreign_key(
+                        TableForeignKey::new()
+                            .name("worker_deployment_id_fkey")
+                            .from_tbl(Worker::Table)
+                            .from_col(Worker::DeploymentId)
+                            .to_tbl(Deployment::Table)
+                            .to_col(Deployment::Id)
+                            .on_delete(ForeignKeyAction::SetNull)
+                            .on_update(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Worker::Table)
+                    .drop_foreign_key(Alias::new("worker_deployment_id_fkey"))
+                    .drop_column(Worker::DeploymentId)
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .drop_table(Table::drop().table(Deployment::Table).to_owned())
+            .await?;
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+enum User {
+    Table,
+    Id,
+}
+
+#[derive(DeriveIden)]
+enum Worker {
+    Table,
+    Id,
+    DeploymentId,
+}
+
+#[derive(DeriveIden)]
+enum Deployment {
+    Table,
+    Id,
+    WorkerId,
+    AuthorId,
+    Entry,
+    Code,
+    Template,
+    Capfile,
+    ContentHash,
+    CreatedAt,
+}
@@ -0,0 +1,138 @@
+use extension::postgres::Type;
+use sea_orm::{EnumIter, Iterable};
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_type(
+                Type::create()
+                    .as_enum(RolloutStatusEnum)
+                    .values(RolloutStatusVariants::iter())
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_table(
+                Table::create()
+                    .table(Rollout::Table)
+                    .if_not_exists()
+                    .col(
+                        uuid(Rollout::Id)
+                            .primary_key()
+                            .default(Expr::cust("gen_random_uuid()")),
+                    )
+                    .col(uuid(Rollout::WorkerId))
+                    .col(uuid(Rollout::DeploymentId))
+                    .col(integer(Rollout::Port))
+                    .col(integer(Rollout::Percentage))
+                    .col(string_null(Rollout::HeaderName))
+                    .col(string_null(Rollout::HeaderValue))
+                    .col(string_null(Rollout::CookieName))
+                    .col(string_null(Rollout::CookieValue))
+                    .col(double(Rollout::MaxErrorRate))
+                    .col(integer(Rollout::MinRequests))
+                    .col(
+                        enumeration(
+                            Rollout::Status,
+                            RolloutStatusEnum,
+                            RolloutStatusVariants::iter(),
+                        )
+                        .default("active"),
+                    )
+                    .col(text_null(Rollout::Reason))
+                    .col(
+                        timestamp_with_time_zone(Rollout::CreatedAt)
+                            .default(Expr::current_timestamp()),
+                    )
+                    .col(timestamp_with_time_zone_null(Rollout::EndedAt))
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("rollout_worker_id_fkey")
+                            .from(Rollout::Table, Rollout::WorkerId)
+                            .to(Worker::Table, Worker::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Cascade),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("rollout_deployment_id_fkey")
+                            .from(Rollout::Table, Rollout::DeploymentId)
+                            .to(Deployment::Table, Deployment::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("rollout_worker_id_status_idx")
+                    .table(Rollout::Table)
+                    .col(Rollout::WorkerId)
+                    .col(Rollout::Status)
+                    .to_owned(),
+            )
+            .await?;
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(Rollout::Table).to_owned())
+            .await?;
+        manager
+            .drop_type(Type::drop().name(RolloutStatusEnum).to_owned())
+            .await?;
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+enum Worker {
+    Table,
+    Id,
+}
+
+#[derive(DeriveIden)]
+enum Deployment {
+    Table,
+    Id,
+}
+
+#[derive(DeriveIden)]
+enum Rollout {
+    Table,
+    Id,
+    WorkerId,
+    DeploymentId,
+    Port,
+    Percentage,
+    HeaderName,
+    HeaderValue,
+    CookieName,
+    CookieValue,
+    MaxErrorRate,
+    MinRequests,
+    Status,
+    Reason,
+    CreatedAt,
+    EndedAt,
+}
+
+#[derive(DeriveIden)]
+struct RolloutStatusEnum;
+
+#[derive(DeriveIden, EnumIter)]
+enum RolloutStatusVariants {
+    Active,
+    Promoted,
+    Aborted,
+}
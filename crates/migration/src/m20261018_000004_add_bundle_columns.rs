@@ -0,0 +1,74 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Worker::Table)
+                    .add_column(json_binary_null(Worker::Modules))
+                    .add_column(json_binary(Worker::Bindings).default(Expr::cust("'{}'::jsonb")))
+                    .add_column(string_null(Worker::CompatibilityDate))
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Deployment::Table)
+                    .add_column(json_binary_null(Deployment::Modules))
+                    .add_column(
+                        json_binary(Deployment::Bindings).default(Expr::cust("'{}'::jsonb")),
+                    )
+                    .add_column(string_null(Deployment::CompatibilityDate))
+                    .to_owned(),
+            )
+            .await?;
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Deployment::Table)
+                    .drop_column(Deployment::Modules)
+                    .drop_column(Deployment::Bindings)
+                    .drop_column(Deployment::CompatibilityDate)
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Worker::Table)
+                    .drop_column(Worker::Modules)
+                    .drop_column(Worker::Bindings)
+                    .drop_column(Worker::CompatibilityDate)
+                    .to_owned(),
+            )
+            .await?;
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+enum Worker {
+    Table,
+    Modules,
+    Bindings,
+    CompatibilityDate,
+}
+
+#[derive(DeriveIden)]
+enum Deployment {
+    Table,
+    Modules,
+    Bindings,
+    CompatibilityDate,
+}
@@ -0,0 +1,74 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(Invitation::Table)
+                    .if_not_exists()
+                    .col(
+                        uuid(Invitation::Id)
+                            .primary_key()
+                            .default(Expr::cust("gen_random_uuid()")),
+                    )
+                    .col(string(Invitation::CodeHash).unique_key())
+                    .col(string_null(Invitation::Email))
+                    .col(uuid(Invitation::CreatedBy))
+                    .col(uuid_null(Invitation::UsedBy))
+                    .col(timestamp_with_time_zone(Invitation::ExpiresAt))
+                    .col(timestamp_with_time_zone_null(Invitation::UsedAt))
+                    .col(
+                        timestamp_with_time_zone(Invitation::CreatedAt)
+                            .default(Expr::current_timestamp()),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("invitation_created_by_fkey")
+                            .from(Invitation::Table, Invitation::CreatedBy)
+                            .to(User::Table, User::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Cascade),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("invitation_used_by_fkey")
+                            .from(Invitation::Table, Invitation::UsedBy)
+                            .to(User::Table, User::Id)
+                            .on_delete(ForeignKeyAction::SetNull)
+                            .on_update(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(Invitation::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum User {
+    Table,
+    Id,
+}
+
+#[derive(DeriveIden)]
+enum Invitation {
+    Table,
+    Id,
+    CodeHash,
+    Email,
+    CreatedBy,
+    UsedBy,
+    ExpiresAt,
+    UsedAt,
+    CreatedAt,
+}
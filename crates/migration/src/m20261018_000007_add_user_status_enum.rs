@@ -0,0 +1,69 @@
+use extension::postgres::Type;
+use sea_orm::{EnumIter, Iterable};
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_type(
+                Type::create()
+                    .as_enum(UserStatusEnum)
+                    .values(UserStatusVariants::iter())
+                    .to_owned(),
+            )
+            .await?;
+
+        // The integer status was never set to anything but 0, which is kept
+        // as active. Other values follow the order of the variants.
+        manager
+            .get_connection()
+            .execute_unprepared(
+                r#"ALTER TABLE "user" ALTER COLUMN "status" DROP DEFAULT;
+ALTER TABLE "user" ALTER COLUMN "status" TYPE user_status_enum USING (CASE "status"
+    WHEN 1 THEN 'suspended'
+    WHEN 2 THEN 'pending_verification'
+    WHEN 3 THEN 'deleted'
+    ELSE 'active'
+END)::user_status_enum;
+ALTER TABLE "user" ALTER COLUMN "status" SET DEFAULT 'active';"#,
+            )
+            .await?;
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .get_connection()
+            .execute_unprepared(
+                r#"ALTER TABLE "user" ALTER COLUMN "status" DROP DEFAULT;
+ALTER TABLE "user" ALTER COLUMN "status" TYPE integer USING (CASE "status"
+    WHEN 'suspended' THEN 1
+    WHEN 'pending_verification' THEN 2
+    WHEN 'deleted' THEN 3
+    ELSE 0
+END);
+ALTER TABLE "user" ALTER COLUMN "status" SET DEFAULT 0;"#,
+            )
+            .await?;
+
+        manager
+            .drop_type(Type::drop().name(UserStatusEnum).to_owned())
+            .await?;
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+struct UserStatusEnum;
+
+#[derive(DeriveIden, EnumIter)]
+enum UserStatusVariants {
+    Active,
+    Suspended,
+    PendingVerification,
+    Deleted,
+}
@@ -0,0 +1,42 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Tokens created before scopes existed keep every scope.
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(PersonalAccessToken::Table)
+                    .add_column(
+                        array(PersonalAccessToken::Scopes, ColumnType::Text).default(Expr::cust(
+                            "ARRAY['workers:read', 'workers:write', 'workers:exec', 'users:read', 'users:write', 'users:admin']::text[]",
+                        )),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(PersonalAccessToken::Table)
+                    .drop_column(PersonalAccessToken::Scopes)
+                    .to_owned(),
+            )
+            .await?;
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+enum PersonalAccessToken {
+    Table,
+    Scopes,
+}
@@ -0,0 +1,161 @@
+use extension::postgres::Type;
+use sea_orm::{EnumIter, Iterable};
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_type(
+                Type::create()
+                    .as_enum(TeamRoleEnum)
+                    .values(TeamRoleVariants::iter())
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_table(
+                Table::create()
+                    .table(Team::Table)
+                    .if_not_exists()
+                    .col(
+                        uuid(Team::Id)
+                            .primary_key()
+                            .default(Expr::cust("gen_random_uuid()")),
+                    )
+                    .col(string(Team::Name).unique_key())
+                    .col(
+                        timestamp_with_time_zone(Team::CreatedAt)
+                            .default(Expr::current_timestamp()),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_table(
+                Table::create()
+                    .table(TeamMember::Table)
+                    .if_not_exists()
+                    .col(uuid(TeamMember::TeamId))
+                    .col(uuid(TeamMember::UserId))
+                    .col(enumeration(
+                        TeamMember::Role,
+                        TeamRoleEnum,
+                        TeamRoleVariants::iter(),
+                    ))
+                    .col(
+                        timestamp_with_time_zone(TeamMember::CreatedAt)
+                            .default(Expr::current_timestamp()),
+                    )
+                    .primary_key(
+                        Index::create()
+                            .col(TeamMember::TeamId)
+                            .col(TeamMember::UserId),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("team_member_team_id_fkey")
+                            .from(TeamMember::Table, TeamMember::TeamId)
+                            .to(Team::Table, Team::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Cascade),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("team_member_user_id_fkey")
+                            .from(TeamMember::Table, TeamMember::UserId)
+                            .to(User::Table, User::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Worker::Table)
+                    .add_column(uuid_null(Worker::TeamId))
+                    .add_foreign_key(
+                        TableForeignKey::new()
+                            .name("worker_team_id_fkey")
+                            .from_tbl(Worker::Table)
+                            .from_col(Worker::TeamId)
+                            .to_tbl(Team::Table)
+                            .to_col(Team::Id)
+                            .on_delete(ForeignKeyAction::SetNull)
+                            .on_update(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Worker::Table)
+                    .drop_foreign_key(Alias::new("worker_team_id_fkey"))
+                    .drop_column(Worker::TeamId)
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .drop_table(Table::drop().table(TeamMember::Table).to_owned())
+            .await?;
+        manager
+            .drop_table(Table::drop().table(Team::Table).to_owned())
+            .await?;
+        manager
+            .drop_type(Type::drop().name(TeamRoleEnum).to_owned())
+            .await?;
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+enum User {
+    Table,
+    Id,
+}
+
+#[derive(DeriveIden)]
+enum Worker {
+    Table,
+    TeamId,
+}
+
+#[derive(DeriveIden)]
+enum Team {
+    Table,
+    Id,
+    Name,
+    CreatedAt,
+}
+
+#[derive(DeriveIden)]
+enum TeamMember {
+    Table,
+    TeamId,
+    UserId,
+    Role,
+    CreatedAt,
+}
+
+#[derive(DeriveIden)]
+struct TeamRoleEnum;
+
+#[derive(DeriveIden, EnumIter)]
+enum TeamRoleVariants {
+    Owner,
+    Maintainer,
+    Viewer,
+}
@@ -0,0 +1,115 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(Role::Table)
+                    .if_not_exists()
+                    .col(string(Role::Name).primary_key())
+                    .col(text_null(Role::Description))
+                    .col(
+                        array(Role::Permissions, ColumnType::Text)
+                            .default(Expr::cust("'{}'::text[]")),
+                    )
+                    .col(
+                        timestamp_with_time_zone(Role::CreatedAt)
+                            .default(Expr::current_timestamp()),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_table(
+                Table::create()
+                    .table(UserRole::Table)
+                    .if_not_exists()
+                    .col(uuid(UserRole::UserId))
+                    .col(string(UserRole::RoleName))
+                    .primary_key(
+                        Index::create()
+                            .col(UserRole::UserId)
+                            .col(UserRole::RoleName),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("user_role_user_id_fkey")
+                            .from(UserRole::Table, UserRole::UserId)
+                            .to(User::Table, User::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Cascade),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("user_role_role_name_fkey")
+                            .from(UserRole::Table, UserRole::RoleName)
+                            .to(Role::Table, Role::Name)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        // Starting points that admins can change or delete.
+        manager
+            .exec_stmt(
+                Query::insert()
+                    .into_table(Role::Table)
+                    .columns([Role::Name, Role::Description, Role::Permissions])
+                    .values_panic([
+                        "operator".into(),
+                        "Starts and stops any worker, without editing it".into(),
+                        Expr::cust("ARRAY['workers.read', 'workers.operate']::text[]"),
+                    ])
+                    .values_panic([
+                        "auditor".into(),
+                        "Reads everything".into(),
+                        Expr::cust(
+                            "ARRAY['workers.read', 'users.read', 'teams.read', 'settings.read', 'roles.read']::text[]",
+                        ),
+                    ])
+                    .to_owned(),
+            )
+            .await?;
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(UserRole::Table).to_owned())
+            .await?;
+        manager
+            .drop_table(Table::drop().table(Role::Table).to_owned())
+            .await?;
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+enum User {
+    Table,
+    Id,
+}
+
+#[derive(DeriveIden)]
+enum Role {
+    Table,
+    Name,
+    Description,
+    Permissions,
+    CreatedAt,
+}
+
+#[derive(DeriveIden)]
+enum UserRole {
+    Table,
+    UserId,
+    RoleName,
+}
@@ -0,0 +1,86 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Session::Table)
+                    .add_column(uuid_null(Session::RefreshTokenId))
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_table(
+                Table::create()
+                    .table(SecurityEvent::Table)
+                    .if_not_exists()
+                    .col(
+                        uuid(SecurityEvent::Id)
+                            .primary_key()
+                            .default(Expr::cust("gen_random_uuid()")),
+                    )
+                    .col(uuid(SecurityEvent::UserId))
+                    .col(string(SecurityEvent::Kind))
+                    .col(string_null(SecurityEvent::IpAddress))
+                    .col(text_null(SecurityEvent::UserAgent))
+                    .col(
+                        timestamp_with_time_zone(SecurityEvent::CreatedAt)
+                            .default(Expr::current_timestamp()),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("security_event_user_id_fkey")
+                            .from(SecurityEvent::Table, SecurityEvent::UserId)
+                            .to(User::Table, User::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(SecurityEvent::Table).to_owned())
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Session::Table)
+                    .drop_column(Session::RefreshTokenId)
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum User {
+    Table,
+    Id,
+}
+
+#[derive(DeriveIden)]
+enum Session {
+    Table,
+    RefreshTokenId,
+}
+
+#[derive(DeriveIden)]
+enum SecurityEvent {
+    Table,
+    Id,
+    UserId,
+    Kind,
+    IpAddress,
+    UserAgent,
+    CreatedAt,
+}
@@ -0,0 +1,302 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use ::entity::deployment;
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use sea_orm::JsonValue;
+use sha2::{Digest, Sha256};
+use similar::TextDiff;
+
+/// Placeholder shown instead of the value of secret bindings.
+pub const MASKED_SECRET: &str = "********";
+
+/// The parts of a deployment that are compared by [`diff_deployments`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Snapshot {
+    /// Module name to source, as written under `src/`.
+    pub modules: BTreeMap<String, String>,
+    pub capfile: String,
+    /// Binding name to its rendered value, with secrets already masked.
+    pub bindings: BTreeMap<String, String>,
+}
+
+impl From<&deployment::Model> for Snapshot {
+    fn from(deployment: &deployment::Model) -> Self {
+        let modules = match deployment.modules.as_ref().and_then(JsonValue::as_object) {
+            Some(modules) => modules
+                .iter()
+                .map(|(name, module)| (name.clone(), render_module(module)))
+                .collect(),
+            None => BTreeMap::from([(deployment.entry.clone(), deployment.code.clone())]),
+        };
+        let bindings = deployment
+            .bindings
+            .as_object()
+            .map(|bindings| {
+                bindings
+                    .iter()
+                    .map(|(name, binding)| (name.clone(), render_binding(binding)))
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        Self {
+            modules,
+            capfile: deployment.capfile.clone(),
+            bindings,
+        }
+    }
+}
+
+/// Returns a unified diff of the code modules, rendered Capfile and bindings
+/// of two deployments. The result is empty when they are identical.
+pub fn diff_deployments(a: &deployment::Model, b: &deployment::Model) -> String {
+    diff_snapshots(&a.into(), &b.into())
+}
+
+pub fn diff_snapshots(a: &Snapshot, b: &Snapshot) -> String {
+    let mut out = String::new();
+
+    let names = a
+        .modules
+        .keys()
+        .chain(b.modules.keys())
+        .collect::<BTreeSet<_>>();
+    for name in names {
+        let path = format!("src/{name}");
+        diff_file(
+            &mut out,
+            a.modules
+                .get(name)
+                .map(|code| (path.as_str(), code.as_str())),
+            b.modules
+                .get(name)
+                .map(|code| (path.as_str(), code.as_str())),
+        );
+    }
+
+    diff_file(
+        &mut out,
+        Some(("Capfile", &a.capfile)),
+        Some(("Capfile", &b.capfile)),
+    );
+
+    let (a_bindings, b_bindings) = (render_bindings(&a.bindings), render_bindings(&b.bindings));
+    diff_file(
+        &mut out,
+        Some(("bindings", &a_bindings)),
+        Some(("bindings", &b_bindings)),
+    );
+
+    out
+}
+
+/// Renders a module stored as `{"type": "esModule", "content": "..."}`.
+/// Binary modules, whose content is base64 encoded, are summarized by their
+/// size and the sha256 of their bytes.
+fn render_module(module: &JsonValue) -> String {
+    let content = module
+        .get("content")
+        .and_then(JsonValue::as_str)
+        .unwrap_or_default();
+
+    match module.get("type").and_then(JsonValue::as_str) {
+        Some("data" | "wasm") => {
+            let bytes = BASE64
+                .decode(content)
+                .unwrap_or_else(|_| content.as_bytes().to_vec());
+            format!(
+                "Binary module, {} base64 bytes, sha256 {:x}\n",
+                content.len(),
+                Sha256::digest(bytes)
+            )
+        }
+        _ => content.to_owned(),
+    }
+}
+
+/// Renders a binding definition such as `{"type": "text", "value": "..."}`
+/// for display, replacing the value of `secret` bindings.
+pub fn render_binding(binding: &JsonValue) -> String {
+    let kind = binding
+        .get("type")
+        .and_then(JsonValue::as_str)
+        .unwrap_or("unknown");
+    let value = binding.get("value").unwrap_or(&JsonValue::Null);
+
+    if kind == "secret" {
+        format!("{kind} {MASKED_SECRET}")
+    } else {
+        format!("{kind} {value}")
+    }
+}
+
+fn render_bindings(bindings: &BTreeMap<String, String>) -> String {
+    bindings
+        .iter()
+        .map(|(name, value)| format!("{name} = {value}\n"))
+        .collect()
+}
+
+/// Appends the diff of one file, `None` standing for a missing file.
+fn diff_file(out: &mut String, old: Option<(&str, &str)>, new: Option<(&str, &str)>) {
+    let (old_path, old_text) = old.map_or(("/dev/null".to_owned(), ""), |(path, text)| {
+        (format!("a/{path}"), text)
+    });
+    let (new_path, new_text) = new.map_or(("/dev/null".to_owned(), ""), |(path, text)| {
+        (format!("b/{path}"), text)
+    });
+
+    if old.is_some() == new.is_some() && old_text == new_text {
+        return;
+    }
+
+    out.push_str(
+        &TextDiff::from_lines(old_text, new_text)
+            .unified_diff()
+            .missing_newline_hint(true)
+            .header(&old_path, &new_path)
+            .to_string(),
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn snapshot(modules: &[(&str, &str)], capfile: &str, bindings: &[(&str, &str)]) -> Snapshot {
+        Snapshot {
+            modules: modules
+                .iter()
+                .map(|(name, code)| (name.to_string(), code.to_string()))
+                .collect(),
+            capfile: capfile.to_string(),
+            bindings: bindings
+                .iter()
+                .map(|(name, value)| (name.to_string(), value.to_string()))
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_render_binding() {
+        assert_eq!(
+            render_binding(&r#"{"type": "text", "value": "prod"}"#.parse().unwrap()),
+            "text \"prod\""
+        );
+        assert_eq!(
+            render_binding(&r#"{"type": "secret", "value": "hunter2"}"#.parse().unwrap()),
+            format!("secret {MASKED_SECRET}")
+        );
+    }
+
+    #[test]
+    fn test_snapshot_from_deployment() {
+        let deployment = deployment::Model {
+            id: Default::default(),
+            worker_id: Default::default(),
+            author_id: Default::default(),
+            entry: "index.js".to_string(),
+            code: "export default {}".to_string(),
+            template: None,
+            capfile: "capfile".to_string(),
+            content_hash: "hash".to_string(),
+            created_at: "2024-01-01T00:00:00+00:00".parse().unwrap(),
+            modules: Some(
+                r#"{"index.js": {"type": "esModule", "content": "export default {}"},
+                    "add.wasm": {"type": "wasm", "content": "AGFzbQ=="}}"#
+                    .parse()
+                    .unwrap(),
+            ),
+            bindings: r#"{"TOKEN": {"type": "secret", "value": "hunter2"}}"#
+                .parse()
+                .unwrap(),
+            compatibility_date: None,
+        };
+
+        assert_eq!(
+            Snapshot::from(&deployment),
+            snapshot(
+                &[
+                    (
+                        "add.wasm",
+                        "Binary module, 8 base64 bytes, sha256 \
+                         cd5d4935a48c0672cb06407bb443bc0087aff947c6b864bac886982c73b3027f\n"
+                    ),
+                    ("index.js", "export default {}"),
+                ],
+                "capfile",
+                &[("TOKEN", "secret ********")],
+            )
+        );
+    }
+
+    #[test]
+    fn test_diff_binary_modules() {
+        let wasm = |content: &str| {
+            render_module(
+                &format!(r#"{{"type": "wasm", "content": "{content}"}}"#)
+                    .parse()
+                    .unwrap(),
+            )
+        };
+        let a = snapshot(&[("add.wasm", &wasm("AGFzbQ=="))], "capfile\n", &[]);
+        let b = snapshot(&[("add.wasm", &wasm("AGFzbg=="))], "capfile\n", &[]);
+
+        assert_eq!(
+            diff_snapshots(&a, &b),
+            "\
+--- a/src/add.wasm
++++ b/src/add.wasm
+@@ -1 +1 @@
+-Binary module, 8 base64 bytes, sha256 cd5d4935a48c0672cb06407bb443bc0087aff947c6b864bac886982c73b3027f
++Binary module, 8 base64 bytes, sha256 df4319405f3e2786bbb915274acdc169972693a0e05c6b1e9868c9993449bfa4
+"
+        );
+    }
+
+    #[test]
+    fn test_diff_identical() {
+        let a = snapshot(&[("index.js", "a\n")], "capfile\n", &[("KEY", "\"v\"")]);
+        assert_eq!(diff_snapshots(&a, &a.clone()), "");
+    }
+
+    #[test]
+    fn test_diff_snapshots() {
+        let a = snapshot(
+            &[("index.js", "one\ntwo\n"), ("old.js", "gone\n")],
+            "port = 8080\n",
+            &[("API_KEY", MASKED_SECRET), ("MODE", "\"dev\"")],
+        );
+        let b = snapshot(
+            &[("index.js", "one\nthree\n"), ("new.js", "added\n")],
+            "port = 8080\n",
+            &[("API_KEY", MASKED_SECRET), ("MODE", "\"prod\"")],
+        );
+
+        assert_eq!(
+            diff_snapshots(&a, &b),
+            "\
+--- a/src/index.js
++++ b/src/index.js
+@@ -1,2 +1,2 @@
+ one
+-two
++three
+--- /dev/null
++++ b/src/new.js
+@@ -0,0 +1 @@
++added
+--- a/src/old.js
++++ /dev/null
+@@ -1 +0,0 @@
+-gone
+--- a/bindings
++++ b/bindings
+@@ -1,2 +1,2 @@
+ API_KEY = ********
+-MODE = \"dev\"
++MODE = \"prod\"
+"
+        );
+    }
+}
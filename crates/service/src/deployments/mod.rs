@@ -0,0 +1,7 @@
+mod diff;
+pub(crate) mod mutation;
+mod query;
+
+pub use diff::{diff_deployments, diff_snapshots, render_binding, Snapshot, MASKED_SECRET};
+pub use mutation::{Mutation, NewDeployment};
+pub use query::Query;
@@ -0,0 +1,300 @@
+use ::entity::{deployment, worker};
+use prelude::Uuid;
+use sea_orm::*;
+
+/// Snapshot of a worker recorded by [`Mutation::create_deployment`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct NewDeployment {
+    pub worker_id: String,
+    pub author_id: String,
+    pub entry: String,
+    pub code: String,
+    pub template: Option<String>,
+    pub capfile: String,
+    pub content_hash: String,
+    pub modules: Option<JsonValue>,
+    pub bindings: JsonValue,
+    pub compatibility_date: Option<String>,
+}
+
+pub struct Mutation;
+
+impl Mutation {
+    /// Records a new deployment. When `live` is set, the deployment also
+    /// becomes the live version of the worker.
+    pub async fn create_deployment(
+        db: &DbConn,
+        deployment: NewDeployment,
+        live: bool,
+    ) -> Result<deployment::Model, DbErr> {
+        let txn = db.begin().await?;
+
+        let deployment = insert(&txn, deployment).await?;
+        if live {
+            activate(&txn, &deployment).await?;
+        }
+
+        txn.commit().await?;
+        Ok(deployment)
+    }
+
+    /// Makes an existing deployment the live version of its worker again.
+    pub async fn activate_deployment(
+        db: &DbConn,
+        deployment: &deployment::Model,
+    ) -> Result<worker::Model, DbErr> {
+        activate(db, deployment).await
+    }
+}
+
+pub(crate) async fn insert<C: ConnectionTrait>(
+    db: &C,
+    deployment: NewDeployment,
+) -> Result<deployment::Model, DbErr> {
+    deployment::ActiveModel {
+        worker_id: Set(Uuid::parse_str(&deployment.worker_id)
+            .map_err(|_| DbErr::Custom("Invalid UUID.".to_owned()))?),
+        author_id: Set(Some(
+            Uuid::parse_str(&deployment.author_id)
+                .map_err(|_| DbErr::Custom("Invalid UUID.".to_owned()))?,
+        )),
+        entry: Set(deployment.entry),
+        code: Set(deployment.code),
+        template: Set(deployment.template),
+        capfile: Set(deployment.capfile),
+        content_hash: Set(deployment.content_hash),
+        modules: Set(deployment.modules),
+        bindings: Set(deployment.bindings),
+        compatibility_date: Set(deployment.compatibility_date),
+        ..Default::default()
+    }
+    .insert(db)
+    .await
+}
+
+pub(crate) async fn activate<C: ConnectionTrait>(
+    db: &C,
+    deployment: &deployment::Model,
+) -> Result<worker::Model, DbErr> {
+    worker::ActiveModel {
+        id: Unchanged(deployment.worker_id),
+        entry: Set(deployment.entry.clone()),
+        code: Set(deployment.code.clone()),
+        template: Set(deployment.template.clone()),
+        deployment_id: Set(Some(deployment.id)),
+        modules: Set(deployment.modules.clone()),
+        bindings: Set(deployment.bindings.clone()),
+        compatibility_date: Set(deployment.compatibility_date.clone()),
+        ..Default::default()
+    }
+    .update(db)
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn create_deployment_with_id(id: &str) -> deployment::Model {
+        deployment::Model {
+            id: Uuid::parse_str(id).unwrap(),
+            worker_id: Uuid::parse_str("00000000-0000-0000-0000-000000000000").unwrap(),
+            author_id: Some(Uuid::parse_str("00000000-0000-0000-0000-000000000000").unwrap()),
+            entry: "entry.js".to_string(),
+            code: "export default {}".to_string(),
+            template: None,
+            capfile: "".to_string(),
+            content_hash: "hash".to_string(),
+            created_at: "2024-01-01T00:00:00+00:00".parse().unwrap(),
+            modules: None,
+            bindings: JsonValue::Object(Default::default()),
+            compatibility_date: None,
+        }
+    }
+
+    fn new_deployment() -> NewDeployment {
+        NewDeployment {
+            worker_id: "00000000-0000-0000-0000-000000000000".to_string(),
+            author_id: "00000000-0000-0000-0000-000000000000".to_string(),
+            entry: "entry.js".to_string(),
+            code: "export default {}".to_string(),
+            template: None,
+            capfile: "".to_string(),
+            content_hash: "hash".to_string(),
+            modules: None,
+            bindings: JsonValue::Object(Default::default()),
+            compatibility_date: None,
+        }
+    }
+
+    fn create_worker_with_deployment_id(deployment_id: &str) -> worker::Model {
+        worker::Model {
+            id: Uuid::parse_str("00000000-0000-0000-0000-000000000000").unwrap(),
+            external_path: "/".to_string(),
+            host_name: "localhost".to_string(),
+            node_name: "default".to_string(),
+            port: 80,
+            entry: "entry.js".to_string(),
+            code: "export default {}".to_string(),
+            name: "Test".to_string(),
+            tunnel_id: None,
+            template: None,
+            user_id: Uuid::parse_str("00000000-0000-0000-0000-000000000000").unwrap(),
+            deployment_id: Some(Uuid::parse_str(deployment_id).unwrap()),
+            modules: None,
+            bindings: JsonValue::Object(Default::default()),
+            compatibility_date: None,
+            team_id: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_create_deployment() {
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([[create_deployment_with_id(
+                "00000000-0000-0000-0000-000000000001",
+            )]])
+            .append_query_results([[create_worker_with_deployment_id(
+                "00000000-0000-0000-0000-000000000001",
+            )]])
+            .into_connection();
+
+        assert_eq!(
+            Mutation::create_deployment(&db, new_deployment(), true)
+                .await
+                .unwrap(),
+            create_deployment_with_id("00000000-0000-0000-0000-000000000001")
+        );
+
+        assert_eq!(
+            db.into_transaction_log(),
+            [Transaction::many([
+                Statement::from_string(DatabaseBackend::Postgres, "BEGIN"),
+                Statement::from_sql_and_values(
+                    DatabaseBackend::Postgres,
+                    r#"INSERT INTO "deployment" ("worker_id", "author_id", "entry", "code", "template", "capfile", "content_hash", "modules", "bindings", "compatibility_date") VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10) RETURNING "id", "worker_id", "author_id", "entry", "code", "template", "capfile", "content_hash", "created_at", "modules", "bindings", "compatibility_date""#,
+                    [
+                        Uuid::parse_str("00000000-0000-0000-0000-000000000000")
+                            .unwrap()
+                            .into(),
+                        Uuid::parse_str("00000000-0000-0000-0000-000000000000")
+                            .unwrap()
+                            .into(),
+                        "entry.js".into(),
+                        "export default {}".into(),
+                        Option::<String>::None.into(),
+                        "".into(),
+                        "hash".into(),
+                        Option::<JsonValue>::None.into(),
+                        JsonValue::Object(Default::default()).into(),
+                        Option::<String>::None.into(),
+                    ]
+                ),
+                Statement::from_sql_and_values(
+                    DatabaseBackend::Postgres,
+                    r#"UPDATE "worker" SET "entry" = $1, "code" = $2, "template" = $3, "deployment_id" = $4, "modules" = $5, "bindings" = $6, "compatibility_date" = $7 WHERE "worker"."id" = $8 RETURNING "id", "external_path", "host_name", "node_name", "port", "entry", "code", "name", "tunnel_id", "template", "user_id", "deployment_id", "modules", "bindings", "compatibility_date", "team_id""#,
+                    [
+                        "entry.js".into(),
+                        "export default {}".into(),
+                        Option::<String>::None.into(),
+                        Uuid::parse_str("00000000-0000-0000-0000-000000000001")
+                            .unwrap()
+                            .into(),
+                        Option::<JsonValue>::None.into(),
+                        JsonValue::Object(Default::default()).into(),
+                        Option::<String>::None.into(),
+                        Uuid::parse_str("00000000-0000-0000-0000-000000000000")
+                            .unwrap()
+                            .into(),
+                    ]
+                ),
+                Statement::from_string(DatabaseBackend::Postgres, "COMMIT"),
+            ])]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_create_deployment_not_live() {
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([[create_deployment_with_id(
+                "00000000-0000-0000-0000-000000000001",
+            )]])
+            .into_connection();
+
+        assert_eq!(
+            Mutation::create_deployment(&db, new_deployment(), false)
+                .await
+                .unwrap(),
+            create_deployment_with_id("00000000-0000-0000-0000-000000000001")
+        );
+
+        assert_eq!(
+            db.into_transaction_log(),
+            [Transaction::many([
+                Statement::from_string(DatabaseBackend::Postgres, "BEGIN"),
+                Statement::from_sql_and_values(
+                    DatabaseBackend::Postgres,
+                    r#"INSERT INTO "deployment" ("worker_id", "author_id", "entry", "code", "template", "capfile", "content_hash", "modules", "bindings", "compatibility_date") VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10) RETURNING "id", "worker_id", "author_id", "entry", "code", "template", "capfile", "content_hash", "created_at", "modules", "bindings", "compatibility_date""#,
+                    [
+                        Uuid::parse_str("00000000-0000-0000-0000-000000000000")
+                            .unwrap()
+                            .into(),
+                        Uuid::parse_str("00000000-0000-0000-0000-000000000000")
+                            .unwrap()
+                            .into(),
+                        "entry.js".into(),
+                        "export default {}".into(),
+                        Option::<String>::None.into(),
+                        "".into(),
+                        "hash".into(),
+                        Option::<JsonValue>::None.into(),
+                        JsonValue::Object(Default::default()).into(),
+                        Option::<String>::None.into(),
+                    ]
+                ),
+                Statement::from_string(DatabaseBackend::Postgres, "COMMIT"),
+            ])]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_activate_deployment() {
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([[create_worker_with_deployment_id(
+                "00000000-0000-0000-0000-000000000001",
+            )]])
+            .into_connection();
+
+        assert_eq!(
+            Mutation::activate_deployment(
+                &db,
+                &create_deployment_with_id("00000000-0000-0000-0000-000000000001")
+            )
+            .await
+            .unwrap(),
+            create_worker_with_deployment_id("00000000-0000-0000-0000-000000000001")
+        );
+
+        assert_eq!(
+            db.into_transaction_log(),
+            [Transaction::from_sql_and_values(
+                DatabaseBackend::Postgres,
+                r#"UPDATE "worker" SET "entry" = $1, "code" = $2, "template" = $3, "deployment_id" = $4, "modules" = $5, "bindings" = $6, "compatibility_date" = $7 WHERE "worker"."id" = $8 RETURNING "id", "external_path", "host_name", "node_name", "port", "entry", "code", "name", "tunnel_id", "template", "user_id", "deployment_id", "modules", "bindings", "compatibility_date", "team_id""#,
+                [
+                    "entry.js".into(),
+                    "export default {}".into(),
+                    Option::<String>::None.into(),
+                    Uuid::parse_str("00000000-0000-0000-0000-000000000001")
+                        .unwrap()
+                        .into(),
+                    Option::<JsonValue>::None.into(),
+                    JsonValue::Object(Default::default()).into(),
+                    Option::<String>::None.into(),
+                    Uuid::parse_str("00000000-0000-0000-0000-000000000000")
+                        .unwrap()
+                        .into(),
+                ]
+            )]
+        );
+    }
+}
@@ -0,0 +1,118 @@
+use ::entity::{deployment, deployment::Entity as Deployment};
+use prelude::Uuid;
+use sea_orm::*;
+
+pub struct Query;
+
+impl Query {
+    pub async fn find_deployment_by_id(
+        db: &DbConn,
+        id: String,
+    ) -> Result<Option<deployment::Model>, DbErr> {
+        let uuid = Uuid::parse_str(&id).map_err(|_| DbErr::Custom("Invalid UUID.".to_owned()))?;
+
+        Deployment::find_by_id(uuid).one(db).await
+    }
+
+    pub async fn find_deployments_by_worker_id(
+        db: &DbConn,
+        worker_id: String,
+    ) -> Result<Vec<deployment::Model>, DbErr> {
+        let uuid =
+            Uuid::parse_str(&worker_id).map_err(|_| DbErr::Custom("Invalid UUID.".to_owned()))?;
+
+        Deployment::find()
+            .filter(deployment::Column::WorkerId.eq(uuid))
+            .order_by_desc(deployment::Column::CreatedAt)
+            .all(db)
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn create_deployment_with_id(id: &str) -> deployment::Model {
+        deployment::Model {
+            id: Uuid::parse_str(id).unwrap(),
+            worker_id: Uuid::parse_str("00000000-0000-0000-0000-000000000000").unwrap(),
+            author_id: Some(Uuid::parse_str("00000000-0000-0000-0000-000000000000").unwrap()),
+            entry: "entry.js".to_string(),
+            code: "export default {}".to_string(),
+            template: None,
+            capfile: "".to_string(),
+            content_hash: "hash".to_string(),
+            created_at: "2024-01-01T00:00:00+00:00".parse().unwrap(),
+            modules: None,
+            bindings: JsonValue::Object(Default::default()),
+            compatibility_date: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_find_deployment_by_id() {
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([[create_deployment_with_id(
+                "00000000-0000-0000-0000-000000000001",
+            )]])
+            .into_connection();
+
+        assert_eq!(
+            Query::find_deployment_by_id(&db, "00000000-0000-0000-0000-000000000001".to_string())
+                .await
+                .unwrap(),
+            Some(create_deployment_with_id(
+                "00000000-0000-0000-0000-000000000001"
+            ))
+        );
+
+        assert_eq!(
+            db.into_transaction_log(),
+            [Transaction::from_sql_and_values(
+                DatabaseBackend::Postgres,
+                r#"SELECT "deployment"."id", "deployment"."worker_id", "deployment"."author_id", "deployment"."entry", "deployment"."code", "deployment"."template", "deployment"."capfile", "deployment"."content_hash", "deployment"."created_at", "deployment"."modules", "deployment"."bindings", "deployment"."compatibility_date" FROM "deployment" WHERE "deployment"."id" = $1 LIMIT $2"#,
+                [
+                    Uuid::parse_str("00000000-0000-0000-0000-000000000001")
+                        .unwrap()
+                        .into(),
+                    1u64.into()
+                ]
+            )]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_find_deployments_by_worker_id() {
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([[
+                create_deployment_with_id("00000000-0000-0000-0000-000000000002"),
+                create_deployment_with_id("00000000-0000-0000-0000-000000000001"),
+            ]])
+            .into_connection();
+
+        assert_eq!(
+            Query::find_deployments_by_worker_id(
+                &db,
+                "00000000-0000-0000-0000-000000000000".to_string()
+            )
+            .await
+            .unwrap(),
+            [
+                create_deployment_with_id("00000000-0000-0000-0000-000000000002"),
+                create_deployment_with_id("00000000-0000-0000-0000-000000000001"),
+            ]
+        );
+
+        assert_eq!(
+            db.into_transaction_log(),
+            [Transaction::from_sql_and_values(
+                DatabaseBackend::Postgres,
+                r#"SELECT "deployment"."id", "deployment"."worker_id", "deployment"."author_id", "deployment"."entry", "deployment"."code", "deployment"."template", "deployment"."capfile", "deployment"."content_hash", "deployment"."created_at", "deployment"."modules", "deployment"."bindings", "deployment"."compatibility_date" FROM "deployment" WHERE "deployment"."worker_id" = $1 ORDER BY "deployment"."created_at" DESC"#,
+                [Uuid::parse_str("00000000-0000-0000-0000-000000000000")
+                    .unwrap()
+                    .into()]
+            )]
+        );
+    }
+}
@@ -0,0 +1,159 @@
+use ::entity::{user, user_identity};
+use prelude::Uuid;
+use sea_orm::*;
+
+pub struct Mutation;
+
+impl Mutation {
+    /// Links an identity of an external issuer to an existing user.
+    pub async fn create_identity(
+        db: &DbConn,
+        user_id: Uuid,
+        issuer: String,
+        subject: String,
+    ) -> Result<user_identity::Model, DbErr> {
+        user_identity::ActiveModel {
+            user_id: Set(user_id),
+            issuer: Set(issuer),
+            subject: Set(subject),
+            ..Default::default()
+        }
+        .insert(db)
+        .await
+    }
+
+    /// Creates a user along with its identity at an external issuer.
+    pub async fn create_user_with_identity(
+        db: &DbConn,
+        email: String,
+        username: String,
+        password: String,
+        issuer: String,
+        subject: String,
+    ) -> Result<user::Model, DbErr> {
+        let txn = db.begin().await?;
+
+        let user = user::ActiveModel {
+            email: Set(email),
+            password: Set(password),
+            username: Set(username),
+            ..Default::default()
+        }
+        .insert(&txn)
+        .await?;
+
+        user_identity::ActiveModel {
+            user_id: Set(user.id),
+            issuer: Set(issuer),
+            subject: Set(subject),
+            ..Default::default()
+        }
+        .insert(&txn)
+        .await?;
+
+        txn.commit().await?;
+        Ok(user)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ::entity::sea_orm_active_enums::{RoleEnum, UserStatusEnum};
+
+    use super::*;
+
+    fn create_identity_for(user_id: Uuid) -> user_identity::Model {
+        user_identity::Model {
+            id: Uuid::parse_str("00000000-0000-0000-0000-000000000001").unwrap(),
+            user_id,
+            issuer: "https://idp.example.com".to_owned(),
+            subject: "248289761001".to_owned(),
+            created_at: "2024-01-01T00:00:00+00:00".parse().unwrap(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_create_identity() {
+        let user_id = Uuid::parse_str("00000000-0000-0000-0000-000000000000").unwrap();
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([[create_identity_for(user_id)]])
+            .into_connection();
+
+        assert_eq!(
+            Mutation::create_identity(
+                &db,
+                user_id,
+                "https://idp.example.com".to_owned(),
+                "248289761001".to_owned(),
+            )
+            .await
+            .expect("Failed to create identity"),
+            create_identity_for(user_id)
+        );
+
+        assert_eq!(
+            db.into_transaction_log(),
+            [Transaction::from_sql_and_values(
+                DatabaseBackend::Postgres,
+                r#"INSERT INTO "user_identity" ("user_id", "issuer", "subject") VALUES ($1, $2, $3) RETURNING "id", "user_id", "issuer", "subject", "created_at""#,
+                [
+                    user_id.into(),
+                    "https://idp.example.com".into(),
+                    "248289761001".into()
+                ]
+            )]
+        )
+    }
+
+    #[tokio::test]
+    async fn test_create_user_with_identity() {
+        let user = user::Model {
+            id: Uuid::parse_str("00000000-0000-0000-0000-000000000000").unwrap(),
+            email: "test@example.com".to_owned(),
+            username: "test".to_owned(),
+            password: "password".to_owned(),
+            roles: vec![RoleEnum::User],
+            status: UserStatusEnum::Active,
+        };
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([[user.to_owned()]])
+            .append_query_results([[create_identity_for(user.id)]])
+            .into_connection();
+
+        assert_eq!(
+            Mutation::create_user_with_identity(
+                &db,
+                "test@example.com".to_owned(),
+                "test".to_owned(),
+                "password".to_owned(),
+                "https://idp.example.com".to_owned(),
+                "248289761001".to_owned(),
+            )
+            .await
+            .expect("Failed to create user"),
+            user
+        );
+
+        assert_eq!(
+            db.into_transaction_log(),
+            [Transaction::many([
+                Statement::from_string(DatabaseBackend::Postgres, "BEGIN"),
+                Statement::from_sql_and_values(
+                    DatabaseBackend::Postgres,
+                    r#"INSERT INTO "user" ("email", "username", "password") VALUES ($1, $2, $3) RETURNING "id", "email", "username", "password", CAST("roles" AS text[]), CAST("status" AS text)"#,
+                    ["test@example.com".into(), "test".into(), "password".into()]
+                ),
+                Statement::from_sql_and_values(
+                    DatabaseBackend::Postgres,
+                    r#"INSERT INTO "user_identity" ("user_id", "issuer", "subject") VALUES ($1, $2, $3) RETURNING "id", "user_id", "issuer", "subject", "created_at""#,
+                    [
+                        user.id.into(),
+                        "https://idp.example.com".into(),
+                        "248289761001".into()
+                    ]
+                ),
+                Statement::from_string(DatabaseBackend::Postgres, "COMMIT"),
+            ])]
+        )
+    }
+}
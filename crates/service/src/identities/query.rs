@@ -0,0 +1,62 @@
+use ::entity::{user_identity, user_identity::Entity as UserIdentity};
+use sea_orm::*;
+
+pub struct Query;
+
+impl Query {
+    pub async fn find_identity(
+        db: &DbConn,
+        issuer: String,
+        subject: String,
+    ) -> Result<Option<user_identity::Model>, DbErr> {
+        UserIdentity::find()
+            .filter(user_identity::Column::Issuer.eq(issuer))
+            .filter(user_identity::Column::Subject.eq(subject))
+            .one(db)
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use prelude::Uuid;
+
+    #[tokio::test]
+    async fn test_find_identity() {
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([[user_identity::Model {
+                id: Uuid::parse_str("00000000-0000-0000-0000-000000000001").unwrap(),
+                user_id: Uuid::parse_str("00000000-0000-0000-0000-000000000000").unwrap(),
+                issuer: "https://idp.example.com".to_owned(),
+                subject: "248289761001".to_owned(),
+                created_at: "2024-01-01T00:00:00+00:00".parse().unwrap(),
+            }]])
+            .into_connection();
+
+        {
+            let identity = Query::find_identity(
+                &db,
+                "https://idp.example.com".to_owned(),
+                "248289761001".to_owned(),
+            )
+            .await
+            .expect("Failed to find identity");
+
+            assert!(identity.is_some());
+        }
+
+        assert_eq!(
+            db.into_transaction_log(),
+            [Transaction::from_sql_and_values(
+                DatabaseBackend::Postgres,
+                r#"SELECT "user_identity"."id", "user_identity"."user_id", "user_identity"."issuer", "user_identity"."subject", "user_identity"."created_at" FROM "user_identity" WHERE "user_identity"."issuer" = $1 AND "user_identity"."subject" = $2 LIMIT $3"#,
+                [
+                    "https://idp.example.com".into(),
+                    "248289761001".into(),
+                    1u64.into()
+                ]
+            )]
+        )
+    }
+}
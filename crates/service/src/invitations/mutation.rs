@@ -0,0 +1,278 @@
+use ::entity::{invitation, invitation::Entity as Invitation, user};
+use prelude::{DateTimeWithTimeZone, Uuid};
+use sea_orm::{sea_query::Expr, *};
+
+pub struct Mutation;
+
+impl Mutation {
+    pub async fn create_invitation(
+        db: &DbConn,
+        code_hash: String,
+        email: Option<String>,
+        created_by: String,
+        expires_at: DateTimeWithTimeZone,
+    ) -> Result<invitation::Model, DbErr> {
+        invitation::ActiveModel {
+            code_hash: Set(code_hash),
+            email: Set(email),
+            created_by: Set(Uuid::parse_str(&created_by)
+                .map_err(|_| DbErr::Custom("Invalid UUID.".to_owned()))?),
+            expires_at: Set(expires_at),
+            ..Default::default()
+        }
+        .insert(db)
+        .await
+    }
+
+    pub async fn delete_invitation(db: &DbConn, id: String) -> Result<DeleteResult, DbErr> {
+        let uuid = Uuid::parse_str(&id).map_err(|_| DbErr::Custom("Invalid UUID.".to_owned()))?;
+
+        Invitation::delete_by_id(uuid).exec(db).await
+    }
+
+    /// Creates a user and marks the invitation as used by it. Returns `None`
+    /// without creating the user when the invitation is used or expired.
+    pub async fn redeem_invitation(
+        db: &DbConn,
+        invitation_id: Uuid,
+        now: DateTimeWithTimeZone,
+        email: String,
+        username: String,
+        password: String,
+    ) -> Result<Option<user::Model>, DbErr> {
+        let txn = db.begin().await?;
+
+        let user = user::ActiveModel {
+            email: Set(email),
+            password: Set(password),
+            username: Set(username),
+            ..Default::default()
+        }
+        .insert(&txn)
+        .await?;
+
+        // Checked again here, so that concurrent sign-ups cannot share a code.
+        let result = Invitation::update_many()
+            .col_expr(invitation::Column::UsedBy, Expr::value(user.id))
+            .col_expr(invitation::Column::UsedAt, Expr::value(now))
+            .filter(invitation::Column::Id.eq(invitation_id))
+            .filter(invitation::Column::UsedAt.is_null())
+            .filter(invitation::Column::ExpiresAt.gt(now))
+            .exec(&txn)
+            .await?;
+        if result.rows_affected == 0 {
+            txn.rollback().await?;
+            return Ok(None);
+        }
+
+        txn.commit().await?;
+        Ok(Some(user))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ::entity::sea_orm_active_enums::{RoleEnum, UserStatusEnum};
+
+    use super::*;
+
+    fn create_invitation_with_id(id: &str) -> invitation::Model {
+        invitation::Model {
+            id: Uuid::parse_str(id).unwrap(),
+            code_hash: "hash".to_owned(),
+            email: Some("test@example.com".to_owned()),
+            created_by: Uuid::parse_str("00000000-0000-0000-0000-000000000000").unwrap(),
+            used_by: None,
+            expires_at: "2024-01-04T00:00:00+00:00".parse().unwrap(),
+            used_at: None,
+            created_at: "2024-01-01T00:00:00+00:00".parse().unwrap(),
+        }
+    }
+
+    fn create_user_with_id(id: &str) -> user::Model {
+        user::Model {
+            id: Uuid::parse_str(id).unwrap(),
+            email: "test@example.com".to_owned(),
+            username: "Test".to_owned(),
+            password: "password".to_owned(),
+            roles: vec![RoleEnum::User],
+            status: UserStatusEnum::Active,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_create_invitation() {
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([[create_invitation_with_id(
+                "00000000-0000-0000-0000-000000000001",
+            )]])
+            .into_connection();
+
+        let expires_at: DateTimeWithTimeZone = "2024-01-04T00:00:00+00:00".parse().unwrap();
+        {
+            let invitation = Mutation::create_invitation(
+                &db,
+                "hash".to_owned(),
+                Some("test@example.com".to_owned()),
+                "00000000-0000-0000-0000-000000000000".to_owned(),
+                expires_at,
+            )
+            .await
+            .expect("Failed to create invitation");
+
+            assert_eq!(
+                invitation,
+                create_invitation_with_id("00000000-0000-0000-0000-000000000001")
+            );
+        }
+
+        assert_eq!(
+            db.into_transaction_log(),
+            [Transaction::from_sql_and_values(
+                DatabaseBackend::Postgres,
+                r#"INSERT INTO "invitation" ("code_hash", "email", "created_by", "expires_at") VALUES ($1, $2, $3, $4) RETURNING "id", "code_hash", "email", "created_by", "used_by", "expires_at", "used_at", "created_at""#,
+                [
+                    "hash".into(),
+                    Some("test@example.com".to_owned()).into(),
+                    Uuid::parse_str("00000000-0000-0000-0000-000000000000")
+                        .unwrap()
+                        .into(),
+                    expires_at.into(),
+                ]
+            )]
+        )
+    }
+
+    #[tokio::test]
+    async fn test_delete_invitation() {
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_exec_results([MockExecResult {
+                last_insert_id: 0,
+                rows_affected: 1,
+            }])
+            .into_connection();
+
+        {
+            let result =
+                Mutation::delete_invitation(&db, "00000000-0000-0000-0000-000000000001".to_owned())
+                    .await
+                    .expect("Failed to delete invitation");
+
+            assert_eq!(result.rows_affected, 1);
+        }
+
+        assert_eq!(
+            db.into_transaction_log(),
+            [Transaction::from_sql_and_values(
+                DatabaseBackend::Postgres,
+                r#"DELETE FROM "invitation" WHERE "invitation"."id" = $1"#,
+                [Uuid::parse_str("00000000-0000-0000-0000-000000000001")
+                    .unwrap()
+                    .into()]
+            )]
+        )
+    }
+
+    #[tokio::test]
+    async fn test_redeem_invitation() {
+        let now: DateTimeWithTimeZone = "2024-01-02T00:00:00+00:00".parse().unwrap();
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([
+                [create_user_with_id("00000000-0000-0000-0000-000000000002")],
+                [create_user_with_id("00000000-0000-0000-0000-000000000003")],
+            ])
+            .append_exec_results([
+                MockExecResult {
+                    last_insert_id: 0,
+                    rows_affected: 1,
+                },
+                MockExecResult {
+                    last_insert_id: 0,
+                    rows_affected: 0,
+                },
+            ])
+            .into_connection();
+
+        let invitation_id = Uuid::parse_str("00000000-0000-0000-0000-000000000001").unwrap();
+        {
+            let user = Mutation::redeem_invitation(
+                &db,
+                invitation_id,
+                now,
+                "test@example.com".to_owned(),
+                "Test".to_owned(),
+                "password".to_owned(),
+            )
+            .await
+            .expect("Failed to redeem invitation");
+            assert_eq!(
+                user,
+                Some(create_user_with_id("00000000-0000-0000-0000-000000000002"))
+            );
+
+            let user = Mutation::redeem_invitation(
+                &db,
+                invitation_id,
+                now,
+                "other@example.com".to_owned(),
+                "Other".to_owned(),
+                "password".to_owned(),
+            )
+            .await
+            .expect("Failed to redeem invitation");
+            assert_eq!(user, None);
+        }
+
+        assert_eq!(
+            db.into_transaction_log(),
+            [
+                Transaction::many([
+                    Statement::from_string(DatabaseBackend::Postgres, "BEGIN"),
+                    Statement::from_sql_and_values(
+                        DatabaseBackend::Postgres,
+                        r#"INSERT INTO "user" ("email", "username", "password") VALUES ($1, $2, $3) RETURNING "id", "email", "username", "password", CAST("roles" AS text[]), CAST("status" AS text)"#,
+                        ["test@example.com".into(), "Test".into(), "password".into()]
+                    ),
+                    Statement::from_sql_and_values(
+                        DatabaseBackend::Postgres,
+                        r#"UPDATE "invitation" SET "used_by" = $1, "used_at" = $2 WHERE "invitation"."id" = $3 AND "invitation"."used_at" IS NULL AND "invitation"."expires_at" > $4"#,
+                        [
+                            Uuid::parse_str("00000000-0000-0000-0000-000000000002")
+                                .unwrap()
+                                .into(),
+                            now.into(),
+                            invitation_id.into(),
+                            now.into(),
+                        ]
+                    ),
+                    Statement::from_string(DatabaseBackend::Postgres, "COMMIT"),
+                ]),
+                Transaction::many([
+                    Statement::from_string(DatabaseBackend::Postgres, "BEGIN"),
+                    Statement::from_sql_and_values(
+                        DatabaseBackend::Postgres,
+                        r#"INSERT INTO "user" ("email", "username", "password") VALUES ($1, $2, $3) RETURNING "id", "email", "username", "password", CAST("roles" AS text[]), CAST("status" AS text)"#,
+                        [
+                            "other@example.com".into(),
+                            "Other".into(),
+                            "password".into()
+                        ]
+                    ),
+                    Statement::from_sql_and_values(
+                        DatabaseBackend::Postgres,
+                        r#"UPDATE "invitation" SET "used_by" = $1, "used_at" = $2 WHERE "invitation"."id" = $3 AND "invitation"."used_at" IS NULL AND "invitation"."expires_at" > $4"#,
+                        [
+                            Uuid::parse_str("00000000-0000-0000-0000-000000000003")
+                                .unwrap()
+                                .into(),
+                            now.into(),
+                            invitation_id.into(),
+                            now.into(),
+                        ]
+                    ),
+                    Statement::from_string(DatabaseBackend::Postgres, "ROLLBACK"),
+                ]),
+            ]
+        )
+    }
+}
@@ -0,0 +1,97 @@
+use ::entity::{invitation, invitation::Entity as Invitation};
+use sea_orm::*;
+
+pub struct Query;
+
+impl Query {
+    pub async fn find_invitation_by_code_hash(
+        db: &DbConn,
+        code_hash: String,
+    ) -> Result<Option<invitation::Model>, DbErr> {
+        Invitation::find()
+            .filter(invitation::Column::CodeHash.eq(code_hash))
+            .one(db)
+            .await
+    }
+
+    pub async fn find_all_invitations(db: &DbConn) -> Result<Vec<invitation::Model>, DbErr> {
+        Invitation::find()
+            .order_by_desc(invitation::Column::CreatedAt)
+            .all(db)
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use prelude::Uuid;
+
+    use super::*;
+
+    fn create_invitation_with_id(id: &str) -> invitation::Model {
+        invitation::Model {
+            id: Uuid::parse_str(id).unwrap(),
+            code_hash: "hash".to_owned(),
+            email: None,
+            created_by: Uuid::parse_str("00000000-0000-0000-0000-000000000000").unwrap(),
+            used_by: None,
+            expires_at: "2024-01-04T00:00:00+00:00".parse().unwrap(),
+            used_at: None,
+            created_at: "2024-01-01T00:00:00+00:00".parse().unwrap(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_find_invitation_by_code_hash() {
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([[create_invitation_with_id(
+                "00000000-0000-0000-0000-000000000001",
+            )]])
+            .into_connection();
+
+        {
+            let invitation = Query::find_invitation_by_code_hash(&db, "hash".to_owned())
+                .await
+                .expect("Failed to find invitation")
+                .expect("Invitation not found");
+
+            assert_eq!(invitation.code_hash, "hash");
+        }
+
+        assert_eq!(
+            db.into_transaction_log(),
+            [Transaction::from_sql_and_values(
+                DatabaseBackend::Postgres,
+                r#"SELECT "invitation"."id", "invitation"."code_hash", "invitation"."email", "invitation"."created_by", "invitation"."used_by", "invitation"."expires_at", "invitation"."used_at", "invitation"."created_at" FROM "invitation" WHERE "invitation"."code_hash" = $1 LIMIT $2"#,
+                ["hash".into(), 1u64.into()]
+            )]
+        )
+    }
+
+    #[tokio::test]
+    async fn test_find_all_invitations() {
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([[
+                create_invitation_with_id("00000000-0000-0000-0000-000000000001"),
+                create_invitation_with_id("00000000-0000-0000-0000-000000000002"),
+            ]])
+            .into_connection();
+
+        {
+            let invitations = Query::find_all_invitations(&db)
+                .await
+                .expect("Failed to find invitations");
+
+            assert_eq!(invitations.len(), 2);
+        }
+
+        assert_eq!(
+            db.into_transaction_log(),
+            [Transaction::from_sql_and_values(
+                DatabaseBackend::Postgres,
+                r#"SELECT "invitation"."id", "invitation"."code_hash", "invitation"."email", "invitation"."created_by", "invitation"."used_by", "invitation"."expires_at", "invitation"."used_at", "invitation"."created_at" FROM "invitation" ORDER BY "invitation"."created_at" DESC"#,
+                []
+            )]
+        )
+    }
+}
@@ -1,3 +1,16 @@
+pub mod deployments;
+pub mod identities;
+pub mod invitations;
+pub mod roles;
+pub mod rollouts;
+pub mod schedules;
+pub mod security_events;
+pub mod sessions;
+pub mod settings;
+pub mod teams;
+pub mod tokens;
+pub mod two_factor;
 pub mod users;
 pub mod workers;
+pub mod workspace;
 pub use sea_orm;
@@ -0,0 +1,5 @@
+mod mutation;
+mod query;
+
+pub use mutation::Mutation;
+pub use query::Query;
@@ -0,0 +1,164 @@
+use ::entity::{role, role::Entity as Role, user_role, user_role::Entity as UserRole};
+use prelude::Uuid;
+use sea_orm::{sea_query::OnConflict, *};
+
+pub struct Mutation;
+
+impl Mutation {
+    /// Creates a role, or replaces the description and permissions of an
+    /// existing one.
+    pub async fn set_role(
+        db: &DbConn,
+        name: String,
+        description: Option<String>,
+        permissions: Vec<String>,
+    ) -> Result<(), DbErr> {
+        Role::insert(role::ActiveModel {
+            name: Set(name),
+            description: Set(description),
+            permissions: Set(permissions),
+            ..Default::default()
+        })
+        .on_conflict(
+            OnConflict::column(role::Column::Name)
+                .update_columns([role::Column::Description, role::Column::Permissions])
+                .to_owned(),
+        )
+        .exec_without_returning(db)
+        .await?;
+        Ok(())
+    }
+
+    pub async fn delete_role(db: &DbConn, name: String) -> Result<DeleteResult, DbErr> {
+        Role::delete_by_id(name).exec(db).await
+    }
+
+    /// Assigns a role to a user, and returns false if it already was.
+    pub async fn assign_role(db: &DbConn, user_id: Uuid, name: String) -> Result<bool, DbErr> {
+        let inserted = UserRole::insert(user_role::ActiveModel {
+            user_id: Set(user_id),
+            role_name: Set(name),
+        })
+        .on_conflict(
+            OnConflict::columns([user_role::Column::UserId, user_role::Column::RoleName])
+                .do_nothing()
+                .to_owned(),
+        )
+        .exec_without_returning(db)
+        .await?;
+        Ok(inserted > 0)
+    }
+
+    pub async fn unassign_role(
+        db: &DbConn,
+        user_id: Uuid,
+        name: String,
+    ) -> Result<DeleteResult, DbErr> {
+        UserRole::delete_by_id((user_id, name)).exec(db).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn exec_db() -> DatabaseConnection {
+        MockDatabase::new(DatabaseBackend::Postgres)
+            .append_exec_results([MockExecResult {
+                last_insert_id: 0,
+                rows_affected: 1,
+            }])
+            .into_connection()
+    }
+
+    #[tokio::test]
+    async fn test_set_role() {
+        let db = exec_db();
+
+        Mutation::set_role(
+            &db,
+            "operator".to_owned(),
+            None,
+            vec!["workers.read".to_owned()],
+        )
+        .await
+        .expect("Failed to set role");
+
+        assert_eq!(
+            db.into_transaction_log(),
+            [Transaction::from_sql_and_values(
+                DatabaseBackend::Postgres,
+                r#"INSERT INTO "role" ("name", "description", "permissions") VALUES ($1, $2, $3) ON CONFLICT ("name") DO UPDATE SET "description" = "excluded"."description", "permissions" = "excluded"."permissions""#,
+                [
+                    "operator".into(),
+                    Option::<String>::None.into(),
+                    vec!["workers.read".to_owned()].into()
+                ]
+            )]
+        )
+    }
+
+    #[tokio::test]
+    async fn test_delete_role() {
+        let db = exec_db();
+
+        {
+            let result = Mutation::delete_role(&db, "operator".to_owned())
+                .await
+                .expect("Failed to delete role");
+
+            assert_eq!(result.rows_affected, 1);
+        }
+
+        assert_eq!(
+            db.into_transaction_log(),
+            [Transaction::from_sql_and_values(
+                DatabaseBackend::Postgres,
+                r#"DELETE FROM "role" WHERE "role"."name" = $1"#,
+                ["operator".into()]
+            )]
+        )
+    }
+
+    #[tokio::test]
+    async fn test_assign_role() {
+        let db = exec_db();
+
+        let user_id = Uuid::parse_str("00000000-0000-0000-0000-000000000000").unwrap();
+        Mutation::assign_role(&db, user_id, "operator".to_owned())
+            .await
+            .expect("Failed to assign role");
+
+        assert_eq!(
+            db.into_transaction_log(),
+            [Transaction::from_sql_and_values(
+                DatabaseBackend::Postgres,
+                r#"INSERT INTO "user_role" ("user_id", "role_name") VALUES ($1, $2) ON CONFLICT ("user_id", "role_name") DO NOTHING"#,
+                [user_id.into(), "operator".into()]
+            )]
+        )
+    }
+
+    #[tokio::test]
+    async fn test_unassign_role() {
+        let db = exec_db();
+
+        let user_id = Uuid::parse_str("00000000-0000-0000-0000-000000000000").unwrap();
+        {
+            let result = Mutation::unassign_role(&db, user_id, "operator".to_owned())
+                .await
+                .expect("Failed to unassign role");
+
+            assert_eq!(result.rows_affected, 1);
+        }
+
+        assert_eq!(
+            db.into_transaction_log(),
+            [Transaction::from_sql_and_values(
+                DatabaseBackend::Postgres,
+                r#"DELETE FROM "user_role" WHERE "user_role"."user_id" = $1 AND "user_role"."role_name" = $2"#,
+                [user_id.into(), "operator".into()]
+            )]
+        )
+    }
+}
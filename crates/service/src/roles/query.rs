@@ -0,0 +1,155 @@
+use ::entity::{role, role::Entity as Role, user_role, user_role::Entity as UserRole};
+use prelude::Uuid;
+use sea_orm::*;
+
+pub struct Query;
+
+impl Query {
+    pub async fn find_all_roles(db: &DbConn) -> Result<Vec<role::Model>, DbErr> {
+        Role::find().order_by_asc(role::Column::Name).all(db).await
+    }
+
+    pub async fn find_role_by_name(
+        db: &DbConn,
+        name: String,
+    ) -> Result<Option<role::Model>, DbErr> {
+        Role::find_by_id(name).one(db).await
+    }
+
+    /// Finds the roles assigned to a user.
+    pub async fn find_roles_by_user_id(
+        db: &DbConn,
+        user_id: Uuid,
+    ) -> Result<Vec<role::Model>, DbErr> {
+        Role::find()
+            .inner_join(UserRole)
+            .filter(user_role::Column::UserId.eq(user_id))
+            .order_by_asc(role::Column::Name)
+            .all(db)
+            .await
+    }
+
+    /// Finds the ids of the users a role is assigned to.
+    pub async fn find_user_ids_by_role(db: &DbConn, name: String) -> Result<Vec<Uuid>, DbErr> {
+        let assignments = UserRole::find()
+            .filter(user_role::Column::RoleName.eq(name))
+            .all(db)
+            .await?;
+        Ok(assignments
+            .into_iter()
+            .map(|assignment| assignment.user_id)
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn create_role(name: &str) -> role::Model {
+        role::Model {
+            name: name.to_owned(),
+            description: None,
+            permissions: vec!["workers.read".to_owned()],
+            created_at: "2024-01-01T00:00:00+00:00".parse().unwrap(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_find_all_roles() {
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([[create_role("auditor"), create_role("operator")]])
+            .into_connection();
+
+        assert_eq!(
+            Query::find_all_roles(&db)
+                .await
+                .expect("Failed to find roles")
+                .len(),
+            2
+        );
+
+        assert_eq!(
+            db.into_transaction_log(),
+            [Transaction::from_sql_and_values(
+                DatabaseBackend::Postgres,
+                r#"SELECT "role"."name", "role"."description", "role"."permissions", "role"."created_at" FROM "role" ORDER BY "role"."name" ASC"#,
+                []
+            )]
+        )
+    }
+
+    #[tokio::test]
+    async fn test_find_role_by_name() {
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([[create_role("operator")]])
+            .into_connection();
+
+        assert_eq!(
+            Query::find_role_by_name(&db, "operator".to_owned())
+                .await
+                .expect("Failed to find role"),
+            Some(create_role("operator"))
+        );
+
+        assert_eq!(
+            db.into_transaction_log(),
+            [Transaction::from_sql_and_values(
+                DatabaseBackend::Postgres,
+                r#"SELECT "role"."name", "role"."description", "role"."permissions", "role"."created_at" FROM "role" WHERE "role"."name" = $1 LIMIT $2"#,
+                ["operator".into(), 1u64.into()]
+            )]
+        )
+    }
+
+    #[tokio::test]
+    async fn test_find_roles_by_user_id() {
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([[create_role("operator")]])
+            .into_connection();
+
+        let user_id = Uuid::parse_str("00000000-0000-0000-0000-000000000000").unwrap();
+        assert_eq!(
+            Query::find_roles_by_user_id(&db, user_id)
+                .await
+                .expect("Failed to find roles"),
+            [create_role("operator")]
+        );
+
+        assert_eq!(
+            db.into_transaction_log(),
+            [Transaction::from_sql_and_values(
+                DatabaseBackend::Postgres,
+                r#"SELECT "role"."name", "role"."description", "role"."permissions", "role"."created_at" FROM "role" INNER JOIN "user_role" ON "role"."name" = "user_role"."role_name" WHERE "user_role"."user_id" = $1 ORDER BY "role"."name" ASC"#,
+                [user_id.into()]
+            )]
+        )
+    }
+
+    #[tokio::test]
+    async fn test_find_user_ids_by_role() {
+        let user_id = Uuid::parse_str("00000000-0000-0000-0000-000000000000").unwrap();
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([[user_role::Model {
+                user_id,
+                role_name: "operator".to_owned(),
+            }]])
+            .into_connection();
+
+        assert_eq!(
+            Query::find_user_ids_by_role(&db, "operator".to_owned())
+                .await
+                .expect("Failed to find users"),
+            [user_id]
+        );
+
+        assert_eq!(
+            db.into_transaction_log(),
+            [Transaction::from_sql_and_values(
+                DatabaseBackend::Postgres,
+                r#"SELECT "user_role"."user_id", "user_role"."role_name" FROM "user_role" WHERE "user_role"."role_name" = $1"#,
+                ["operator".into()]
+            )]
+        )
+    }
+}
@@ -0,0 +1,260 @@
+use ::entity::{rollout, rollout::Entity as Rollout, sea_orm_active_enums::RolloutStatusEnum};
+use prelude::{DateTimeWithTimeZone, Uuid};
+use sea_orm::*;
+
+pub struct Mutation;
+
+impl Mutation {
+    #[allow(clippy::too_many_arguments)]
+    pub async fn create_rollout(
+        db: &DbConn,
+        worker_id: String,
+        deployment_id: String,
+        port: i32,
+        percentage: i32,
+        header: Option<(String, String)>,
+        cookie: Option<(String, String)>,
+        max_error_rate: f64,
+        min_requests: i32,
+    ) -> Result<rollout::Model, DbErr> {
+        let (header_name, header_value) = header.unzip();
+        let (cookie_name, cookie_value) = cookie.unzip();
+
+        rollout::ActiveModel {
+            worker_id: Set(Uuid::parse_str(&worker_id)
+                .map_err(|_| DbErr::Custom("Invalid UUID.".to_owned()))?),
+            deployment_id: Set(Uuid::parse_str(&deployment_id)
+                .map_err(|_| DbErr::Custom("Invalid UUID.".to_owned()))?),
+            port: Set(port),
+            percentage: Set(percentage),
+            header_name: Set(header_name),
+            header_value: Set(header_value),
+            cookie_name: Set(cookie_name),
+            cookie_value: Set(cookie_value),
+            max_error_rate: Set(max_error_rate),
+            min_requests: Set(min_requests),
+            ..Default::default()
+        }
+        .insert(db)
+        .await
+    }
+
+    pub async fn update_rollout_split(
+        db: &DbConn,
+        id: String,
+        percentage: i32,
+        header: Option<(String, String)>,
+        cookie: Option<(String, String)>,
+    ) -> Result<rollout::Model, DbErr> {
+        let uuid = Uuid::parse_str(&id).map_err(|_| DbErr::Custom("Invalid UUID.".to_owned()))?;
+        let (header_name, header_value) = header.unzip();
+        let (cookie_name, cookie_value) = cookie.unzip();
+
+        let rollout: rollout::ActiveModel = Rollout::find_by_id(uuid)
+            .one(db)
+            .await?
+            .ok_or(DbErr::Custom("Cannot find rollout.".to_owned()))
+            .map(Into::into)?;
+
+        rollout::ActiveModel {
+            id: rollout.id,
+            percentage: Set(percentage),
+            header_name: Set(header_name),
+            header_value: Set(header_value),
+            cookie_name: Set(cookie_name),
+            cookie_value: Set(cookie_value),
+            ..rollout
+        }
+        .update(db)
+        .await
+    }
+
+    /// Ends a rollout, recording whether the candidate was promoted or aborted.
+    pub async fn finish_rollout(
+        db: &DbConn,
+        id: String,
+        status: RolloutStatusEnum,
+        reason: Option<String>,
+        ended_at: DateTimeWithTimeZone,
+    ) -> Result<rollout::Model, DbErr> {
+        let uuid = Uuid::parse_str(&id).map_err(|_| DbErr::Custom("Invalid UUID.".to_owned()))?;
+
+        rollout::ActiveModel {
+            id: Unchanged(uuid),
+            status: Set(status),
+            reason: Set(reason),
+            ended_at: Set(Some(ended_at)),
+            ..Default::default()
+        }
+        .update(db)
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn create_rollout_with_id(id: &str) -> rollout::Model {
+        rollout::Model {
+            id: Uuid::parse_str(id).unwrap(),
+            worker_id: Uuid::parse_str("00000000-0000-0000-0000-000000000000").unwrap(),
+            deployment_id: Uuid::parse_str("00000000-0000-0000-0000-000000000000").unwrap(),
+            port: 8081,
+            percentage: 10,
+            header_name: None,
+            header_value: None,
+            cookie_name: None,
+            cookie_value: None,
+            max_error_rate: 0.05,
+            min_requests: 20,
+            status: RolloutStatusEnum::Active,
+            reason: None,
+            created_at: "2024-01-01T00:00:00+00:00".parse().unwrap(),
+            ended_at: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_create_rollout() {
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([[create_rollout_with_id(
+                "00000000-0000-0000-0000-000000000001",
+            )]])
+            .into_connection();
+
+        assert_eq!(
+            Mutation::create_rollout(
+                &db,
+                "00000000-0000-0000-0000-000000000000".to_string(),
+                "00000000-0000-0000-0000-000000000000".to_string(),
+                8081,
+                10,
+                None,
+                Some(("canary".to_string(), "1".to_string())),
+                0.05,
+                20,
+            )
+            .await
+            .unwrap(),
+            create_rollout_with_id("00000000-0000-0000-0000-000000000001")
+        );
+
+        assert_eq!(
+            db.into_transaction_log(),
+            [Transaction::from_sql_and_values(
+                DatabaseBackend::Postgres,
+                r#"INSERT INTO "rollout" ("worker_id", "deployment_id", "port", "percentage", "header_name", "header_value", "cookie_name", "cookie_value", "max_error_rate", "min_requests") VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10) RETURNING "id", "worker_id", "deployment_id", "port", "percentage", "header_name", "header_value", "cookie_name", "cookie_value", "max_error_rate", "min_requests", CAST("status" AS text), "reason", "created_at", "ended_at""#,
+                [
+                    Uuid::parse_str("00000000-0000-0000-0000-000000000000")
+                        .unwrap()
+                        .into(),
+                    Uuid::parse_str("00000000-0000-0000-0000-000000000000")
+                        .unwrap()
+                        .into(),
+                    8081i32.into(),
+                    10i32.into(),
+                    Option::<String>::None.into(),
+                    Option::<String>::None.into(),
+                    "canary".into(),
+                    "1".into(),
+                    0.05f64.into(),
+                    20i32.into(),
+                ]
+            )]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_update_rollout_split() {
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([
+                [create_rollout_with_id(
+                    "00000000-0000-0000-0000-000000000001",
+                )],
+                [create_rollout_with_id(
+                    "00000000-0000-0000-0000-000000000001",
+                )],
+            ])
+            .into_connection();
+
+        assert_eq!(
+            Mutation::update_rollout_split(
+                &db,
+                "00000000-0000-0000-0000-000000000001".to_string(),
+                50,
+                Some(("x-canary".to_string(), "1".to_string())),
+                None,
+            )
+            .await
+            .unwrap(),
+            create_rollout_with_id("00000000-0000-0000-0000-000000000001")
+        );
+
+        assert_eq!(
+            db.into_transaction_log(),
+            [
+                Transaction::from_sql_and_values(
+                    DatabaseBackend::Postgres,
+                    r#"SELECT "rollout"."id", "rollout"."worker_id", "rollout"."deployment_id", "rollout"."port", "rollout"."percentage", "rollout"."header_name", "rollout"."header_value", "rollout"."cookie_name", "rollout"."cookie_value", "rollout"."max_error_rate", "rollout"."min_requests", CAST("rollout"."status" AS text), "rollout"."reason", "rollout"."created_at", "rollout"."ended_at" FROM "rollout" WHERE "rollout"."id" = $1 LIMIT $2"#,
+                    [
+                        Uuid::parse_str("00000000-0000-0000-0000-000000000001")
+                            .unwrap()
+                            .into(),
+                        1u64.into()
+                    ]
+                ),
+                Transaction::from_sql_and_values(
+                    DatabaseBackend::Postgres,
+                    r#"UPDATE "rollout" SET "percentage" = $1, "header_name" = $2, "header_value" = $3, "cookie_name" = $4, "cookie_value" = $5 WHERE "rollout"."id" = $6 RETURNING "id", "worker_id", "deployment_id", "port", "percentage", "header_name", "header_value", "cookie_name", "cookie_value", "max_error_rate", "min_requests", CAST("status" AS text), "reason", "created_at", "ended_at""#,
+                    [
+                        50i32.into(),
+                        "x-canary".into(),
+                        "1".into(),
+                        Option::<String>::None.into(),
+                        Option::<String>::None.into(),
+                        Uuid::parse_str("00000000-0000-0000-0000-000000000001")
+                            .unwrap()
+                            .into(),
+                    ]
+                )
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_finish_rollout() {
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([[create_rollout_with_id(
+                "00000000-0000-0000-0000-000000000001",
+            )]])
+            .into_connection();
+
+        let ended_at: DateTimeWithTimeZone = "2024-01-01T01:00:00+00:00".parse().unwrap();
+        Mutation::finish_rollout(
+            &db,
+            "00000000-0000-0000-0000-000000000001".to_string(),
+            RolloutStatusEnum::Aborted,
+            Some("error rate too high".to_string()),
+            ended_at,
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(
+            db.into_transaction_log(),
+            [Transaction::from_sql_and_values(
+                DatabaseBackend::Postgres,
+                r#"UPDATE "rollout" SET "status" = CAST($1 AS rollout_status_enum), "reason" = $2, "ended_at" = $3 WHERE "rollout"."id" = $4 RETURNING "id", "worker_id", "deployment_id", "port", "percentage", "header_name", "header_value", "cookie_name", "cookie_value", "max_error_rate", "min_requests", CAST("status" AS text), "reason", "created_at", "ended_at""#,
+                [
+                    RolloutStatusEnum::Aborted.into(),
+                    "error rate too high".into(),
+                    ended_at.into(),
+                    Uuid::parse_str("00000000-0000-0000-0000-000000000001")
+                        .unwrap()
+                        .into(),
+                ]
+            )]
+        );
+    }
+}
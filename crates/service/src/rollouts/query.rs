@@ -0,0 +1,114 @@
+use ::entity::{rollout, rollout::Entity as Rollout, sea_orm_active_enums::RolloutStatusEnum};
+use prelude::Uuid;
+use sea_orm::*;
+
+pub struct Query;
+
+impl Query {
+    pub async fn find_active_rollout_by_worker_id(
+        db: &DbConn,
+        worker_id: String,
+    ) -> Result<Option<rollout::Model>, DbErr> {
+        let uuid =
+            Uuid::parse_str(&worker_id).map_err(|_| DbErr::Custom("Invalid UUID.".to_owned()))?;
+
+        Rollout::find()
+            .filter(rollout::Column::WorkerId.eq(uuid))
+            .filter(rollout::Column::Status.eq(RolloutStatusEnum::Active))
+            .one(db)
+            .await
+    }
+
+    pub async fn find_active_rollouts(db: &DbConn) -> Result<Vec<rollout::Model>, DbErr> {
+        Rollout::find()
+            .filter(rollout::Column::Status.eq(RolloutStatusEnum::Active))
+            .all(db)
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn create_rollout_with_id(id: &str) -> rollout::Model {
+        rollout::Model {
+            id: Uuid::parse_str(id).unwrap(),
+            worker_id: Uuid::parse_str("00000000-0000-0000-0000-000000000000").unwrap(),
+            deployment_id: Uuid::parse_str("00000000-0000-0000-0000-000000000000").unwrap(),
+            port: 8081,
+            percentage: 10,
+            header_name: None,
+            header_value: None,
+            cookie_name: None,
+            cookie_value: None,
+            max_error_rate: 0.05,
+            min_requests: 20,
+            status: RolloutStatusEnum::Active,
+            reason: None,
+            created_at: "2024-01-01T00:00:00+00:00".parse().unwrap(),
+            ended_at: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_find_active_rollout_by_worker_id() {
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([[create_rollout_with_id(
+                "00000000-0000-0000-0000-000000000001",
+            )]])
+            .into_connection();
+
+        assert_eq!(
+            Query::find_active_rollout_by_worker_id(
+                &db,
+                "00000000-0000-0000-0000-000000000000".to_string()
+            )
+            .await
+            .unwrap(),
+            Some(create_rollout_with_id(
+                "00000000-0000-0000-0000-000000000001"
+            ))
+        );
+
+        assert_eq!(
+            db.into_transaction_log(),
+            [Transaction::from_sql_and_values(
+                DatabaseBackend::Postgres,
+                r#"SELECT "rollout"."id", "rollout"."worker_id", "rollout"."deployment_id", "rollout"."port", "rollout"."percentage", "rollout"."header_name", "rollout"."header_value", "rollout"."cookie_name", "rollout"."cookie_value", "rollout"."max_error_rate", "rollout"."min_requests", CAST("rollout"."status" AS text), "rollout"."reason", "rollout"."created_at", "rollout"."ended_at" FROM "rollout" WHERE "rollout"."worker_id" = $1 AND "rollout"."status" = (CAST($2 AS rollout_status_enum)) LIMIT $3"#,
+                [
+                    Uuid::parse_str("00000000-0000-0000-0000-000000000000")
+                        .unwrap()
+                        .into(),
+                    "active".into(),
+                    1u64.into()
+                ]
+            )]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_find_active_rollouts() {
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([[create_rollout_with_id(
+                "00000000-0000-0000-0000-000000000001",
+            )]])
+            .into_connection();
+
+        assert_eq!(
+            Query::find_active_rollouts(&db).await.unwrap(),
+            [create_rollout_with_id(
+                "00000000-0000-0000-0000-000000000001"
+            )]
+        );
+
+        assert_eq!(
+            db.into_transaction_log(),
+            [Transaction::from_sql_and_values(
+                DatabaseBackend::Postgres,
+                r#"SELECT "rollout"."id", "rollout"."worker_id", "rollout"."deployment_id", "rollout"."port", "rollout"."percentage", "rollout"."header_name", "rollout"."header_value", "rollout"."cookie_name", "rollout"."cookie_value", "rollout"."max_error_rate", "rollout"."min_requests", CAST("rollout"."status" AS text), "rollout"."reason", "rollout"."created_at", "rollout"."ended_at" FROM "rollout" WHERE "rollout"."status" = (CAST($1 AS rollout_status_enum))"#,
+                ["active".into()]
+            )]
+        );
+    }
+}
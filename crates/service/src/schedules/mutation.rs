@@ -0,0 +1,313 @@
+use ::entity::{
+    schedule_run, sea_orm_active_enums::RunStatusEnum, worker_schedule,
+    worker_schedule::Entity as WorkerSchedule,
+};
+use prelude::{DateTimeWithTimeZone, Uuid};
+use sea_orm::*;
+
+pub struct Mutation;
+
+impl Mutation {
+    pub async fn create_schedule(
+        db: &DbConn,
+        worker_id: String,
+        cron: String,
+        path: Option<String>,
+    ) -> Result<worker_schedule::ActiveModel, DbErr> {
+        worker_schedule::ActiveModel {
+            worker_id: Set(Uuid::parse_str(&worker_id)
+                .map_err(|_| DbErr::Custom("Invalid UUID.".to_owned()))?),
+            cron: Set(cron),
+            path: Set(path),
+            ..Default::default()
+        }
+        .save(db)
+        .await
+    }
+
+    pub async fn update_schedule(
+        db: &DbConn,
+        id: String,
+        cron: String,
+        path: Option<String>,
+        enabled: bool,
+    ) -> Result<worker_schedule::Model, DbErr> {
+        let uuid = Uuid::parse_str(&id).map_err(|_| DbErr::Custom("Invalid UUID.".to_owned()))?;
+
+        let schedule: worker_schedule::ActiveModel = WorkerSchedule::find_by_id(uuid)
+            .one(db)
+            .await?
+            .ok_or(DbErr::Custom("Cannot find schedule.".to_owned()))
+            .map(Into::into)?;
+
+        worker_schedule::ActiveModel {
+            id: schedule.id,
+            cron: Set(cron),
+            path: Set(path),
+            enabled: Set(enabled),
+            ..schedule
+        }
+        .update(db)
+        .await
+    }
+
+    pub async fn delete_schedule(db: &DbConn, id: String) -> Result<DeleteResult, DbErr> {
+        let uuid = Uuid::parse_str(&id).map_err(|_| DbErr::Custom("Invalid UUID.".to_owned()))?;
+
+        let schedule: worker_schedule::ActiveModel = WorkerSchedule::find_by_id(uuid)
+            .one(db)
+            .await?
+            .ok_or(DbErr::Custom("Cannot find schedule.".to_owned()))
+            .map(Into::into)?;
+
+        schedule.delete(db).await
+    }
+
+    pub async fn create_run(
+        db: &DbConn,
+        schedule_id: String,
+        started_at: DateTimeWithTimeZone,
+        duration_ms: i64,
+        status: RunStatusEnum,
+        status_code: Option<i32>,
+        output: String,
+    ) -> Result<schedule_run::ActiveModel, DbErr> {
+        schedule_run::ActiveModel {
+            schedule_id: Set(Uuid::parse_str(&schedule_id)
+                .map_err(|_| DbErr::Custom("Invalid UUID.".to_owned()))?),
+            started_at: Set(started_at),
+            duration_ms: Set(duration_ms),
+            status: Set(status),
+            status_code: Set(status_code),
+            output: Set(output),
+            ..Default::default()
+        }
+        .save(db)
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn create_schedule_with_id(id: &str) -> worker_schedule::Model {
+        worker_schedule::Model {
+            id: Uuid::parse_str(id).unwrap(),
+            worker_id: Uuid::parse_str("00000000-0000-0000-0000-000000000000").unwrap(),
+            cron: "*/5 * * * *".to_string(),
+            path: None,
+            enabled: true,
+            created_at: "2024-01-01T00:00:00+00:00".parse().unwrap(),
+        }
+    }
+
+    fn create_run_with_id(id: &str) -> schedule_run::Model {
+        schedule_run::Model {
+            id: Uuid::parse_str(id).unwrap(),
+            schedule_id: Uuid::parse_str("00000000-0000-0000-0000-000000000000").unwrap(),
+            started_at: "2024-01-01T00:05:00+00:00".parse().unwrap(),
+            duration_ms: 12,
+            status: RunStatusEnum::Success,
+            status_code: Some(200),
+            output: "ok".to_string(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_create_schedule() {
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([[create_schedule_with_id(
+                "00000000-0000-0000-0000-000000000000",
+            )]])
+            .into_connection();
+
+        {
+            let schedule = Mutation::create_schedule(
+                &db,
+                "00000000-0000-0000-0000-000000000000".to_string(),
+                "*/5 * * * *".to_string(),
+                None,
+            )
+            .await
+            .expect("Failed to create schedule");
+
+            assert_eq!(
+                schedule,
+                worker_schedule::ActiveModel {
+                    id: Unchanged(Uuid::parse_str("00000000-0000-0000-0000-000000000000").unwrap()),
+                    worker_id: Unchanged(
+                        Uuid::parse_str("00000000-0000-0000-0000-000000000000").unwrap()
+                    ),
+                    cron: Unchanged("*/5 * * * *".to_string()),
+                    path: Unchanged(None),
+                    enabled: Unchanged(true),
+                    created_at: Unchanged("2024-01-01T00:00:00+00:00".parse().unwrap()),
+                }
+            );
+        }
+
+        assert_eq!(
+            db.into_transaction_log(),
+            [Transaction::from_sql_and_values(
+                DatabaseBackend::Postgres,
+                r#"INSERT INTO "worker_schedule" ("worker_id", "cron", "path") VALUES ($1, $2, $3) RETURNING "id", "worker_id", "cron", "path", "enabled", "created_at""#,
+                [
+                    Uuid::parse_str("00000000-0000-0000-0000-000000000000")
+                        .unwrap()
+                        .into(),
+                    "*/5 * * * *".into(),
+                    Option::<String>::None.into(),
+                ]
+            )]
+        )
+    }
+
+    #[tokio::test]
+    async fn test_update_schedule() {
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([
+                [create_schedule_with_id(
+                    "00000000-0000-0000-0000-000000000000",
+                )],
+                [create_schedule_with_id(
+                    "00000000-0000-0000-0000-000000000000",
+                )],
+            ])
+            .into_connection();
+
+        {
+            let schedule = Mutation::update_schedule(
+                &db,
+                "00000000-0000-0000-0000-000000000000".to_string(),
+                "*/5 * * * *".to_string(),
+                None,
+                true,
+            )
+            .await
+            .expect("Failed to update schedule");
+
+            assert_eq!(
+                schedule,
+                create_schedule_with_id("00000000-0000-0000-0000-000000000000")
+            );
+        }
+
+        assert_eq!(
+            db.into_transaction_log(),
+            [
+                Transaction::from_sql_and_values(
+                    DatabaseBackend::Postgres,
+                    r#"SELECT "worker_schedule"."id", "worker_schedule"."worker_id", "worker_schedule"."cron", "worker_schedule"."path", "worker_schedule"."enabled", "worker_schedule"."created_at" FROM "worker_schedule" WHERE "worker_schedule"."id" = $1 LIMIT $2"#,
+                    [
+                        Uuid::parse_str("00000000-0000-0000-0000-000000000000")
+                            .unwrap()
+                            .into(),
+                        1u64.into()
+                    ]
+                ),
+                Transaction::from_sql_and_values(
+                    DatabaseBackend::Postgres,
+                    r#"UPDATE "worker_schedule" SET "cron" = $1, "path" = $2, "enabled" = $3 WHERE "worker_schedule"."id" = $4 RETURNING "id", "worker_id", "cron", "path", "enabled", "created_at""#,
+                    [
+                        "*/5 * * * *".into(),
+                        Option::<String>::None.into(),
+                        true.into(),
+                        Uuid::parse_str("00000000-0000-0000-0000-000000000000")
+                            .unwrap()
+                            .into()
+                    ]
+                )
+            ]
+        )
+    }
+
+    #[tokio::test]
+    async fn test_delete_schedule() {
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([[create_schedule_with_id(
+                "00000000-0000-0000-0000-000000000000",
+            )]])
+            .append_exec_results([MockExecResult {
+                last_insert_id: 0,
+                rows_affected: 1,
+            }])
+            .into_connection();
+
+        {
+            let result =
+                Mutation::delete_schedule(&db, "00000000-0000-0000-0000-000000000000".to_string())
+                    .await
+                    .expect("Failed to delete schedule");
+
+            assert_eq!(result.rows_affected, 1);
+        }
+
+        assert_eq!(
+            db.into_transaction_log(),
+            [
+                Transaction::from_sql_and_values(
+                    DatabaseBackend::Postgres,
+                    r#"SELECT "worker_schedule"."id", "worker_schedule"."worker_id", "worker_schedule"."cron", "worker_schedule"."path", "worker_schedule"."enabled", "worker_schedule"."created_at" FROM "worker_schedule" WHERE "worker_schedule"."id" = $1 LIMIT $2"#,
+                    [
+                        Uuid::parse_str("00000000-0000-0000-0000-000000000000")
+                            .unwrap()
+                            .into(),
+                        1u64.into()
+                    ]
+                ),
+                Transaction::from_sql_and_values(
+                    DatabaseBackend::Postgres,
+                    r#"DELETE FROM "worker_schedule" WHERE "worker_schedule"."id" = $1"#,
+                    [Uuid::parse_str("00000000-0000-0000-0000-000000000000")
+                        .unwrap()
+                        .into()]
+                )
+            ]
+        )
+    }
+
+    #[tokio::test]
+    async fn test_create_run() {
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([[create_run_with_id("00000000-0000-0000-0000-000000000000")]])
+            .into_connection();
+
+        {
+            let run = Mutation::create_run(
+                &db,
+                "00000000-0000-0000-0000-000000000000".to_string(),
+                "2024-01-01T00:05:00+00:00".parse().unwrap(),
+                12,
+                RunStatusEnum::Success,
+                Some(200),
+                "ok".to_string(),
+            )
+            .await
+            .expect("Failed to create run");
+
+            assert_eq!(run.status, Unchanged(RunStatusEnum::Success));
+        }
+
+        assert_eq!(
+            db.into_transaction_log(),
+            [Transaction::from_sql_and_values(
+                DatabaseBackend::Postgres,
+                r#"INSERT INTO "schedule_run" ("schedule_id", "started_at", "duration_ms", "status", "status_code", "output") VALUES ($1, $2, $3, CAST($4 AS run_status_enum), $5, $6) RETURNING "id", "schedule_id", "started_at", "duration_ms", CAST("status" AS text), "status_code", "output""#,
+                [
+                    Uuid::parse_str("00000000-0000-0000-0000-000000000000")
+                        .unwrap()
+                        .into(),
+                    "2024-01-01T00:05:00+00:00"
+                        .parse::<DateTimeWithTimeZone>()
+                        .unwrap()
+                        .into(),
+                    12i64.into(),
+                    RunStatusEnum::Success.into(),
+                    Some(200).into(),
+                    "ok".into(),
+                ]
+            )]
+        )
+    }
+}
@@ -0,0 +1,237 @@
+use ::entity::{
+    schedule_run, schedule_run::Entity as ScheduleRun, worker, worker::Entity as Worker,
+    worker_schedule, worker_schedule::Entity as WorkerSchedule,
+};
+use prelude::Uuid;
+use sea_orm::*;
+
+pub struct Query;
+
+impl Query {
+    pub async fn find_schedule_by_id(
+        db: &DbConn,
+        id: String,
+    ) -> Result<Option<worker_schedule::Model>, DbErr> {
+        let uuid = Uuid::parse_str(&id).map_err(|_| DbErr::Custom("Invalid UUID.".to_owned()))?;
+
+        WorkerSchedule::find_by_id(uuid).one(db).await
+    }
+
+    pub async fn find_schedules_by_worker_id(
+        db: &DbConn,
+        worker_id: String,
+    ) -> Result<Vec<worker_schedule::Model>, DbErr> {
+        let uuid =
+            Uuid::parse_str(&worker_id).map_err(|_| DbErr::Custom("Invalid UUID.".to_owned()))?;
+
+        WorkerSchedule::find()
+            .filter(worker_schedule::Column::WorkerId.eq(uuid))
+            .order_by_asc(worker_schedule::Column::CreatedAt)
+            .all(db)
+            .await
+    }
+
+    pub async fn find_enabled_schedules_with_worker(
+        db: &DbConn,
+    ) -> Result<Vec<(worker_schedule::Model, Option<worker::Model>)>, DbErr> {
+        WorkerSchedule::find()
+            .filter(worker_schedule::Column::Enabled.eq(true))
+            .find_also_related(Worker)
+            .all(db)
+            .await
+    }
+
+    pub async fn find_runs_by_schedule_id(
+        db: &DbConn,
+        schedule_id: String,
+        limit: u64,
+    ) -> Result<Vec<schedule_run::Model>, DbErr> {
+        let uuid =
+            Uuid::parse_str(&schedule_id).map_err(|_| DbErr::Custom("Invalid UUID.".to_owned()))?;
+
+        ScheduleRun::find()
+            .filter(schedule_run::Column::ScheduleId.eq(uuid))
+            .order_by_desc(schedule_run::Column::StartedAt)
+            .limit(limit)
+            .all(db)
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ::entity::sea_orm_active_enums::RunStatusEnum;
+
+    use super::*;
+
+    fn create_schedule_with_id(id: &str) -> worker_schedule::Model {
+        worker_schedule::Model {
+            id: Uuid::parse_str(id).unwrap(),
+            worker_id: Uuid::parse_str("00000000-0000-0000-0000-000000000000").unwrap(),
+            cron: "*/5 * * * *".to_string(),
+            path: None,
+            enabled: true,
+            created_at: "2024-01-01T00:00:00+00:00".parse().unwrap(),
+        }
+    }
+
+    fn create_worker_with_id(id: &str) -> worker::Model {
+        worker::Model {
+            id: Uuid::parse_str(id).unwrap(),
+            external_path: "/".to_string(),
+            host_name: "localhost".to_string(),
+            node_name: "default".to_string(),
+            port: 12345,
+            entry: "entry.js".to_string(),
+            code: "".to_string(),
+            name: "Test".to_string(),
+            tunnel_id: None,
+            template: None,
+            user_id: Uuid::parse_str("00000000-0000-0000-0000-000000000000").unwrap(),
+            deployment_id: None,
+            modules: None,
+            bindings: JsonValue::Object(Default::default()),
+            compatibility_date: None,
+            team_id: None,
+        }
+    }
+
+    fn create_run_with_id(id: &str) -> schedule_run::Model {
+        schedule_run::Model {
+            id: Uuid::parse_str(id).unwrap(),
+            schedule_id: Uuid::parse_str("00000000-0000-0000-0000-000000000000").unwrap(),
+            started_at: "2024-01-01T00:05:00+00:00".parse().unwrap(),
+            duration_ms: 12,
+            status: RunStatusEnum::Success,
+            status_code: Some(200),
+            output: "ok".to_string(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_find_schedule_by_id() {
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([[create_schedule_with_id(
+                "00000000-0000-0000-0000-000000000000",
+            )]])
+            .into_connection();
+
+        {
+            let id = "00000000-0000-0000-0000-000000000000";
+            let schedule = Query::find_schedule_by_id(&db, id.to_string())
+                .await
+                .expect("Failed to find schedule")
+                .expect("Schedule not found");
+
+            assert_eq!(schedule.id, Uuid::parse_str(id).unwrap());
+        }
+
+        assert_eq!(
+            db.into_transaction_log(),
+            [Transaction::from_sql_and_values(
+                DatabaseBackend::Postgres,
+                r#"SELECT "worker_schedule"."id", "worker_schedule"."worker_id", "worker_schedule"."cron", "worker_schedule"."path", "worker_schedule"."enabled", "worker_schedule"."created_at" FROM "worker_schedule" WHERE "worker_schedule"."id" = $1 LIMIT $2"#,
+                [
+                    Uuid::parse_str("00000000-0000-0000-0000-000000000000")
+                        .unwrap()
+                        .into(),
+                    1u64.into()
+                ]
+            )]
+        )
+    }
+
+    #[tokio::test]
+    async fn test_find_schedules_by_worker_id() {
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([[
+                create_schedule_with_id("00000000-0000-0000-0000-000000000000"),
+                create_schedule_with_id("00000000-0000-0000-0000-000000000001"),
+            ]])
+            .into_connection();
+
+        {
+            let schedules = Query::find_schedules_by_worker_id(
+                &db,
+                "00000000-0000-0000-0000-000000000000".to_string(),
+            )
+            .await
+            .expect("Failed to find schedules");
+
+            assert_eq!(schedules.len(), 2);
+        }
+
+        assert_eq!(
+            db.into_transaction_log(),
+            [Transaction::from_sql_and_values(
+                DatabaseBackend::Postgres,
+                r#"SELECT "worker_schedule"."id", "worker_schedule"."worker_id", "worker_schedule"."cron", "worker_schedule"."path", "worker_schedule"."enabled", "worker_schedule"."created_at" FROM "worker_schedule" WHERE "worker_schedule"."worker_id" = $1 ORDER BY "worker_schedule"."created_at" ASC"#,
+                [Uuid::parse_str("00000000-0000-0000-0000-000000000000")
+                    .unwrap()
+                    .into()]
+            )]
+        )
+    }
+
+    #[tokio::test]
+    async fn test_find_enabled_schedules_with_worker() {
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([[(
+                create_schedule_with_id("00000000-0000-0000-0000-000000000000"),
+                create_worker_with_id("00000000-0000-0000-0000-000000000000"),
+            )]])
+            .into_connection();
+
+        {
+            let schedules = Query::find_enabled_schedules_with_worker(&db)
+                .await
+                .expect("Failed to find schedules");
+
+            assert_eq!(schedules.len(), 1);
+            assert_eq!(schedules[0].1.as_ref().unwrap().port, 12345);
+        }
+
+        assert_eq!(
+            db.into_transaction_log(),
+            [Transaction::from_sql_and_values(
+                DatabaseBackend::Postgres,
+                r#"SELECT "worker_schedule"."id" AS "A_id", "worker_schedule"."worker_id" AS "A_worker_id", "worker_schedule"."cron" AS "A_cron", "worker_schedule"."path" AS "A_path", "worker_schedule"."enabled" AS "A_enabled", "worker_schedule"."created_at" AS "A_created_at", "worker"."id" AS "B_id", "worker"."external_path" AS "B_external_path", "worker"."host_name" AS "B_host_name", "worker"."node_name" AS "B_node_name", "worker"."port" AS "B_port", "worker"."entry" AS "B_entry", "worker"."code" AS "B_code", "worker"."name" AS "B_name", "worker"."tunnel_id" AS "B_tunnel_id", "worker"."template" AS "B_template", "worker"."user_id" AS "B_user_id", "worker"."deployment_id" AS "B_deployment_id", "worker"."modules" AS "B_modules", "worker"."bindings" AS "B_bindings", "worker"."compatibility_date" AS "B_compatibility_date", "worker"."team_id" AS "B_team_id" FROM "worker_schedule" LEFT JOIN "worker" ON "worker_schedule"."worker_id" = "worker"."id" WHERE "worker_schedule"."enabled" = $1"#,
+                [true.into()]
+            )]
+        )
+    }
+
+    #[tokio::test]
+    async fn test_find_runs_by_schedule_id() {
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([[create_run_with_id("00000000-0000-0000-0000-000000000000")]])
+            .into_connection();
+
+        {
+            let runs = Query::find_runs_by_schedule_id(
+                &db,
+                "00000000-0000-0000-0000-000000000000".to_string(),
+                50,
+            )
+            .await
+            .expect("Failed to find runs");
+
+            assert_eq!(runs.len(), 1);
+            assert_eq!(runs[0].status, RunStatusEnum::Success);
+        }
+
+        assert_eq!(
+            db.into_transaction_log(),
+            [Transaction::from_sql_and_values(
+                DatabaseBackend::Postgres,
+                r#"SELECT "schedule_run"."id", "schedule_run"."schedule_id", "schedule_run"."started_at", "schedule_run"."duration_ms", CAST("schedule_run"."status" AS text), "schedule_run"."status_code", "schedule_run"."output" FROM "schedule_run" WHERE "schedule_run"."schedule_id" = $1 ORDER BY "schedule_run"."started_at" DESC LIMIT $2"#,
+                [
+                    Uuid::parse_str("00000000-0000-0000-0000-000000000000")
+                        .unwrap()
+                        .into(),
+                    50u64.into()
+                ]
+            )]
+        )
+    }
+}
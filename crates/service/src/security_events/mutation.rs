@@ -0,0 +1,73 @@
+use ::entity::security_event;
+use prelude::Uuid;
+use sea_orm::*;
+
+pub struct Mutation;
+
+impl Mutation {
+    pub async fn create_event(
+        db: &DbConn,
+        user_id: Uuid,
+        kind: String,
+        ip_address: Option<String>,
+        user_agent: Option<String>,
+    ) -> Result<security_event::Model, DbErr> {
+        security_event::ActiveModel {
+            user_id: Set(user_id),
+            kind: Set(kind),
+            ip_address: Set(ip_address),
+            user_agent: Set(user_agent),
+            ..Default::default()
+        }
+        .insert(db)
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_create_event() {
+        let user_id = Uuid::parse_str("00000000-0000-0000-0000-000000000000").unwrap();
+        let event = security_event::Model {
+            id: Uuid::parse_str("00000000-0000-0000-0000-000000000001").unwrap(),
+            user_id,
+            kind: "refresh_token_reused".to_owned(),
+            ip_address: Some("127.0.0.1".to_owned()),
+            user_agent: None,
+            created_at: "2024-01-01T00:00:00+00:00".parse().unwrap(),
+        };
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([[event.to_owned()]])
+            .into_connection();
+
+        assert_eq!(
+            Mutation::create_event(
+                &db,
+                user_id,
+                "refresh_token_reused".to_owned(),
+                Some("127.0.0.1".to_owned()),
+                None,
+            )
+            .await
+            .expect("Failed to create security event"),
+            event
+        );
+
+        assert_eq!(
+            db.into_transaction_log(),
+            [Transaction::from_sql_and_values(
+                DatabaseBackend::Postgres,
+                r#"INSERT INTO "security_event" ("user_id", "kind", "ip_address", "user_agent") VALUES ($1, $2, $3, $4) RETURNING "id", "user_id", "kind", "ip_address", "user_agent", "created_at""#,
+                [
+                    user_id.into(),
+                    "refresh_token_reused".into(),
+                    Some("127.0.0.1".to_owned()).into(),
+                    Option::<String>::None.into(),
+                ]
+            )]
+        )
+    }
+}
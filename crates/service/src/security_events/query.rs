@@ -0,0 +1,61 @@
+use ::entity::{security_event, security_event::Entity as SecurityEvent};
+use prelude::Uuid;
+use sea_orm::*;
+
+pub struct Query;
+
+impl Query {
+    /// Finds the most recent security events of a user, newest first.
+    pub async fn find_events_by_user_id(
+        db: &DbConn,
+        user_id: String,
+        limit: u64,
+    ) -> Result<Vec<security_event::Model>, DbErr> {
+        let uuid =
+            Uuid::parse_str(&user_id).map_err(|_| DbErr::Custom("Invalid UUID.".to_owned()))?;
+
+        SecurityEvent::find()
+            .filter(security_event::Column::UserId.eq(uuid))
+            .order_by_desc(security_event::Column::CreatedAt)
+            .limit(limit)
+            .all(db)
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_find_events_by_user_id() {
+        let user_id = Uuid::parse_str("00000000-0000-0000-0000-000000000000").unwrap();
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([[security_event::Model {
+                id: Uuid::parse_str("00000000-0000-0000-0000-000000000001").unwrap(),
+                user_id,
+                kind: "refresh_token_reused".to_owned(),
+                ip_address: None,
+                user_agent: None,
+                created_at: "2024-01-01T00:00:00+00:00".parse().unwrap(),
+            }]])
+            .into_connection();
+
+        {
+            let events = Query::find_events_by_user_id(&db, user_id.to_string(), 100)
+                .await
+                .expect("Failed to find security events");
+
+            assert_eq!(events.len(), 1);
+        }
+
+        assert_eq!(
+            db.into_transaction_log(),
+            [Transaction::from_sql_and_values(
+                DatabaseBackend::Postgres,
+                r#"SELECT "security_event"."id", "security_event"."user_id", "security_event"."kind", "security_event"."ip_address", "security_event"."user_agent", "security_event"."created_at" FROM "security_event" WHERE "security_event"."user_id" = $1 ORDER BY "security_event"."created_at" DESC LIMIT $2"#,
+                [user_id.into(), 100u64.into()]
+            )]
+        )
+    }
+}
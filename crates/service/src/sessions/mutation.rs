@@ -0,0 +1,326 @@
+use ::entity::{session, session::Entity as Session};
+use prelude::{DateTimeWithTimeZone, Uuid};
+use sea_orm::{sea_query::Expr, *};
+
+pub struct Mutation;
+
+impl Mutation {
+    pub async fn create_session(
+        db: &DbConn,
+        user_id: String,
+        user_agent: Option<String>,
+        ip_address: Option<String>,
+        expires_at: DateTimeWithTimeZone,
+        refresh_token_id: Uuid,
+    ) -> Result<session::Model, DbErr> {
+        session::ActiveModel {
+            user_id: Set(
+                Uuid::parse_str(&user_id).map_err(|_| DbErr::Custom("Invalid UUID.".to_owned()))?
+            ),
+            user_agent: Set(user_agent),
+            ip_address: Set(ip_address),
+            expires_at: Set(expires_at),
+            refresh_token_id: Set(Some(refresh_token_id)),
+            ..Default::default()
+        }
+        .insert(db)
+        .await
+    }
+
+    /// Moves a session on from its current refresh token to the next one,
+    /// which also extends it. Returns false, changing nothing, when
+    /// `current_token_id` is not the current token of the session.
+    #[allow(clippy::too_many_arguments)]
+    pub async fn rotate_session(
+        db: &DbConn,
+        id: Uuid,
+        current_token_id: Option<Uuid>,
+        next_token_id: Uuid,
+        user_agent: Option<String>,
+        ip_address: Option<String>,
+        used_at: DateTimeWithTimeZone,
+        expires_at: DateTimeWithTimeZone,
+    ) -> Result<bool, DbErr> {
+        let current = match current_token_id {
+            Some(token_id) => session::Column::RefreshTokenId.eq(token_id),
+            None => session::Column::RefreshTokenId.is_null(),
+        };
+
+        let result = Session::update_many()
+            .col_expr(
+                session::Column::RefreshTokenId,
+                Expr::value(Some(next_token_id)),
+            )
+            .col_expr(session::Column::UserAgent, Expr::value(user_agent))
+            .col_expr(session::Column::IpAddress, Expr::value(ip_address))
+            .col_expr(session::Column::LastUsedAt, Expr::value(used_at))
+            .col_expr(session::Column::ExpiresAt, Expr::value(expires_at))
+            .filter(session::Column::Id.eq(id))
+            .filter(current)
+            .exec(db)
+            .await?;
+        Ok(result.rows_affected == 1)
+    }
+
+    /// Deletes a session of a user. Sessions of other users are left alone.
+    pub async fn delete_session(
+        db: &DbConn,
+        user_id: String,
+        id: String,
+    ) -> Result<DeleteResult, DbErr> {
+        let user_id =
+            Uuid::parse_str(&user_id).map_err(|_| DbErr::Custom("Invalid UUID.".to_owned()))?;
+        let id = Uuid::parse_str(&id).map_err(|_| DbErr::Custom("Invalid UUID.".to_owned()))?;
+
+        Session::delete_many()
+            .filter(session::Column::Id.eq(id))
+            .filter(session::Column::UserId.eq(user_id))
+            .exec(db)
+            .await
+    }
+
+    pub async fn delete_sessions_by_user_id(
+        db: &DbConn,
+        user_id: String,
+    ) -> Result<DeleteResult, DbErr> {
+        let user_id =
+            Uuid::parse_str(&user_id).map_err(|_| DbErr::Custom("Invalid UUID.".to_owned()))?;
+
+        Session::delete_many()
+            .filter(session::Column::UserId.eq(user_id))
+            .exec(db)
+            .await
+    }
+
+    /// Deletes the sessions of a user that expired before `now`.
+    pub async fn delete_expired_sessions(
+        db: &DbConn,
+        user_id: String,
+        now: DateTimeWithTimeZone,
+    ) -> Result<DeleteResult, DbErr> {
+        let user_id =
+            Uuid::parse_str(&user_id).map_err(|_| DbErr::Custom("Invalid UUID.".to_owned()))?;
+
+        Session::delete_many()
+            .filter(session::Column::UserId.eq(user_id))
+            .filter(session::Column::ExpiresAt.lte(now))
+            .exec(db)
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn user_id() -> Uuid {
+        Uuid::parse_str("00000000-0000-0000-0000-000000000000").unwrap()
+    }
+
+    fn token_id() -> Uuid {
+        Uuid::parse_str("00000000-0000-0000-0000-000000000002").unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_create_session() {
+        let expires_at: DateTimeWithTimeZone = "2024-01-08T00:00:00+00:00".parse().unwrap();
+        let session = session::Model {
+            id: Uuid::parse_str("00000000-0000-0000-0000-000000000001").unwrap(),
+            user_id: user_id(),
+            user_agent: Some("curl/8.0".to_owned()),
+            ip_address: None,
+            created_at: "2024-01-01T00:00:00+00:00".parse().unwrap(),
+            last_used_at: "2024-01-01T00:00:00+00:00".parse().unwrap(),
+            expires_at,
+            refresh_token_id: Some(token_id()),
+        };
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([[session.to_owned()]])
+            .into_connection();
+
+        assert_eq!(
+            Mutation::create_session(
+                &db,
+                user_id().to_string(),
+                Some("curl/8.0".to_owned()),
+                None,
+                expires_at,
+                token_id(),
+            )
+            .await
+            .expect("Failed to create session"),
+            session
+        );
+
+        assert_eq!(
+            db.into_transaction_log(),
+            [Transaction::from_sql_and_values(
+                DatabaseBackend::Postgres,
+                r#"INSERT INTO "session" ("user_id", "user_agent", "ip_address", "expires_at", "refresh_token_id") VALUES ($1, $2, $3, $4, $5) RETURNING "id", "user_id", "user_agent", "ip_address", "created_at", "last_used_at", "expires_at", "refresh_token_id""#,
+                [
+                    user_id().into(),
+                    Some("curl/8.0".to_owned()).into(),
+                    Option::<String>::None.into(),
+                    expires_at.into(),
+                    Some(token_id()).into(),
+                ]
+            )]
+        )
+    }
+
+    #[tokio::test]
+    async fn test_rotate_session() {
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_exec_results([
+                MockExecResult {
+                    last_insert_id: 0,
+                    rows_affected: 1,
+                },
+                MockExecResult {
+                    last_insert_id: 0,
+                    rows_affected: 0,
+                },
+            ])
+            .into_connection();
+
+        let id = Uuid::parse_str("00000000-0000-0000-0000-000000000001").unwrap();
+        let next_token_id = Uuid::parse_str("00000000-0000-0000-0000-000000000003").unwrap();
+        let used_at: DateTimeWithTimeZone = "2024-01-02T00:00:00+00:00".parse().unwrap();
+        let expires_at: DateTimeWithTimeZone = "2024-01-09T00:00:00+00:00".parse().unwrap();
+        for (current_token_id, rotated) in [(Some(token_id()), true), (None, false)] {
+            assert_eq!(
+                Mutation::rotate_session(
+                    &db,
+                    id,
+                    current_token_id,
+                    next_token_id,
+                    Some("curl/8.0".to_owned()),
+                    Some("127.0.0.1".to_owned()),
+                    used_at,
+                    expires_at,
+                )
+                .await
+                .expect("Failed to rotate session"),
+                rotated
+            );
+        }
+
+        assert_eq!(
+            db.into_transaction_log(),
+            [
+                Transaction::from_sql_and_values(
+                    DatabaseBackend::Postgres,
+                    r#"UPDATE "session" SET "refresh_token_id" = $1, "user_agent" = $2, "ip_address" = $3, "last_used_at" = $4, "expires_at" = $5 WHERE "session"."id" = $6 AND "session"."refresh_token_id" = $7"#,
+                    [
+                        Some(next_token_id).into(),
+                        Some("curl/8.0".to_owned()).into(),
+                        Some("127.0.0.1".to_owned()).into(),
+                        used_at.into(),
+                        expires_at.into(),
+                        id.into(),
+                        token_id().into()
+                    ]
+                ),
+                Transaction::from_sql_and_values(
+                    DatabaseBackend::Postgres,
+                    r#"UPDATE "session" SET "refresh_token_id" = $1, "user_agent" = $2, "ip_address" = $3, "last_used_at" = $4, "expires_at" = $5 WHERE "session"."id" = $6 AND "session"."refresh_token_id" IS NULL"#,
+                    [
+                        Some(next_token_id).into(),
+                        Some("curl/8.0".to_owned()).into(),
+                        Some("127.0.0.1".to_owned()).into(),
+                        used_at.into(),
+                        expires_at.into(),
+                        id.into()
+                    ]
+                )
+            ]
+        )
+    }
+
+    #[tokio::test]
+    async fn test_delete_session() {
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_exec_results([MockExecResult {
+                last_insert_id: 0,
+                rows_affected: 1,
+            }])
+            .into_connection();
+
+        {
+            let result = Mutation::delete_session(
+                &db,
+                user_id().to_string(),
+                "00000000-0000-0000-0000-000000000001".to_owned(),
+            )
+            .await
+            .expect("Failed to delete session");
+
+            assert_eq!(result.rows_affected, 1);
+        }
+
+        assert_eq!(
+            db.into_transaction_log(),
+            [Transaction::from_sql_and_values(
+                DatabaseBackend::Postgres,
+                r#"DELETE FROM "session" WHERE "session"."id" = $1 AND "session"."user_id" = $2"#,
+                [
+                    Uuid::parse_str("00000000-0000-0000-0000-000000000001")
+                        .unwrap()
+                        .into(),
+                    user_id().into(),
+                ]
+            )]
+        )
+    }
+
+    #[tokio::test]
+    async fn test_delete_sessions_by_user_id() {
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_exec_results([MockExecResult {
+                last_insert_id: 0,
+                rows_affected: 2,
+            }])
+            .into_connection();
+
+        {
+            let result = Mutation::delete_sessions_by_user_id(&db, user_id().to_string())
+                .await
+                .expect("Failed to delete sessions");
+
+            assert_eq!(result.rows_affected, 2);
+        }
+
+        assert_eq!(
+            db.into_transaction_log(),
+            [Transaction::from_sql_and_values(
+                DatabaseBackend::Postgres,
+                r#"DELETE FROM "session" WHERE "session"."user_id" = $1"#,
+                [user_id().into()]
+            )]
+        )
+    }
+
+    #[tokio::test]
+    async fn test_delete_expired_sessions() {
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_exec_results([MockExecResult {
+                last_insert_id: 0,
+                rows_affected: 1,
+            }])
+            .into_connection();
+
+        let now: DateTimeWithTimeZone = "2024-01-09T00:00:00+00:00".parse().unwrap();
+        Mutation::delete_expired_sessions(&db, user_id().to_string(), now)
+            .await
+            .expect("Failed to delete expired sessions");
+
+        assert_eq!(
+            db.into_transaction_log(),
+            [Transaction::from_sql_and_values(
+                DatabaseBackend::Postgres,
+                r#"DELETE FROM "session" WHERE "session"."user_id" = $1 AND "session"."expires_at" <= $2"#,
+                [user_id().into(), now.into()]
+            )]
+        )
+    }
+}
@@ -0,0 +1,122 @@
+use ::entity::{session, session::Entity as Session};
+use prelude::{DateTimeWithTimeZone, Uuid};
+use sea_orm::*;
+
+pub struct Query;
+
+impl Query {
+    pub async fn find_session_by_id(
+        db: &DbConn,
+        id: String,
+    ) -> Result<Option<session::Model>, DbErr> {
+        let uuid = Uuid::parse_str(&id).map_err(|_| DbErr::Custom("Invalid UUID.".to_owned()))?;
+
+        Session::find_by_id(uuid).one(db).await
+    }
+
+    /// Finds the sessions of a user that have not expired at `now`, most
+    /// recently used first.
+    pub async fn find_sessions_by_user_id(
+        db: &DbConn,
+        user_id: String,
+        now: DateTimeWithTimeZone,
+    ) -> Result<Vec<session::Model>, DbErr> {
+        let uuid =
+            Uuid::parse_str(&user_id).map_err(|_| DbErr::Custom("Invalid UUID.".to_owned()))?;
+
+        Session::find()
+            .filter(session::Column::UserId.eq(uuid))
+            .filter(session::Column::ExpiresAt.gt(now))
+            .order_by_desc(session::Column::LastUsedAt)
+            .all(db)
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn create_session_with_id(id: &str) -> session::Model {
+        session::Model {
+            id: Uuid::parse_str(id).unwrap(),
+            user_id: Uuid::parse_str("00000000-0000-0000-0000-000000000000").unwrap(),
+            user_agent: Some("curl/8.0".to_owned()),
+            ip_address: Some("127.0.0.1".to_owned()),
+            created_at: "2024-01-01T00:00:00+00:00".parse().unwrap(),
+            last_used_at: "2024-01-01T00:00:00+00:00".parse().unwrap(),
+            expires_at: "2024-01-08T00:00:00+00:00".parse().unwrap(),
+            refresh_token_id: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_find_session_by_id() {
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([[create_session_with_id(
+                "00000000-0000-0000-0000-000000000001",
+            )]])
+            .into_connection();
+
+        {
+            let session =
+                Query::find_session_by_id(&db, "00000000-0000-0000-0000-000000000001".to_owned())
+                    .await
+                    .expect("Failed to find session")
+                    .expect("Session not found");
+
+            assert_eq!(session.user_agent.as_deref(), Some("curl/8.0"));
+        }
+
+        assert_eq!(
+            db.into_transaction_log(),
+            [Transaction::from_sql_and_values(
+                DatabaseBackend::Postgres,
+                r#"SELECT "session"."id", "session"."user_id", "session"."user_agent", "session"."ip_address", "session"."created_at", "session"."last_used_at", "session"."expires_at", "session"."refresh_token_id" FROM "session" WHERE "session"."id" = $1 LIMIT $2"#,
+                [
+                    Uuid::parse_str("00000000-0000-0000-0000-000000000001")
+                        .unwrap()
+                        .into(),
+                    1u64.into()
+                ]
+            )]
+        )
+    }
+
+    #[tokio::test]
+    async fn test_find_sessions_by_user_id() {
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([[
+                create_session_with_id("00000000-0000-0000-0000-000000000001"),
+                create_session_with_id("00000000-0000-0000-0000-000000000002"),
+            ]])
+            .into_connection();
+
+        let now: DateTimeWithTimeZone = "2024-01-02T00:00:00+00:00".parse().unwrap();
+        {
+            let sessions = Query::find_sessions_by_user_id(
+                &db,
+                "00000000-0000-0000-0000-000000000000".to_owned(),
+                now,
+            )
+            .await
+            .expect("Failed to find sessions");
+
+            assert_eq!(sessions.len(), 2);
+        }
+
+        assert_eq!(
+            db.into_transaction_log(),
+            [Transaction::from_sql_and_values(
+                DatabaseBackend::Postgres,
+                r#"SELECT "session"."id", "session"."user_id", "session"."user_agent", "session"."ip_address", "session"."created_at", "session"."last_used_at", "session"."expires_at", "session"."refresh_token_id" FROM "session" WHERE "session"."user_id" = $1 AND "session"."expires_at" > $2 ORDER BY "session"."last_used_at" DESC"#,
+                [
+                    Uuid::parse_str("00000000-0000-0000-0000-000000000000")
+                        .unwrap()
+                        .into(),
+                    now.into()
+                ]
+            )]
+        )
+    }
+}
@@ -0,0 +1,55 @@
+use ::entity::{setting, setting::Entity as Setting};
+use sea_orm::{
+    sea_query::{Expr, OnConflict},
+    *,
+};
+
+pub struct Mutation;
+
+impl Mutation {
+    /// Creates or replaces a setting.
+    pub async fn set_setting(db: &DbConn, key: &str, value: String) -> Result<(), DbErr> {
+        Setting::insert(setting::ActiveModel {
+            key: Set(key.to_owned()),
+            value: Set(value),
+            ..Default::default()
+        })
+        .on_conflict(
+            OnConflict::column(setting::Column::Key)
+                .update_column(setting::Column::Value)
+                .value(setting::Column::UpdatedAt, Expr::current_timestamp())
+                .to_owned(),
+        )
+        .exec_without_returning(db)
+        .await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_set_setting() {
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_exec_results([MockExecResult {
+                last_insert_id: 0,
+                rows_affected: 1,
+            }])
+            .into_connection();
+
+        Mutation::set_setting(&db, "registration_mode", "closed".to_owned())
+            .await
+            .expect("Failed to set setting");
+
+        assert_eq!(
+            db.into_transaction_log(),
+            [Transaction::from_sql_and_values(
+                DatabaseBackend::Postgres,
+                r#"INSERT INTO "setting" ("key", "value") VALUES ($1, $2) ON CONFLICT ("key") DO UPDATE SET "value" = "excluded"."value", "updated_at" = CURRENT_TIMESTAMP"#,
+                ["registration_mode".into(), "closed".into()]
+            )]
+        )
+    }
+}
@@ -0,0 +1,48 @@
+use ::entity::setting::Entity as Setting;
+use sea_orm::*;
+
+pub struct Query;
+
+impl Query {
+    pub async fn find_setting(db: &DbConn, key: &str) -> Result<Option<String>, DbErr> {
+        Ok(Setting::find_by_id(key.to_owned())
+            .one(db)
+            .await?
+            .map(|setting| setting.value))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ::entity::setting;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_find_setting() {
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([[setting::Model {
+                key: "registration_mode".to_owned(),
+                value: "open".to_owned(),
+                updated_at: "2024-01-01T00:00:00+00:00".parse().unwrap(),
+            }]])
+            .into_connection();
+
+        {
+            let value = Query::find_setting(&db, "registration_mode")
+                .await
+                .expect("Failed to find setting");
+
+            assert_eq!(value.as_deref(), Some("open"));
+        }
+
+        assert_eq!(
+            db.into_transaction_log(),
+            [Transaction::from_sql_and_values(
+                DatabaseBackend::Postgres,
+                r#"SELECT "setting"."key", "setting"."value", "setting"."updated_at" FROM "setting" WHERE "setting"."key" = $1 LIMIT $2"#,
+                ["registration_mode".into(), 1u64.into()]
+            )]
+        )
+    }
+}
@@ -0,0 +1,225 @@
+use ::entity::{
+    sea_orm_active_enums::TeamRoleEnum, team, team::Entity as Team, team_member,
+    team_member::Entity as TeamMember,
+};
+use prelude::Uuid;
+use sea_orm::{sea_query::OnConflict, *};
+
+pub struct Mutation;
+
+impl Mutation {
+    /// Creates a team with its creator as the owner.
+    pub async fn create_team(
+        db: &DbConn,
+        name: String,
+        owner_id: String,
+    ) -> Result<team::Model, DbErr> {
+        let owner_id =
+            Uuid::parse_str(&owner_id).map_err(|_| DbErr::Custom("Invalid UUID.".to_owned()))?;
+        let txn = db.begin().await?;
+
+        let team = team::ActiveModel {
+            name: Set(name),
+            ..Default::default()
+        }
+        .insert(&txn)
+        .await?;
+
+        TeamMember::insert(team_member::ActiveModel {
+            team_id: Set(team.id),
+            user_id: Set(owner_id),
+            role: Set(TeamRoleEnum::Owner),
+            ..Default::default()
+        })
+        .exec_without_returning(&txn)
+        .await?;
+
+        txn.commit().await?;
+        Ok(team)
+    }
+
+    pub async fn delete_team(db: &DbConn, id: String) -> Result<DeleteResult, DbErr> {
+        let uuid = Uuid::parse_str(&id).map_err(|_| DbErr::Custom("Invalid UUID.".to_owned()))?;
+
+        Team::delete_by_id(uuid).exec(db).await
+    }
+
+    /// Adds a member to a team, or changes the role of an existing one.
+    pub async fn set_team_member(
+        db: &DbConn,
+        team_id: Uuid,
+        user_id: Uuid,
+        role: TeamRoleEnum,
+    ) -> Result<(), DbErr> {
+        TeamMember::insert(team_member::ActiveModel {
+            team_id: Set(team_id),
+            user_id: Set(user_id),
+            role: Set(role),
+            ..Default::default()
+        })
+        .on_conflict(
+            OnConflict::columns([team_member::Column::TeamId, team_member::Column::UserId])
+                .update_column(team_member::Column::Role)
+                .to_owned(),
+        )
+        .exec_without_returning(db)
+        .await?;
+        Ok(())
+    }
+
+    pub async fn remove_team_member(
+        db: &DbConn,
+        team_id: Uuid,
+        user_id: Uuid,
+    ) -> Result<DeleteResult, DbErr> {
+        TeamMember::delete_by_id((team_id, user_id)).exec(db).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn create_team_with_id(id: &str) -> team::Model {
+        team::Model {
+            id: Uuid::parse_str(id).unwrap(),
+            name: "Platform".to_owned(),
+            created_at: "2024-01-01T00:00:00+00:00".parse().unwrap(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_create_team() {
+        let team = create_team_with_id("00000000-0000-0000-0000-000000000001");
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([[team.to_owned()]])
+            .append_exec_results([MockExecResult {
+                last_insert_id: 0,
+                rows_affected: 1,
+            }])
+            .into_connection();
+
+        assert_eq!(
+            Mutation::create_team(
+                &db,
+                "Platform".to_owned(),
+                "00000000-0000-0000-0000-000000000000".to_owned(),
+            )
+            .await
+            .expect("Failed to create team"),
+            team
+        );
+
+        assert_eq!(
+            db.into_transaction_log(),
+            [Transaction::many([
+                Statement::from_string(DatabaseBackend::Postgres, "BEGIN"),
+                Statement::from_sql_and_values(
+                    DatabaseBackend::Postgres,
+                    r#"INSERT INTO "team" ("name") VALUES ($1) RETURNING "id", "name", "created_at""#,
+                    ["Platform".into()]
+                ),
+                Statement::from_sql_and_values(
+                    DatabaseBackend::Postgres,
+                    r#"INSERT INTO "team_member" ("team_id", "user_id", "role") VALUES ($1, $2, CAST($3 AS team_role_enum))"#,
+                    [
+                        team.id.into(),
+                        Uuid::parse_str("00000000-0000-0000-0000-000000000000")
+                            .unwrap()
+                            .into(),
+                        TeamRoleEnum::Owner.into(),
+                    ]
+                ),
+                Statement::from_string(DatabaseBackend::Postgres, "COMMIT"),
+            ])]
+        )
+    }
+
+    #[tokio::test]
+    async fn test_delete_team() {
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_exec_results([MockExecResult {
+                last_insert_id: 0,
+                rows_affected: 1,
+            }])
+            .into_connection();
+
+        {
+            let result =
+                Mutation::delete_team(&db, "00000000-0000-0000-0000-000000000001".to_owned())
+                    .await
+                    .expect("Failed to delete team");
+
+            assert_eq!(result.rows_affected, 1);
+        }
+
+        assert_eq!(
+            db.into_transaction_log(),
+            [Transaction::from_sql_and_values(
+                DatabaseBackend::Postgres,
+                r#"DELETE FROM "team" WHERE "team"."id" = $1"#,
+                [Uuid::parse_str("00000000-0000-0000-0000-000000000001")
+                    .unwrap()
+                    .into()]
+            )]
+        )
+    }
+
+    #[tokio::test]
+    async fn test_set_team_member() {
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_exec_results([MockExecResult {
+                last_insert_id: 0,
+                rows_affected: 1,
+            }])
+            .into_connection();
+
+        let team_id = Uuid::parse_str("00000000-0000-0000-0000-000000000001").unwrap();
+        let user_id = Uuid::parse_str("00000000-0000-0000-0000-000000000000").unwrap();
+        Mutation::set_team_member(&db, team_id, user_id, TeamRoleEnum::Maintainer)
+            .await
+            .expect("Failed to set team member");
+
+        assert_eq!(
+            db.into_transaction_log(),
+            [Transaction::from_sql_and_values(
+                DatabaseBackend::Postgres,
+                r#"INSERT INTO "team_member" ("team_id", "user_id", "role") VALUES ($1, $2, CAST($3 AS team_role_enum)) ON CONFLICT ("team_id", "user_id") DO UPDATE SET "role" = "excluded"."role""#,
+                [
+                    team_id.into(),
+                    user_id.into(),
+                    TeamRoleEnum::Maintainer.into()
+                ]
+            )]
+        )
+    }
+
+    #[tokio::test]
+    async fn test_remove_team_member() {
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_exec_results([MockExecResult {
+                last_insert_id: 0,
+                rows_affected: 1,
+            }])
+            .into_connection();
+
+        let team_id = Uuid::parse_str("00000000-0000-0000-0000-000000000001").unwrap();
+        let user_id = Uuid::parse_str("00000000-0000-0000-0000-000000000000").unwrap();
+        {
+            let result = Mutation::remove_team_member(&db, team_id, user_id)
+                .await
+                .expect("Failed to remove team member");
+
+            assert_eq!(result.rows_affected, 1);
+        }
+
+        assert_eq!(
+            db.into_transaction_log(),
+            [Transaction::from_sql_and_values(
+                DatabaseBackend::Postgres,
+                r#"DELETE FROM "team_member" WHERE "team_member"."team_id" = $1 AND "team_member"."user_id" = $2"#,
+                [team_id.into(), user_id.into()]
+            )]
+        )
+    }
+}
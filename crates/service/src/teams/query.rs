@@ -0,0 +1,243 @@
+use ::entity::{
+    sea_orm_active_enums::TeamRoleEnum, team, team::Entity as Team, team_member,
+    team_member::Entity as TeamMember, user,
+};
+use prelude::Uuid;
+use sea_orm::*;
+
+pub struct Query;
+
+impl Query {
+    pub async fn find_team_by_id(db: &DbConn, id: String) -> Result<Option<team::Model>, DbErr> {
+        let uuid = Uuid::parse_str(&id).map_err(|_| DbErr::Custom("Invalid UUID.".to_owned()))?;
+
+        Team::find_by_id(uuid).one(db).await
+    }
+
+    pub async fn find_all_teams(db: &DbConn) -> Result<Vec<team::Model>, DbErr> {
+        Team::find().order_by_asc(team::Column::Name).all(db).await
+    }
+
+    /// Finds the teams a user is a member of.
+    pub async fn find_teams_by_user_id(
+        db: &DbConn,
+        user_id: String,
+    ) -> Result<Vec<team::Model>, DbErr> {
+        let uuid =
+            Uuid::parse_str(&user_id).map_err(|_| DbErr::Custom("Invalid UUID.".to_owned()))?;
+
+        Team::find()
+            .inner_join(TeamMember)
+            .filter(team_member::Column::UserId.eq(uuid))
+            .order_by_asc(team::Column::Name)
+            .all(db)
+            .await
+    }
+
+    pub async fn find_team_members(
+        db: &DbConn,
+        team_id: Uuid,
+    ) -> Result<Vec<(team_member::Model, Option<user::Model>)>, DbErr> {
+        TeamMember::find()
+            .find_also_related(user::Entity)
+            .filter(team_member::Column::TeamId.eq(team_id))
+            .order_by_asc(team_member::Column::CreatedAt)
+            .all(db)
+            .await
+    }
+
+    pub async fn find_team_member(
+        db: &DbConn,
+        team_id: Uuid,
+        user_id: Uuid,
+    ) -> Result<Option<team_member::Model>, DbErr> {
+        TeamMember::find_by_id((team_id, user_id)).one(db).await
+    }
+
+    pub async fn count_team_owners(db: &DbConn, team_id: Uuid) -> Result<u64, DbErr> {
+        TeamMember::find()
+            .filter(team_member::Column::TeamId.eq(team_id))
+            .filter(team_member::Column::Role.eq(TeamRoleEnum::Owner))
+            .count(db)
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ::entity::sea_orm_active_enums::{RoleEnum, UserStatusEnum};
+
+    use super::*;
+
+    fn create_team_with_id(id: &str) -> team::Model {
+        team::Model {
+            id: Uuid::parse_str(id).unwrap(),
+            name: "Platform".to_owned(),
+            created_at: "2024-01-01T00:00:00+00:00".parse().unwrap(),
+        }
+    }
+
+    fn create_member(team_id: &str, user_id: &str, role: TeamRoleEnum) -> team_member::Model {
+        team_member::Model {
+            team_id: Uuid::parse_str(team_id).unwrap(),
+            user_id: Uuid::parse_str(user_id).unwrap(),
+            role,
+            created_at: "2024-01-01T00:00:00+00:00".parse().unwrap(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_find_team_by_id() {
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([[create_team_with_id("00000000-0000-0000-0000-000000000001")]])
+            .into_connection();
+
+        {
+            let team =
+                Query::find_team_by_id(&db, "00000000-0000-0000-0000-000000000001".to_owned())
+                    .await
+                    .expect("Failed to find team")
+                    .expect("Team not found");
+
+            assert_eq!(team.name, "Platform");
+        }
+
+        assert_eq!(
+            db.into_transaction_log(),
+            [Transaction::from_sql_and_values(
+                DatabaseBackend::Postgres,
+                r#"SELECT "team"."id", "team"."name", "team"."created_at" FROM "team" WHERE "team"."id" = $1 LIMIT $2"#,
+                [
+                    Uuid::parse_str("00000000-0000-0000-0000-000000000001")
+                        .unwrap()
+                        .into(),
+                    1u64.into()
+                ]
+            )]
+        )
+    }
+
+    #[tokio::test]
+    async fn test_find_teams_by_user_id() {
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([[create_team_with_id("00000000-0000-0000-0000-000000000001")]])
+            .into_connection();
+
+        {
+            let teams = Query::find_teams_by_user_id(
+                &db,
+                "00000000-0000-0000-0000-000000000000".to_owned(),
+            )
+            .await
+            .expect("Failed to find teams");
+
+            assert_eq!(teams.len(), 1);
+        }
+
+        assert_eq!(
+            db.into_transaction_log(),
+            [Transaction::from_sql_and_values(
+                DatabaseBackend::Postgres,
+                r#"SELECT "team"."id", "team"."name", "team"."created_at" FROM "team" INNER JOIN "team_member" ON "team"."id" = "team_member"."team_id" WHERE "team_member"."user_id" = $1 ORDER BY "team"."name" ASC"#,
+                [Uuid::parse_str("00000000-0000-0000-0000-000000000000")
+                    .unwrap()
+                    .into()]
+            )]
+        )
+    }
+
+    #[tokio::test]
+    async fn test_find_team_members() {
+        let user = user::Model {
+            id: Uuid::parse_str("00000000-0000-0000-0000-000000000000").unwrap(),
+            email: "test@example.com".to_owned(),
+            username: "Test".to_owned(),
+            password: "password".to_owned(),
+            roles: vec![RoleEnum::User],
+            status: UserStatusEnum::Active,
+        };
+        let member = create_member(
+            "00000000-0000-0000-0000-000000000001",
+            "00000000-0000-0000-0000-000000000000",
+            TeamRoleEnum::Owner,
+        );
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([[(member.to_owned(), user.to_owned())]])
+            .into_connection();
+
+        let team_id = Uuid::parse_str("00000000-0000-0000-0000-000000000001").unwrap();
+        {
+            let members = Query::find_team_members(&db, team_id)
+                .await
+                .expect("Failed to find team members");
+
+            assert_eq!(members, [(member, Some(user))]);
+        }
+
+        assert_eq!(
+            db.into_transaction_log(),
+            [Transaction::from_sql_and_values(
+                DatabaseBackend::Postgres,
+                r#"SELECT "team_member"."team_id" AS "A_team_id", "team_member"."user_id" AS "A_user_id", CAST("team_member"."role" AS text) AS "A_role", "team_member"."created_at" AS "A_created_at", "user"."id" AS "B_id", "user"."email" AS "B_email", "user"."username" AS "B_username", "user"."password" AS "B_password", CAST("user"."roles" AS text[]) AS "B_roles", CAST("user"."status" AS text) AS "B_status" FROM "team_member" LEFT JOIN "user" ON "team_member"."user_id" = "user"."id" WHERE "team_member"."team_id" = $1 ORDER BY "team_member"."created_at" ASC"#,
+                [team_id.into()]
+            )]
+        )
+    }
+
+    #[tokio::test]
+    async fn test_find_team_member() {
+        let member = create_member(
+            "00000000-0000-0000-0000-000000000001",
+            "00000000-0000-0000-0000-000000000000",
+            TeamRoleEnum::Viewer,
+        );
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([[member.to_owned()]])
+            .into_connection();
+
+        let team_id = Uuid::parse_str("00000000-0000-0000-0000-000000000001").unwrap();
+        let user_id = Uuid::parse_str("00000000-0000-0000-0000-000000000000").unwrap();
+        assert_eq!(
+            Query::find_team_member(&db, team_id, user_id)
+                .await
+                .expect("Failed to find team member"),
+            Some(member)
+        );
+
+        assert_eq!(
+            db.into_transaction_log(),
+            [Transaction::from_sql_and_values(
+                DatabaseBackend::Postgres,
+                r#"SELECT "team_member"."team_id", "team_member"."user_id", CAST("team_member"."role" AS text), "team_member"."created_at" FROM "team_member" WHERE "team_member"."team_id" = $1 AND "team_member"."user_id" = $2 LIMIT $3"#,
+                [team_id.into(), user_id.into(), 1u64.into()]
+            )]
+        )
+    }
+
+    #[tokio::test]
+    async fn test_count_team_owners() {
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([[std::collections::BTreeMap::from([(
+                "num_items",
+                Value::BigInt(Some(2)),
+            )])]])
+            .into_connection();
+
+        let team_id = Uuid::parse_str("00000000-0000-0000-0000-000000000001").unwrap();
+        assert_eq!(
+            Query::count_team_owners(&db, team_id)
+                .await
+                .expect("Failed to count team owners"),
+            2
+        );
+
+        assert_eq!(
+            db.into_transaction_log(),
+            [Transaction::from_sql_and_values(
+                DatabaseBackend::Postgres,
+                r#"SELECT COUNT(*) AS num_items FROM (SELECT "team_member"."team_id", "team_member"."user_id", CAST("team_member"."role" AS text), "team_member"."created_at" FROM "team_member" WHERE "team_member"."team_id" = $1 AND "team_member"."role" = (CAST($2 AS team_role_enum))) AS "sub_query""#,
+                [team_id.into(), TeamRoleEnum::Owner.into()]
+            )]
+        )
+    }
+}
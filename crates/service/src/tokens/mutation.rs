@@ -0,0 +1,179 @@
+use ::entity::{personal_access_token, personal_access_token::Entity as PersonalAccessToken};
+use prelude::{DateTimeWithTimeZone, Uuid};
+use sea_orm::{sea_query::Expr, *};
+
+pub struct Mutation;
+
+impl Mutation {
+    pub async fn create_token(
+        db: &DbConn,
+        user_id: String,
+        name: String,
+        token_hash: String,
+        expires_at: Option<DateTimeWithTimeZone>,
+        scopes: Vec<String>,
+    ) -> Result<personal_access_token::Model, DbErr> {
+        personal_access_token::ActiveModel {
+            user_id: Set(
+                Uuid::parse_str(&user_id).map_err(|_| DbErr::Custom("Invalid UUID.".to_owned()))?
+            ),
+            name: Set(name),
+            token_hash: Set(token_hash),
+            expires_at: Set(expires_at),
+            scopes: Set(scopes),
+            ..Default::default()
+        }
+        .insert(db)
+        .await
+    }
+
+    /// Records the use of a token.
+    pub async fn touch_token(
+        db: &DbConn,
+        id: Uuid,
+        used_at: DateTimeWithTimeZone,
+    ) -> Result<(), DbErr> {
+        PersonalAccessToken::update_many()
+            .col_expr(
+                personal_access_token::Column::LastUsedAt,
+                Expr::value(used_at),
+            )
+            .filter(personal_access_token::Column::Id.eq(id))
+            .exec(db)
+            .await?;
+        Ok(())
+    }
+
+    /// Deletes a token of a user. Tokens of other users are left alone.
+    pub async fn delete_token(
+        db: &DbConn,
+        user_id: String,
+        id: String,
+    ) -> Result<DeleteResult, DbErr> {
+        let user_id =
+            Uuid::parse_str(&user_id).map_err(|_| DbErr::Custom("Invalid UUID.".to_owned()))?;
+        let id = Uuid::parse_str(&id).map_err(|_| DbErr::Custom("Invalid UUID.".to_owned()))?;
+
+        PersonalAccessToken::delete_many()
+            .filter(personal_access_token::Column::Id.eq(id))
+            .filter(personal_access_token::Column::UserId.eq(user_id))
+            .exec(db)
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_create_token() {
+        let token = personal_access_token::Model {
+            id: Uuid::parse_str("00000000-0000-0000-0000-000000000001").unwrap(),
+            user_id: Uuid::parse_str("00000000-0000-0000-0000-000000000000").unwrap(),
+            name: "ci".to_owned(),
+            token_hash: "hash".to_owned(),
+            expires_at: None,
+            last_used_at: None,
+            created_at: "2024-01-01T00:00:00+00:00".parse().unwrap(),
+            scopes: vec!["workers:read".to_owned()],
+        };
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([[token.to_owned()]])
+            .into_connection();
+
+        assert_eq!(
+            Mutation::create_token(
+                &db,
+                "00000000-0000-0000-0000-000000000000".to_owned(),
+                "ci".to_owned(),
+                "hash".to_owned(),
+                None,
+                vec!["workers:read".to_owned()],
+            )
+            .await
+            .expect("Failed to create token"),
+            token
+        );
+
+        assert_eq!(
+            db.into_transaction_log(),
+            [Transaction::from_sql_and_values(
+                DatabaseBackend::Postgres,
+                r#"INSERT INTO "personal_access_token" ("user_id", "name", "token_hash", "expires_at", "scopes") VALUES ($1, $2, $3, $4, $5) RETURNING "id", "user_id", "name", "token_hash", "expires_at", "last_used_at", "created_at", "scopes""#,
+                [
+                    Uuid::parse_str("00000000-0000-0000-0000-000000000000")
+                        .unwrap()
+                        .into(),
+                    "ci".into(),
+                    "hash".into(),
+                    Option::<DateTimeWithTimeZone>::None.into(),
+                    vec!["workers:read".to_owned()].into(),
+                ]
+            )]
+        )
+    }
+
+    #[tokio::test]
+    async fn test_touch_token() {
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_exec_results([MockExecResult {
+                last_insert_id: 0,
+                rows_affected: 1,
+            }])
+            .into_connection();
+
+        let id = Uuid::parse_str("00000000-0000-0000-0000-000000000001").unwrap();
+        let used_at: DateTimeWithTimeZone = "2024-01-02T00:00:00+00:00".parse().unwrap();
+        Mutation::touch_token(&db, id, used_at)
+            .await
+            .expect("Failed to touch token");
+
+        assert_eq!(
+            db.into_transaction_log(),
+            [Transaction::from_sql_and_values(
+                DatabaseBackend::Postgres,
+                r#"UPDATE "personal_access_token" SET "last_used_at" = $1 WHERE "personal_access_token"."id" = $2"#,
+                [used_at.into(), id.into()]
+            )]
+        )
+    }
+
+    #[tokio::test]
+    async fn test_delete_token() {
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_exec_results([MockExecResult {
+                last_insert_id: 0,
+                rows_affected: 1,
+            }])
+            .into_connection();
+
+        {
+            let result = Mutation::delete_token(
+                &db,
+                "00000000-0000-0000-0000-000000000000".to_owned(),
+                "00000000-0000-0000-0000-000000000001".to_owned(),
+            )
+            .await
+            .expect("Failed to delete token");
+
+            assert_eq!(result.rows_affected, 1);
+        }
+
+        assert_eq!(
+            db.into_transaction_log(),
+            [Transaction::from_sql_and_values(
+                DatabaseBackend::Postgres,
+                r#"DELETE FROM "personal_access_token" WHERE "personal_access_token"."id" = $1 AND "personal_access_token"."user_id" = $2"#,
+                [
+                    Uuid::parse_str("00000000-0000-0000-0000-000000000001")
+                        .unwrap()
+                        .into(),
+                    Uuid::parse_str("00000000-0000-0000-0000-000000000000")
+                        .unwrap()
+                        .into(),
+                ]
+            )]
+        )
+    }
+}
@@ -0,0 +1,106 @@
+use ::entity::{personal_access_token, personal_access_token::Entity as PersonalAccessToken};
+use prelude::Uuid;
+use sea_orm::*;
+
+pub struct Query;
+
+impl Query {
+    pub async fn find_token_by_hash(
+        db: &DbConn,
+        token_hash: String,
+    ) -> Result<Option<personal_access_token::Model>, DbErr> {
+        PersonalAccessToken::find()
+            .filter(personal_access_token::Column::TokenHash.eq(token_hash))
+            .one(db)
+            .await
+    }
+
+    pub async fn find_tokens_by_user_id(
+        db: &DbConn,
+        user_id: String,
+    ) -> Result<Vec<personal_access_token::Model>, DbErr> {
+        let uuid =
+            Uuid::parse_str(&user_id).map_err(|_| DbErr::Custom("Invalid UUID.".to_owned()))?;
+
+        PersonalAccessToken::find()
+            .filter(personal_access_token::Column::UserId.eq(uuid))
+            .order_by_desc(personal_access_token::Column::CreatedAt)
+            .all(db)
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn create_token_with_id(id: &str) -> personal_access_token::Model {
+        personal_access_token::Model {
+            id: Uuid::parse_str(id).unwrap(),
+            user_id: Uuid::parse_str("00000000-0000-0000-0000-000000000000").unwrap(),
+            name: "ci".to_owned(),
+            token_hash: "hash".to_owned(),
+            expires_at: None,
+            last_used_at: None,
+            created_at: "2024-01-01T00:00:00+00:00".parse().unwrap(),
+            scopes: vec!["workers:read".to_owned()],
+        }
+    }
+
+    #[tokio::test]
+    async fn test_find_token_by_hash() {
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([[create_token_with_id("00000000-0000-0000-0000-000000000001")]])
+            .into_connection();
+
+        {
+            let token = Query::find_token_by_hash(&db, "hash".to_owned())
+                .await
+                .expect("Failed to find token")
+                .expect("Token not found");
+
+            assert_eq!(token.name, "ci");
+        }
+
+        assert_eq!(
+            db.into_transaction_log(),
+            [Transaction::from_sql_and_values(
+                DatabaseBackend::Postgres,
+                r#"SELECT "personal_access_token"."id", "personal_access_token"."user_id", "personal_access_token"."name", "personal_access_token"."token_hash", "personal_access_token"."expires_at", "personal_access_token"."last_used_at", "personal_access_token"."created_at", "personal_access_token"."scopes" FROM "personal_access_token" WHERE "personal_access_token"."token_hash" = $1 LIMIT $2"#,
+                ["hash".into(), 1u64.into()]
+            )]
+        )
+    }
+
+    #[tokio::test]
+    async fn test_find_tokens_by_user_id() {
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([[
+                create_token_with_id("00000000-0000-0000-0000-000000000001"),
+                create_token_with_id("00000000-0000-0000-0000-000000000002"),
+            ]])
+            .into_connection();
+
+        {
+            let tokens = Query::find_tokens_by_user_id(
+                &db,
+                "00000000-0000-0000-0000-000000000000".to_owned(),
+            )
+            .await
+            .expect("Failed to find tokens");
+
+            assert_eq!(tokens.len(), 2);
+        }
+
+        assert_eq!(
+            db.into_transaction_log(),
+            [Transaction::from_sql_and_values(
+                DatabaseBackend::Postgres,
+                r#"SELECT "personal_access_token"."id", "personal_access_token"."user_id", "personal_access_token"."name", "personal_access_token"."token_hash", "personal_access_token"."expires_at", "personal_access_token"."last_used_at", "personal_access_token"."created_at", "personal_access_token"."scopes" FROM "personal_access_token" WHERE "personal_access_token"."user_id" = $1 ORDER BY "personal_access_token"."created_at" DESC"#,
+                [Uuid::parse_str("00000000-0000-0000-0000-000000000000")
+                    .unwrap()
+                    .into()]
+            )]
+        )
+    }
+}
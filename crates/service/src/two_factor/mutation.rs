@@ -0,0 +1,305 @@
+use ::entity::{
+    recovery_code, recovery_code::Entity as RecoveryCode, user_totp, user_totp::Entity as UserTotp,
+};
+use prelude::{DateTimeWithTimeZone, Uuid};
+use sea_orm::{
+    sea_query::{Expr, OnConflict},
+    *,
+};
+
+pub struct Mutation;
+
+impl Mutation {
+    /// Stores the secret of an enrollment, replacing one that was never
+    /// confirmed. Returns false, without changes, when the user already
+    /// confirmed a secret.
+    pub async fn start_totp(db: &DbConn, user_id: Uuid, secret: String) -> Result<bool, DbErr> {
+        let result = UserTotp::insert(user_totp::ActiveModel {
+            user_id: Set(user_id),
+            secret: Set(secret),
+            confirmed_at: Set(None),
+            last_used_step: Set(None),
+            ..Default::default()
+        })
+        .on_conflict(
+            OnConflict::column(user_totp::Column::UserId)
+                .update_columns([
+                    user_totp::Column::Secret,
+                    user_totp::Column::ConfirmedAt,
+                    user_totp::Column::LastUsedStep,
+                ])
+                .action_and_where(Expr::col((UserTotp, user_totp::Column::ConfirmedAt)).is_null())
+                .to_owned(),
+        )
+        .exec_without_returning(db)
+        .await?;
+        Ok(result > 0)
+    }
+
+    /// Confirms the enrollment of a user and replaces the recovery codes.
+    /// Returns false when there is no enrollment to confirm.
+    pub async fn confirm_totp(
+        db: &DbConn,
+        user_id: Uuid,
+        now: DateTimeWithTimeZone,
+        code_hashes: Vec<String>,
+    ) -> Result<bool, DbErr> {
+        let txn = db.begin().await?;
+
+        let result = UserTotp::update_many()
+            .col_expr(user_totp::Column::ConfirmedAt, Expr::value(now))
+            .filter(user_totp::Column::UserId.eq(user_id))
+            .filter(user_totp::Column::ConfirmedAt.is_null())
+            .exec(&txn)
+            .await?;
+        if result.rows_affected == 0 {
+            txn.rollback().await?;
+            return Ok(false);
+        }
+
+        RecoveryCode::delete_many()
+            .filter(recovery_code::Column::UserId.eq(user_id))
+            .exec(&txn)
+            .await?;
+        RecoveryCode::insert_many(code_hashes.into_iter().map(|code_hash| {
+            recovery_code::ActiveModel {
+                user_id: Set(user_id),
+                code_hash: Set(code_hash),
+                ..Default::default()
+            }
+        }))
+        .exec_without_returning(&txn)
+        .await?;
+
+        txn.commit().await?;
+        Ok(true)
+    }
+
+    /// Records the time step of an accepted code. Returns false when that
+    /// step or a later one was used before, so that codes work once.
+    pub async fn use_totp_step(db: &DbConn, user_id: Uuid, step: i64) -> Result<bool, DbErr> {
+        let result = UserTotp::update_many()
+            .col_expr(user_totp::Column::LastUsedStep, Expr::value(step))
+            .filter(user_totp::Column::UserId.eq(user_id))
+            .filter(
+                Condition::any()
+                    .add(user_totp::Column::LastUsedStep.is_null())
+                    .add(user_totp::Column::LastUsedStep.lt(step)),
+            )
+            .exec(db)
+            .await?;
+        Ok(result.rows_affected > 0)
+    }
+
+    /// Marks a recovery code as used. Returns false when the user has no
+    /// such code left.
+    pub async fn use_recovery_code(
+        db: &DbConn,
+        user_id: Uuid,
+        code_hash: String,
+        now: DateTimeWithTimeZone,
+    ) -> Result<bool, DbErr> {
+        let result = RecoveryCode::update_many()
+            .col_expr(recovery_code::Column::UsedAt, Expr::value(now))
+            .filter(recovery_code::Column::UserId.eq(user_id))
+            .filter(recovery_code::Column::CodeHash.eq(code_hash))
+            .filter(recovery_code::Column::UsedAt.is_null())
+            .exec(db)
+            .await?;
+        Ok(result.rows_affected > 0)
+    }
+
+    pub async fn replace_recovery_codes(
+        db: &DbConn,
+        user_id: Uuid,
+        code_hashes: Vec<String>,
+    ) -> Result<(), DbErr> {
+        let txn = db.begin().await?;
+
+        RecoveryCode::delete_many()
+            .filter(recovery_code::Column::UserId.eq(user_id))
+            .exec(&txn)
+            .await?;
+        RecoveryCode::insert_many(code_hashes.into_iter().map(|code_hash| {
+            recovery_code::ActiveModel {
+                user_id: Set(user_id),
+                code_hash: Set(code_hash),
+                ..Default::default()
+            }
+        }))
+        .exec_without_returning(&txn)
+        .await?;
+
+        txn.commit().await
+    }
+
+    /// Turns two-factor authentication off, forgetting the secret and the
+    /// recovery codes.
+    pub async fn delete_totp(db: &DbConn, user_id: Uuid) -> Result<(), DbErr> {
+        let txn = db.begin().await?;
+
+        UserTotp::delete_by_id(user_id).exec(&txn).await?;
+        RecoveryCode::delete_many()
+            .filter(recovery_code::Column::UserId.eq(user_id))
+            .exec(&txn)
+            .await?;
+
+        txn.commit().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn user_id() -> Uuid {
+        Uuid::parse_str("00000000-0000-0000-0000-000000000000").unwrap()
+    }
+
+    fn exec_db(rows_affected: &[u64]) -> DatabaseConnection {
+        MockDatabase::new(DatabaseBackend::Postgres)
+            .append_exec_results(rows_affected.iter().map(|rows_affected| MockExecResult {
+                last_insert_id: 0,
+                rows_affected: *rows_affected,
+            }))
+            .into_connection()
+    }
+
+    #[tokio::test]
+    async fn test_start_totp() {
+        let db = exec_db(&[1, 0]);
+
+        assert!(Mutation::start_totp(&db, user_id(), "SECRET".to_owned())
+            .await
+            .expect("Failed to start TOTP"));
+        assert!(!Mutation::start_totp(&db, user_id(), "SECRET".to_owned())
+            .await
+            .expect("Failed to start TOTP"));
+
+        let statement = Transaction::from_sql_and_values(
+            DatabaseBackend::Postgres,
+            r#"INSERT INTO "user_totp" ("user_id", "secret", "confirmed_at", "last_used_step") VALUES ($1, $2, $3, $4) ON CONFLICT ("user_id") DO UPDATE SET "secret" = "excluded"."secret", "confirmed_at" = "excluded"."confirmed_at", "last_used_step" = "excluded"."last_used_step" WHERE "user_totp"."confirmed_at" IS NULL"#,
+            [
+                user_id().into(),
+                "SECRET".into(),
+                Option::<DateTimeWithTimeZone>::None.into(),
+                Option::<i64>::None.into(),
+            ],
+        );
+        assert_eq!(db.into_transaction_log(), [statement.clone(), statement]);
+    }
+
+    #[tokio::test]
+    async fn test_confirm_totp() {
+        let now: DateTimeWithTimeZone = "2024-01-01T00:00:00+00:00".parse().unwrap();
+        let db = exec_db(&[1, 2, 1, 0]);
+
+        assert!(
+            Mutation::confirm_totp(&db, user_id(), now, vec!["hash".to_owned()])
+                .await
+                .expect("Failed to confirm TOTP")
+        );
+        assert!(
+            !Mutation::confirm_totp(&db, user_id(), now, vec!["hash".to_owned()])
+                .await
+                .expect("Failed to confirm TOTP")
+        );
+
+        let update = Statement::from_sql_and_values(
+            DatabaseBackend::Postgres,
+            r#"UPDATE "user_totp" SET "confirmed_at" = $1 WHERE "user_totp"."user_id" = $2 AND "user_totp"."confirmed_at" IS NULL"#,
+            [now.into(), user_id().into()],
+        );
+        assert_eq!(
+            db.into_transaction_log(),
+            [
+                Transaction::many([
+                    Statement::from_string(DatabaseBackend::Postgres, "BEGIN"),
+                    update.clone(),
+                    Statement::from_sql_and_values(
+                        DatabaseBackend::Postgres,
+                        r#"DELETE FROM "recovery_code" WHERE "recovery_code"."user_id" = $1"#,
+                        [user_id().into()]
+                    ),
+                    Statement::from_sql_and_values(
+                        DatabaseBackend::Postgres,
+                        r#"INSERT INTO "recovery_code" ("user_id", "code_hash") VALUES ($1, $2)"#,
+                        [user_id().into(), "hash".into()]
+                    ),
+                    Statement::from_string(DatabaseBackend::Postgres, "COMMIT"),
+                ]),
+                Transaction::many([
+                    Statement::from_string(DatabaseBackend::Postgres, "BEGIN"),
+                    update,
+                    Statement::from_string(DatabaseBackend::Postgres, "ROLLBACK"),
+                ]),
+            ]
+        )
+    }
+
+    #[tokio::test]
+    async fn test_use_totp_step() {
+        let db = exec_db(&[1]);
+
+        assert!(Mutation::use_totp_step(&db, user_id(), 42)
+            .await
+            .expect("Failed to use TOTP step"));
+
+        assert_eq!(
+            db.into_transaction_log(),
+            [Transaction::from_sql_and_values(
+                DatabaseBackend::Postgres,
+                r#"UPDATE "user_totp" SET "last_used_step" = $1 WHERE "user_totp"."user_id" = $2 AND ("user_totp"."last_used_step" IS NULL OR "user_totp"."last_used_step" < $3)"#,
+                [42i64.into(), user_id().into(), 42i64.into()]
+            )]
+        )
+    }
+
+    #[tokio::test]
+    async fn test_use_recovery_code() {
+        let now: DateTimeWithTimeZone = "2024-01-01T00:00:00+00:00".parse().unwrap();
+        let db = exec_db(&[0]);
+
+        assert!(
+            !Mutation::use_recovery_code(&db, user_id(), "hash".to_owned(), now)
+                .await
+                .expect("Failed to use recovery code")
+        );
+
+        assert_eq!(
+            db.into_transaction_log(),
+            [Transaction::from_sql_and_values(
+                DatabaseBackend::Postgres,
+                r#"UPDATE "recovery_code" SET "used_at" = $1 WHERE "recovery_code"."user_id" = $2 AND "recovery_code"."code_hash" = $3 AND "recovery_code"."used_at" IS NULL"#,
+                [now.into(), user_id().into(), "hash".into()]
+            )]
+        )
+    }
+
+    #[tokio::test]
+    async fn test_delete_totp() {
+        let db = exec_db(&[1, 10]);
+
+        Mutation::delete_totp(&db, user_id())
+            .await
+            .expect("Failed to delete TOTP");
+
+        assert_eq!(
+            db.into_transaction_log(),
+            [Transaction::many([
+                Statement::from_string(DatabaseBackend::Postgres, "BEGIN"),
+                Statement::from_sql_and_values(
+                    DatabaseBackend::Postgres,
+                    r#"DELETE FROM "user_totp" WHERE "user_totp"."user_id" = $1"#,
+                    [user_id().into()]
+                ),
+                Statement::from_sql_and_values(
+                    DatabaseBackend::Postgres,
+                    r#"DELETE FROM "recovery_code" WHERE "recovery_code"."user_id" = $1"#,
+                    [user_id().into()]
+                ),
+                Statement::from_string(DatabaseBackend::Postgres, "COMMIT"),
+            ])]
+        )
+    }
+}
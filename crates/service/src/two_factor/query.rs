@@ -0,0 +1,94 @@
+use ::entity::{
+    recovery_code, recovery_code::Entity as RecoveryCode, user_totp, user_totp::Entity as UserTotp,
+};
+use prelude::Uuid;
+use sea_orm::*;
+
+pub struct Query;
+
+impl Query {
+    pub async fn find_totp_by_user_id(
+        db: &DbConn,
+        user_id: String,
+    ) -> Result<Option<user_totp::Model>, DbErr> {
+        let uuid =
+            Uuid::parse_str(&user_id).map_err(|_| DbErr::Custom("Invalid UUID.".to_owned()))?;
+
+        UserTotp::find_by_id(uuid).one(db).await
+    }
+
+    /// Counts the recovery codes of a user that were not used yet.
+    pub async fn count_recovery_codes(db: &DbConn, user_id: String) -> Result<u64, DbErr> {
+        let uuid =
+            Uuid::parse_str(&user_id).map_err(|_| DbErr::Custom("Invalid UUID.".to_owned()))?;
+
+        RecoveryCode::find()
+            .filter(recovery_code::Column::UserId.eq(uuid))
+            .filter(recovery_code::Column::UsedAt.is_null())
+            .count(db)
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_find_totp_by_user_id() {
+        let user_id = Uuid::parse_str("00000000-0000-0000-0000-000000000000").unwrap();
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([[user_totp::Model {
+                user_id,
+                secret: "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ".to_owned(),
+                confirmed_at: None,
+                last_used_step: None,
+                created_at: "2024-01-01T00:00:00+00:00".parse().unwrap(),
+            }]])
+            .into_connection();
+
+        {
+            let totp = Query::find_totp_by_user_id(&db, user_id.to_string())
+                .await
+                .expect("Failed to find TOTP");
+
+            assert!(totp.is_some());
+        }
+
+        assert_eq!(
+            db.into_transaction_log(),
+            [Transaction::from_sql_and_values(
+                DatabaseBackend::Postgres,
+                r#"SELECT "user_totp"."user_id", "user_totp"."secret", "user_totp"."confirmed_at", "user_totp"."last_used_step", "user_totp"."created_at" FROM "user_totp" WHERE "user_totp"."user_id" = $1 LIMIT $2"#,
+                [user_id.into(), 1u64.into()]
+            )]
+        )
+    }
+
+    #[tokio::test]
+    async fn test_count_recovery_codes() {
+        let user_id = Uuid::parse_str("00000000-0000-0000-0000-000000000000").unwrap();
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([[std::collections::BTreeMap::from([(
+                "num_items",
+                Value::BigInt(Some(8)),
+            )])]])
+            .into_connection();
+
+        assert_eq!(
+            Query::count_recovery_codes(&db, user_id.to_string())
+                .await
+                .expect("Failed to count recovery codes"),
+            8
+        );
+
+        assert_eq!(
+            db.into_transaction_log(),
+            [Transaction::from_sql_and_values(
+                DatabaseBackend::Postgres,
+                r#"SELECT COUNT(*) AS num_items FROM (SELECT "recovery_code"."id", "recovery_code"."user_id", "recovery_code"."code_hash", "recovery_code"."used_at", "recovery_code"."created_at" FROM "recovery_code" WHERE "recovery_code"."user_id" = $1 AND "recovery_code"."used_at" IS NULL) AS "sub_query""#,
+                [user_id.into()]
+            )]
+        )
+    }
+}
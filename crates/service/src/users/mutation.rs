@@ -1,4 +1,8 @@
-use ::entity::{user, user::Entity as User};
+use ::entity::{
+    sea_orm_active_enums::{RoleEnum, UserStatusEnum},
+    user,
+    user::Entity as User,
+};
 use prelude::Uuid;
 use sea_orm::*;
 
@@ -21,6 +25,25 @@ impl Mutation {
         .await
     }
 
+    /// Creates a user holding the given roles instead of the default ones.
+    pub async fn create_user_with_roles(
+        db: &DbConn,
+        email: String,
+        username: String,
+        password: String,
+        roles: Vec<RoleEnum>,
+    ) -> Result<user::Model, DbErr> {
+        user::ActiveModel {
+            email: Set(email),
+            password: Set(password),
+            username: Set(username),
+            roles: Set(roles),
+            ..Default::default()
+        }
+        .insert(db)
+        .await
+    }
+
     pub async fn update_user(
         db: &DbConn,
         id: String,
@@ -47,6 +70,38 @@ impl Mutation {
         .await
     }
 
+    pub async fn update_user_roles(
+        db: &DbConn,
+        id: String,
+        roles: Vec<RoleEnum>,
+    ) -> Result<user::Model, DbErr> {
+        let uuid = Uuid::parse_str(&id).map_err(|_| DbErr::Custom("Invalid UUID.".to_owned()))?;
+
+        user::ActiveModel {
+            id: Unchanged(uuid),
+            roles: Set(roles),
+            ..Default::default()
+        }
+        .update(db)
+        .await
+    }
+
+    pub async fn update_user_status(
+        db: &DbConn,
+        id: String,
+        status: UserStatusEnum,
+    ) -> Result<user::Model, DbErr> {
+        let uuid = Uuid::parse_str(&id).map_err(|_| DbErr::Custom("Invalid UUID.".to_owned()))?;
+
+        user::ActiveModel {
+            id: Unchanged(uuid),
+            status: Set(status),
+            ..Default::default()
+        }
+        .update(db)
+        .await
+    }
+
     pub async fn delete_user(db: &DbConn, id: String) -> Result<DeleteResult, DbErr> {
         let uuid = Uuid::parse_str(&id).map_err(|_| DbErr::Custom("Invalid UUID.".to_owned()))?;
 
@@ -62,8 +117,6 @@ impl Mutation {
 
 #[cfg(test)]
 mod tests {
-    use ::entity::sea_orm_active_enums::RoleEnum;
-
     use super::*;
 
     fn create_user_with_id(id: &str) -> user::Model {
@@ -73,7 +126,7 @@ mod tests {
             username: "Test".to_owned(),
             password: "password".to_owned(),
             roles: vec![RoleEnum::User],
-            status: 0,
+            status: UserStatusEnum::Active,
         }
     }
 
@@ -101,7 +154,7 @@ mod tests {
                     username: Unchanged("Test".to_string()),
                     password: Unchanged("password".to_string()),
                     roles: Unchanged(vec![RoleEnum::User]),
-                    status: Unchanged(0),
+                    status: Unchanged(UserStatusEnum::Active),
                 }
             );
         }
@@ -110,12 +163,50 @@ mod tests {
             db.into_transaction_log(),
             [Transaction::from_sql_and_values(
                 DatabaseBackend::Postgres,
-                r#"INSERT INTO "user" ("email", "username", "password") VALUES ($1, $2, $3) RETURNING "id", "email", "username", "password", CAST("roles" AS text[]), "status""#,
+                r#"INSERT INTO "user" ("email", "username", "password") VALUES ($1, $2, $3) RETURNING "id", "email", "username", "password", CAST("roles" AS text[]), CAST("status" AS text)"#,
                 ["test@example.com".into(), "Test".into(), "password".into()]
             )]
         )
     }
 
+    #[tokio::test]
+    async fn test_create_user_with_roles() {
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([[user::Model {
+                roles: vec![RoleEnum::User, RoleEnum::Admin],
+                ..create_user_with_id("00000000-0000-0000-0000-000000000000")
+            }]])
+            .into_connection();
+
+        {
+            let user = Mutation::create_user_with_roles(
+                &db,
+                "test@example.com".to_owned(),
+                "Test".to_owned(),
+                "password".to_owned(),
+                vec![RoleEnum::User, RoleEnum::Admin],
+            )
+            .await
+            .expect("Failed to create user");
+
+            assert_eq!(user.roles, [RoleEnum::User, RoleEnum::Admin]);
+        }
+
+        assert_eq!(
+            db.into_transaction_log(),
+            [Transaction::from_sql_and_values(
+                DatabaseBackend::Postgres,
+                r#"INSERT INTO "user" ("email", "username", "password", "roles") VALUES ($1, $2, $3, CAST($4 AS role_enum[])) RETURNING "id", "email", "username", "password", CAST("roles" AS text[]), CAST("status" AS text)"#,
+                [
+                    "test@example.com".into(),
+                    "Test".into(),
+                    "password".into(),
+                    vec![RoleEnum::User, RoleEnum::Admin].into(),
+                ]
+            )]
+        )
+    }
+
     #[tokio::test]
     async fn test_update_user() {
         let db = MockDatabase::new(DatabaseBackend::Postgres)
@@ -144,7 +235,7 @@ mod tests {
                     username: "Test".to_string(),
                     password: "password".to_string(),
                     roles: vec![RoleEnum::User],
-                    status: 0,
+                    status: UserStatusEnum::Active,
                 }
             );
         }
@@ -154,7 +245,7 @@ mod tests {
             [
                 Transaction::from_sql_and_values(
                     DatabaseBackend::Postgres,
-                    r#"SELECT "user"."id", "user"."email", "user"."username", "user"."password", CAST("user"."roles" AS text[]), "user"."status" FROM "user" WHERE "user"."id" = $1 LIMIT $2"#,
+                    r#"SELECT "user"."id", "user"."email", "user"."username", "user"."password", CAST("user"."roles" AS text[]), CAST("user"."status" AS text) FROM "user" WHERE "user"."id" = $1 LIMIT $2"#,
                     [
                         Uuid::parse_str("00000000-0000-0000-0000-000000000000")
                             .unwrap()
@@ -164,7 +255,7 @@ mod tests {
                 ),
                 Transaction::from_sql_and_values(
                     DatabaseBackend::Postgres,
-                    r#"UPDATE "user" SET "email" = $1, "username" = $2, "password" = $3 WHERE "user"."id" = $4 RETURNING "id", "email", "username", "password", CAST("roles" AS text[]), "status""#,
+                    r#"UPDATE "user" SET "email" = $1, "username" = $2, "password" = $3 WHERE "user"."id" = $4 RETURNING "id", "email", "username", "password", CAST("roles" AS text[]), CAST("status" AS text)"#,
                     [
                         "test@example.com".into(),
                         "Test".into(),
@@ -178,6 +269,78 @@ mod tests {
         )
     }
 
+    #[tokio::test]
+    async fn test_update_user_roles() {
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([[user::Model {
+                roles: vec![RoleEnum::Admin, RoleEnum::User],
+                ..create_user_with_id("00000000-0000-0000-0000-000000000000")
+            }]])
+            .into_connection();
+
+        {
+            let user = Mutation::update_user_roles(
+                &db,
+                "00000000-0000-0000-0000-000000000000".to_owned(),
+                vec![RoleEnum::Admin, RoleEnum::User],
+            )
+            .await
+            .expect("Failed to update user roles");
+
+            assert_eq!(user.roles, [RoleEnum::Admin, RoleEnum::User]);
+        }
+
+        assert_eq!(
+            db.into_transaction_log(),
+            [Transaction::from_sql_and_values(
+                DatabaseBackend::Postgres,
+                r#"UPDATE "user" SET "roles" = CAST($1 AS role_enum[]) WHERE "user"."id" = $2 RETURNING "id", "email", "username", "password", CAST("roles" AS text[]), CAST("status" AS text)"#,
+                [
+                    vec![RoleEnum::Admin, RoleEnum::User].into(),
+                    Uuid::parse_str("00000000-0000-0000-0000-000000000000")
+                        .unwrap()
+                        .into(),
+                ]
+            )]
+        )
+    }
+
+    #[tokio::test]
+    async fn test_update_user_status() {
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([[user::Model {
+                status: UserStatusEnum::Suspended,
+                ..create_user_with_id("00000000-0000-0000-0000-000000000000")
+            }]])
+            .into_connection();
+
+        {
+            let user = Mutation::update_user_status(
+                &db,
+                "00000000-0000-0000-0000-000000000000".to_owned(),
+                UserStatusEnum::Suspended,
+            )
+            .await
+            .expect("Failed to update user status");
+
+            assert_eq!(user.status, UserStatusEnum::Suspended);
+        }
+
+        assert_eq!(
+            db.into_transaction_log(),
+            [Transaction::from_sql_and_values(
+                DatabaseBackend::Postgres,
+                r#"UPDATE "user" SET "status" = CAST($1 AS user_status_enum) WHERE "user"."id" = $2 RETURNING "id", "email", "username", "password", CAST("roles" AS text[]), CAST("status" AS text)"#,
+                [
+                    UserStatusEnum::Suspended.into(),
+                    Uuid::parse_str("00000000-0000-0000-0000-000000000000")
+                        .unwrap()
+                        .into(),
+                ]
+            )]
+        )
+    }
+
     #[tokio::test]
     async fn test_delete_user() {
         let db = MockDatabase::new(DatabaseBackend::Postgres)
@@ -202,7 +365,7 @@ mod tests {
             [
                 Transaction::from_sql_and_values(
                     DatabaseBackend::Postgres,
-                    r#"SELECT "user"."id", "user"."email", "user"."username", "user"."password", CAST("user"."roles" AS text[]), "user"."status" FROM "user" WHERE "user"."id" = $1 LIMIT $2"#,
+                    r#"SELECT "user"."id", "user"."email", "user"."username", "user"."password", CAST("user"."roles" AS text[]), CAST("user"."status" AS text) FROM "user" WHERE "user"."id" = $1 LIMIT $2"#,
                     [
                         Uuid::parse_str("00000000-0000-0000-0000-000000000000")
                             .unwrap()
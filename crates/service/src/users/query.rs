@@ -34,11 +34,15 @@ impl Query {
     pub async fn find_all_users(db: &DbConn) -> Result<Vec<user::Model>, DbErr> {
         User::find().all(db).await
     }
+
+    pub async fn count_users(db: &DbConn) -> Result<u64, DbErr> {
+        User::find().count(db).await
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use ::entity::sea_orm_active_enums::RoleEnum;
+    use ::entity::sea_orm_active_enums::{RoleEnum, UserStatusEnum};
 
     use super::*;
 
@@ -49,7 +53,7 @@ mod tests {
             username: "Test".to_owned(),
             password: "password".to_owned(),
             roles: vec![RoleEnum::User],
-            status: 0,
+            status: UserStatusEnum::Active,
         }
     }
 
@@ -73,7 +77,7 @@ mod tests {
             db.into_transaction_log(),
             [Transaction::from_sql_and_values(
                 DatabaseBackend::Postgres,
-                r#"SELECT "user"."id", "user"."email", "user"."username", "user"."password", CAST("user"."roles" AS text[]), "user"."status" FROM "user" WHERE "user"."id" = $1 LIMIT $2"#,
+                r#"SELECT "user"."id", "user"."email", "user"."username", "user"."password", CAST("user"."roles" AS text[]), CAST("user"."status" AS text) FROM "user" WHERE "user"."id" = $1 LIMIT $2"#,
                 [
                     Uuid::parse_str("00000000-0000-0000-0000-000000000000")
                         .unwrap()
@@ -104,7 +108,7 @@ mod tests {
             db.into_transaction_log(),
             [Transaction::from_sql_and_values(
                 DatabaseBackend::Postgres,
-                r#"SELECT "user"."id", "user"."email", "user"."username", "user"."password", CAST("user"."roles" AS text[]), "user"."status" FROM "user" WHERE "user"."username" = $1 LIMIT $2"#,
+                r#"SELECT "user"."id", "user"."email", "user"."username", "user"."password", CAST("user"."roles" AS text[]), CAST("user"."status" AS text) FROM "user" WHERE "user"."username" = $1 LIMIT $2"#,
                 ["Test".into(), 1u64.into()]
             )]
         )
@@ -130,7 +134,7 @@ mod tests {
             db.into_transaction_log(),
             [Transaction::from_sql_and_values(
                 DatabaseBackend::Postgres,
-                r#"SELECT "user"."id", "user"."email", "user"."username", "user"."password", CAST("user"."roles" AS text[]), "user"."status" FROM "user" WHERE "user"."email" = $1 LIMIT $2"#,
+                r#"SELECT "user"."id", "user"."email", "user"."username", "user"."password", CAST("user"."roles" AS text[]), CAST("user"."status" AS text) FROM "user" WHERE "user"."email" = $1 LIMIT $2"#,
                 ["test@example.com".into(), 1u64.into()]
             )]
         )
@@ -157,7 +161,34 @@ mod tests {
             db.into_transaction_log(),
             [Transaction::from_sql_and_values(
                 DatabaseBackend::Postgres,
-                r#"SELECT "user"."id", "user"."email", "user"."username", "user"."password", CAST("user"."roles" AS text[]), "user"."status" FROM "user""#,
+                r#"SELECT "user"."id", "user"."email", "user"."username", "user"."password", CAST("user"."roles" AS text[]), CAST("user"."status" AS text) FROM "user""#,
+                []
+            )]
+        )
+    }
+
+    #[tokio::test]
+    async fn test_count_users() {
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([[std::collections::BTreeMap::from([(
+                "num_items",
+                Value::BigInt(Some(0)),
+            )])]])
+            .into_connection();
+
+        {
+            let count = Query::count_users(&db)
+                .await
+                .expect("Failed to count users");
+
+            assert_eq!(count, 0);
+        }
+
+        assert_eq!(
+            db.into_transaction_log(),
+            [Transaction::from_sql_and_values(
+                DatabaseBackend::Postgres,
+                r#"SELECT COUNT(*) AS num_items FROM (SELECT "user"."id", "user"."email", "user"."username", "user"."password", CAST("user"."roles" AS text[]), CAST("user"."status" AS text) FROM "user") AS "sub_query""#,
                 []
             )]
         )
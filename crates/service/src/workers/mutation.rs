@@ -1,6 +1,6 @@
 use ::entity::{worker, worker::Entity as Worker};
 use prelude::Uuid;
-use sea_orm::*;
+use sea_orm::{sea_query::Expr, *};
 
 pub struct Mutation;
 
@@ -25,6 +25,7 @@ impl Mutation {
         .await
     }
 
+    #[allow(clippy::too_many_arguments)]
     pub async fn update_worker(
         db: &DbConn,
         id: String,
@@ -72,6 +73,21 @@ impl Mutation {
 
         worker.delete(db).await
     }
+
+    /// Hands a worker to a team, or back to its user alone with `None`.
+    pub async fn set_worker_team(
+        db: &DbConn,
+        id: String,
+        team_id: Option<Uuid>,
+    ) -> Result<UpdateResult, DbErr> {
+        let uuid = Uuid::parse_str(&id).map_err(|_| DbErr::Custom("Invalid UUID.".to_owned()))?;
+
+        Worker::update_many()
+            .col_expr(worker::Column::TeamId, Expr::value(team_id))
+            .filter(worker::Column::Id.eq(uuid))
+            .exec(db)
+            .await
+    }
 }
 
 #[cfg(test)]
@@ -91,6 +107,11 @@ mod tests {
             tunnel_id: None,
             template: None,
             user_id: Uuid::parse_str("00000000-0000-0000-0000-000000000000").unwrap(),
+            deployment_id: None,
+            modules: None,
+            bindings: JsonValue::Object(Default::default()),
+            compatibility_date: None,
+            team_id: None,
         }
     }
 
@@ -129,6 +150,11 @@ mod tests {
                     user_id: Unchanged(
                         Uuid::parse_str("00000000-0000-0000-0000-000000000000").unwrap()
                     ),
+                    deployment_id: Unchanged(None),
+                    modules: Unchanged(None),
+                    bindings: Unchanged(JsonValue::Object(Default::default())),
+                    compatibility_date: Unchanged(None),
+                    team_id: Unchanged(None),
                 }
             );
         }
@@ -137,7 +163,7 @@ mod tests {
             db.into_transaction_log(),
             [Transaction::from_sql_and_values(
                 DatabaseBackend::Postgres,
-                r#"INSERT INTO "worker" ("port", "code", "name", "user_id") VALUES ($1, $2, $3, $4) RETURNING "id", "external_path", "host_name", "node_name", "port", "entry", "code", "name", "tunnel_id", "template", "user_id""#,
+                r#"INSERT INTO "worker" ("port", "code", "name", "user_id") VALUES ($1, $2, $3, $4) RETURNING "id", "external_path", "host_name", "node_name", "port", "entry", "code", "name", "tunnel_id", "template", "user_id", "deployment_id", "modules", "bindings", "compatibility_date", "team_id""#,
                 [
                     80.into(),
                     "".into(),
@@ -193,6 +219,11 @@ mod tests {
                     tunnel_id: None,
                     template: None,
                     user_id: Uuid::parse_str("00000000-0000-0000-0000-000000000000").unwrap(),
+                    deployment_id: None,
+                    modules: None,
+                    bindings: JsonValue::Object(Default::default()),
+                    compatibility_date: None,
+                    team_id: None,
                 }
             );
         }
@@ -202,7 +233,7 @@ mod tests {
             [
                 Transaction::from_sql_and_values(
                     DatabaseBackend::Postgres,
-                    r#"SELECT "worker"."id", "worker"."external_path", "worker"."host_name", "worker"."node_name", "worker"."port", "worker"."entry", "worker"."code", "worker"."name", "worker"."tunnel_id", "worker"."template", "worker"."user_id" FROM "worker" WHERE "worker"."id" = $1 LIMIT $2"#,
+                    r#"SELECT "worker"."id", "worker"."external_path", "worker"."host_name", "worker"."node_name", "worker"."port", "worker"."entry", "worker"."code", "worker"."name", "worker"."tunnel_id", "worker"."template", "worker"."user_id", "worker"."deployment_id", "worker"."modules", "worker"."bindings", "worker"."compatibility_date", "worker"."team_id" FROM "worker" WHERE "worker"."id" = $1 LIMIT $2"#,
                     [
                         Uuid::parse_str("00000000-0000-0000-0000-000000000000")
                             .unwrap()
@@ -212,7 +243,7 @@ mod tests {
                 ),
                 Transaction::from_sql_and_values(
                     DatabaseBackend::Postgres,
-                    r#"UPDATE "worker" SET "external_path" = $1, "host_name" = $2, "node_name" = $3, "port" = $4, "code" = $5, "name" = $6, "tunnel_id" = $7, "template" = $8 WHERE "worker"."id" = $9 RETURNING "id", "external_path", "host_name", "node_name", "port", "entry", "code", "name", "tunnel_id", "template", "user_id""#,
+                    r#"UPDATE "worker" SET "external_path" = $1, "host_name" = $2, "node_name" = $3, "port" = $4, "code" = $5, "name" = $6, "tunnel_id" = $7, "template" = $8 WHERE "worker"."id" = $9 RETURNING "id", "external_path", "host_name", "node_name", "port", "entry", "code", "name", "tunnel_id", "template", "user_id", "deployment_id", "modules", "bindings", "compatibility_date", "team_id""#,
                     [
                         "/".into(),
                         "localhost".into(),
@@ -262,7 +293,7 @@ mod tests {
             [
                 Transaction::from_sql_and_values(
                     DatabaseBackend::Postgres,
-                    r#"SELECT "worker"."id", "worker"."external_path", "worker"."host_name", "worker"."node_name", "worker"."port", "worker"."entry", "worker"."code", "worker"."name", "worker"."tunnel_id", "worker"."template", "worker"."user_id" FROM "worker" WHERE "worker"."id" = $1 LIMIT $2"#,
+                    r#"SELECT "worker"."id", "worker"."external_path", "worker"."host_name", "worker"."node_name", "worker"."port", "worker"."entry", "worker"."code", "worker"."name", "worker"."tunnel_id", "worker"."template", "worker"."user_id", "worker"."deployment_id", "worker"."modules", "worker"."bindings", "worker"."compatibility_date", "worker"."team_id" FROM "worker" WHERE "worker"."id" = $1 LIMIT $2"#,
                     [
                         Uuid::parse_str("00000000-0000-0000-0000-000000000000")
                             .unwrap()
@@ -280,4 +311,33 @@ mod tests {
             ]
         )
     }
+
+    #[tokio::test]
+    async fn test_set_worker_team() {
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_exec_results([MockExecResult {
+                last_insert_id: 0,
+                rows_affected: 1,
+            }])
+            .into_connection();
+
+        let id = Uuid::parse_str("00000000-0000-0000-0000-000000000000").unwrap();
+        let team_id = Uuid::parse_str("00000000-0000-0000-0000-000000000001").unwrap();
+        {
+            let result = Mutation::set_worker_team(&db, id.to_string(), Some(team_id))
+                .await
+                .expect("Failed to set worker team");
+
+            assert_eq!(result.rows_affected, 1);
+        }
+
+        assert_eq!(
+            db.into_transaction_log(),
+            [Transaction::from_sql_and_values(
+                DatabaseBackend::Postgres,
+                r#"UPDATE "worker" SET "team_id" = $1 WHERE "worker"."id" = $2"#,
+                [Some(team_id).into(), id.into()]
+            )]
+        )
+    }
 }
@@ -1,4 +1,4 @@
-use ::entity::{worker, worker::Entity as Worker};
+use ::entity::{team_member, team_member::Entity as TeamMember, worker, worker::Entity as Worker};
 use prelude::Uuid;
 use sea_orm::*;
 
@@ -18,6 +18,10 @@ impl Query {
         Worker::find().all(db).await
     }
 
+    pub async fn count_workers(db: &DbConn) -> Result<u64, DbErr> {
+        Worker::find().count(db).await
+    }
+
     pub async fn find_user_workers_with_user_id(
         db: &DbConn,
         user_id: String,
@@ -27,6 +31,29 @@ impl Query {
             .all(db)
             .await
     }
+
+    /// Finds the workers a user owns or can reach through a team.
+    pub async fn find_workers_accessible_by_user_id(
+        db: &DbConn,
+        user_id: String,
+    ) -> Result<Vec<worker::Model>, DbErr> {
+        let uuid =
+            Uuid::parse_str(&user_id).map_err(|_| DbErr::Custom("Invalid UUID.".to_owned()))?;
+        let team_ids = TeamMember::find()
+            .select_only()
+            .column(team_member::Column::TeamId)
+            .filter(team_member::Column::UserId.eq(uuid))
+            .into_query();
+
+        Worker::find()
+            .filter(
+                Condition::any()
+                    .add(worker::Column::UserId.eq(uuid))
+                    .add(worker::Column::TeamId.in_subquery(team_ids)),
+            )
+            .all(db)
+            .await
+    }
 }
 
 #[cfg(test)]
@@ -46,6 +73,11 @@ mod tests {
             tunnel_id: None,
             template: None,
             user_id: Uuid::parse_str("00000000-0000-0000-0000-000000000000").unwrap(),
+            deployment_id: None,
+            modules: None,
+            bindings: JsonValue::Object(Default::default()),
+            compatibility_date: None,
+            team_id: None,
         }
     }
 
@@ -71,7 +103,7 @@ mod tests {
             db.into_transaction_log(),
             [Transaction::from_sql_and_values(
                 DatabaseBackend::Postgres,
-                r#"SELECT "worker"."id", "worker"."external_path", "worker"."host_name", "worker"."node_name", "worker"."port", "worker"."entry", "worker"."code", "worker"."name", "worker"."tunnel_id", "worker"."template", "worker"."user_id" FROM "worker" WHERE "worker"."id" = $1 LIMIT $2"#,
+                r#"SELECT "worker"."id", "worker"."external_path", "worker"."host_name", "worker"."node_name", "worker"."port", "worker"."entry", "worker"."code", "worker"."name", "worker"."tunnel_id", "worker"."template", "worker"."user_id", "worker"."deployment_id", "worker"."modules", "worker"."bindings", "worker"."compatibility_date", "worker"."team_id" FROM "worker" WHERE "worker"."id" = $1 LIMIT $2"#,
                 [
                     Uuid::parse_str("00000000-0000-0000-0000-000000000000")
                         .unwrap()
@@ -103,7 +135,34 @@ mod tests {
             db.into_transaction_log(),
             [Transaction::from_sql_and_values(
                 DatabaseBackend::Postgres,
-                r#"SELECT "worker"."id", "worker"."external_path", "worker"."host_name", "worker"."node_name", "worker"."port", "worker"."entry", "worker"."code", "worker"."name", "worker"."tunnel_id", "worker"."template", "worker"."user_id" FROM "worker""#,
+                r#"SELECT "worker"."id", "worker"."external_path", "worker"."host_name", "worker"."node_name", "worker"."port", "worker"."entry", "worker"."code", "worker"."name", "worker"."tunnel_id", "worker"."template", "worker"."user_id", "worker"."deployment_id", "worker"."modules", "worker"."bindings", "worker"."compatibility_date", "worker"."team_id" FROM "worker""#,
+                []
+            )]
+        )
+    }
+
+    #[tokio::test]
+    async fn test_count_workers() {
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([[std::collections::BTreeMap::from([(
+                "num_items",
+                Value::BigInt(Some(3)),
+            )])]])
+            .into_connection();
+
+        {
+            let count = Query::count_workers(&db)
+                .await
+                .expect("Failed to count workers");
+
+            assert_eq!(count, 3);
+        }
+
+        assert_eq!(
+            db.into_transaction_log(),
+            [Transaction::from_sql_and_values(
+                DatabaseBackend::Postgres,
+                r#"SELECT COUNT(*) AS num_items FROM (SELECT "worker"."id", "worker"."external_path", "worker"."host_name", "worker"."node_name", "worker"."port", "worker"."entry", "worker"."code", "worker"."name", "worker"."tunnel_id", "worker"."template", "worker"."user_id", "worker"."deployment_id", "worker"."modules", "worker"."bindings", "worker"."compatibility_date", "worker"."team_id" FROM "worker") AS "sub_query""#,
                 []
             )]
         )
@@ -133,11 +192,38 @@ mod tests {
             db.into_transaction_log(),
             [Transaction::from_sql_and_values(
                 DatabaseBackend::Postgres,
-                r#"SELECT "worker"."id", "worker"."external_path", "worker"."host_name", "worker"."node_name", "worker"."port", "worker"."entry", "worker"."code", "worker"."name", "worker"."tunnel_id", "worker"."template", "worker"."user_id" FROM "worker" WHERE "worker"."user_id" = $1"#,
+                r#"SELECT "worker"."id", "worker"."external_path", "worker"."host_name", "worker"."node_name", "worker"."port", "worker"."entry", "worker"."code", "worker"."name", "worker"."tunnel_id", "worker"."template", "worker"."user_id", "worker"."deployment_id", "worker"."modules", "worker"."bindings", "worker"."compatibility_date", "worker"."team_id" FROM "worker" WHERE "worker"."user_id" = $1"#,
                 [Uuid::parse_str("00000000-0000-0000-0000-000000000000")
                     .unwrap()
                     .into()]
             )]
         )
     }
+
+    #[tokio::test]
+    async fn test_find_workers_accessible_by_user_id() {
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([[create_worker_with_id(
+                "00000000-0000-0000-0000-000000000000",
+            )]])
+            .into_connection();
+
+        let user_id = Uuid::parse_str("00000000-0000-0000-0000-000000000000").unwrap();
+        {
+            let workers = Query::find_workers_accessible_by_user_id(&db, user_id.to_string())
+                .await
+                .expect("Failed to find workers");
+
+            assert_eq!(workers.len(), 1);
+        }
+
+        assert_eq!(
+            db.into_transaction_log(),
+            [Transaction::from_sql_and_values(
+                DatabaseBackend::Postgres,
+                r#"SELECT "worker"."id", "worker"."external_path", "worker"."host_name", "worker"."node_name", "worker"."port", "worker"."entry", "worker"."code", "worker"."name", "worker"."tunnel_id", "worker"."template", "worker"."user_id", "worker"."deployment_id", "worker"."modules", "worker"."bindings", "worker"."compatibility_date", "worker"."team_id" FROM "worker" WHERE "worker"."user_id" = $1 OR "worker"."team_id" IN (SELECT "team_member"."team_id" FROM "team_member" WHERE "team_member"."user_id" = $2)"#,
+                [user_id.into(), user_id.into()]
+            )]
+        )
+    }
 }
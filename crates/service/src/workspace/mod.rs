@@ -0,0 +1,3 @@
+mod mutation;
+
+pub use mutation::{Mutation, ScheduleState, WorkerChange, WorkerState};
@@ -0,0 +1,250 @@
+use ::entity::{
+    worker, worker::Entity as Worker, worker_schedule, worker_schedule::Entity as WorkerSchedule,
+};
+use prelude::Uuid;
+use sea_orm::*;
+
+use crate::deployments::{
+    mutation::{activate, insert},
+    NewDeployment,
+};
+
+/// Desired state of a worker, as applied by [`Mutation::apply`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct WorkerState {
+    /// Row of the worker. Its `deployment_id` is ignored.
+    pub worker: worker::Model,
+    /// Replaces every schedule of the worker when set.
+    pub schedules: Option<Vec<ScheduleState>>,
+    /// Recorded and activated after the worker is written.
+    pub deployment: Option<NewDeployment>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct ScheduleState {
+    pub cron: String,
+    pub path: Option<String>,
+    pub enabled: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum WorkerChange {
+    Create(WorkerState),
+    Update(WorkerState),
+    Delete(Uuid),
+}
+
+pub struct Mutation;
+
+impl Mutation {
+    /// Applies a set of changes to the workers in a single transaction, so
+    /// that either all of them or none are visible.
+    pub async fn apply(db: &DbConn, changes: Vec<WorkerChange>) -> Result<(), DbErr> {
+        let txn = db.begin().await?;
+
+        for change in changes {
+            match change {
+                WorkerChange::Create(state) => {
+                    let worker = state.worker.to_owned();
+                    worker::ActiveModel {
+                        id: Set(worker.id),
+                        user_id: Set(worker.user_id),
+                        tunnel_id: Set(worker.tunnel_id.to_owned()),
+                        ..columns(worker)
+                    }
+                    .insert(&txn)
+                    .await?;
+                    write_state(&txn, state).await?;
+                }
+                WorkerChange::Update(state) => {
+                    let worker = state.worker.to_owned();
+                    worker::ActiveModel {
+                        id: Unchanged(worker.id),
+                        ..columns(worker)
+                    }
+                    .update(&txn)
+                    .await?;
+                    write_state(&txn, state).await?;
+                }
+                WorkerChange::Delete(id) => {
+                    Worker::delete_by_id(id).exec(&txn).await?;
+                }
+            }
+        }
+
+        txn.commit().await
+    }
+}
+
+/// Columns of a worker that are managed declaratively.
+fn columns(worker: worker::Model) -> worker::ActiveModel {
+    worker::ActiveModel {
+        external_path: Set(worker.external_path),
+        host_name: Set(worker.host_name),
+        node_name: Set(worker.node_name),
+        port: Set(worker.port),
+        entry: Set(worker.entry),
+        code: Set(worker.code),
+        name: Set(worker.name),
+        template: Set(worker.template),
+        modules: Set(worker.modules),
+        bindings: Set(worker.bindings),
+        compatibility_date: Set(worker.compatibility_date),
+        ..Default::default()
+    }
+}
+
+async fn write_state<C: ConnectionTrait>(db: &C, state: WorkerState) -> Result<(), DbErr> {
+    if let Some(schedules) = state.schedules {
+        WorkerSchedule::delete_many()
+            .filter(worker_schedule::Column::WorkerId.eq(state.worker.id))
+            .exec(db)
+            .await?;
+        if !schedules.is_empty() {
+            WorkerSchedule::insert_many(schedules.into_iter().map(|schedule| {
+                worker_schedule::ActiveModel {
+                    worker_id: Set(state.worker.id),
+                    cron: Set(schedule.cron),
+                    path: Set(schedule.path),
+                    enabled: Set(schedule.enabled),
+                    ..Default::default()
+                }
+            }))
+            .exec_without_returning(db)
+            .await?;
+        }
+    }
+
+    if let Some(deployment) = state.deployment {
+        let deployment = insert(db, deployment).await?;
+        activate(db, &deployment).await?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn create_worker_with_id(id: &str) -> worker::Model {
+        worker::Model {
+            id: Uuid::parse_str(id).unwrap(),
+            external_path: "/".to_string(),
+            host_name: "localhost".to_string(),
+            node_name: "default".to_string(),
+            port: 8080,
+            entry: "entry.js".to_string(),
+            code: "export default {}".to_string(),
+            name: "api".to_string(),
+            tunnel_id: None,
+            template: None,
+            user_id: Uuid::parse_str("00000000-0000-0000-0000-000000000000").unwrap(),
+            deployment_id: None,
+            modules: None,
+            bindings: JsonValue::Object(Default::default()),
+            compatibility_date: None,
+            team_id: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_apply() {
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([[create_worker_with_id(
+                "00000000-0000-0000-0000-000000000001",
+            )]])
+            .append_exec_results([
+                MockExecResult {
+                    last_insert_id: 0,
+                    rows_affected: 0,
+                },
+                MockExecResult {
+                    last_insert_id: 0,
+                    rows_affected: 1,
+                },
+                MockExecResult {
+                    last_insert_id: 0,
+                    rows_affected: 1,
+                },
+            ])
+            .into_connection();
+
+        Mutation::apply(
+            &db,
+            vec![
+                WorkerChange::Create(WorkerState {
+                    worker: create_worker_with_id("00000000-0000-0000-0000-000000000001"),
+                    schedules: Some(vec![ScheduleState {
+                        cron: "0 * * * * *".to_string(),
+                        path: None,
+                        enabled: true,
+                    }]),
+                    deployment: None,
+                }),
+                WorkerChange::Delete(
+                    Uuid::parse_str("00000000-0000-0000-0000-000000000002").unwrap(),
+                ),
+            ],
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(
+            db.into_transaction_log(),
+            [Transaction::many([
+                Statement::from_string(DatabaseBackend::Postgres, "BEGIN"),
+                Statement::from_sql_and_values(
+                    DatabaseBackend::Postgres,
+                    r#"INSERT INTO "worker" ("id", "external_path", "host_name", "node_name", "port", "entry", "code", "name", "tunnel_id", "template", "user_id", "modules", "bindings", "compatibility_date") VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14) RETURNING "id", "external_path", "host_name", "node_name", "port", "entry", "code", "name", "tunnel_id", "template", "user_id", "deployment_id", "modules", "bindings", "compatibility_date", "team_id""#,
+                    [
+                        Uuid::parse_str("00000000-0000-0000-0000-000000000001")
+                            .unwrap()
+                            .into(),
+                        "/".into(),
+                        "localhost".into(),
+                        "default".into(),
+                        8080.into(),
+                        "entry.js".into(),
+                        "export default {}".into(),
+                        "api".into(),
+                        Option::<String>::None.into(),
+                        Option::<String>::None.into(),
+                        Uuid::parse_str("00000000-0000-0000-0000-000000000000")
+                            .unwrap()
+                            .into(),
+                        Option::<JsonValue>::None.into(),
+                        JsonValue::Object(Default::default()).into(),
+                        Option::<String>::None.into(),
+                    ]
+                ),
+                Statement::from_sql_and_values(
+                    DatabaseBackend::Postgres,
+                    r#"DELETE FROM "worker_schedule" WHERE "worker_schedule"."worker_id" = $1"#,
+                    [Uuid::parse_str("00000000-0000-0000-0000-000000000001")
+                        .unwrap()
+                        .into(),]
+                ),
+                Statement::from_sql_and_values(
+                    DatabaseBackend::Postgres,
+                    r#"INSERT INTO "worker_schedule" ("worker_id", "cron", "path", "enabled") VALUES ($1, $2, $3, $4)"#,
+                    [
+                        Uuid::parse_str("00000000-0000-0000-0000-000000000001")
+                            .unwrap()
+                            .into(),
+                        "0 * * * * *".into(),
+                        Option::<String>::None.into(),
+                        true.into(),
+                    ]
+                ),
+                Statement::from_sql_and_values(
+                    DatabaseBackend::Postgres,
+                    r#"DELETE FROM "worker" WHERE "worker"."id" = $1"#,
+                    [Uuid::parse_str("00000000-0000-0000-0000-000000000002")
+                        .unwrap()
+                        .into(),]
+                ),
+                Statement::from_string(DatabaseBackend::Postgres, "COMMIT"),
+            ])]
+        );
+    }
+}
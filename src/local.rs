@@ -0,0 +1,92 @@
+//! Commands that work on the database directly, for bootstrapping a
+//! manager before anyone can log in to it.
+
+use api::auth::hash_password;
+use entity::sea_orm_active_enums::RoleEnum;
+use migration::{Migrator, MigratorTrait};
+use service::{
+    sea_orm::{Database, DatabaseConnection},
+    two_factor::Mutation as TwoFactorMutation,
+    users::{Mutation, Query},
+};
+
+use crate::{read_password, CliResult, MigrateCommand, UserCommand};
+
+async fn connect() -> CliResult<DatabaseConnection> {
+    dotenv::dotenv().ok();
+    let database_url = dotenv::var("DATABASE_URL").map_err(|_| "DATABASE_URL is not set")?;
+    Ok(Database::connect(&database_url).await?)
+}
+
+fn hash(password: String) -> CliResult<String> {
+    Ok(hash_password(&password).map_err(|err| format!("Failed to hash the password: {}", err))?)
+}
+
+pub async fn migrate(command: MigrateCommand) -> CliResult {
+    let db = connect().await?;
+
+    match command {
+        MigrateCommand::Up { steps } => Migrator::up(&db, steps).await?,
+        MigrateCommand::Down { steps } => Migrator::down(&db, Some(steps)).await?,
+        MigrateCommand::Status => {
+            for migration in Migrator::get_migration_with_status(&db).await? {
+                println!("{}\t{}", migration.status(), migration.name());
+            }
+        }
+    }
+    Ok(())
+}
+
+pub async fn user(command: UserCommand) -> CliResult {
+    let db = connect().await?;
+
+    match command {
+        UserCommand::Create {
+            email,
+            username,
+            password,
+            admin,
+        } => {
+            if Query::find_user_by_email(&db, email.to_owned())
+                .await?
+                .is_some()
+            {
+                return Err(format!("A user with the email {} already exists", email).into());
+            }
+            let password = hash(read_password(password)?)?;
+
+            let roles = if admin {
+                vec![RoleEnum::User, RoleEnum::Admin]
+            } else {
+                vec![RoleEnum::User]
+            };
+            let user =
+                Mutation::create_user_with_roles(&db, email, username, password, roles).await?;
+            let id = user.id.to_string();
+            println!("{}", id);
+        }
+        UserCommand::ResetPassword { email, password } => {
+            let user = Query::find_user_by_email(&db, email.to_owned())
+                .await?
+                .ok_or_else(|| format!("No user has the email {}", email))?;
+            let password = hash(read_password(password)?)?;
+
+            Mutation::update_user(
+                &db,
+                user.id.to_string(),
+                user.email,
+                user.username,
+                password,
+            )
+            .await?;
+        }
+        UserCommand::ResetTwoFactor { email } => {
+            let user = Query::find_user_by_email(&db, email.to_owned())
+                .await?
+                .ok_or_else(|| format!("No user has the email {}", email))?;
+
+            TwoFactorMutation::delete_totp(&db, user.id).await?;
+        }
+    }
+    Ok(())
+}
@@ -1,3 +1,198 @@
-fn main() {
-    api::start();
+mod local;
+mod remote;
+
+use std::{error::Error, future::Future, path::PathBuf, process::ExitCode};
+
+use clap::{Args, Parser, Subcommand};
+
+pub type CliResult<T = ()> = Result<T, Box<dyn Error + Send + Sync>>;
+
+#[derive(Parser)]
+#[command(version, about = "Runs and manages workerd workers")]
+struct Cli {
+    /// Address of a running manager, for the commands that talk to it.
+    #[arg(long, global = true, env = "MANAGER_URL")]
+    url: Option<String>,
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Runs the manager. This is the default command.
+    Serve,
+    /// Runs the database migrations.
+    #[command(subcommand)]
+    Migrate(MigrateCommand),
+    /// Manages users directly in the database.
+    #[command(subcommand)]
+    User(UserCommand),
+    /// Logs in to a running manager and stores the tokens.
+    Login(LoginArgs),
+    /// Logs out of a running manager and forgets the tokens.
+    Logout(LogoutArgs),
+    /// Manages the workers of a running manager.
+    #[command(subcommand)]
+    Worker(WorkerCommand),
+    /// Applies a workspace manifest to a running manager.
+    Apply(ApplyArgs),
+}
+
+#[derive(Subcommand)]
+pub enum MigrateCommand {
+    /// Applies pending migrations.
+    Up {
+        /// Number of migrations to apply. All of them by default.
+        #[arg(short = 'n', long)]
+        steps: Option<u32>,
+    },
+    /// Rolls back applied migrations.
+    Down {
+        /// Number of migrations to roll back.
+        #[arg(short = 'n', long, default_value_t = 1)]
+        steps: u32,
+    },
+    /// Lists the migrations and whether they are applied.
+    Status,
+}
+
+#[derive(Subcommand)]
+pub enum UserCommand {
+    /// Creates a user.
+    Create {
+        #[arg(long)]
+        email: String,
+        #[arg(long)]
+        username: String,
+        /// Read from the terminal when omitted.
+        #[arg(long)]
+        password: Option<String>,
+        /// Grants the admin role.
+        #[arg(long)]
+        admin: bool,
+    },
+    /// Sets the password of a user.
+    ResetPassword {
+        #[arg(long)]
+        email: String,
+        /// Read from the terminal when omitted.
+        #[arg(long)]
+        password: Option<String>,
+    },
+    /// Turns off two-factor authentication of a user who lost both the
+    /// authenticator and the recovery codes.
+    ResetTwoFactor {
+        #[arg(long)]
+        email: String,
+    },
+}
+
+#[derive(Args)]
+pub struct LoginArgs {
+    #[arg(long)]
+    pub email: String,
+    /// Read from the terminal when omitted.
+    #[arg(long)]
+    pub password: Option<String>,
+    /// One-time password or recovery code, for accounts with two-factor
+    /// authentication. Read from the terminal when needed and omitted.
+    #[arg(long)]
+    pub code: Option<String>,
+}
+
+#[derive(Args)]
+pub struct LogoutArgs {
+    /// Ends every session of the user, not just this one.
+    #[arg(long)]
+    pub all: bool,
+}
+
+#[derive(Subcommand)]
+pub enum WorkerCommand {
+    /// Lists the workers.
+    List,
+    /// Starts a worker.
+    Start { id: String },
+    /// Stops a worker.
+    Stop { id: String },
+    /// Prints the recent output of a worker.
+    Logs {
+        id: String,
+        /// Number of lines to print.
+        #[arg(short = 'n', long)]
+        lines: Option<usize>,
+    },
+}
+
+#[derive(Args)]
+pub struct ApplyArgs {
+    /// Manifest in YAML, TOML or JSON, chosen by its extension.
+    #[arg(short = 'f', long = "file")]
+    pub file: PathBuf,
+    /// Prints the plan without changing anything.
+    #[arg(long)]
+    pub dry_run: bool,
+}
+
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+
+    let result = match cli.command.unwrap_or(Command::Serve) {
+        Command::Serve => {
+            api::start();
+            Ok(())
+        }
+        Command::Migrate(command) => block_on(local::migrate(command)),
+        Command::User(command) => block_on(local::user(command)),
+        Command::Login(args) => block_on(remote::login(cli.url, args)),
+        Command::Logout(args) => block_on(remote::logout(cli.url, args)),
+        Command::Worker(command) => block_on(remote::worker(cli.url, command)),
+        Command::Apply(args) => block_on(remote::apply(cli.url, args)),
+    };
+
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("error: {}", err);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// Runs a command on its own runtime, as `serve` starts one of its own.
+fn block_on(command: impl Future<Output = CliResult>) -> CliResult {
+    tokio::runtime::Runtime::new()?.block_on(command)
+}
+
+/// Returns the given password, or asks for it on the terminal.
+pub fn read_password(password: Option<String>) -> CliResult<String> {
+    if let Some(password) = password {
+        return Ok(password);
+    }
+
+    let password = read_line("Password: ")?;
+    if password.is_empty() {
+        return Err("The password must not be empty".into());
+    }
+    Ok(password)
+}
+
+/// Reads a one-time password or recovery code, unless given.
+pub fn read_code(code: Option<String>) -> CliResult<String> {
+    if let Some(code) = code {
+        return Ok(code);
+    }
+
+    let code = read_line("Two-factor code: ")?;
+    if code.trim().is_empty() {
+        return Err("The code must not be empty".into());
+    }
+    Ok(code)
+}
+
+fn read_line(prompt: &str) -> CliResult<String> {
+    eprint!("{}", prompt);
+    let mut line = String::new();
+    std::io::stdin().read_line(&mut line)?;
+    Ok(line.trim_end_matches(['\r', '\n']).to_owned())
 }
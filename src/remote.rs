@@ -0,0 +1,573 @@
+//! Commands that talk to a running manager over HTTP, with the tokens
+//! stored by `login`.
+
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use api::{
+    apply::{read_manifest, ApplyResponse, PlanAction, WorkspaceManifest},
+    workers::WorkerInfoResponse,
+};
+use bytes::Bytes;
+use http_body_util::{BodyExt, Full};
+use hyper::{
+    header::{AUTHORIZATION, CONTENT_TYPE, USER_AGENT},
+    Method, Request, StatusCode,
+};
+use hyper_util::{
+    client::legacy::{connect::HttpConnector, Client},
+    rt::TokioExecutor,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use crate::{read_code, read_password, ApplyArgs, CliResult, LoginArgs, LogoutArgs, WorkerCommand};
+
+const DEFAULT_URL: &str = "http://localhost:8000";
+
+/// Tokens of a manager, stored in the credentials file.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+struct Credentials {
+    url: String,
+    access_token: String,
+    refresh_token: String,
+}
+
+#[derive(Deserialize)]
+struct Tokens {
+    access_token: String,
+    refresh_token: String,
+    /// Returned once, by a login that enrolled in two-factor
+    /// authentication.
+    #[serde(default)]
+    recovery_codes: Option<Vec<String>>,
+}
+
+/// Answer of `/auth/login`, tokens unless a second factor is needed.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum LoginResponse {
+    Tokens(Tokens),
+    SecondFactorRequired(SecondFactorChallenge),
+}
+
+#[derive(Deserialize)]
+struct SecondFactorChallenge {
+    challenge: String,
+    enrollment_required: bool,
+}
+
+#[derive(Deserialize)]
+struct TotpEnrollment {
+    secret: String,
+    provisioning_uri: String,
+}
+
+#[derive(Deserialize)]
+struct Message {
+    message: String,
+}
+
+/// Path of the credentials file, `$WORKERD_MANAGER_CONFIG` or
+/// `workerd-manager/credentials.json` in the user's config directory.
+fn credentials_path() -> CliResult<PathBuf> {
+    if let Ok(path) = std::env::var("WORKERD_MANAGER_CONFIG") {
+        return Ok(path.into());
+    }
+    let config_dir = match std::env::var("XDG_CONFIG_HOME") {
+        Ok(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => PathBuf::from(std::env::var("HOME").map_err(|_| "HOME is not set")?).join(".config"),
+    };
+    Ok(config_dir.join("workerd-manager").join("credentials.json"))
+}
+
+fn load_credentials() -> CliResult<Option<Credentials>> {
+    match fs::read(credentials_path()?) {
+        Ok(content) => Ok(Some(serde_json::from_slice(&content)?)),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(err) => Err(err.into()),
+    }
+}
+
+fn save_credentials(credentials: &Credentials) -> CliResult {
+    let path = credentials_path()?;
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    io::Write::write_all(
+        &mut options.open(path)?,
+        &serde_json::to_vec_pretty(credentials)?,
+    )?;
+    Ok(())
+}
+
+fn remove_credentials() -> CliResult {
+    match fs::remove_file(credentials_path()?) {
+        Err(err) if err.kind() != io::ErrorKind::NotFound => Err(err.into()),
+        _ => Ok(()),
+    }
+}
+
+struct Manager {
+    http: Client<HttpConnector, Full<Bytes>>,
+    url: String,
+    /// Only set when the stored tokens belong to `url`.
+    credentials: Option<Credentials>,
+}
+
+impl Manager {
+    fn new(url: Option<String>) -> CliResult<Self> {
+        let stored = load_credentials()?;
+        let url = url
+            .or_else(|| {
+                stored
+                    .as_ref()
+                    .map(|credentials| credentials.url.to_owned())
+            })
+            .unwrap_or_else(|| DEFAULT_URL.to_owned())
+            .trim_end_matches('/')
+            .to_owned();
+        let credentials = stored.filter(|credentials| credentials.url == url);
+
+        Ok(Self {
+            http: Client::builder(TokioExecutor::new()).build_http(),
+            url,
+            credentials,
+        })
+    }
+
+    async fn send(
+        &self,
+        method: Method,
+        path: &str,
+        token: Option<&str>,
+        content_type: Option<&str>,
+        body: Bytes,
+    ) -> CliResult<(StatusCode, Bytes)> {
+        let mut request = Request::builder()
+            .method(method)
+            .uri(format!("{}{}", self.url, path))
+            // Names the CLI in the session list of the user.
+            .header(
+                USER_AGENT,
+                concat!(env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VERSION")),
+            );
+        if let Some(token) = token {
+            request = request.header(AUTHORIZATION, format!("Bearer {}", token));
+        }
+        if let Some(content_type) = content_type {
+            request = request.header(CONTENT_TYPE, content_type);
+        }
+
+        let response = self.http.request(request.body(Full::new(body))?).await?;
+        let status = response.status();
+        let body = response.into_body().collect().await?.to_bytes();
+        Ok((status, body))
+    }
+
+    /// Sends an authenticated request, refreshing the tokens once when the
+    /// access token has expired.
+    async fn request(
+        &mut self,
+        method: Method,
+        path: &str,
+        content_type: Option<&str>,
+        body: Bytes,
+    ) -> CliResult<Bytes> {
+        let Some(credentials) = &self.credentials else {
+            return Err(format!("Not logged in to {}, run `login` first", self.url).into());
+        };
+
+        let access_token = credentials.access_token.to_owned();
+        let (mut status, mut response) = self
+            .send(
+                method.to_owned(),
+                path,
+                Some(&access_token),
+                content_type,
+                body.to_owned(),
+            )
+            .await?;
+        if is_invalid_token(status, &response) {
+            let access_token = self.refresh().await?;
+            (status, response) = self
+                .send(method, path, Some(&access_token), content_type, body)
+                .await?;
+        }
+
+        if !status.is_success() {
+            return Err(error_message(status, &response).into());
+        }
+        Ok(response)
+    }
+
+    async fn refresh(&mut self) -> CliResult<String> {
+        let Some(credentials) = self.credentials.to_owned() else {
+            return Err("Not logged in".into());
+        };
+
+        let (status, response) = self
+            .send(
+                Method::POST,
+                "/auth/refresh-tokens",
+                Some(&credentials.refresh_token),
+                None,
+                Bytes::new(),
+            )
+            .await?;
+        if !status.is_success() {
+            return Err("The session has expired, run `login` again".into());
+        }
+
+        let tokens: Tokens = serde_json::from_slice(&response)?;
+        let credentials = Credentials {
+            access_token: tokens.access_token,
+            refresh_token: tokens.refresh_token,
+            ..credentials
+        };
+        save_credentials(&credentials)?;
+        let access_token = credentials.access_token.to_owned();
+        self.credentials = Some(credentials);
+        Ok(access_token)
+    }
+}
+
+fn is_invalid_token(status: StatusCode, body: &[u8]) -> bool {
+    matches!(status, StatusCode::BAD_REQUEST | StatusCode::UNAUTHORIZED)
+        && serde_json::from_slice::<Message>(body)
+            .is_ok_and(|message| message.message == "Invalid token")
+}
+
+fn error_message(status: StatusCode, body: &[u8]) -> String {
+    match serde_json::from_slice::<Message>(body) {
+        Ok(Message { message }) => format!("{}: {}", status, message),
+        Err(_) => status.to_string(),
+    }
+}
+
+pub async fn login(url: Option<String>, args: LoginArgs) -> CliResult {
+    let manager = Manager::new(url)?;
+    let password = read_password(args.password)?;
+
+    let body = json!({ "email": args.email, "password": password });
+    let (status, response) = manager
+        .send(
+            Method::POST,
+            "/auth/login",
+            None,
+            Some("application/json"),
+            serde_json::to_vec(&body)?.into(),
+        )
+        .await?;
+    if !status.is_success() {
+        return Err(error_message(status, &response).into());
+    }
+
+    let tokens = match serde_json::from_slice(&response)? {
+        LoginResponse::Tokens(tokens) => tokens,
+        LoginResponse::SecondFactorRequired(challenge) => {
+            complete_login(&manager, challenge, args.code).await?
+        }
+    };
+    save_credentials(&Credentials {
+        url: manager.url.to_owned(),
+        access_token: tokens.access_token,
+        refresh_token: tokens.refresh_token,
+    })?;
+    println!("Logged in to {}", manager.url);
+
+    if let Some(recovery_codes) = tokens.recovery_codes {
+        println!("\nKeep these recovery codes, each logs in once without the authenticator:");
+        for code in recovery_codes {
+            println!("  {}", code);
+        }
+    }
+    Ok(())
+}
+
+/// Answers the second factor challenge of a login, enrolling first when
+/// the account must and has no authenticator yet.
+async fn complete_login(
+    manager: &Manager,
+    challenge: SecondFactorChallenge,
+    code: Option<String>,
+) -> CliResult<Tokens> {
+    let mut code = code;
+    if challenge.enrollment_required {
+        let body = json!({ "challenge": challenge.challenge });
+        let (status, response) = manager
+            .send(
+                Method::POST,
+                "/auth/login/two-factor/enroll",
+                None,
+                Some("application/json"),
+                serde_json::to_vec(&body)?.into(),
+            )
+            .await?;
+        if !status.is_success() {
+            return Err(error_message(status, &response).into());
+        }
+
+        let enrollment: TotpEnrollment = serde_json::from_slice(&response)?;
+        eprintln!("This account requires two-factor authentication.");
+        eprintln!(
+            "Add this secret to an authenticator app: {}",
+            enrollment.secret
+        );
+        eprintln!("or scan a QR code of: {}", enrollment.provisioning_uri);
+        // A code given upfront cannot belong to the new secret.
+        code = None;
+    }
+
+    let body = json!({ "challenge": challenge.challenge, "code": read_code(code)?.trim() });
+    let (status, response) = manager
+        .send(
+            Method::POST,
+            "/auth/login/two-factor",
+            None,
+            Some("application/json"),
+            serde_json::to_vec(&body)?.into(),
+        )
+        .await?;
+    if !status.is_success() {
+        return Err(error_message(status, &response).into());
+    }
+    Ok(serde_json::from_slice(&response)?)
+}
+
+/// Revokes the stored session, then forgets it even when the manager
+/// refused, as the tokens are of no use either way.
+pub async fn logout(url: Option<String>, args: LogoutArgs) -> CliResult {
+    let mut manager = Manager::new(url)?;
+    let Some(credentials) = manager.credentials.to_owned() else {
+        return Err(format!("Not logged in to {}", manager.url).into());
+    };
+
+    let result = if args.all {
+        manager
+            .request(Method::POST, "/auth/logout-all", None, Bytes::new())
+            .await
+            .map(|_| ())
+    } else {
+        match manager
+            .send(
+                Method::POST,
+                "/auth/logout",
+                Some(&credentials.refresh_token),
+                None,
+                Bytes::new(),
+            )
+            .await
+        {
+            Ok((status, response)) if !status.is_success() => {
+                Err(error_message(status, &response).into())
+            }
+            result => result.map(|_| ()),
+        }
+    };
+    remove_credentials()?;
+    result?;
+
+    println!("Logged out of {}", manager.url);
+    Ok(())
+}
+
+pub async fn worker(url: Option<String>, command: WorkerCommand) -> CliResult {
+    let mut manager = Manager::new(url)?;
+
+    match command {
+        WorkerCommand::List => {
+            let response = manager
+                .request(Method::GET, "/workers", None, Bytes::new())
+                .await?;
+            let workers: Vec<WorkerInfoResponse> = serde_json::from_slice(&response)?;
+            for worker in workers {
+                println!(
+                    "{:<36}  {:<24}  {:>5}  {}{}",
+                    worker.id, worker.name, worker.port, worker.host_name, worker.external_path
+                );
+            }
+        }
+        WorkerCommand::Start { id } => {
+            let response = manager
+                .request(
+                    Method::POST,
+                    &format!("/workers/{}/exec", id),
+                    None,
+                    Bytes::new(),
+                )
+                .await?;
+            let message: Message = serde_json::from_slice(&response)?;
+            println!("{}", message.message);
+        }
+        WorkerCommand::Stop { id } => {
+            let response = manager
+                .request(
+                    Method::DELETE,
+                    &format!("/workers/{}/exec", id),
+                    None,
+                    Bytes::new(),
+                )
+                .await?;
+            let message: Message = serde_json::from_slice(&response)?;
+            println!("{}", message.message);
+        }
+        WorkerCommand::Logs { id, lines } => {
+            let path = match lines {
+                Some(lines) => format!("/workers/{}/logs?lines={}", id, lines),
+                None => format!("/workers/{}/logs", id),
+            };
+            let response = manager
+                .request(Method::GET, &path, None, Bytes::new())
+                .await?;
+            print!("{}", String::from_utf8_lossy(&response));
+        }
+    }
+    Ok(())
+}
+
+pub async fn apply(url: Option<String>, args: ApplyArgs) -> CliResult {
+    let mut manager = Manager::new(url)?;
+
+    let content = fs::read_to_string(&args.file)?;
+    let content_type = match args.file.extension().and_then(|ext| ext.to_str()) {
+        Some("toml") => "application/toml",
+        Some("json") => "application/json",
+        _ => "application/yaml",
+    };
+    let mut manifest = read_manifest(Some(content_type), &content)
+        .map_err(|_| format!("{} is not a valid workspace manifest", args.file.display()))?;
+    let dir = args.file.parent().unwrap_or(Path::new("."));
+    resolve_code_paths(&mut manifest, |path| fs::read_to_string(dir.join(path)))?;
+
+    let response = manager
+        .request(
+            Method::POST,
+            &format!("/apply?dry_run={}", args.dry_run),
+            Some("application/json"),
+            serde_json::to_vec(&manifest)?.into(),
+        )
+        .await?;
+    let response: ApplyResponse = serde_json::from_slice(&response)?;
+
+    if response.plan.is_empty() {
+        println!("Nothing to change");
+    }
+    for step in response.plan {
+        let action = match step.action {
+            PlanAction::Create => "create",
+            PlanAction::Update => "update",
+            PlanAction::Delete => "delete",
+        };
+        if step.fields.is_empty() {
+            println!("{} {}", action, step.name);
+        } else {
+            println!("{} {} ({})", action, step.name, step.fields.join(", "));
+        }
+    }
+    if response.dry_run {
+        println!("Dry run, nothing was changed");
+    }
+    if !response.failures.is_empty() {
+        for failure in &response.failures {
+            println!("failed to reload {}: {}", failure.name, failure.error);
+        }
+        return Err("The manifest was applied, but some workers failed to reload".into());
+    }
+    Ok(())
+}
+
+/// Replaces the `code_path` of every worker with the content of the file,
+/// which also names the entry unless the manifest does.
+fn resolve_code_paths(
+    manifest: &mut WorkspaceManifest,
+    read: impl Fn(&str) -> io::Result<String>,
+) -> CliResult {
+    for spec in &mut manifest.workers {
+        let Some(code_path) = spec.code_path.take() else {
+            continue;
+        };
+        if spec.code.is_some() {
+            return Err(format!("Worker {} sets both code and code_path", spec.name).into());
+        }
+
+        let code =
+            read(&code_path).map_err(|err| format!("Failed to read {}: {}", code_path, err))?;
+        spec.code = Some(code);
+        if spec.entry.is_none() {
+            spec.entry = Path::new(&code_path)
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned());
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolve_code_paths() {
+        let mut manifest = read_manifest(
+            None,
+            r#"
+workers:
+  - name: api
+    port: 8080
+    code_path: src/api.js
+  - name: web
+    port: 8081
+    entry: index.js
+    code_path: web.js
+  - name: inline
+    port: 8082
+    code: export default {}
+"#,
+        )
+        .unwrap();
+
+        resolve_code_paths(&mut manifest, |path| Ok(format!("// {}", path))).unwrap();
+
+        let resolved: Vec<_> = manifest
+            .workers
+            .iter()
+            .map(|spec| (spec.entry.as_deref(), spec.code.as_deref(), &spec.code_path))
+            .collect();
+        assert_eq!(
+            resolved,
+            [
+                (Some("api.js"), Some("// src/api.js"), &None),
+                (Some("index.js"), Some("// web.js"), &None),
+                (None, Some("export default {}"), &None),
+            ]
+        );
+
+        let mut manifest = read_manifest(
+            None,
+            "workers: [{name: api, port: 8080, code: x, code_path: api.js}]",
+        )
+        .unwrap();
+        assert!(resolve_code_paths(&mut manifest, |_| Ok(String::new())).is_err());
+    }
+
+    #[test]
+    fn test_is_invalid_token() {
+        assert!(is_invalid_token(
+            StatusCode::BAD_REQUEST,
+            br#"{"message":"Invalid token"}"#
+        ));
+        assert!(!is_invalid_token(
+            StatusCode::BAD_REQUEST,
+            br#"{"message":"Invalid cron expression"}"#
+        ));
+        assert!(!is_invalid_token(StatusCode::UNAUTHORIZED, b"Unauthorized"));
+    }
+}